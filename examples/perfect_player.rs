@@ -0,0 +1,42 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The engine playing perfect tic-tac-toe against itself, without a window.
+//!
+//! ```text
+//! cargo run --example perfect_player
+//! ```
+
+extern crate tic_tac_minimax;
+
+use tic_tac_minimax::prelude::*;
+
+fn main() {
+    let mut game = Game::<State>::new(CheckBox::X);
+    game.set_difficulty(Difficulty::Perfect);
+
+    let mut rng = tic_tac_minimax::rand::thread_rng();
+    while game.state().result() == GameResult::InProgress {
+        let player = game.player();
+        let (x, y) = game.play_engine_move(&mut rng).expect("The game isn't over");
+        println!("{:?} plays {}", player, tic_tac_minimax::game::square_name(x, y));
+    }
+
+    let mut board = String::new();
+    game.state().dump(0, &mut board).unwrap();
+    println!("{}{:?}", board, game.state().result());
+}
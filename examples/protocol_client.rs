@@ -0,0 +1,79 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Drives an engine speaking the `--protocol` commands, as a tournament
+//! harness would, and has it play a whole game against itself.
+//!
+//! ```text
+//! cargo run --example protocol_client [<engine command>]
+//! ```
+//!
+//! The engine command defaults to `tic-tac-minimax --protocol`, from the
+//! `PATH`.
+
+extern crate tic_tac_minimax;
+
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use tic_tac_minimax::prelude::*;
+
+fn main() {
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    if args.is_empty() {
+        args = vec!["tic-tac-minimax".to_owned(), "--protocol".to_owned()];
+    }
+
+    let mut engine = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|err| panic!("Couldn't run {}: {}", args[0], err));
+    let mut input = engine.stdin.take().unwrap();
+    let mut output = BufReader::new(engine.stdout.take().unwrap());
+    let mut ask = |command: &str| -> String {
+        writeln!(input, "{}", command).expect("The engine quit");
+        let mut reply = String::new();
+        output.read_line(&mut reply).expect("The engine quit");
+        println!("> {}\n< {}", command, reply.trim());
+        reply.trim().to_owned()
+    };
+
+    assert_eq!(ask("isready"), "readyok");
+    let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+    while !tree.state().is_over() {
+        let mut marks = String::new();
+        for y in 0..3 {
+            for x in 0..3 {
+                marks.push(tree.state().get(x, y).dump_char());
+            }
+        }
+        let player = tree.player().dump_char();
+        let _ = ask(&format!("position {} {}", marks, player));
+        let reply = ask("go depth 9");
+        let words = reply.split_whitespace().collect::<Vec<_>>();
+        let mv = match words.as_slice() {
+            ["bestmove", x, y, "pv", ..] => (x.parse().unwrap(), y.parse().unwrap()),
+            _ => panic!("Unexpected reply: {}", reply),
+        };
+        tree.choose(mv).expect("The engine played an illegal move");
+    }
+    writeln!(input, "quit").expect("The engine quit");
+    engine.wait().expect("The engine didn't quit");
+    println!("{:?}", tree.state().result());
+}
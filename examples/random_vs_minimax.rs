@@ -0,0 +1,66 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Random moves against the engine, at every difficulty, to see how often
+//! each of them gets beaten.
+//!
+//! ```text
+//! cargo run --example random_vs_minimax [<games>]
+//! ```
+
+extern crate tic_tac_minimax;
+
+use std::env;
+use tic_tac_minimax::prelude::*;
+use tic_tac_minimax::rand::{Rng, SeedableRng};
+use tic_tac_minimax::rand::rngs::StdRng;
+
+/// Plays a game where X moves at random and O is the engine, looking only
+/// `depth` levels ahead.
+fn play<R: Rng>(depth: usize, rng: &mut R) -> GameResult {
+    let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+    while !tree.state().is_over() {
+        let index = if tree.player() == CheckBox::X {
+            let moves = tree.state().successors(CheckBox::X).len();
+            rng.gen_range(0..moves)
+        } else {
+            tree.find_move_index(depth).index().expect("The game isn't over")
+        };
+        tree.choose_with_index(index);
+    }
+    tree.state().result()
+}
+
+fn main() {
+    let games = env::args().nth(1).and_then(|g| g.parse().ok()).unwrap_or(100);
+    // Always the same games, so that runs can be compared.
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for difficulty in Difficulty::all().iter() {
+        let depth = difficulty.max_depth().min(State::depth_limit());
+        let (mut engine_wins, mut draws, mut random_wins) = (0, 0, 0);
+        for _ in 0..games {
+            match play(depth, &mut rng) {
+                GameResult::OWins => engine_wins += 1,
+                GameResult::XWins => random_wins += 1,
+                _ => draws += 1,
+            }
+        }
+        println!("{:>8} (depth {}): {} won, {} drawn, {} lost",
+                 difficulty.name(), depth, engine_wins, draws, random_wins);
+    }
+}
@@ -0,0 +1,108 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/* Generated from src/ffi.rs with `cbindgen --config cbindgen.toml --output
+ * include/tic_tac_minimax.h`, don't edit by hand. */
+
+#ifndef TIC_TAC_MINIMAX_H
+#define TIC_TAC_MINIMAX_H
+
+#include <stddef.h>
+
+#define TTT_IN_PROGRESS 0
+
+#define TTT_X_WINS 1
+
+#define TTT_O_WINS 2
+
+#define TTT_DRAW 3
+
+#define TTT_OK 0
+
+#define TTT_GAME_OVER 1
+
+#define TTT_OCCUPIED 2
+
+#define TTT_OUT_OF_BOUNDS 3
+
+#define TTT_ILLEGAL 4
+
+/**
+ * The handle or some other pointer was null, or the variant unknown.
+ */
+#define TTT_INVALID_ARGUMENT 5
+
+/**
+ * The budget of `ttt_best_move` that the engine plays with by default.
+ */
+#define TTT_DEFAULT_NODE_BUDGET 100000
+
+/**
+ * A game, as C sees it.
+ */
+typedef struct TttGame TttGame;
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/**
+ * Starts a game of the variant with the given id, like `"connect4"`, or of
+ * tic-tac-toe if `variant` is null. X moves first.
+ *
+ * Returns null if the variant is unknown.
+ */
+TttGame *ttt_new_game(const char *variant);
+
+/**
+ * Plays the square in column `x` and row `y` as the player to move.
+ *
+ * Returns `TTT_OK`, or why the move couldn't be played.
+ */
+int ttt_play(TttGame *game, size_t x, size_t y);
+
+/**
+ * Stores in `x` and `y` the square of the best move for the player to
+ * move, without playing it.
+ *
+ * The engine searches deeper and deeper, up to as deep as the game goes,
+ * but only while the next search is expected to visit at most
+ * `node_budget` positions in all, so that it returns in a time that
+ * depends on the budget rather than on the game. It searches one level at
+ * least, even with a budget of zero. With `TTT_DEFAULT_NODE_BUDGET` it
+ * plays like the engine does by default.
+ *
+ * Returns `TTT_OK`, or `TTT_GAME_OVER` if there's no move to play.
+ */
+int ttt_best_move(const TttGame *game, size_t node_budget, size_t *x, size_t *y);
+
+/**
+ * Returns one of `TTT_IN_PROGRESS`, `TTT_X_WINS`, `TTT_O_WINS` or
+ * `TTT_DRAW`, or `TTT_INVALID_ARGUMENT` if `game` is null.
+ */
+int ttt_game_result(const TttGame *game);
+
+/**
+ * Destroys a game. Does nothing if `game` is null.
+ */
+void ttt_free(TttGame *game);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif /* TIC_TAC_MINIMAX_H */
@@ -0,0 +1,279 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The adaptive difficulty, where the engine plays as strong as it takes for
+//! the human to win about as often as they'd like.
+//!
+//! The engine keeps a model of the human from their last games: how often
+//! they won, and how often they missed the best move. After each game it
+//! plays stronger if the human won more often than the target, and weaker if
+//! they won less. The strength sets how deep the engine searches, and it
+//! makes about as many mistakes as the human does, fewer the stronger it
+//! plays.
+
+use config::{Config, Value};
+use game_state::GameState;
+use minimax::MiniMaxTree;
+use rand::Rng;
+use std::collections::VecDeque;
+
+/// The section of the configuration file with the model.
+const SECTION: &str = "adaptive";
+
+/// How many of the last games the model remembers.
+pub const RECENT_GAMES: usize = 10;
+
+/// How much the strength changes after a game, for each point of difference
+/// between how often the human won and the target.
+const GAIN: f64 = 0.25;
+
+/// How often the engine doesn't play the best move at the lowest strength,
+/// at least and at most, whatever the human does.
+const MIN_MISTAKE_PROBABILITY: f64 = 0.1;
+const MAX_MISTAKE_PROBABILITY: f64 = 0.5;
+
+/// How a game against the engine went for the human.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct GameSummary {
+    pub won: bool,
+    /// The moves of the human that scored worse than their best one.
+    pub mistakes: u32,
+    /// The moves of the human.
+    pub moves: u32,
+}
+
+/// What the engine knows of the human, and how strong it plays against them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpponentModel {
+    /// The last games, the most recent last.
+    games: VecDeque<GameSummary>,
+    /// From 0, the weakest, to 1, searching as deep as the game allows and
+    /// never missing the best move.
+    strength: f64,
+    /// The fraction of the games the human should win.
+    pub target_win_rate: f64,
+}
+
+impl Default for OpponentModel {
+    fn default() -> Self {
+        Self {
+            games: VecDeque::new(),
+            strength: 0.5,
+            target_win_rate: 0.5,
+        }
+    }
+}
+
+impl OpponentModel {
+    /// Reads the model from `config`, leaving out the games that don't parse
+    /// and using the defaults for whatever is missing or invalid.
+    pub fn from_config(config: &Config) -> Self {
+        let mut model = OpponentModel::default();
+        if let Some(games) = config.get_string(SECTION, "games") {
+            model.games = games.split_whitespace().filter_map(parse_summary).collect();
+            while model.games.len() > RECENT_GAMES {
+                model.games.pop_front();
+            }
+        }
+        if let Some(strength) = config.get_integer(SECTION, "strength-percent") {
+            if (0..=100).contains(&strength) {
+                model.strength = strength as f64 / 100.0;
+            }
+        }
+        if let Some(target) = config.get_integer(SECTION, "target-percent") {
+            if (0..=100).contains(&target) {
+                model.target_win_rate = target as f64 / 100.0;
+            }
+        }
+        model
+    }
+
+    pub fn write_to(&self, config: &mut Config) {
+        let games = self.games.iter()
+            .map(|g| format!("{}/{}/{}", if g.won { 1 } else { 0 }, g.mistakes, g.moves))
+            .collect::<Vec<_>>();
+        config.set(SECTION, "games", Value::String(games.join(" ")));
+        config.set(SECTION, "strength-percent", Value::Integer((self.strength * 100.0).round() as i64));
+        config.set(SECTION, "target-percent", Value::Integer((self.target_win_rate * 100.0).round() as i64));
+    }
+
+    pub fn strength(&self) -> f64 {
+        self.strength
+    }
+
+    /// Returns the fraction of the last games the human won, if they played
+    /// any.
+    pub fn win_rate(&self) -> Option<f64> {
+        if self.games.is_empty() {
+            return None;
+        }
+        let wins = self.games.iter().filter(|g| g.won).count();
+        Some(wins as f64 / self.games.len() as f64)
+    }
+
+    /// Returns the fraction of the moves of the last games where the human
+    /// missed the best move, if they played any.
+    pub fn mistake_rate(&self) -> Option<f64> {
+        let moves: u32 = self.games.iter().map(|g| g.moves).sum();
+        if moves == 0 {
+            return None;
+        }
+        let mistakes: u32 = self.games.iter().map(|g| g.mistakes).sum();
+        Some(mistakes as f64 / moves as f64)
+    }
+
+    /// Remembers `game`, forgetting the oldest one if there are too many,
+    /// and plays stronger or weaker from now on depending on how often the
+    /// human won.
+    pub fn record(&mut self, game: GameSummary) {
+        self.games.push_back(game);
+        if self.games.len() > RECENT_GAMES {
+            self.games.pop_front();
+        }
+        let win_rate = self.win_rate().expect("Just recorded a game");
+        let strength = self.strength + GAIN * (win_rate - self.target_win_rate);
+        self.strength = strength.clamp(0.0, 1.0);
+    }
+
+    /// Returns how the engine plays against the human now, in a game that
+    /// goes up to `depth_limit` levels deep.
+    pub fn policy(&self, depth_limit: usize) -> Policy {
+        let extra_depth = (self.strength * (depth_limit.max(1) - 1) as f64).round() as usize;
+        // Without games to go by, assume the human errs half as much as the
+        // engine can.
+        let human_mistakes = self.mistake_rate().unwrap_or(MAX_MISTAKE_PROBABILITY / 2.0);
+        let mistakes = human_mistakes.clamp(MIN_MISTAKE_PROBABILITY, MAX_MISTAKE_PROBABILITY);
+        Policy {
+            depth: 1 + extra_depth,
+            mistake_probability: mistakes * (1.0 - self.strength),
+        }
+    }
+}
+
+/// Parses a game as `OpponentModel::write_to` writes it, like `1/2/5` for a
+/// win with two mistakes in five moves.
+fn parse_summary(text: &str) -> Option<GameSummary> {
+    let mut fields = text.split('/').map(|f| f.parse::<u32>().ok());
+    let won = match fields.next()?? {
+        0 => false,
+        1 => true,
+        _ => return None,
+    };
+    let mistakes = fields.next()??;
+    let moves = fields.next()??;
+    if fields.next().is_some() || mistakes > moves {
+        return None;
+    }
+    Some(GameSummary { won, mistakes, moves })
+}
+
+/// How the engine plays at some strength.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Policy {
+    /// The number of levels to search.
+    pub depth: usize,
+    /// The probability of not playing the best move that was found.
+    pub mistake_probability: f64,
+}
+
+impl Policy {
+    /// Finds the index of the move to play in the current state of `tree`,
+    /// searching up to `max_depth` levels if the policy searches deeper.
+    ///
+    /// Returns `None` if the game is already over.
+    pub fn find_move_index<S, R>(
+        &self,
+        tree: &mut MiniMaxTree<S>,
+        max_depth: usize,
+        randomize_ties: bool,
+        rng: &mut R)
+        -> Option<usize>
+        where S: GameState,
+              R: Rng + ?Sized,
+    {
+        let depth = self.depth.min(max_depth);
+        tree.find_move_index_with_mistakes(depth, self.mistake_probability, randomize_ties, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use state::{CheckBox, State};
+
+    fn game(won: bool, mistakes: u32) -> GameSummary {
+        GameSummary { won, mistakes, moves: 4 }
+    }
+
+    #[test]
+    fn plays_stronger_against_humans_that_win_more() {
+        let mut model = OpponentModel::default();
+        let start = model.policy(9);
+        for _ in 0..RECENT_GAMES {
+            model.record(game(true, 0));
+        }
+        assert_eq!(model.strength(), 1.0);
+        assert_eq!(model.policy(9), Policy { depth: 9, mistake_probability: 0.0 });
+
+        for _ in 0..RECENT_GAMES * 2 {
+            model.record(game(false, 2));
+        }
+        assert_eq!(model.win_rate(), Some(0.0));
+        assert_eq!(model.mistake_rate(), Some(0.5));
+        assert_eq!(model.strength(), 0.0);
+        let weakest = model.policy(9);
+        assert_eq!(weakest, Policy { depth: 1, mistake_probability: MAX_MISTAKE_PROBABILITY });
+        assert!(weakest.depth < start.depth);
+        assert!(weakest.mistake_probability > start.mistake_probability);
+    }
+
+    #[test]
+    fn persists_the_last_games() {
+        let mut model = OpponentModel { target_win_rate: 0.3, ..OpponentModel::default() };
+        for i in 0..RECENT_GAMES + 3 {
+            model.record(game(i % 3 == 0, i as u32 % 4));
+        }
+        let mut config = Config::default();
+        model.write_to(&mut config);
+        let parsed = OpponentModel::from_config(&Config::parse(&config.to_string()).unwrap());
+        assert_eq!(parsed.games, model.games);
+        assert_eq!(parsed.games.len(), RECENT_GAMES);
+        assert_eq!(parsed.target_win_rate, 0.3);
+        assert!((parsed.strength() - model.strength()).abs() <= 0.005);
+
+        let mut config = Config::default();
+        config.set(SECTION, "games", Value::String("1/0/3 2/0/3 0/4/3 0/1/3 x".to_owned()));
+        config.set(SECTION, "strength-percent", Value::Integer(250));
+        let parsed = OpponentModel::from_config(&config);
+        assert_eq!(parsed.games, vec![game(true, 0), game(false, 1)].into_iter()
+                       .map(|g| GameSummary { moves: 3, ..g }).collect::<VecDeque<_>>());
+        assert_eq!(parsed.strength(), 0.5);
+    }
+
+    #[test]
+    fn the_strongest_policy_plays_perfectly() {
+        let policy = OpponentModel { strength: 1.0, ..OpponentModel::default() }.policy(9);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        while let Some(index) = policy.find_move_index(&mut tree, 9, true, &mut rng) {
+            tree.choose_with_index(index);
+        }
+        assert_eq!(tree.state().score(), 0);
+    }
+}
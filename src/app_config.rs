@@ -0,0 +1,230 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! How the binary was asked to run, from its command line and the saved
+//! settings, which both the GUI and the terminal start from.
+
+use config::{self, Config};
+use minimax::{Difficulty, SearchConfig};
+use settings::Settings;
+use state::CheckBox;
+use std::path::PathBuf;
+use variant::Variant;
+
+pub const USAGE: &str = "\
+usage: tic-tac-minimax [options]
+
+  --mode <mode>          gui (default, if built with it) or text
+  --variant <name>       tictactoe, ultimate, connect4, scrabble, misere,
+                         wild, notakto, cube or qubic
+  --size <n>             of the board, 3 or 4 for the cube
+  --difficulty <name>    easy, medium, hard, perfect, auto or adaptive
+  --depth <n>            levels the engine searches, if fewer than the
+                         difficulty does
+  --first-player <who>   human (default) or engine
+  --config <file>        read and save the settings in <file> rather than
+                         in the default one
+
+The options override the saved settings for this run, until the settings are
+changed in the GUI.";
+
+/// The frontend to play on.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Mode {
+    Gui,
+    Text,
+}
+
+/// The options of the command line, which are all optional.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Options {
+    mode: Option<Mode>,
+    variant: Option<Variant>,
+    size: Option<usize>,
+    difficulty: Option<Difficulty>,
+    depth: Option<String>,
+    engine_first: bool,
+    config_path: Option<PathBuf>,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+        let invalid = || format!("invalid value for {}: {}", arg, value);
+        match arg.as_str() {
+            "--mode" => {
+                options.mode = Some(match value.as_str() {
+                    "gui" => Mode::Gui,
+                    "text" => Mode::Text,
+                    _ => return Err(invalid()),
+                });
+            }
+            "--variant" => {
+                options.variant = Some(Variant::from_id(value)
+                    .ok_or_else(|| format!("unknown variant: {}", value))?);
+            }
+            "--size" => options.size = Some(value.parse::<usize>().map_err(|_| invalid())?),
+            "--difficulty" => {
+                options.difficulty = Some(Difficulty::all().iter().cloned()
+                    .find(|d| d.name().eq_ignore_ascii_case(value))
+                    .ok_or_else(|| format!("unknown difficulty: {}", value))?);
+            }
+            "--depth" => options.depth = Some(value.clone()),
+            "--first-player" => {
+                options.engine_first = match value.as_str() {
+                    "human" => false,
+                    "engine" => true,
+                    _ => return Err(invalid()),
+                };
+            }
+            "--config" => options.config_path = Some(PathBuf::from(value)),
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+/// Loads the configuration at `path`, or the default one.
+///
+/// Returns where to save it too, which is nowhere if it couldn't be read,
+/// since it may be from a newer version.
+fn load_config(path: Option<PathBuf>) -> (Config, Option<PathBuf>) {
+    let path = match path.or_else(config::default_path) {
+        Some(path) => path,
+        None => return (Config::default(), None),
+    };
+    match Config::load(&path) {
+        Ok(config) => (config, Some(path)),
+        Err(err) => {
+            eprintln!("Couldn't load {}, not saving settings: {}", path.display(), err);
+            (Config::default(), None)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AppConfig {
+    pub mode: Mode,
+    /// The configuration file the settings come from, and where to save it,
+    /// if anywhere.
+    pub config: Config,
+    pub config_path: Option<PathBuf>,
+    /// The saved settings, with the options of the command line on top.
+    pub settings: Settings,
+    /// Whether the engine moves first, rather than the human.
+    pub engine_first: bool,
+}
+
+impl AppConfig {
+    /// Parses the options in `args`, which don't include the name of the
+    /// binary, on top of the configuration they say.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let options = parse_options(args)?;
+        let (config, config_path) = load_config(options.config_path.clone());
+        let mut app_config = Self::new(config, config_path);
+        app_config.apply(&options)?;
+        Ok(app_config)
+    }
+
+    /// Returns the config of the saved settings, and nothing else.
+    pub fn new(config: Config, config_path: Option<PathBuf>) -> Self {
+        Self {
+            mode: if cfg!(feature = "gui") { Mode::Gui } else { Mode::Text },
+            settings: Settings::from_config(&config),
+            config,
+            config_path,
+            engine_first: false,
+        }
+    }
+
+    fn apply(&mut self, options: &Options) -> Result<(), String> {
+        if let Some(mode) = options.mode {
+            if mode == Mode::Gui && !cfg!(feature = "gui") {
+                return Err("this build has no GUI".to_owned());
+            }
+            self.mode = mode;
+        }
+        if let Some(variant) = options.variant {
+            self.settings.variant = variant;
+        }
+        if let Some(size) = options.size {
+            let variant = self.settings.variant;
+            self.settings.variant = variant.with_size(size)
+                .ok_or_else(|| format!("{} doesn't come in size {}", variant.name(), size))?;
+        }
+        if let Some(difficulty) = options.difficulty {
+            self.settings.engine.difficulty = difficulty;
+        }
+        // The limit depends on the variant, which may come after the depth.
+        if let Some(ref depth) = options.depth {
+            let depth = SearchConfig::parse(depth, self.settings.variant.depth_limit())
+                .map_err(|err| err.to_string())?;
+            self.settings.engine.depth = Some(depth.depth());
+        }
+        self.engine_first = options.engine_first;
+        Ok(())
+    }
+
+    /// The mark of whoever moves first.
+    pub fn first_player(&self) -> CheckBox {
+        if self.engine_first {
+            self.settings.human.next_player()
+        } else {
+            self.settings.human
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_owned()).collect()
+    }
+
+    #[test]
+    fn overrides_the_saved_settings() {
+        let saved = Settings { variant: Variant::ConnectFour, human: CheckBox::O, ..Settings::default() };
+        let mut config = Config::default();
+        saved.write_to(&mut config);
+
+        let mut app_config = AppConfig::new(config, None);
+        let options = parse_options(&args("--depth 2 --variant cube --size 4 --mode text \
+                                           --difficulty hard --first-player engine")).unwrap();
+        app_config.apply(&options).unwrap();
+        assert_eq!(app_config.mode, Mode::Text);
+        assert_eq!(app_config.settings.variant, Variant::Qubic);
+        assert_eq!(app_config.settings.engine.difficulty, Difficulty::Hard);
+        assert_eq!(app_config.settings.engine.depth, Some(2));
+        assert_eq!(app_config.first_player(), CheckBox::X);
+
+        // Whatever isn't given stays as saved.
+        let mut app_config = AppConfig::new(app_config.config, None);
+        app_config.apply(&Options::default()).unwrap();
+        assert_eq!(app_config.settings.variant, Variant::ConnectFour);
+        assert_eq!(app_config.first_player(), CheckBox::O);
+
+        assert!(app_config.apply(&parse_options(&args("--size 4")).unwrap()).is_err());
+        assert!(app_config.apply(&parse_options(&args("--depth 0")).unwrap()).is_err());
+        assert!(parse_options(&args("--first-player nobody")).is_err());
+        assert!(parse_options(&args("--variant")).is_err());
+        assert!(parse_options(&args("--speed 3")).is_err());
+    }
+}
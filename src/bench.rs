@@ -0,0 +1,212 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Self-play benchmarks of the search, to measure search changes.
+
+use connect_four::ConnectFourState;
+use cube::{CubeState, QubicState};
+use evaluator::{self, DefaultEvaluator, Evaluator};
+use game_state::{GameResult, GameState};
+use minimax::{MiniMaxTree, SearchConfig};
+use number_scrabble::NumberScrabbleState;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rules::{MisereState, NotaktoState, WildState};
+use state::{CheckBox, State};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use ultimate::UltimateState;
+use variant::Variant;
+
+pub const USAGE: &str = "\
+usage: tic-tac-minimax bench [options]
+
+  --variant <name>     tictactoe (default), ultimate, connect4, scrabble,
+                       misere, wild, notakto, cube or qubic
+  --depth <n>          search <n> levels (default: as deep as the game allows)
+  --evaluator <name>   the heuristic of the search, default, or positional for
+                       tic-tac-toe (default: default)
+  --games <n>          play <n> games (default: 100)
+  --random-plies <n>   open each game with <n> random moves (default: 2)
+  --seed <n>           seed for the random moves (default: 0)
+
+Timings of debug builds include the checks of the search, use --release.";
+
+#[derive(Debug)]
+struct Options {
+    variant: Variant,
+    depth: Option<usize>,
+    evaluator: String,
+    games: usize,
+    random_plies: usize,
+    seed: u64,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        variant: Variant::TicTacToe,
+        depth: None,
+        evaluator: "default".to_owned(),
+        games: 100,
+        random_plies: 2,
+        seed: 0,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+        let number = || {
+            value.parse::<u64>().map_err(|_| format!("invalid value for {}: {}", arg, value))
+        };
+        match arg.as_str() {
+            "--variant" => {
+                options.variant = Variant::from_id(value)
+                    .ok_or_else(|| format!("unknown variant: {}", value))?;
+            }
+            "--depth" => options.depth = Some(number()? as usize),
+            "--evaluator" => options.evaluator = value.clone(),
+            "--games" => options.games = number()? as usize,
+            "--random-plies" => options.random_plies = number()? as usize,
+            "--seed" => options.seed = number()?,
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+
+    if let Some(depth) = options.depth {
+        let config = SearchConfig::new(depth, options.variant.depth_limit()).map_err(|err| err.to_string())?;
+        options.depth = Some(config.depth());
+    }
+    Ok(options)
+}
+
+/// What happened in a benchmark.
+#[derive(Debug, Default)]
+struct Report {
+    x_wins: usize,
+    o_wins: usize,
+    draws: usize,
+    /// The number of moves the engine searched.
+    moves: usize,
+    search_time: Duration,
+    nodes: usize,
+    cutoffs: usize,
+    /// The number of nodes whose scores came from the search of an earlier
+    /// move.
+    score_hits: usize,
+    nodes_without_pruning: usize,
+}
+
+fn bench<S: GameState>(options: &Options, evaluator: Arc<dyn Evaluator<S>>) -> Report {
+    let depth = options.depth.unwrap_or(S::depth_limit());
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut report = Report::default();
+
+    for _ in 0..options.games {
+        let mut tree = MiniMaxTree::<S>::new(CheckBox::X);
+        tree.set_evaluator(evaluator.clone());
+        let mut ply = 0;
+        while !tree.state().is_over() {
+            if ply < options.random_plies {
+                let moves = tree.state().successors(tree.player()).len();
+                tree.choose_with_index(rng.gen_range(0..moves));
+            } else {
+                report.nodes_without_pruning += tree.nodes_without_pruning(depth);
+                let start = Instant::now();
+                // Not `find_move_index`, which may skip the search.
+                let (index, _) = tree.find_best_move(depth)
+                    .expect("Should find a move in a game that isn't over");
+                report.search_time += start.elapsed();
+                report.nodes += tree.nodes_visited();
+                report.cutoffs += tree.search_stats().cutoffs;
+                report.score_hits += tree.search_stats().score_hits;
+                report.moves += 1;
+                tree.choose_with_index(index);
+            }
+            ply += 1;
+        }
+
+        match tree.state().result() {
+            GameResult::XWins => report.x_wins += 1,
+            GameResult::OWins => report.o_wins += 1,
+            GameResult::Draw => report.draws += 1,
+            _ => unreachable!(),
+        }
+    }
+    report
+}
+
+/// Returns how many nodes per second the search visits on this machine,
+/// searching tic-tac-toe positions for a moment.
+pub fn nodes_per_second() -> f64 {
+    let time = Duration::from_millis(30);
+    let start = Instant::now();
+    let mut nodes = 0;
+    while start.elapsed() < time {
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        tree.choose((1, 1)).unwrap();
+        tree.choose((0, 0)).unwrap();
+        tree.find_best_move(State::depth_limit());
+        nodes += tree.nodes_visited();
+    }
+    nodes as f64 / seconds(start.elapsed())
+}
+
+pub fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+/// Runs the benchmark the arguments describe, and prints its report.
+pub fn run(args: &[String]) -> Result<(), String> {
+    if args.iter().any(|arg| arg == "--help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let options = parse_options(args)?;
+    if options.variant != Variant::TicTacToe && options.evaluator != "default" {
+        return Err(format!("{} only has the default evaluator", options.variant.name()));
+    }
+    let report = match options.variant {
+        Variant::TicTacToe => {
+            let evaluator = evaluator::tic_tac_toe_evaluator(&options.evaluator)
+                .ok_or_else(|| format!("unknown evaluator: {}", options.evaluator))?;
+            bench::<State>(&options, evaluator)
+        }
+        Variant::Ultimate => bench::<UltimateState>(&options, Arc::new(DefaultEvaluator)),
+        Variant::ConnectFour => bench::<ConnectFourState>(&options, Arc::new(DefaultEvaluator)),
+        Variant::NumberScrabble => bench::<NumberScrabbleState>(&options, Arc::new(DefaultEvaluator)),
+        Variant::Misere => bench::<MisereState>(&options, Arc::new(DefaultEvaluator)),
+        Variant::Wild => bench::<WildState>(&options, Arc::new(DefaultEvaluator)),
+        Variant::Notakto => bench::<NotaktoState>(&options, Arc::new(DefaultEvaluator)),
+        Variant::Cube => bench::<CubeState>(&options, Arc::new(DefaultEvaluator)),
+        Variant::Qubic => bench::<QubicState>(&options, Arc::new(DefaultEvaluator)),
+    };
+
+    let moves = report.moves.max(1) as f64;
+    let millis = seconds(report.search_time) * 1000.0;
+    println!("{}, {} games, {} evaluator", options.variant.name(), options.games, options.evaluator);
+    println!("results: X wins {}, O wins {}, draws {}",
+             report.x_wins, report.o_wins, report.draws);
+    println!("moves searched: {}, {:.3}ms per move", report.moves, millis / moves);
+    println!("nodes: {} ({:.1} per move)", report.nodes, report.nodes as f64 / moves);
+    println!("cutoffs: {} ({:.1} per move)", report.cutoffs, report.cutoffs as f64 / moves);
+    println!("reused scores: {} ({:.1} per move)", report.score_hits, report.score_hits as f64 / moves);
+    println!("nodes without pruning: {} ({:.1}% visited with pruning)",
+             report.nodes_without_pruning,
+             100.0 * report.nodes as f64 / report.nodes_without_pruning.max(1) as f64);
+    Ok(())
+}
@@ -0,0 +1,127 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The best tic-tac-toe moves of the first couple of plies, which are the
+//! most expensive ones to search.
+
+use state::{CheckBox, State};
+
+/// The deepest position in the book, in number of marks.
+const BOOK_PLIES: usize = 2;
+
+/// A position of each symmetry class up to `BOOK_PLIES` marks, in rows, with
+/// X to move, and the move the engine plays there.
+///
+/// These are the moves a full search picks, so the book only saves time.
+const BOOK: [(&str, (usize, usize)); 16] = [
+    ("_________", (1, 1)),
+    // After the opponent's first move.
+    ("____O____", (0, 0)),
+    ("O________", (1, 1)),
+    ("_O_______", (1, 1)),
+    // After a move each.
+    ("OX_______", (1, 1)),
+    ("O_X______", (0, 2)),
+    ("O___X____", (0, 2)),
+    ("O____X___", (2, 0)),
+    ("O_______X", (0, 2)),
+    ("XO_______", (1, 1)),
+    ("X___O____", (0, 2)),
+    ("X____O___", (1, 1)),
+    ("_O_X_____", (1, 1)),
+    ("_O__X____", (0, 0)),
+    ("_O_____X_", (1, 1)),
+    ("_X__O____", (0, 0)),
+];
+
+/// Returns the square that `(x, y)` goes to under the `index`th of the eight
+/// symmetries of the board.
+fn transform(index: usize, (x, y): (usize, usize)) -> (usize, usize) {
+    let (mut x, mut y) = if index & 4 != 0 { (y, x) } else { (x, y) };
+    for _ in 0..index & 3 {
+        let rotated = (2 - y, x);
+        x = rotated.0;
+        y = rotated.1;
+    }
+    (x, y)
+}
+
+/// Returns how `state` looks under the `index`th symmetry, in rows, from the
+/// point of view of `player`, as if they were X.
+fn key(state: &State, player: CheckBox, index: usize) -> String {
+    let mut key = vec!['_'; 9];
+    for x in 0..3 {
+        for y in 0..3 {
+            let mark = state.get(x, y);
+            let mark = if player == CheckBox::X { mark } else { mark.next_player() };
+            let (tx, ty) = transform(index, (x, y));
+            key[ty * 3 + tx] = mark.dump_char();
+        }
+    }
+    key.into_iter().collect()
+}
+
+/// Returns the book move for `player` in `state`, if it's in the book.
+pub fn lookup(state: &State, player: CheckBox) -> Option<(usize, usize)> {
+    let marks = (0..9).filter(|&i| state.get(i % 3, i / 3) != CheckBox::Empty).count();
+    if marks > BOOK_PLIES || state.score() != 0 {
+        return None;
+    }
+    for index in 0..8 {
+        let key = key(state, player, index);
+        let mv = match BOOK.iter().find(|entry| entry.0 == key) {
+            Some(&(_, mv)) => mv,
+            None => continue,
+        };
+        // Back from the book's symmetry to ours.
+        return (0..9).map(|i| (i % 3, i / 3)).find(|&square| transform(index, square) == mv);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minimax::MiniMaxTree;
+
+    #[test]
+    fn covers_the_openings_with_the_best_moves() {
+        let mut positions = 0;
+        let mut check = |moves: &[(usize, usize)], first_player: CheckBox| {
+            let mut tree = MiniMaxTree::<State>::new(first_player);
+            for &mv in moves {
+                tree.choose(mv).unwrap();
+            }
+            let mv = lookup(tree.state(), tree.player()).expect("Should be in the book");
+            let scores = tree.evaluate_all_moves(9);
+            let best = scores.iter().map(|s| s.1).max_by_key(|&s| s * tree.player() as i8).unwrap();
+            assert_eq!(scores.iter().find(|s| s.0 == mv).unwrap().1, best, "{:?}", moves);
+            positions += 1;
+        };
+        let squares = (0..9).map(|i| (i % 3, i / 3)).collect::<Vec<_>>();
+        for &first_player in &[CheckBox::X, CheckBox::O] {
+            check(&[], first_player);
+            for &a in &squares {
+                check(&[a], first_player);
+                for &b in squares.iter().filter(|&&b| b != a) {
+                    check(&[a, b], first_player);
+                }
+            }
+        }
+        assert_eq!(positions, 2 * (1 + 9 + 9 * 8));
+    }
+}
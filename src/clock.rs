@@ -0,0 +1,339 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A chess clock: each player has some time for the whole game, and maybe
+//! some more after each of their moves, and loses if they run out of it.
+//!
+//! What happens to a player who runs out of time is up to the
+//! [`TimeoutPolicy`](enum.TimeoutPolicy.html) of the game.
+//!
+//! The time of a player can be paused, like while they're away from the
+//! game, so that it doesn't run even when it's their turn.
+
+use state::CheckBox;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// How many more moves to expect a player to make, to split their time
+/// among them.
+const MOVES_TO_GO: u32 = 20;
+
+fn index(player: CheckBox) -> usize {
+    match player {
+        CheckBox::X => 0,
+        CheckBox::O => 1,
+        CheckBox::Empty => panic!("Only players have time"),
+    }
+}
+
+/// How much time a player has.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TimeControl {
+    /// The time for the whole game.
+    pub budget: Duration,
+    /// The time added after each of their moves.
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    /// Returns a time control with `budget` seconds for the whole game, and
+    /// `increment` more after each move.
+    pub fn from_secs(budget: u64, increment: u64) -> Self {
+        Self {
+            budget: Duration::from_secs(budget),
+            increment: Duration::from_secs(increment),
+        }
+    }
+
+    /// Parses a time control like `Display` writes it, in whole seconds,
+    /// like `300+5`, or just `300` without an increment.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.splitn(2, '+');
+        let budget = parts.next()?.parse::<u64>().ok()?;
+        let increment = match parts.next() {
+            Some(increment) => increment.parse::<u64>().ok()?,
+            None => 0,
+        };
+        Some(Self::from_secs(budget, increment))
+    }
+}
+
+/// Writes the time control like the `TimeControl` tag of PGN, like `300+5`.
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}+{}", self.budget.as_secs(), self.increment.as_secs())
+    }
+}
+
+/// What happens when a player runs out of time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TimeoutPolicy {
+    /// They lose the game.
+    Forfeit,
+    /// The engine plays the best move it sees at a glance for them, on
+    /// every turn they have no time for.
+    GreedyMove,
+}
+
+impl TimeoutPolicy {
+    pub fn all() -> [TimeoutPolicy; 2] {
+        [TimeoutPolicy::Forfeit, TimeoutPolicy::GreedyMove]
+    }
+
+    /// The name of the policy in settings and saved games.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TimeoutPolicy::Forfeit => "forfeit",
+            TimeoutPolicy::GreedyMove => "greedy-move",
+        }
+    }
+
+    /// The inverse of `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        TimeoutPolicy::all().iter().cloned().find(|p| p.name() == name)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Clock {
+    /// The time X and O had left when they last stopped thinking.
+    left: [Duration; 2],
+    /// The time X and O get after each of their moves.
+    increments: [Duration; 2],
+    /// Who's thinking, and since when, if anyone.
+    running: Option<(CheckBox, Instant)>,
+    /// Whether the time of X and O is paused.
+    paused: [bool; 2],
+    /// Whether the time of X and O stops while they're away, which is up to
+    /// whoever pauses it.
+    pauses_when_away: [bool; 2],
+}
+
+impl Clock {
+    /// Returns a stopped clock, with `budget` for each player.
+    pub fn new(budget: Duration) -> Self {
+        let control = TimeControl { budget, increment: Duration::default() };
+        Self::with_time_controls([control; 2])
+    }
+
+    /// Returns a stopped clock, with the time `controls` of X and O, in that
+    /// order.
+    pub fn with_time_controls(controls: [TimeControl; 2]) -> Self {
+        Self {
+            left: [controls[0].budget, controls[1].budget],
+            increments: [controls[0].increment, controls[1].increment],
+            running: None,
+            paused: [false; 2],
+            pauses_when_away: [false; 2],
+        }
+    }
+
+    /// Makes the time of `player` stop while they're away, like while the
+    /// window is in the background, or not. Only players the clock knows
+    /// to be people should.
+    pub fn set_pauses_when_away(&mut self, player: CheckBox, pauses: bool) {
+        self.pauses_when_away[index(player)] = pauses;
+    }
+
+    /// Returns whether the time of `player` stops while they're away.
+    pub fn pauses_when_away(&self, player: CheckBox) -> bool {
+        self.pauses_when_away[index(player)]
+    }
+
+    /// Makes the time of `player` run from `now` on, or stops the clock if
+    /// `player` is empty. Whoever was thinking until then is charged for it,
+    /// and gets their increment unless they ran out of time.
+    pub fn run(&mut self, player: CheckBox, now: Instant) {
+        if let Some((running, _)) = self.running {
+            if running == player {
+                return;
+            }
+            self.charge(now);
+            let left = &mut self.left[index(running)];
+            if *left > Duration::default() {
+                *left += self.increments[index(running)];
+            }
+        }
+        self.running = match player {
+            CheckBox::Empty => None,
+            player => Some((player, now)),
+        };
+    }
+
+    /// Stops the time of `player` at `now` until `resume`, even while
+    /// they're thinking.
+    pub fn pause(&mut self, player: CheckBox, now: Instant) {
+        self.charge(now);
+        self.paused[index(player)] = true;
+    }
+
+    /// Makes the time of `player` run again from `now` on, if they're
+    /// thinking.
+    pub fn resume(&mut self, player: CheckBox, now: Instant) {
+        self.charge(now);
+        self.paused[index(player)] = false;
+    }
+
+    /// Returns whether the time of `player` is paused.
+    pub fn is_paused(&self, player: CheckBox) -> bool {
+        self.paused[index(player)]
+    }
+
+    /// Charges whoever is thinking for the time until `now`, unless their
+    /// time is paused, and counts from `now` on.
+    fn charge(&mut self, now: Instant) {
+        let player = match self.running {
+            Some((player, _)) => player,
+            None => return,
+        };
+        self.left[index(player)] = self.left(player, now);
+        self.running = Some((player, now));
+    }
+
+    /// Returns the time `player` has left at `now`.
+    pub fn left(&self, player: CheckBox, now: Instant) -> Duration {
+        let left = self.left[index(player)];
+        match self.running {
+            Some((running, since)) if running == player && !self.is_paused(player) => {
+                left.checked_sub(now.duration_since(since)).unwrap_or_default()
+            }
+            _ => left,
+        }
+    }
+
+    /// Returns whether `player` ran out of time by `now`.
+    pub fn is_out_of_time(&self, player: CheckBox, now: Instant) -> bool {
+        self.left(player, now) == Duration::default()
+    }
+
+    /// Returns how long `player` can think about their next move at `now`,
+    /// saving time for the rest of the game.
+    pub fn move_time(&self, player: CheckBox, now: Instant) -> Duration {
+        let left = self.left(player, now);
+        (left / MOVES_TO_GO + self.increments[index(player)]).min(left)
+    }
+}
+
+/// Formats `time` like clocks show it, with tenths of a second when there
+/// are less than ten seconds left.
+pub fn format(time: Duration) -> String {
+    let secs = time.as_secs();
+    if secs < 10 {
+        format!("0:0{}.{}", secs, time.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn charges_whoever_is_thinking() {
+        let start = Instant::now();
+        let secs = |s| start + Duration::from_secs(s);
+        let mut clock = Clock::new(Duration::from_secs(60));
+        assert_eq!(clock.left(CheckBox::X, secs(5)), Duration::from_secs(60));
+
+        clock.run(CheckBox::X, start);
+        // Telling the clock again who's thinking changes nothing.
+        clock.run(CheckBox::X, secs(5));
+        assert_eq!(clock.left(CheckBox::X, secs(10)), Duration::from_secs(50));
+        clock.run(CheckBox::O, secs(10));
+        assert_eq!(clock.left(CheckBox::X, secs(30)), Duration::from_secs(50));
+        assert_eq!(clock.left(CheckBox::O, secs(30)), Duration::from_secs(40));
+        assert_eq!(clock.move_time(CheckBox::O, secs(30)), Duration::from_secs(2));
+        assert!(!clock.is_out_of_time(CheckBox::O, secs(69)));
+        assert!(clock.is_out_of_time(CheckBox::O, secs(70)));
+        assert!(!clock.is_out_of_time(CheckBox::X, secs(70)));
+
+        clock.run(CheckBox::Empty, secs(40));
+        assert_eq!(clock.left(CheckBox::O, secs(100)), Duration::from_secs(30));
+
+        assert_eq!(format(Duration::from_secs(75)), "1:15");
+        assert_eq!(format(Duration::from_millis(9_450)), "0:09.4");
+    }
+
+    #[test]
+    fn stops_while_paused() {
+        let start = Instant::now();
+        let secs = |s| start + Duration::from_secs(s);
+        let mut clock = Clock::new(Duration::from_secs(60));
+        assert!(!clock.pauses_when_away(CheckBox::X));
+        clock.set_pauses_when_away(CheckBox::X, true);
+        assert!(clock.pauses_when_away(CheckBox::X));
+        assert!(!clock.pauses_when_away(CheckBox::O));
+        clock.run(CheckBox::X, start);
+        clock.pause(CheckBox::X, secs(10));
+        assert!(clock.is_paused(CheckBox::X));
+        assert_eq!(clock.left(CheckBox::X, secs(100)), Duration::from_secs(50));
+        assert!(!clock.is_out_of_time(CheckBox::X, secs(1000)));
+
+        // Only the time of the paused player stops.
+        clock.run(CheckBox::O, secs(20));
+        assert_eq!(clock.left(CheckBox::O, secs(30)), Duration::from_secs(50));
+        clock.run(CheckBox::X, secs(30));
+        assert_eq!(clock.left(CheckBox::X, secs(40)), Duration::from_secs(50));
+
+        clock.resume(CheckBox::X, secs(40));
+        assert!(!clock.is_paused(CheckBox::X));
+        assert_eq!(clock.left(CheckBox::X, secs(45)), Duration::from_secs(45));
+    }
+
+    #[test]
+    fn adds_the_increment_of_each_player() {
+        let start = Instant::now();
+        let secs = |s| start + Duration::from_secs(s);
+        let human = TimeControl::parse("30+5").unwrap();
+        let engine = TimeControl::parse("1+1").unwrap();
+        assert_eq!(human, TimeControl::from_secs(30, 5));
+        assert_eq!(human.to_string(), "30+5");
+        assert_eq!(TimeControl::parse("60"), Some(TimeControl::from_secs(60, 0)));
+        assert_eq!(TimeControl::parse("60+"), None);
+
+        let mut clock = Clock::with_time_controls([human, engine]);
+        assert_eq!(clock.move_time(CheckBox::O, start), Duration::from_millis(1000));
+        clock.run(CheckBox::X, start);
+        clock.run(CheckBox::O, secs(10));
+        assert_eq!(clock.left(CheckBox::X, secs(10)), Duration::from_secs(25));
+        clock.run(CheckBox::X, start + Duration::from_millis(10_500));
+        assert_eq!(clock.left(CheckBox::O, secs(11)), Duration::from_millis(1500));
+
+        // No increment saves a player who ran out of time.
+        clock.run(CheckBox::O, secs(40));
+        assert_eq!(clock.left(CheckBox::X, secs(40)), Duration::default());
+        assert!(clock.is_out_of_time(CheckBox::X, secs(40)));
+    }
+
+    #[test]
+    fn tells_apart_who_ran_out_of_time() {
+        let start = Instant::now();
+        let secs = |s| start + Duration::from_secs(s);
+        let mut clock = Clock::new(Duration::from_secs(10));
+        clock.run(CheckBox::X, start);
+        clock.run(CheckBox::O, secs(20));
+        assert!(clock.is_out_of_time(CheckBox::X, secs(20)));
+        assert!(!clock.is_out_of_time(CheckBox::O, secs(25)));
+
+        // Someone who moves out of time doesn't hide the other one running
+        // out of it too.
+        clock.run(CheckBox::X, secs(30));
+        assert!(clock.is_out_of_time(CheckBox::O, secs(30)));
+        assert!(clock.is_out_of_time(CheckBox::X, secs(30)));
+    }
+}
@@ -0,0 +1,147 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A small command interpreter to inspect and drive a game from text.
+
+use game::{Game, Job};
+use game_state::{GameState, Savable};
+use minimax::{Difficulty, MiniMaxTree, SearchConfig};
+use state::CheckBox;
+
+const HELP: &str = "\
+show                      print the board
+eval depth <n>            search <n> levels and print the best move, and
+                          the line of play expected after it
+eval depth <n> margin <m> print a move at most <m> worse than the best one
+dump tree <n>             print the searched tree, up to <n> levels
+setoption difficulty <d>  set the difficulty (Easy, Medium, Hard, Perfect,
+                          Auto, Adaptive)
+loadpos [<move> ...]      start a new game with the given moves, written
+                          like in saved games, like 1,1 in tic-tac-toe
+help                      print this help";
+
+/// Runs a single command against `game`.
+///
+/// Returns the output of the command, or a description of what was wrong
+/// with it.
+pub fn execute<S: Savable>(game: &mut Game<S>, line: &str) -> Result<String, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    if let Some(search) = parse_search::<S>(&words) {
+        return search?.run(game.tree_mut());
+    }
+    match words.as_slice() {
+        [] => Ok(String::new()),
+        ["help"] => Ok(HELP.to_owned()),
+        ["show"] => {
+            let mut out = String::new();
+            game.state().dump(0, &mut out).unwrap();
+            Ok(out)
+        }
+        ["dump", "tree", levels] => {
+            let levels = levels.parse::<usize>()
+                .map_err(|_| format!("invalid number of levels: {}", levels))?;
+            let mut out = String::new();
+            game.tree_mut().dump_up_to(levels, &mut out).unwrap();
+            Ok(out)
+        }
+        ["setoption", "difficulty", name] => {
+            let difficulty = Difficulty::from_name(name)
+                .ok_or_else(|| format!("unknown difficulty: {}", name))?;
+            game.set_difficulty(difficulty);
+            Ok(String::new())
+        }
+        ["loadpos", moves @ ..] => {
+            // Games always start with X.
+            let mut new_game = Game::<S>::new(CheckBox::X);
+            for &player in &[CheckBox::X, CheckBox::O] {
+                new_game.set_player_config(player, game.player_config(player).clone())?;
+            }
+            for m in moves {
+                let mv = S::read_move(m)
+                    .ok_or_else(|| format!("invalid move: {}", m))?;
+                new_game.choose(mv)
+                    .map_err(|_| format!("illegal move: {}", m))?;
+            }
+            *game = new_game;
+            Ok(String::new())
+        }
+        _ => Err(format!("unknown command: {} (try \"help\")", line.trim())),
+    }
+}
+
+/// Returns a job that runs `line` on another thread if it's an `eval`
+/// command, which only searches, or `None` if it's up to `execute`.
+///
+/// The job searches a tree of its own, so it doesn't change the game, nor
+/// reuse what the game searched already.
+pub fn search_job<S: Savable>(game: &Game<S>, line: &str) -> Option<Job<Result<String, String>>> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let search = parse_search::<S>(&words)?;
+    let mut tree = game.engine_tree(game.player());
+    Some(Box::new(move || search?.run(&mut tree)))
+}
+
+/// An `eval` command.
+enum Search {
+    /// Prints the best move and the line of play after it.
+    Eval(usize),
+    /// Prints a move at most the margin worse than the best one.
+    EvalWithin(usize, i8),
+}
+
+/// Parses the command in `words` if it's an `eval` one.
+fn parse_search<S: GameState>(words: &[&str]) -> Option<Result<Search, String>> {
+    Some(match *words {
+        ["eval", "depth", depth] => parse_depth::<S>(depth).map(Search::Eval),
+        ["eval", "depth", depth, "margin", margin] => parse_depth::<S>(depth).and_then(|depth| {
+            match margin.parse::<i8>() {
+                Ok(margin) if margin >= 0 => Ok(Search::EvalWithin(depth, margin)),
+                _ => Err(format!("invalid margin: {}", margin)),
+            }
+        }),
+        _ => return None,
+    })
+}
+
+impl Search {
+    fn run<S: Savable>(self, tree: &mut MiniMaxTree<S>) -> Result<String, String> {
+        match self {
+            Search::Eval(depth) => match tree.find_move_with_pv(depth) {
+                Some((mv, pv, score)) => {
+                    let pv = pv.iter().map(|&mv| S::write_move(mv)).collect::<Vec<_>>();
+                    Ok(format!("bestmove {} score {} nodes {} pv {}",
+                               S::write_move(mv), score, tree.nodes_visited(), pv.join(" ")))
+                }
+                None => Ok("no move, the game is over".to_owned()),
+            },
+            Search::EvalWithin(depth, margin) => match tree.find_move_within_margin(depth, margin) {
+                Some((index, score)) => {
+                    let mv = tree.move_for_index(index);
+                    Ok(format!("move {} score {} nodes {}",
+                               S::write_move(mv), score, tree.nodes_visited()))
+                }
+                None => Ok("no move, the game is over".to_owned()),
+            },
+        }
+    }
+}
+
+fn parse_depth<S: GameState>(depth: &str) -> Result<usize, String> {
+    SearchConfig::parse(depth, S::depth_limit())
+        .map(|config| config.depth())
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,292 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The configuration file, which is a small subset of TOML:
+//!
+//! ```text
+//! version = 1
+//!
+//! [theme]
+//! x-glyph = "X"
+//! font-size = 14
+//! ```
+//!
+//! That is, comment lines, section headers, and keys with string, integer or
+//! boolean values.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The version of the configuration file this build writes.
+///
+/// Files of older versions are migrated when loading, and newer versions are
+/// refused.
+pub const CONFIG_VERSION: i64 = 1;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::String(ref s) => {
+                f.write_str("\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\t' => f.write_str("\\t")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                f.write_str("\"")
+            }
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    /// A line of the file (counting from 1) is not valid.
+    Syntax(usize),
+    UnsupportedVersion(i64),
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref err) => write!(f, "{}", err),
+            ConfigError::Syntax(line) => write!(f, "invalid syntax at line {}", line),
+            ConfigError::UnsupportedVersion(version) => {
+                write!(f, "unsupported config version {}", version)
+            }
+        }
+    }
+}
+
+/// Returns where the configuration file lives, following the XDG base
+/// directory spec.
+pub fn default_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("tic-tac-minimax").join("config.toml"))
+}
+
+fn parse_value(s: &str) -> Option<Value> {
+    if s == "true" || s == "false" {
+        return Some(Value::Boolean(s == "true"));
+    }
+    if let Ok(i) = s.parse::<i64>() {
+        return Some(Value::Integer(i));
+    }
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+        return None;
+    }
+
+    let mut ret = String::new();
+    let mut chars = s[1..s.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        ret.push(match c {
+            '"' => return None,
+            '\\' => match chars.next()? {
+                '"' => '"',
+                '\\' => '\\',
+                'n' => '\n',
+                't' => '\t',
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(Value::String(ret))
+}
+
+/// The settings in a configuration file, by section and key. Keys before any
+/// section header are in the "" section.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    sections: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+impl Config {
+    pub fn parse(s: &str) -> Result<Self, ConfigError> {
+        let mut config = Config::default();
+        let mut section = String::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len() - 1].trim().to_owned();
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let value = parts.next().and_then(|v| parse_value(v.trim()));
+            match value {
+                Some(value) if !key.is_empty() => config.set(&section, key, value),
+                _ => return Err(ConfigError::Syntax(i + 1)),
+            }
+        }
+
+        match config.get_integer("", "version") {
+            Some(CONFIG_VERSION) => {}
+            // TODO(emilio): Migrate older versions once there are any.
+            Some(version) => return Err(ConfigError::UnsupportedVersion(version)),
+            None => return Err(ConfigError::UnsupportedVersion(0)),
+        }
+        Ok(config)
+    }
+
+    /// Loads the configuration at `path`, or returns an empty one if there's
+    /// no such file.
+    pub fn load<P>(path: P) -> Result<Self, ConfigError>
+        where P: AsRef<Path>,
+    {
+        let mut contents = String::new();
+        match File::open(path) {
+            Ok(mut file) => file.read_to_string(&mut contents)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Config::default());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        Self::parse(&contents)
+    }
+
+    /// Saves the configuration at `path`, creating its directory if needed.
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+        where P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = File::create(path)?;
+        write!(file, "{}", self)
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&Value> {
+        self.sections.get(section)?.get(key)
+    }
+
+    pub fn get_string(&self, section: &str, key: &str) -> Option<&str> {
+        match self.get(section, key) {
+            Some(Value::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn get_integer(&self, section: &str, key: &str) -> Option<i64> {
+        match self.get(section, key) {
+            Some(&Value::Integer(i)) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn get_boolean(&self, section: &str, key: &str) -> Option<bool> {
+        match self.get(section, key) {
+            Some(&Value::Boolean(b)) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, section: &str, key: &str, value: Value) {
+        self.sections.entry(section.to_owned())
+            .or_default()
+            .insert(key.to_owned(), value);
+    }
+}
+
+impl fmt::Display for Config {
+    /// Writes the configuration in the file format, always with the current
+    /// version.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "version = {}", CONFIG_VERSION)?;
+        for (name, section) in &self.sections {
+            if !name.is_empty() {
+                writeln!(f, "\n[{}]", name)?;
+            }
+            for (key, value) in section {
+                if name.is_empty() && key == "version" {
+                    continue;
+                }
+                writeln!(f, "{} = {}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let mut config = Config::default();
+        config.set("theme", "x-glyph", Value::String("\"✗\"\\\n".to_owned()));
+        config.set("theme", "font-size", Value::Integer(-3));
+        config.set("engine", "fast", Value::Boolean(true));
+        config.set("", "top", Value::Integer(1));
+
+        let written = config.to_string();
+        assert!(written.starts_with("version = 1\ntop = 1\n"), "{}", written);
+        let parsed = Config::parse(&written).unwrap();
+        assert_eq!(parsed.to_string(), written);
+        assert_eq!(parsed.get_string("theme", "x-glyph"), Some("\"✗\"\\\n"));
+        assert_eq!(parsed.get_integer("theme", "font-size"), Some(-3));
+        assert_eq!(parsed.get_boolean("engine", "fast"), Some(true));
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        match Config::parse("version = 1\nkey = unquoted\n") {
+            Err(ConfigError::Syntax(2)) => {}
+            other => panic!("{:?}", other),
+        }
+        match Config::parse("version = 2\n") {
+            Err(ConfigError::UnsupportedVersion(2)) => {}
+            other => panic!("{:?}", other),
+        }
+        match Config::parse("# No version\n[theme]\n") {
+            Err(ConfigError::UnsupportedVersion(0)) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Connect Four: players drop their marks into the columns of an upright
+//! 7x6 grid, and the first one to get four in a row wins.
+
+use game_state::{GameResult, GameState, GridState, MoveError, Savable};
+use state::CheckBox;
+use std::fmt;
+use variant::Variant;
+
+const COLUMNS: usize = 7;
+const ROWS: usize = 6;
+
+/// The directions a line of four can go in, as (column, row) steps.
+const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+
+/// Returns all the lines of four squares in the grid, as (column, row)
+/// squares, with row 0 at the bottom.
+fn windows() -> Vec<[(usize, usize); 4]> {
+    let mut ret = vec![];
+    for x in 0..COLUMNS as isize {
+        for y in 0..ROWS as isize {
+            for &(dx, dy) in DIRECTIONS.iter() {
+                let (end_x, end_y) = (x + 3 * dx, y + 3 * dy);
+                if end_x < 0 || end_x >= COLUMNS as isize ||
+                    end_y < 0 || end_y >= ROWS as isize {
+                    continue;
+                }
+                let mut window = [(0, 0); 4];
+                for (i, square) in window.iter_mut().enumerate() {
+                    *square = ((x + i as isize * dx) as usize,
+                               (y + i as isize * dy) as usize);
+                }
+                ret.push(window);
+            }
+        }
+    }
+    ret
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConnectFourState {
+    /// The squares of each column, from the bottom up.
+    columns: [[CheckBox; ROWS]; COLUMNS],
+    /// The number of marks in each column.
+    heights: [usize; COLUMNS],
+}
+
+impl ConnectFourState {
+    /// Returns the line of four that won the game, if any.
+    fn winning_window(&self) -> Option<[(usize, usize); 4]> {
+        windows().into_iter().find(|window| {
+            let first = self.columns[window[0].0][window[0].1];
+            first != CheckBox::Empty &&
+                window.iter().all(|&(x, y)| self.columns[x][y] == first)
+        })
+    }
+}
+
+impl GameState for ConnectFourState {
+    /// The column to drop the mark into.
+    type Move = usize;
+
+    fn initial() -> Self {
+        Self {
+            columns: [[CheckBox::Empty; ROWS]; COLUMNS],
+            heights: [0; COLUMNS],
+        }
+    }
+
+    fn winner(&self) -> CheckBox {
+        self.winning_window().map_or(CheckBox::Empty, |window| {
+            self.columns[window[0].0][window[0].1]
+        })
+    }
+
+    fn successors(&self, player: CheckBox) -> Vec<(Self::Move, Self)> {
+        if self.winner() != CheckBox::Empty {
+            return vec![];
+        }
+        (0..COLUMNS).filter(|x| self.heights[*x] != ROWS).map(|x| {
+            let mut ret = self.clone();
+            ret.columns[x][ret.heights[x]] = player;
+            ret.heights[x] += 1;
+            (x, ret)
+        }).collect()
+    }
+
+    /// Lines of four that only one player has marks in could still be
+    /// completed, so count them, weighting the fuller ones more.
+    fn evaluate(&self) -> i8 {
+        const WEIGHTS: [i32; 5] = [0, 0, 1, 4, 0];
+
+        let mut score = 0;
+        for window in windows() {
+            let count = |player| {
+                window.iter().filter(|&&(x, y)| self.columns[x][y] == player).count()
+            };
+            let (x, o) = (count(CheckBox::X), count(CheckBox::O));
+            if x == 4 || o == 4 {
+                return self.columns[window[0].0][window[0].1] as i8;
+            }
+            match (x, o) {
+                (0, o) => score += WEIGHTS[o],
+                (x, 0) => score -= WEIGHTS[x],
+                _ => {}
+            }
+        }
+        (score / 4).clamp(-9, 9) as i8
+    }
+
+    /// The central columns are part of more lines, so try them first.
+    fn move_priority(&self, mv: Self::Move) -> u8 {
+        (mv as isize - COLUMNS as isize / 2).unsigned_abs() as u8
+    }
+
+    fn move_error(&self, column: Self::Move) -> MoveError {
+        if column >= COLUMNS {
+            MoveError::OutOfBounds
+        } else if self.heights[column] == ROWS {
+            MoveError::Occupied
+        } else {
+            MoveError::Illegal
+        }
+    }
+
+    fn is_center_move(&self, column: Self::Move) -> bool {
+        column == COLUMNS / 2
+    }
+
+    fn depth_limit() -> usize {
+        6
+    }
+
+    /// Like the default, without generating the successors.
+    fn result(&self) -> GameResult {
+        match self.winner() {
+            CheckBox::X => GameResult::XWins,
+            CheckBox::O => GameResult::OWins,
+            CheckBox::Empty if self.heights.iter().any(|&h| h != ROWS) => GameResult::InProgress,
+            CheckBox::Empty => GameResult::Draw,
+        }
+    }
+
+    fn dump<W>(&self, indent: usize, dest: &mut W) -> fmt::Result
+        where W: fmt::Write,
+    {
+        for y in 0..ROWS {
+            for _ in 0..indent {
+                dest.write_char(' ')?;
+            }
+            for x in 0..COLUMNS {
+                dest.write_char(self.mark(x, y).dump_char())?;
+            }
+            dest.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+impl GridState for ConnectFourState {
+    fn grid_size(&self) -> (usize, usize) {
+        (COLUMNS, ROWS)
+    }
+
+    /// Row `y` counts from the top, like in the GUI.
+    fn mark(&self, x: usize, y: usize) -> CheckBox {
+        self.columns[x][ROWS - 1 - y]
+    }
+
+    /// Clicking anywhere in a column drops a mark into it.
+    fn move_for_square(&self, x: usize, _y: usize) -> Option<Self::Move> {
+        if self.heights[x] != ROWS && self.winner() == CheckBox::Empty {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    fn winning_squares(&self) -> Vec<(usize, usize)> {
+        self.winning_window().map_or(vec![], |window| {
+            window.iter().map(|&(x, y)| (x, ROWS - 1 - y)).collect()
+        })
+    }
+}
+
+impl Savable for ConnectFourState {
+    fn variant() -> Variant {
+        Variant::ConnectFour
+    }
+
+    fn write_move(column: Self::Move) -> String {
+        column.to_string()
+    }
+
+    fn read_move(s: &str) -> Option<Self::Move> {
+        s.parse().ok()
+    }
+}
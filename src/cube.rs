@@ -0,0 +1,375 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tic-tac-toe in three dimensions: a cube of layers stacked on top of each
+//! other, where a line can go across a layer, down through the layers, or
+//! along any of the diagonals in between.
+//!
+//! The 3x3x3 cube is an easy win for whoever takes the center first, the
+//! 4x4x4 one is Qubic, which is much harder to search.
+//!
+//! Squares are numbered `x + size * y + size * size * layer`, and the grid
+//! shows the layers side by side, from the top one.
+
+use game_state::{GameResult, GameState, GridState, MoveError, Savable};
+use state::CheckBox;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+use variant::Variant;
+
+/// The biggest cube there is, so that states fit in an array.
+const MAX_SQUARES: usize = 64;
+
+/// The lines of a cube, and which of them go through each square.
+#[derive(Debug)]
+pub struct Geometry {
+    lines: Vec<Vec<usize>>,
+    lines_through: Vec<Vec<usize>>,
+}
+
+impl Geometry {
+    fn new(size: usize) -> Self {
+        let size = size as isize;
+        let mut lines = vec![];
+        // Every direction, but only one of each pair of opposite ones.
+        for dx in -1..2 {
+            for dy in -1..2 {
+                for dz in -1..2 {
+                    if (dx, dy, dz) <= (0, 0, 0) {
+                        continue;
+                    }
+                    for start in 0..size * size * size {
+                        let (x, y, z) = (start % size, start / size % size, start / (size * size));
+                        let end = (x + (size - 1) * dx, y + (size - 1) * dy, z + (size - 1) * dz);
+                        let inside = |c: isize| c >= 0 && c < size;
+                        // Lines start on a face, so the square before the
+                        // start is off the cube.
+                        if !inside(end.0) || !inside(end.1) || !inside(end.2) ||
+                            (inside(x - dx) && inside(y - dy) && inside(z - dz)) {
+                            continue;
+                        }
+                        lines.push((0..size).map(|i| {
+                            ((x + i * dx) + size * (y + i * dy) + size * size * (z + i * dz)) as usize
+                        }).collect::<Vec<_>>());
+                    }
+                }
+            }
+        }
+        let mut lines_through = vec![vec![]; (size * size * size) as usize];
+        for (index, line) in lines.iter().enumerate() {
+            for &square in line {
+                lines_through[square].push(index);
+            }
+        }
+        Self {
+            lines,
+            lines_through,
+        }
+    }
+}
+
+/// The size of a cube, as a type.
+pub trait CubeSize: Clone + PartialEq + fmt::Debug + Send + 'static {
+    fn size() -> usize;
+
+    /// How deep the engine can search in reasonable time.
+    fn depth_limit() -> usize;
+
+    fn geometry() -> &'static Geometry;
+
+    fn variant() -> Variant;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Three;
+
+impl CubeSize for Three {
+    fn size() -> usize {
+        3
+    }
+
+    fn depth_limit() -> usize {
+        4
+    }
+
+    fn geometry() -> &'static Geometry {
+        static GEOMETRY: OnceLock<Geometry> = OnceLock::new();
+        GEOMETRY.get_or_init(|| Geometry::new(Self::size()))
+    }
+
+    fn variant() -> Variant {
+        Variant::Cube
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Four;
+
+impl CubeSize for Four {
+    fn size() -> usize {
+        4
+    }
+
+    fn depth_limit() -> usize {
+        3
+    }
+
+    fn geometry() -> &'static Geometry {
+        static GEOMETRY: OnceLock<Geometry> = OnceLock::new();
+        GEOMETRY.get_or_init(|| Geometry::new(Self::size()))
+    }
+
+    fn variant() -> Variant {
+        Variant::Qubic
+    }
+}
+
+pub type CubeState = State3D<Three>;
+pub type QubicState = State3D<Four>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct State3D<C: CubeSize> {
+    squares: [CheckBox; MAX_SQUARES],
+    /// The line that won the game, if any.
+    winning_line: Option<usize>,
+    size: PhantomData<C>,
+}
+
+impl<C: CubeSize> State3D<C> {
+    fn square_count() -> usize {
+        C::size() * C::size() * C::size()
+    }
+
+    /// Returns the square shown in column `x` and row `y` of the grid.
+    fn square_at(x: usize, y: usize) -> usize {
+        let size = C::size();
+        x % size + size * y + size * size * (x / size)
+    }
+
+    /// Returns the column and row of the grid `square` is shown in.
+    fn grid_position(square: usize) -> (usize, usize) {
+        let size = C::size();
+        let layer = square / (size * size);
+        (square % size + size * layer, square / size % size)
+    }
+
+    fn play(&self, square: usize, player: CheckBox) -> Self {
+        let mut ret = self.clone();
+        ret.squares[square] = player;
+        let geometry = C::geometry();
+        ret.winning_line = geometry.lines_through[square].iter().cloned().find(|&line| {
+            geometry.lines[line].iter().all(|&s| ret.squares[s] == player)
+        });
+        ret
+    }
+}
+
+impl<C: CubeSize> GameState for State3D<C> {
+    /// The square to mark.
+    type Move = usize;
+
+    fn initial() -> Self {
+        Self {
+            squares: [CheckBox::Empty; MAX_SQUARES],
+            winning_line: None,
+            size: PhantomData,
+        }
+    }
+
+    fn winner(&self) -> CheckBox {
+        self.winning_line.map_or(CheckBox::Empty, |line| {
+            self.squares[C::geometry().lines[line][0]]
+        })
+    }
+
+    fn successors(&self, player: CheckBox) -> Vec<(Self::Move, Self)> {
+        if self.winning_line.is_some() {
+            return vec![];
+        }
+        (0..Self::square_count())
+            .filter(|&square| self.squares[square] == CheckBox::Empty)
+            .map(|square| (square, self.play(square, player)))
+            .collect()
+    }
+
+    /// Lines that only one player has marks in could still be completed, so
+    /// count them, weighting the ones a mark away from done the most.
+    fn evaluate(&self) -> i8 {
+        if self.winning_line.is_some() {
+            return self.winner() as i8;
+        }
+        let size = C::size();
+        let weight = |marks: usize| match size - marks {
+            1 => 4,
+            2 if marks > 0 => 1,
+            _ => 0,
+        };
+        let mut score = 0;
+        for line in &C::geometry().lines {
+            let count = |player| line.iter().filter(|&&s| self.squares[s] == player).count();
+            match (count(CheckBox::X), count(CheckBox::O)) {
+                (0, o) => score += weight(o),
+                (x, 0) => score -= weight(x),
+                _ => {}
+            }
+        }
+        (score / 4).clamp(-9, 9) as i8
+    }
+
+    /// The squares in more lines are better, so try them first.
+    fn move_priority(&self, square: Self::Move) -> u8 {
+        let geometry = C::geometry();
+        let most = geometry.lines_through.iter().map(|l| l.len()).max().unwrap_or(0);
+        (most - geometry.lines_through[square].len()) as u8
+    }
+
+    fn move_error(&self, square: Self::Move) -> MoveError {
+        if square >= Self::square_count() {
+            MoveError::OutOfBounds
+        } else if self.squares[square] != CheckBox::Empty {
+            MoveError::Occupied
+        } else {
+            MoveError::Illegal
+        }
+    }
+
+    /// The center is the middle square of the 3x3x3 cube, and any of the
+    /// eight middle ones of the 4x4x4 cube.
+    fn is_center_move(&self, square: Self::Move) -> bool {
+        let size = C::size() as isize;
+        let square = square as isize;
+        let central = |c: isize| (2 * c - (size - 1)).abs() <= 1;
+        central(square % size) && central(square / size % size) && central(square / (size * size))
+    }
+
+    fn depth_limit() -> usize {
+        C::depth_limit()
+    }
+
+    /// Like the default, without generating the successors.
+    fn result(&self) -> GameResult {
+        match self.winner() {
+            CheckBox::X => GameResult::XWins,
+            CheckBox::O => GameResult::OWins,
+            CheckBox::Empty if self.squares[..Self::square_count()].contains(&CheckBox::Empty) => {
+                GameResult::InProgress
+            }
+            CheckBox::Empty => GameResult::Draw,
+        }
+    }
+
+    /// Dumps the layers side by side, like the GUI shows them.
+    fn dump<W>(&self, indent: usize, dest: &mut W) -> fmt::Result
+        where W: fmt::Write,
+    {
+        let size = C::size();
+        for y in 0..size {
+            for _ in 0..indent {
+                dest.write_char(' ')?;
+            }
+            for x in 0..size * size {
+                if x != 0 && x % size == 0 {
+                    dest.write_char(' ')?;
+                }
+                dest.write_char(self.squares[Self::square_at(x, y)].dump_char())?;
+            }
+            dest.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+impl<C: CubeSize> GridState for State3D<C> {
+    fn grid_size(&self) -> (usize, usize) {
+        (C::size() * C::size(), C::size())
+    }
+
+    /// Each layer is a block.
+    fn block_size(&self) -> Option<usize> {
+        Some(C::size())
+    }
+
+    fn mark(&self, x: usize, y: usize) -> CheckBox {
+        self.squares[Self::square_at(x, y)]
+    }
+
+    fn move_for_square(&self, x: usize, y: usize) -> Option<Self::Move> {
+        let square = Self::square_at(x, y);
+        if self.winning_line.is_some() || self.squares[square] != CheckBox::Empty {
+            return None;
+        }
+        Some(square)
+    }
+
+    fn winning_squares(&self) -> Vec<(usize, usize)> {
+        self.winning_line.map_or(vec![], |line| {
+            C::geometry().lines[line].iter().map(|&s| Self::grid_position(s)).collect()
+        })
+    }
+}
+
+impl<C: CubeSize> Savable for State3D<C> {
+    fn variant() -> Variant {
+        C::variant()
+    }
+
+    fn write_move(square: Self::Move) -> String {
+        square.to_string()
+    }
+
+    fn read_move(s: &str) -> Option<Self::Move> {
+        s.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minimax::MiniMaxTree;
+
+    #[test]
+    fn finds_lines_across_the_layers() {
+        // Rows, columns and pillars, the diagonals of each of their planes,
+        // and the four that go through the middle of the cube.
+        assert_eq!(Three::geometry().lines.len(), 49);
+        assert_eq!(Four::geometry().lines.len(), 76);
+        assert_eq!(Three::geometry().lines_through[13].len(), 13);
+
+        // X goes from the top left of the top layer to the bottom right of
+        // the bottom one.
+        let mut state = QubicState::initial();
+        for &(x, o) in &[(0, 1), (21, 2), (42, 3)] {
+            state = state.play(x, CheckBox::X).play(o, CheckBox::O);
+        }
+        assert_eq!(state.winner(), CheckBox::Empty);
+        state = state.play(63, CheckBox::X);
+        assert_eq!(state.winner(), CheckBox::X);
+        assert!(state.successors(CheckBox::O).is_empty());
+        assert_eq!(state.winning_squares(), [(0, 0), (5, 1), (10, 2), (15, 3)]);
+        assert_eq!(state.mark(5, 1), CheckBox::X);
+        assert!(state.is_center_move(21) && !state.is_center_move(0));
+
+        // The engine completes the line rather than looking elsewhere.
+        let mut tree = MiniMaxTree::<QubicState>::new(CheckBox::X);
+        for &mv in &[0, 1, 21, 2, 42, 3] {
+            tree.choose(mv).unwrap();
+        }
+        let (index, score) = tree.find_best_move(QubicState::depth_limit()).unwrap();
+        assert_eq!((tree.move_for_index(index), score), (63, -10));
+    }
+}
@@ -0,0 +1,276 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The turn loop of a game, as a stream of what happens in it, so that every
+//! kind of frontend plays games the same way.
+//!
+//! The driver plays the engine's moves itself, and stops when it's the turn
+//! of someone else, be it a human at the keyboard or the other side of a
+//! connection:
+//!
+//! ```
+//! extern crate tic_tac_minimax;
+//!
+//! use tic_tac_minimax::driver::{GameDriver, GameEvent};
+//! use tic_tac_minimax::frontend::Click;
+//! use tic_tac_minimax::prelude::*;
+//!
+//! let mut game = Variant::TicTacToe.new_game(CheckBox::X);
+//! let mut rng = tic_tac_minimax::rand::thread_rng();
+//! let mut driver = GameDriver::new(game.board_mut(), &mut rng);
+//! driver.set_engine(CheckBox::O, true);
+//! let mut squares = (0..3).flat_map(|y| (0..3).map(move |x| (x, y)));
+//! while let Some(event) = driver.next() {
+//!     if let GameEvent::AwaitingInput(_) = event {
+//!         // Play the first square that's free.
+//!         while let Some((x, y)) = squares.next() {
+//!             if driver.submit(Click { x: x, y: y, other_mark: false }).is_ok() {
+//!                 break;
+//!             }
+//!         }
+//!     }
+//! }
+//! assert!(driver.board().result() != GameResult::InProgress);
+//! ```
+
+use frontend::Click;
+use game::Board;
+use game_state::{GameResult, MoveError};
+use rand::RngCore;
+use state::CheckBox;
+use std::collections::VecDeque;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum GameEvent {
+    /// The player marked the square with the given name, as
+    /// `Board::move_names` names them.
+    MoveMade(CheckBox, String),
+    /// It's the turn of a player the engine doesn't play.
+    AwaitingInput(CheckBox),
+    /// The player offered a draw.
+    DrawOffered(CheckBox),
+    /// The player turned down the draw their opponent offered.
+    DrawDeclined(CheckBox),
+    /// The game ended. This is the last event.
+    GameOver(GameResult),
+}
+
+/// Plays a game on a board, with the engine playing the players it's told
+/// to.
+///
+/// Once it's waiting for input, the driver returns `None` until `submit`
+/// plays a move, so a `while let` loop over it ends at each human turn, and
+/// can pick up where it left off after.
+pub struct GameDriver<'a> {
+    board: &'a mut dyn Board,
+    rng: &'a mut dyn RngCore,
+    /// Whether the engine plays X and O, in that order.
+    engine: [bool; 2],
+    /// What happened that wasn't returned yet.
+    pending: VecDeque<GameEvent>,
+    /// Whether the player to move was told to move, or the game being over
+    /// was told.
+    waiting: bool,
+}
+
+fn side(player: CheckBox) -> usize {
+    match player {
+        CheckBox::X => 0,
+        _ => 1,
+    }
+}
+
+impl<'a> GameDriver<'a> {
+    /// Drives the game on `board`, with the engine playing nobody yet.
+    pub fn new(board: &'a mut dyn Board, rng: &'a mut dyn RngCore) -> Self {
+        Self {
+            board,
+            rng,
+            engine: [false, false],
+            pending: VecDeque::new(),
+            waiting: false,
+        }
+    }
+
+    /// Sets whether the engine plays the moves of `player`.
+    pub fn set_engine(&mut self, player: CheckBox, engine: bool) {
+        self.engine[side(player)] = engine;
+    }
+
+    pub fn board(&self) -> &dyn Board {
+        &*self.board
+    }
+
+    /// The board, to change how the engine plays between moves.
+    pub fn board_mut(&mut self) -> &mut dyn Board {
+        &mut *self.board
+    }
+
+    /// Plays `click` for the player to move, whether or not the driver is
+    /// waiting for them.
+    pub fn submit(&mut self, click: Click) -> Result<(), MoveError> {
+        if click.other_mark {
+            self.board.click_other(click.x, click.y)?;
+        } else {
+            self.board.click(click.x, click.y)?;
+        }
+        self.made_move();
+        Ok(())
+    }
+
+    /// Resigns the game for `player`, whether or not it's their turn.
+    pub fn resign(&mut self, player: CheckBox) {
+        self.board.resign(player);
+        self.waiting = false;
+    }
+
+    /// Offers a draw from `player`, which the engine answers right away if
+    /// it plays their opponent.
+    ///
+    /// Returns whether it was offered, see `Board::offer_draw`.
+    pub fn offer_draw(&mut self, player: CheckBox) -> bool {
+        if !self.board.offer_draw(player) {
+            return false;
+        }
+        self.pending.push_back(GameEvent::DrawOffered(player));
+        let opponent = player.next_player();
+        if self.engine[side(opponent)] {
+            let accept = self.board.engine_accepts_draw(opponent);
+            self.answer_draw_offer(opponent, accept);
+        }
+        true
+    }
+
+    /// Answers the draw offer of the opponent of `player`.
+    ///
+    /// Returns whether there was one.
+    pub fn answer_draw_offer(&mut self, player: CheckBox, accept: bool) -> bool {
+        if !self.board.answer_draw_offer(player, accept) {
+            return false;
+        }
+        if accept {
+            self.waiting = false;
+        } else {
+            self.pending.push_back(GameEvent::DrawDeclined(player));
+        }
+        true
+    }
+
+    /// Queues the event of the move that was just played.
+    fn made_move(&mut self) {
+        if let Some((player, name)) = self.board.move_names().pop() {
+            self.pending.push_back(GameEvent::MoveMade(player, name));
+        }
+        self.waiting = false;
+    }
+}
+
+impl<'a> Iterator for GameDriver<'a> {
+    type Item = GameEvent;
+
+    fn next(&mut self) -> Option<GameEvent> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        if self.waiting {
+            return None;
+        }
+
+        let result = self.board.result();
+        if result != GameResult::InProgress {
+            self.waiting = true;
+            return Some(GameEvent::GameOver(result));
+        }
+
+        let player = self.board.player();
+        // The engine may have nothing to play, if it ran out of time.
+        if self.engine[side(player)] && self.board.play_engine_move(self.rng) {
+            self.made_move();
+            return self.pending.pop_front();
+        }
+        self.waiting = true;
+        Some(GameEvent::AwaitingInput(player))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use variant::Variant;
+
+    #[test]
+    fn waits_for_the_human() {
+        let mut game = Variant::TicTacToe.new_game(CheckBox::X);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut driver = GameDriver::new(game.board_mut(), &mut rng);
+        driver.set_engine(CheckBox::O, true);
+        assert_eq!(driver.next(), Some(GameEvent::AwaitingInput(CheckBox::X)));
+        assert_eq!(driver.next(), None);
+
+        driver.submit(Click { x: 1, y: 1, other_mark: false }).unwrap();
+        assert_eq!(driver.submit(Click { x: 1, y: 1, other_mark: false }).err(), Some(MoveError::Occupied));
+        match driver.next() {
+            Some(GameEvent::MoveMade(CheckBox::X, _)) => {}
+            other => panic!("Expected the human's move, got {:?}", other),
+        }
+        match driver.next() {
+            Some(GameEvent::MoveMade(CheckBox::O, _)) => {}
+            other => panic!("Expected the engine's move, got {:?}", other),
+        }
+        assert_eq!(driver.next(), Some(GameEvent::AwaitingInput(CheckBox::X)));
+        assert_eq!(driver.board().plies(), 2);
+    }
+
+    #[test]
+    fn ends_by_resigning_or_agreeing_to_draw() {
+        let mut game = Variant::TicTacToe.new_game(CheckBox::X);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut driver = GameDriver::new(game.board_mut(), &mut rng);
+        assert_eq!(driver.next(), Some(GameEvent::AwaitingInput(CheckBox::X)));
+        assert!(driver.offer_draw(CheckBox::X));
+        assert_eq!(driver.next(), Some(GameEvent::DrawOffered(CheckBox::X)));
+        assert!(driver.answer_draw_offer(CheckBox::O, false));
+        assert_eq!(driver.next(), Some(GameEvent::DrawDeclined(CheckBox::O)));
+        assert_eq!(driver.next(), None);
+        driver.resign(CheckBox::O);
+        assert_eq!(driver.next(), Some(GameEvent::GameOver(GameResult::Resigned(CheckBox::O))));
+
+        // The engine takes a draw from the start, where it can't win.
+        let mut game = Variant::TicTacToe.new_game(CheckBox::X);
+        let mut driver = GameDriver::new(game.board_mut(), &mut rng);
+        driver.set_engine(CheckBox::O, true);
+        assert_eq!(driver.next(), Some(GameEvent::AwaitingInput(CheckBox::X)));
+        assert!(driver.offer_draw(CheckBox::X));
+        assert_eq!(driver.next(), Some(GameEvent::DrawOffered(CheckBox::X)));
+        assert_eq!(driver.next(), Some(GameEvent::GameOver(GameResult::DrawAgreed)));
+    }
+
+    #[test]
+    fn plays_the_engine_against_itself() {
+        let mut game = Variant::TicTacToe.new_game(CheckBox::X);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut driver = GameDriver::new(game.board_mut(), &mut rng);
+        driver.set_engine(CheckBox::X, true);
+        driver.set_engine(CheckBox::O, true);
+        let events = driver.by_ref().collect::<Vec<_>>();
+        assert_eq!(events.len(), 10);
+        assert_eq!(events.last(), Some(&GameEvent::GameOver(GameResult::Draw)));
+        assert_eq!(driver.next(), None);
+    }
+}
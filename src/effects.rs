@@ -0,0 +1,141 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Feedback for what happens on the board besides drawing it, like
+//! animations and sounds.
+//!
+//! The frontends tell what happened by comparing frames of the board, and
+//! hand the cues to whatever effects they have, if any.
+
+use game::Position;
+use game_state::GameResult;
+use state::CheckBox;
+
+/// Something that happened on the board.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Cue {
+    /// A mark was placed in column `x` and row `y`.
+    Placed(usize, usize),
+    /// The game was won, with the squares of the winning line, if any.
+    Won(Vec<(usize, usize)>),
+    /// The game ended in a draw.
+    Drawn,
+}
+
+/// Something that reacts to cues.
+pub trait Effects {
+    fn play(&self, cue: &Cue);
+}
+
+/// The effects when there's nobody to see or hear them.
+#[derive(Debug)]
+pub struct NoEffects;
+
+impl Effects for NoEffects {
+    fn play(&self, _cue: &Cue) {}
+}
+
+/// What the board looked like at some point.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    cols: usize,
+    /// The marks of the board, in rows.
+    marks: Vec<CheckBox>,
+    winning_squares: Vec<(usize, usize)>,
+    result: GameResult,
+}
+
+impl Frame {
+    pub fn new(position: &dyn Position, result: GameResult) -> Self {
+        let (cols, rows) = position.grid_size();
+        let mut marks = Vec::with_capacity(cols * rows);
+        for y in 0..rows {
+            for x in 0..cols {
+                marks.push(position.mark(x, y));
+            }
+        }
+        Self {
+            cols,
+            marks,
+            winning_squares: position.winning_squares(),
+            result,
+        }
+    }
+}
+
+/// Returns the cues for the board going from `before` to `after`.
+///
+/// There are none if there's no `before`, or it's of a board of another
+/// size, since then it's a different game rather than a move.
+pub fn cues(before: Option<&Frame>, after: &Frame) -> Vec<Cue> {
+    let before = match before {
+        Some(before) if before.cols == after.cols && before.marks.len() == after.marks.len() => before,
+        _ => return vec![],
+    };
+
+    let mut cues = before.marks.iter().zip(after.marks.iter()).enumerate()
+        .filter(|&(_, (&b, &a))| b == CheckBox::Empty && a != CheckBox::Empty)
+        .map(|(i, _)| Cue::Placed(i % after.cols, i / after.cols))
+        .collect::<Vec<_>>();
+    if before.result == GameResult::InProgress {
+        match after.result {
+            GameResult::InProgress => {}
+            GameResult::Draw | GameResult::DrawAgreed => cues.push(Cue::Drawn),
+            GameResult::XWins | GameResult::OWins | GameResult::TimeForfeit(..) | GameResult::Resigned(..) => {
+                cues.push(Cue::Won(after.winning_squares.clone()))
+            }
+        }
+    }
+    cues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use variant::{AnyGame, Variant};
+
+    fn frame(game: &AnyGame) -> Frame {
+        let board = game.board();
+        Frame::new(&*board.position(board.plies()), board.result())
+    }
+
+    #[test]
+    fn cues_moves_and_results() {
+        let mut game = Variant::TicTacToe.new_game(CheckBox::X);
+        let start = frame(&game);
+        assert!(cues(None, &start).is_empty());
+
+        for &(x, y) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+            game.board_mut().click(x, y).unwrap();
+        }
+        let before = frame(&game);
+        assert_eq!(cues(Some(&start), &before).len(), 4);
+
+        game.board_mut().click(0, 2).unwrap();
+        let after = frame(&game);
+        assert_eq!(cues(Some(&before), &after), [
+            Cue::Placed(0, 2),
+            Cue::Won(vec![(0, 0), (0, 1), (0, 2)]),
+        ]);
+        assert!(cues(Some(&after), &after).is_empty());
+        // Undoing doesn't place anything.
+        assert!(cues(Some(&after), &before).is_empty());
+
+        let other = Variant::ConnectFour.new_game(CheckBox::X);
+        assert!(cues(Some(&after), &frame(&other)).is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The heuristics that score the positions where the search stops.
+
+use game_state::GameState;
+use state::{CheckBox, State};
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+/// A heuristic score of the states of a game.
+///
+/// Like `GameState::evaluate`, positive scores are good for O, wins are 10
+/// for O and -10 for X, and all the other states must score strictly in
+/// between.
+pub trait Evaluator<S: GameState>: fmt::Debug + Send + Sync {
+    fn evaluate(&self, state: &S) -> i8;
+}
+
+/// The evaluation of the game itself, `GameState::evaluate`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DefaultEvaluator;
+
+impl<S: GameState> Evaluator<S> for DefaultEvaluator {
+    fn evaluate(&self, state: &S) -> i8 {
+        state.evaluate()
+    }
+}
+
+/// Scores tic-tac-toe positions by how many lines go through the squares of
+/// each player: four through the center, three through the corners and two
+/// through the edges.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PositionalEvaluator;
+
+impl Evaluator<State> for PositionalEvaluator {
+    fn evaluate(&self, state: &State) -> i8 {
+        if state.winner() != CheckBox::Empty {
+            return state.score();
+        }
+        let mut score = 0;
+        for x in 0..3 {
+            for y in 0..3 {
+                let lines = match (x, y) {
+                    (1, 1) => 4,
+                    (x, y) if x != 1 && y != 1 => 3,
+                    _ => 2,
+                };
+                score += lines * state.get(x, y) as i8 / CheckBox::O as i8;
+            }
+        }
+        // Never as good as a win.
+        score.max(CheckBox::X as i8 + 1).min(CheckBox::O as i8 - 1)
+    }
+}
+
+/// Returns the evaluator for tic-tac-toe called `name`, for the options of
+/// the benchmark.
+pub fn tic_tac_toe_evaluator(name: &str) -> Option<Arc<dyn Evaluator<State>>> {
+    Some(match name {
+        "default" => Arc::new(DefaultEvaluator),
+        "positional" => Arc::new(PositionalEvaluator),
+        _ => return None,
+    })
+}
+
+/// Returns the evaluator called `name` for the states of `S`: "default" for
+/// every game, and the ones of `tic_tac_toe_evaluator` for tic-tac-toe.
+pub fn evaluator_named<S: GameState>(name: &str) -> Option<Arc<dyn Evaluator<S>>> {
+    if name == "default" {
+        return Some(Arc::new(DefaultEvaluator));
+    }
+    // Only tic-tac-toe has more, and this is how to tell whether `S` is it.
+    let evaluator: Box<dyn Any> = Box::new(tic_tac_toe_evaluator(name)?);
+    evaluator.downcast::<Arc<dyn Evaluator<S>>>().ok().map(|e| *e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minimax::MiniMaxTree;
+
+    #[test]
+    fn positional_evaluation_changes_shallow_searches_only() {
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        tree.set_evaluator(Arc::new(PositionalEvaluator));
+        let scores = tree.evaluate_all_moves(1);
+        assert_eq!(scores[0], ((1, 1), -4));
+        assert!(scores.contains(&((0, 0), -3)));
+        assert!(scores.contains(&((1, 0), -2)));
+
+        // Wins still score the most, so perfect play still draws.
+        while let Some(index) = tree.find_move_index(9).index() {
+            tree.choose_with_index(index);
+        }
+        assert_eq!(tree.state().score(), 0);
+    }
+}
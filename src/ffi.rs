@@ -0,0 +1,190 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A C API for the engine, declared in `include/tic_tac_minimax.h`.
+//!
+//! Games are opaque handles that `ttt_new_game` creates and `ttt_free`
+//! destroys, and that belong to the caller in between. A handle must be
+//! used from one thread at a time, and never after it's freed.
+
+use game_state::{GameResult, MoveError};
+use state::CheckBox;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use variant::{AnyGame, Variant};
+
+/// A game, as C sees it.
+pub struct TttGame(AnyGame);
+
+pub const TTT_IN_PROGRESS: c_int = 0;
+pub const TTT_X_WINS: c_int = 1;
+pub const TTT_O_WINS: c_int = 2;
+pub const TTT_DRAW: c_int = 3;
+
+pub const TTT_OK: c_int = 0;
+pub const TTT_GAME_OVER: c_int = 1;
+pub const TTT_OCCUPIED: c_int = 2;
+pub const TTT_OUT_OF_BOUNDS: c_int = 3;
+pub const TTT_ILLEGAL: c_int = 4;
+/// The handle or some other pointer was null, or the variant unknown.
+pub const TTT_INVALID_ARGUMENT: c_int = 5;
+
+/// The budget of `ttt_best_move` that the engine plays with by default.
+pub const TTT_DEFAULT_NODE_BUDGET: usize = 100_000;
+
+/// Starts a game of the variant with the given id, like `"connect4"`, or of
+/// tic-tac-toe if `variant` is null. X moves first.
+///
+/// Returns null if the variant is unknown.
+///
+/// # Safety
+///
+/// `variant` must be null or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn ttt_new_game(variant: *const c_char) -> *mut TttGame {
+    let variant = if variant.is_null() {
+        Variant::TicTacToe
+    } else {
+        match CStr::from_ptr(variant).to_str().ok().and_then(Variant::from_id) {
+            Some(variant) => variant,
+            None => return ptr::null_mut(),
+        }
+    };
+    Box::into_raw(Box::new(TttGame(variant.new_game(CheckBox::X))))
+}
+
+/// Plays the square in column `x` and row `y` as the player to move.
+///
+/// Returns `TTT_OK`, or why the move couldn't be played.
+///
+/// # Safety
+///
+/// `game` must be a handle from `ttt_new_game` that wasn't freed.
+#[no_mangle]
+pub unsafe extern "C" fn ttt_play(game: *mut TttGame, x: usize, y: usize) -> c_int {
+    let game = match game.as_mut() {
+        Some(game) => game,
+        None => return TTT_INVALID_ARGUMENT,
+    };
+    match game.0.board_mut().click(x, y) {
+        Ok(()) => TTT_OK,
+        Err(MoveError::GameOver) => TTT_GAME_OVER,
+        Err(MoveError::Occupied) => TTT_OCCUPIED,
+        Err(MoveError::OutOfBounds) => TTT_OUT_OF_BOUNDS,
+        Err(MoveError::Illegal) => TTT_ILLEGAL,
+    }
+}
+
+/// Stores in `x` and `y` the square of the best move for the player to
+/// move, without playing it.
+///
+/// The engine searches deeper and deeper, up to as deep as the game goes,
+/// but only while the next search is expected to visit at most
+/// `node_budget` positions in all, so that it returns in a time that
+/// depends on the budget rather than on the game. It searches one level at
+/// least, even with a budget of zero. With `TTT_DEFAULT_NODE_BUDGET` it
+/// plays like the engine does by default.
+///
+/// Returns `TTT_OK`, or `TTT_GAME_OVER` if there's no move to play.
+///
+/// # Safety
+///
+/// `game` must be a handle from `ttt_new_game` that wasn't freed, and `x`
+/// and `y` valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn ttt_best_move(game: *const TttGame, node_budget: usize, x: *mut usize, y: *mut usize) -> c_int {
+    let game = match game.as_ref() {
+        Some(game) if !x.is_null() && !y.is_null() => game,
+        _ => return TTT_INVALID_ARGUMENT,
+    };
+    let board = game.0.board();
+    match board.hint_squares_within_budget(board.player(), node_budget).first() {
+        Some(&(best_x, best_y)) => {
+            *x = best_x;
+            *y = best_y;
+            TTT_OK
+        }
+        None => TTT_GAME_OVER,
+    }
+}
+
+/// Returns one of `TTT_IN_PROGRESS`, `TTT_X_WINS`, `TTT_O_WINS` or
+/// `TTT_DRAW`, or `TTT_INVALID_ARGUMENT` if `game` is null.
+///
+/// # Safety
+///
+/// `game` must be a handle from `ttt_new_game` that wasn't freed.
+#[no_mangle]
+pub unsafe extern "C" fn ttt_game_result(game: *const TttGame) -> c_int {
+    let game = match game.as_ref() {
+        Some(game) => game,
+        None => return TTT_INVALID_ARGUMENT,
+    };
+    match game.0.board().result() {
+        GameResult::InProgress => TTT_IN_PROGRESS,
+        GameResult::XWins | GameResult::TimeForfeit(CheckBox::O) | GameResult::Resigned(CheckBox::O) => TTT_X_WINS,
+        GameResult::OWins | GameResult::TimeForfeit(_) | GameResult::Resigned(_) => TTT_O_WINS,
+        GameResult::Draw | GameResult::DrawAgreed => TTT_DRAW,
+    }
+}
+
+/// Destroys a game. Does nothing if `game` is null.
+///
+/// # Safety
+///
+/// `game` must be null or a handle from `ttt_new_game` that wasn't freed.
+#[no_mangle]
+pub unsafe extern "C" fn ttt_free(game: *mut TttGame) {
+    if !game.is_null() {
+        drop(Box::from_raw(game));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_through_handles() {
+        assert_eq!(TTT_DEFAULT_NODE_BUDGET, ::game::DEFAULT_NODE_BUDGET);
+        unsafe {
+            assert!(ttt_new_game(b"chess\0".as_ptr() as *const c_char).is_null());
+            let game = ttt_new_game(ptr::null());
+            assert_eq!(ttt_play(game, 1, 1), TTT_OK);
+            assert_eq!(ttt_play(game, 1, 1), TTT_OCCUPIED);
+            assert_eq!(ttt_play(game, 3, 1), TTT_OUT_OF_BOUNDS);
+
+            let (mut x, mut y) = (0, 0);
+            while ttt_game_result(game) == TTT_IN_PROGRESS {
+                assert_eq!(ttt_best_move(game, TTT_DEFAULT_NODE_BUDGET, &mut x, &mut y), TTT_OK);
+                assert_eq!(ttt_play(game, x, y), TTT_OK);
+            }
+            assert_eq!(ttt_game_result(game), TTT_DRAW);
+            assert_eq!(ttt_best_move(game, TTT_DEFAULT_NODE_BUDGET, &mut x, &mut y), TTT_GAME_OVER);
+            assert_eq!(ttt_play(game, x, y), TTT_GAME_OVER);
+            ttt_free(game);
+            ttt_free(ptr::null_mut());
+
+            // Rather than searching the whole game.
+            let game = ttt_new_game(b"qubic\0".as_ptr() as *const c_char);
+            assert_eq!(ttt_best_move(game, 1000, &mut x, &mut y), TTT_OK);
+            assert_eq!(ttt_play(game, x, y), TTT_OK);
+            ttt_free(game);
+        }
+    }
+}
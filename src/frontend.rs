@@ -0,0 +1,83 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! What a human plays the engine through, so that it can be played on
+//! more than one kind of UI.
+
+use driver::{GameDriver, GameEvent};
+use game::Board;
+use game_state::{GameResult, MoveError};
+use rand::RngCore;
+use state::CheckBox;
+use variant::AnyGame;
+
+/// A square the human wants to play.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Click {
+    pub x: usize,
+    pub y: usize,
+    /// Whether to play the other mark of the square, in games that have
+    /// one.
+    pub other_mark: bool,
+}
+
+pub trait Frontend {
+    /// Shows the board as it is now.
+    fn render(&mut self, board: &dyn Board);
+
+    /// Asks the human for their move, blocking until they make one.
+    ///
+    /// Returns `None` if they leave instead.
+    fn prompt_move(&mut self, board: &dyn Board) -> Option<Click>;
+
+    /// Says why the move the human asked for couldn't be played.
+    fn reject_move(&mut self, err: MoveError);
+
+    /// Tells how the game ended.
+    fn announce_result(&mut self, result: GameResult);
+}
+
+/// Plays `game` on `frontend` until it's over or the human leaves, with the
+/// engine playing against `human`.
+///
+/// Returns the result of the game, which is still in progress if the human
+/// left.
+pub fn play(frontend: &mut dyn Frontend, game: &mut AnyGame, human: CheckBox, rng: &mut dyn RngCore) -> GameResult {
+    let mut driver = GameDriver::new(game.board_mut(), rng);
+    driver.set_engine(human.next_player(), true);
+    while let Some(event) = driver.next() {
+        match event {
+            GameEvent::MoveMade(..) | GameEvent::DrawOffered(..) | GameEvent::DrawDeclined(..) => {}
+            GameEvent::AwaitingInput(_) => loop {
+                frontend.render(driver.board());
+                let click = match frontend.prompt_move(driver.board()) {
+                    Some(click) => click,
+                    None => return driver.board().result(),
+                };
+                match driver.submit(click) {
+                    Ok(()) => break,
+                    Err(err) => frontend.reject_move(err),
+                }
+            },
+            GameEvent::GameOver(result) => {
+                frontend.render(driver.board());
+                frontend.announce_result(result);
+            }
+        }
+    }
+    driver.board().result()
+}
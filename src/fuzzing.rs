@@ -0,0 +1,159 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Checks of what holds for any input, for the fuzz targets in `fuzz/`,
+//! which feed them arbitrary bytes.
+//!
+//! Each check panics if the engine panics, or if what it did breaks the
+//! rules: a move overwriting a mark, a score out of range, or a game that
+//! goes on after it's over.
+
+use game::Game;
+use game_state::{GameState, GridState, MoveError};
+use minimax::MiniMaxTree;
+use protocol;
+use state::{CheckBox, State};
+use std::str;
+
+/// Whether `mv` can be played in `state`: on the board, and empty.
+fn is_free(state: &State, (x, y): (usize, usize)) -> bool {
+    let (cols, rows) = state.grid_size();
+    x < cols && y < rows && state.get(x, y) == CheckBox::Empty
+}
+
+/// Checks the scores of `state`, and that it has no moves if it's over.
+fn check_state(state: &State) {
+    let score = state.evaluate();
+    assert!(score >= CheckBox::X as i8 && score <= CheckBox::O as i8, "score out of range: {}", score);
+    if state.is_over() {
+        assert!(state.successors(CheckBox::X).is_empty(), "moves after the end: {:?}", state);
+        assert!(state.successors(CheckBox::O).is_empty(), "moves after the end: {:?}", state);
+    }
+}
+
+/// Checks that searching `state` for `player` finds a move that can be
+/// played, with a score in range, unless the game is over.
+fn check_search(state: &State, player: CheckBox) {
+    let mut tree = MiniMaxTree::from_state(state.clone(), player);
+    match tree.find_best_move(2) {
+        Some((index, score)) => {
+            assert!(!state.is_over(), "a move after the end: {:?}", state);
+            assert!(is_free(state, tree.move_for_index(index)));
+            assert!(score >= CheckBox::X as i8 && score <= CheckBox::O as i8);
+        }
+        None => assert!(state.is_over(), "no move before the end: {:?}", state),
+    }
+}
+
+/// Parses `data` as a position like `X_O/_X_/__O X`, and checks what it
+/// parses to.
+pub fn notation(data: &[u8]) {
+    let text = match str::from_utf8(data) {
+        Ok(text) => text,
+        Err(..) => return,
+    };
+    let _ = State::from_pasted(text);
+    let (state, player) = match State::from_notation(text) {
+        Ok(position) => position,
+        Err(..) => return,
+    };
+    let notation = state.to_notation(player);
+    assert_eq!(State::from_notation(&notation), Ok((state.clone(), player)));
+    check_state(&state);
+    if state.validate(player).is_ok() {
+        check_search(&state, player);
+    }
+}
+
+/// Plays the squares in `data`, a column and a row for each byte pair, as
+/// long as there are bytes, and checks every move.
+pub fn moves(data: &[u8]) {
+    let mut game = Game::<State>::new(CheckBox::X);
+    for square in data.chunks(2) {
+        let mv = (square[0] as usize, *square.get(1).unwrap_or(&0) as usize);
+        let before = game.state().clone();
+        let player = game.player();
+        let result = game.choose(mv);
+        let after = game.state().clone();
+        check_state(&after);
+        match result {
+            Ok(()) => {
+                assert!(!before.is_over(), "played {:?} after the end", mv);
+                assert!(is_free(&before, mv), "played {:?} on a mark", mv);
+                let mut expected = before;
+                expected.set(mv.0, mv.1, player);
+                assert_eq!(after, expected, "{:?} changed more than its square", mv);
+                assert_eq!(game.player(), player.next_player());
+            }
+            Err(err) => {
+                assert_eq!(after, before, "the rejected {:?} changed the board", mv);
+                assert_eq!(game.player(), player);
+                if before.is_over() {
+                    assert_eq!(err, MoveError::GameOver);
+                } else {
+                    assert!(!is_free(&before, mv), "rejected the free {:?}", mv);
+                }
+            }
+        }
+    }
+    check_search(game.state(), game.player());
+}
+
+/// Answers the lines of `data` in protocol mode, checking that every reply
+/// is one the protocol has.
+pub fn protocol(data: &[u8]) {
+    let mut output = vec![];
+    // Input that isn't text ends the session with an error, like it should.
+    let _ = protocol::run(data, &mut output);
+    let output = String::from_utf8(output).expect("Replies should be text");
+    for reply in output.lines() {
+        let word = reply.split(' ').next().unwrap_or("");
+        assert!(["readyok", "bestmove", "error"].contains(&word), "unexpected reply: {}", reply);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    /// Bytes that look a bit like the input of each check, so that random
+    /// ones get past the parsers often enough.
+    fn random_input<R: Rng>(rng: &mut R, alphabet: &[u8]) -> Vec<u8> {
+        let len = rng.gen_range(0..40);
+        (0..len).map(|_| {
+            if rng.gen_bool(0.9) {
+                alphabet[rng.gen_range(0..alphabet.len())]
+            } else {
+                rng.gen()
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn holds_for_random_input() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..2000 {
+            notation(&random_input(&mut rng, b"XO__/ "));
+            moves(&random_input(&mut rng, &[0, 1, 2, 3]));
+            protocol(&random_input(&mut rng, b"position go depth 1 2 XO_\n"));
+        }
+        moves(&[1, 1, 0, 0, 1, 1, 255, 0, 2, 2, 1, 0, 1, 2, 0, 2]);
+        protocol(b"position XXXOOO___ X\ngo\nposition XXXXXXXXX O\ngo depth 3\n");
+    }
+}
@@ -0,0 +1,2211 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A game of tic-tac-toe: the minimax tree plus the moves that led to it.
+//!
+//! Games can be saved to a small line-based text format:
+//!
+//! ```text
+//! tic-tac-minimax-save 7
+//! variant tictactoe
+//! first-player X
+//! player-x difficulty=Perfect,randomize=no,evaluator=default,no-center-opening=no
+//! player-o difficulty=Easy,randomize=yes,evaluator=default,no-center-opening=no
+//! seed 0
+//! moves 1,1 0,0 2,2
+//! hash d6d292c292b788d1
+//! ```
+//!
+//! The first line identifies the file and the version of the format. The
+//! board and the player to move are not stored, they're computed by
+//! replaying the moves, which each variant writes its own way, see
+//! `Savable::write_move`. The `player-x` and `player-o` lines say how the
+//! engine plays for each side, like `PlayerConfig::to_spec` writes them.
+//! Tic-tac-toe games that didn't start from the empty
+//! board have a `position` line too, like `position X_O/___/___ X`, and games
+//! that ended off the board, by resigning, running out of time or agreeing to
+//! a draw, a `result` line, like `result x-resigned`. Games played with a
+//! clock have `time-x` and `time-o` lines, with the seconds each side had for
+//! the whole game and after each move, like `time-x 300+5`, and an
+//! `on-timeout` line with what happens to whoever runs out of it, like
+//! `on-timeout greedy-move`. The moves played for a player out of time are in
+//! a `timed-out` line, counting from one, like `timed-out 5 7`.
+
+use adaptive::{GameSummary, OpponentModel, Policy};
+use clock::{TimeControl, TimeoutPolicy};
+use evaluator::{self, DefaultEvaluator, Evaluator};
+use commands;
+use game_state::{GameResult, GameState, GridState, MoveError, Savable};
+use log::{self, Level};
+use minimax::{CancelToken, Difficulty, MiniMaxTree, SearchConfig, Solution, Tally};
+use player::PlayerConfig;
+use rand::{Rng, RngCore};
+use scoreboard;
+use state::{CheckBox, State};
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::mem;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Arc;
+use variant::Variant;
+
+/// The first word of every saved game.
+const SAVE_FORMAT_MAGIC: &str = "tic-tac-minimax-save";
+
+/// The version of the format `save` writes.
+///
+/// This needs to be bumped on every change to the format, teaching `migrate`
+/// how to upgrade files in the previous version.
+const SAVE_FORMAT_VERSION: u32 = 7;
+
+/// The fields of a saved game, by name.
+type Fields = HashMap<String, String>;
+
+/// An error loading a saved game.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    /// The file is not a saved game.
+    NotASavedGame,
+    /// The file was saved by a newer version.
+    UnsupportedVersion(u32),
+    /// A field is missing or malformed.
+    InvalidField(&'static str),
+    /// A saved move can't be played.
+    IllegalMove(String),
+    /// The record doesn't match its hash, so it was edited, or it's read
+    /// differently than when it was saved.
+    HashMismatch,
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref err) => write!(f, "{}", err),
+            LoadError::NotASavedGame => write!(f, "not a saved game"),
+            LoadError::UnsupportedVersion(v) => {
+                write!(f, "unsupported save format version {}", v)
+            }
+            LoadError::InvalidField(name) => {
+                write!(f, "missing or invalid field \"{}\"", name)
+            }
+            LoadError::IllegalMove(ref mv) => write!(f, "illegal move {}", mv),
+            LoadError::HashMismatch => write!(f, "the game doesn't match its hash"),
+        }
+    }
+}
+
+/// Upgrades the fields of a game saved with format `version` to the current
+/// version.
+fn migrate(version: u32, fields: &mut Fields) -> Result<(), LoadError> {
+    if version == 0 || version > SAVE_FORMAT_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+    // Each step upgrades from one version to the next.
+    for from in version..SAVE_FORMAT_VERSION {
+        match from {
+            // Version 1 had no seed nor hash, and never varied the moves.
+            1 => {
+                fields.insert("randomize".to_owned(), "false".to_owned());
+                fields.insert("seed".to_owned(), "0".to_owned());
+            }
+            // Version 2 only saved tic-tac-toe.
+            2 => {
+                fields.insert("variant".to_owned(), Variant::TicTacToe.id().to_owned());
+            }
+            // Version 3 saved how the engine played against the human, and
+            // both sides played like that.
+            3 => {
+                let difficulty = fields.get("difficulty").ok_or(LoadError::InvalidField("difficulty"))?;
+                let randomize = match fields.get("randomize").map(|v| v.as_str()) {
+                    Some("true") => "yes",
+                    Some("false") => "no",
+                    _ => return Err(LoadError::InvalidField("randomize")),
+                };
+                let spec = format!("difficulty={},randomize={}", difficulty, randomize);
+                fields.insert("player-x".to_owned(), spec.clone());
+                fields.insert("player-o".to_owned(), spec);
+            }
+            // Version 4 didn't save results off the board, so those games
+            // come back still in progress.
+            4 => {}
+            // Version 5 didn't save the time of the players, so those games
+            // come back without a clock.
+            5 => {}
+            // Version 6 didn't save what happens when a player runs out of
+            // time, which could only be losing.
+            6 => {}
+            _ => unreachable!("Every older version should have a step"),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the 64-bit FNV-1a hash of `bytes`, which unlike the hashers of
+/// the standard library is the same everywhere and forever.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Returns the index of `player` in the arrays of X and O.
+fn side(player: CheckBox) -> usize {
+    if player == CheckBox::X { 0 } else { 1 }
+}
+
+/// The number of nodes the engine searches per move in `Difficulty::Auto`,
+/// unless told otherwise.
+pub const DEFAULT_NODE_BUDGET: usize = 100_000;
+
+/// The most nodes the engine keeps from one move to the next. A whole game
+/// of tic-tac-toe fits, the bigger games keep what's closest to the board.
+const RETAINED_NODE_LIMIT: usize = 1 << 20;
+
+/// Returns a tree of the start of a game, to search as the engine does.
+fn new_tree<S: GameState>(initial: S, first_player: CheckBox) -> MiniMaxTree<S> {
+    let mut tree = MiniMaxTree::from_state(initial, first_player);
+    tree.set_node_limit(Some(RETAINED_NODE_LIMIT));
+    tree
+}
+
+/// A function to call when a game is over, with its result.
+pub type GameOverHook = Box<dyn FnMut(GameResult)>;
+
+/// The game over hook of a game, if any.
+struct Hook(Option<GameOverHook>);
+
+impl fmt::Debug for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(if self.0.is_some() { "Hook(Some(..))" } else { "Hook(None)" })
+    }
+}
+
+/// The search tree of the start of a game, which `Board::warm_up` searched,
+/// for `Board::adopt_warm_tree`.
+pub struct WarmTree(Box<dyn Any + Send>);
+
+/// Work to run on another thread, like a search, returning what it found.
+pub type Job<T> = Box<dyn FnOnce() -> T + Send>;
+
+/// A search to run on another thread.
+pub type WarmUpJob = Job<WarmTree>;
+
+/// The move the engine chose on another thread, which `Board::think`
+/// searched, for `Board::finish_thinking`.
+pub struct EngineReply(Box<dyn Any + Send>);
+
+/// What the engine found thinking on another thread, see `Game::think`.
+struct Thought<S: GameState> {
+    tree: MiniMaxTree<S>,
+    search: EngineSearch<S>,
+    /// The index of the move it chose, and whether the handicap changed it,
+    /// or `None` if it didn't choose any.
+    choice: Option<(usize, bool)>,
+    /// How many moves the game had when it started thinking.
+    plies: usize,
+}
+
+/// How the engine chooses the move of the player to move, besides the tree
+/// it searches, so that it can choose on another thread.
+struct EngineSearch<S: GameState> {
+    config: PlayerConfig,
+    max_depth: usize,
+    evaluator: Arc<dyn Evaluator<S>>,
+    node_budget: usize,
+    adaptive: Policy,
+    /// Whether it's one of the first moves of each side.
+    opening: bool,
+}
+
+impl<S: GameState> EngineSearch<S> {
+    /// Returns the index of the move to play in the current state of `tree`,
+    /// and whether the handicap changed it, or `None` if the game is over or
+    /// the search was cancelled.
+    fn choose<R>(&self, tree: &mut MiniMaxTree<S>, rng: &mut R) -> Option<(usize, bool)>
+        where R: Rng + ?Sized,
+    {
+        let config = &self.config;
+        let max_depth = self.max_depth;
+        tree.set_evaluator(self.evaluator.clone());
+        let index = match config.difficulty {
+            Difficulty::Auto => {
+                tree.find_move_index_within_budget(self.node_budget, max_depth)?
+            }
+            Difficulty::Adaptive => {
+                self.adaptive.find_move_index(tree, max_depth, config.randomize, rng)?
+            }
+            difficulty => {
+                tree.find_move_index_with_difficulty(difficulty, max_depth, config.randomize, rng)?
+            }
+        };
+        if !self.is_handicapped(tree.state(), tree.move_for_index(index)) {
+            return Some((index, false));
+        }
+        let index = self.best_move_index_within_handicap(tree)?;
+        Some((index, true))
+    }
+
+    /// Returns whether the handicap keeps the player from playing `mv` in
+    /// `state`.
+    fn is_handicapped(&self, state: &S, mv: S::Move) -> bool {
+        self.config.handicap.no_center_opening && self.opening && state.is_center_move(mv)
+    }
+
+    /// Returns the index of the best move of the player to move in `tree`
+    /// among the ones the handicap allows.
+    fn best_move_index_within_handicap(&self, tree: &mut MiniMaxTree<S>) -> Option<usize> {
+        // Scores are from O's point of view.
+        let sign = if tree.player() == CheckBox::O { 1 } else { -1 };
+        let scores = tree.evaluate_all_moves(self.max_depth);
+        scores.into_iter().enumerate()
+            .filter(|&(_, (mv, _))| !self.is_handicapped(tree.state(), mv))
+            .max_by_key(|&(_, (_, score))| score * sign)
+            .map(|(index, _)| index)
+    }
+}
+
+/// What the analysis of a position found, see `Board::analyze`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    /// The score of the move each square plays, from O's point of view, for
+    /// the squares that play one.
+    pub square_scores: Vec<((usize, usize), i8)>,
+    /// The outcome with best play, if the game is small enough to solve.
+    pub solution: Option<Solution>,
+    /// The line of play the engine expects, like
+    /// `Board::principal_variation`.
+    pub principal_variation: Vec<(CheckBox, String)>,
+    /// How many exact moves the player to move needs to hold a draw, if
+    /// that's the best they can get and the game is small enough to prove
+    /// it.
+    pub exact_moves_to_draw: Option<usize>,
+}
+
+/// An analysis to run on another thread, which returns `None` if it was
+/// cancelled.
+pub type AnalysisJob = Job<Option<Analysis>>;
+
+#[derive(Debug)]
+pub struct Game<S: GameState = State> {
+    tree: MiniMaxTree<S>,
+    /// The player that moved first.
+    first_player: CheckBox,
+    /// How the engine plays for X and O, in that order.
+    players: [PlayerConfig; 2],
+    /// The evaluators of `players`.
+    evaluators: [Arc<dyn Evaluator<S>>; 2],
+    /// The number of nodes to search per move in `Difficulty::Auto`.
+    node_budget: usize,
+    /// How the engine plays in `Difficulty::Adaptive`.
+    adaptive: Policy,
+    /// What the random numbers of the engine are seeded with, for the
+    /// record.
+    seed: u64,
+    /// The time X and O have, in that order, if the game is played with a
+    /// clock, for the record.
+    time_controls: Option<[TimeControl; 2]>,
+    /// What happens when a player runs out of time.
+    timeout_policy: TimeoutPolicy,
+    /// The moves played for a player who ran out of time, counting from
+    /// one.
+    timed_out: Vec<usize>,
+    /// The moves played so far, in order.
+    moves: Vec<S::Move>,
+    /// The position after each of `moves`, after the initial one.
+    history: Vec<S>,
+    /// The moves that have been undone, with the most recently undone last.
+    undone: Vec<S::Move>,
+    game_over_hook: Hook,
+    /// How the game ended off the board, if it did: by running out of time,
+    /// by resigning or by agreeing to a draw.
+    adjudication: Option<GameResult>,
+    /// The player that offered a draw the other one didn't answer yet.
+    draw_offer: Option<CheckBox>,
+    /// Whether the game has been over at some point, even if the move that
+    /// ended it was undone afterwards.
+    ended: bool,
+    /// Whether the engine is thinking on another thread, and nothing was
+    /// played or undone since it started.
+    thinking: bool,
+}
+
+impl<S: GameState> Game<S> {
+    pub fn new(first_player: CheckBox) -> Self {
+        Self::from_state(S::initial(), first_player)
+    }
+
+    /// Starts a game from `initial` rather than from the start, with
+    /// `first_player` to move.
+    ///
+    /// Whether the position can come up in a game is up to the caller, see
+    /// `State::validate`.
+    pub fn from_state(initial: S, first_player: CheckBox) -> Self {
+        let tree = new_tree(initial.clone(), first_player);
+        Self {
+            tree,
+            first_player,
+            players: [PlayerConfig::default(), PlayerConfig::default()],
+            evaluators: [Arc::new(DefaultEvaluator), Arc::new(DefaultEvaluator)],
+            node_budget: DEFAULT_NODE_BUDGET,
+            adaptive: OpponentModel::default().policy(S::depth_limit()),
+            seed: 0,
+            time_controls: None,
+            timeout_policy: TimeoutPolicy::Forfeit,
+            timed_out: vec![],
+            moves: vec![],
+            history: vec![initial],
+            undone: vec![],
+            game_over_hook: Hook(None),
+            adjudication: None,
+            draw_offer: None,
+            ended: false,
+            thinking: false,
+        }
+    }
+
+    /// Sets the function to call when the game is over.
+    ///
+    /// It's called at most once per game, so that undoing the last move and
+    /// finishing the game again doesn't report it twice. Games that were
+    /// already over when setting it are not reported.
+    pub fn set_game_over_hook(&mut self, hook: GameOverHook) {
+        self.game_over_hook = Hook(Some(hook));
+    }
+
+    /// Calls the game over hook if the last move ended the game for the
+    /// first time.
+    fn check_game_over(&mut self) {
+        let result = self.result();
+        if self.ended || result == GameResult::InProgress {
+            return;
+        }
+        self.ended = true;
+        log::log(Level::Info, "game", "game over", &[
+            ("result", &format_args!("{:?}", result)),
+            ("moves", &self.moves.len()),
+        ]);
+        if let Some(ref mut hook) = self.game_over_hook.0 {
+            hook(result);
+        }
+    }
+
+    /// Returns how hard the engine plays for the second player, which is the
+    /// side it plays against a human.
+    pub fn difficulty(&self) -> Difficulty {
+        self.player_config(self.first_player.next_player()).difficulty
+    }
+
+    /// Makes the engine play as hard for both sides.
+    pub fn set_difficulty(&mut self, difficulty: Difficulty) {
+        for config in &mut self.players {
+            config.difficulty = difficulty;
+        }
+    }
+
+    /// Returns how the engine plays for `player`.
+    pub fn player_config(&self, player: CheckBox) -> &PlayerConfig {
+        &self.players[side(player)]
+    }
+
+    /// Makes the engine play for `player`, and hint its moves, as `config`
+    /// says.
+    ///
+    /// Returns an error if this game has no evaluator with the name of the
+    /// config.
+    pub fn set_player_config(&mut self, player: CheckBox, config: PlayerConfig) -> Result<(), String> {
+        let evaluator = evaluator::evaluator_named::<S>(&config.evaluator)
+            .ok_or_else(|| format!("unknown evaluator for this game: {}", config.evaluator))?;
+        self.evaluators[side(player)] = evaluator;
+        self.players[side(player)] = config;
+        Ok(())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Records that the engine plays with random numbers seeded with `seed`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn time_controls(&self) -> Option<[TimeControl; 2]> {
+        self.time_controls
+    }
+
+    /// Records that X and O play with the time of `controls`, in that order,
+    /// or without a clock.
+    pub fn set_time_controls(&mut self, controls: Option<[TimeControl; 2]>) {
+        self.time_controls = controls;
+    }
+
+    pub fn timeout_policy(&self) -> TimeoutPolicy {
+        self.timeout_policy
+    }
+
+    /// Makes `policy` say what happens when a player runs out of time.
+    pub fn set_timeout_policy(&mut self, policy: TimeoutPolicy) {
+        self.timeout_policy = policy;
+    }
+
+    /// Returns the moves played for a player who ran out of time, counting
+    /// from one.
+    pub fn timed_out(&self) -> &[usize] {
+        &self.timed_out
+    }
+
+    /// Records that the last move was played for a player who ran out of
+    /// time, like when replaying a game.
+    pub fn mark_timed_out(&mut self) {
+        let plies = self.moves.len();
+        if plies > 0 && self.timed_out.last() != Some(&plies) {
+            self.timed_out.push(plies);
+        }
+    }
+
+    /// Makes the engine play against the human `model` describes in
+    /// `Difficulty::Adaptive`.
+    pub fn set_opponent_model(&mut self, model: &OpponentModel) {
+        self.adaptive = model.policy(S::depth_limit());
+    }
+
+    /// Sets the number of nodes to search per move in `Difficulty::Auto`.
+    pub fn set_node_budget(&mut self, node_budget: usize) {
+        self.node_budget = node_budget;
+    }
+
+    /// Makes the engine pick at random among the moves tying for the best
+    /// score, or always the same one, for both sides.
+    pub fn set_randomize(&mut self, randomize: bool) {
+        for config in &mut self.players {
+            config.randomize = randomize;
+        }
+    }
+
+    /// Returns the search tree of the game, to search or inspect it without
+    /// playing any move.
+    pub fn tree_mut(&mut self) -> &mut MiniMaxTree<S> {
+        &mut self.tree
+    }
+
+    /// Returns the current state of the board.
+    pub fn state(&self) -> &S {
+        self.tree.state()
+    }
+
+    /// Returns a job that searches the start of a game like this one as deep
+    /// as the engine does, so that its replies are quick from the first one.
+    ///
+    /// The job stops early once `cancel` is cancelled, and then its tree is
+    /// no use.
+    pub fn warm_up(&self, cancel: CancelToken) -> WarmUpJob {
+        let first_player = self.first_player;
+        let initial = self.history[0].clone();
+        Box::new(move || {
+            let mut tree = MiniMaxTree::from_state(initial, first_player);
+            tree.set_cancel_token(Some(cancel));
+            // Not `find_move_index`, which would just look up the book.
+            tree.find_best_move(S::depth_limit());
+            WarmTree(Box::new(tree))
+        })
+    }
+
+    /// Makes the engine search from `tree` from now on, if it is of a game
+    /// like this one, from the same position, catching up with the moves
+    /// played since.
+    ///
+    /// Returns whether the tree was adopted.
+    pub fn adopt_warm_tree(&mut self, tree: WarmTree) -> bool {
+        let mut tree = match tree.0.downcast::<MiniMaxTree<S>>() {
+            Ok(tree) => *tree,
+            Err(..) => return false,
+        };
+        if tree.player() != self.first_player || tree.is_cancelled() || *tree.state() != self.history[0] {
+            return false;
+        }
+        for &mv in &self.moves {
+            if tree.choose(mv).is_err() {
+                return false;
+            }
+        }
+        tree.set_cancel_token(None);
+        tree.set_node_limit(Some(RETAINED_NODE_LIMIT));
+        self.tree = tree;
+        true
+    }
+
+    /// Returns the player that has to move.
+    pub fn player(&self) -> CheckBox {
+        self.tree.player()
+    }
+
+    /// Returns the outcome of the game so far.
+    pub fn result(&self) -> GameResult {
+        match self.adjudication {
+            Some(result) => result,
+            None => self.state().result(),
+        }
+    }
+
+    /// Ends the game with `result`, unless it's over already. No moves can
+    /// be played or undone after that.
+    fn adjudicate(&mut self, result: GameResult) {
+        if self.result() == GameResult::InProgress {
+            self.thinking = false;
+            self.adjudication = Some(result);
+            self.draw_offer = None;
+            self.check_game_over();
+        }
+    }
+
+    /// Ends the game because `player` ran out of time, unless it's over
+    /// already.
+    pub fn run_out_of_time(&mut self, player: CheckBox) {
+        self.adjudicate(GameResult::TimeForfeit(player))
+    }
+
+    /// Makes `player` run out of time, as the timeout policy of the game
+    /// says.
+    ///
+    /// Returns the move played for them, if the policy is to move for them
+    /// and it's their turn.
+    pub fn time_out(&mut self, player: CheckBox) -> Option<S::Move> {
+        if self.result() != GameResult::InProgress {
+            return None;
+        }
+        match self.timeout_policy {
+            TimeoutPolicy::GreedyMove => {
+                if self.player() != player {
+                    return None;
+                }
+                // Just one level, which is instant.
+                let mv = self.tree.best_move_for(player, 1)?;
+                log::log(Level::Info, "game", "moved out of time", &[
+                    ("player", &player.dump_char()),
+                    ("move", &format_args!("{:?}", mv)),
+                ]);
+                self.choose(mv).expect("The engine should only play legal moves");
+                self.mark_timed_out();
+                Some(mv)
+            }
+            TimeoutPolicy::Forfeit => {
+                self.run_out_of_time(player);
+                None
+            }
+        }
+    }
+
+    /// Ends the game with `player` resigning, whether or not it's their
+    /// turn, unless it's over already.
+    pub fn resign(&mut self, player: CheckBox) {
+        self.adjudicate(GameResult::Resigned(player))
+    }
+
+    /// Offers a draw from `player`, which stands until the other player
+    /// answers it or somebody moves.
+    ///
+    /// Returns whether it was offered, which it isn't if the game is over or
+    /// there's an offer already.
+    pub fn offer_draw(&mut self, player: CheckBox) -> bool {
+        if self.result() != GameResult::InProgress || self.draw_offer.is_some() {
+            return false;
+        }
+        self.draw_offer = Some(player);
+        true
+    }
+
+    /// Returns the player whose draw offer is waiting for an answer, if any.
+    pub fn draw_offer(&self) -> Option<CheckBox> {
+        self.draw_offer
+    }
+
+    /// Answers the draw offer of the opponent of `player`, ending the game in
+    /// a draw if they `accept` it.
+    ///
+    /// Returns whether there was such an offer.
+    pub fn answer_draw_offer(&mut self, player: CheckBox, accept: bool) -> bool {
+        if self.draw_offer != Some(player.next_player()) {
+            return false;
+        }
+        self.draw_offer = None;
+        if accept {
+            self.adjudicate(GameResult::DrawAgreed);
+        }
+        true
+    }
+
+    /// Returns whether the engine, playing `player`, would take a draw now,
+    /// which it does when its search says it can't win.
+    pub fn engine_accepts_draw(&self, player: CheckBox) -> bool {
+        if self.result() != GameResult::InProgress {
+            return false;
+        }
+        let config = self.player_config(player);
+        let max_depth = config.search_config(S::depth_limit()).depth();
+        let mut tree = MiniMaxTree::from_state(self.state().clone(), self.player());
+        tree.set_evaluator(self.evaluators[side(player)].clone());
+        // Scores are from O's point of view.
+        let sign = |player| if player == CheckBox::O { 1 } else { -1 };
+        let best = tree.evaluate_all_moves(max_depth).into_iter()
+            .map(|(_, score)| score * sign(self.player()))
+            .max();
+        match best {
+            Some(best) => best * sign(self.player()) * sign(player) <= 0,
+            None => true,
+        }
+    }
+
+    /// Records that `mv` was just played.
+    fn push_move(&mut self, mv: S::Move) {
+        // The tree moved already, and players alternate.
+        log::log(Level::Debug, "game", "moved", &[
+            ("player", &self.player().next_player().dump_char()),
+            ("move", &format_args!("{:?}", mv)),
+        ]);
+        self.draw_offer = None;
+        self.thinking = false;
+        self.moves.push(mv);
+        self.history.push(self.tree.state().clone());
+        self.check_game_over();
+    }
+
+    /// Plays `mv` as the current player.
+    ///
+    /// Returns an error if the move is not legal. Playing a new move forgets
+    /// all the moves that could be redone.
+    pub fn choose(&mut self, mv: S::Move) -> Result<(), MoveError> {
+        if self.adjudication.is_some() {
+            return Err(MoveError::GameOver);
+        }
+        self.tree.choose(mv)?;
+        self.push_move(mv);
+        self.undone.clear();
+        Ok(())
+    }
+
+    /// Returns a description of the game for bug reports: how it started,
+    /// the moves played so far and the board they lead to.
+    pub fn describe(&self) -> String {
+        let mut ret = format!("first-player {}\ndifficulty {}\nmoves",
+                              self.first_player.dump_char(), self.difficulty().name());
+        for mv in &self.moves {
+            ret.push_str(&format!(" {:?}", mv));
+        }
+        ret.push('\n');
+        self.state().dump(0, &mut ret).expect("Writing to a string can't fail");
+        ret
+    }
+
+    /// Returns a tree of the current position with `player` to move, which
+    /// scores positions like the engine does for `player`, to search
+    /// without changing the game.
+    pub fn engine_tree(&self, player: CheckBox) -> MiniMaxTree<S> {
+        let mut tree = MiniMaxTree::from_state(self.state().clone(), player);
+        tree.set_evaluator(self.evaluators[side(player)].clone());
+        tree
+    }
+
+    /// Returns the move the engine would play for `player`, searching as deep
+    /// as its config allows, without playing it.
+    pub fn hint(&self, player: CheckBox) -> Option<S::Move> {
+        self.hint_job(player, CancelToken::new())()
+    }
+
+    /// Like `hint`, but searching deeper and deeper only while the next
+    /// search is expected to keep within `node_budget` nodes, like the Auto
+    /// difficulty. At least one level is always searched.
+    pub fn hint_within_budget(&self, player: CheckBox, node_budget: usize) -> Option<S::Move> {
+        let config = self.player_config(player);
+        let max_depth = config.search_config(S::depth_limit()).depth();
+        let mut tree = self.engine_tree(player);
+        let index = tree.find_move_index_within_budget(node_budget, max_depth)?;
+        Some(tree.move_for_index(index))
+    }
+
+    /// Like `hint`, but as a job to run on another thread, which finds no
+    /// move if `cancel` is cancelled.
+    pub fn hint_job(&self, player: CheckBox, cancel: CancelToken) -> Job<Option<S::Move>> {
+        let config = self.player_config(player);
+        let max_depth = config.search_config(S::depth_limit()).depth();
+        let mut tree = self.engine_tree(player);
+        tree.set_cancel_token(Some(cancel));
+        Box::new(move || {
+            let index = tree.find_move_index(max_depth).index()?;
+            Some(tree.move_for_index(index))
+        })
+    }
+
+    /// Summarizes how `player` played this game so far: whether they won,
+    /// and how many of their moves scored worse than their best one,
+    /// searching as deep as their config allows.
+    pub fn summary(&self, player: CheckBox) -> GameSummary {
+        self.summary_job(player)()
+    }
+
+    /// Like `summary`, but as a job to run on another thread.
+    pub fn summary_job(&self, player: CheckBox) -> Job<GameSummary> {
+        let config = self.player_config(player);
+        let max_depth = config.search_config(S::depth_limit()).depth();
+        let evaluator = self.evaluators[side(player)].clone();
+        let first_player = self.first_player;
+        let history = self.history.clone();
+        let played = self.moves.clone();
+        let mut results = scoreboard::Tally::default();
+        results.record(self.result(), player);
+        Box::new(move || {
+            // Scores are from O's point of view.
+            let sign = if player == CheckBox::O { 1 } else { -1 };
+            let mut mover = first_player;
+            let (mut mistakes, mut moves) = (0, 0);
+            for (state, &mv) in history.into_iter().zip(&played) {
+                if mover == player {
+                    let mut tree = MiniMaxTree::from_state(state, player);
+                    tree.set_evaluator(evaluator.clone());
+                    let scores = tree.evaluate_all_moves(max_depth);
+                    let best = scores.iter().map(|&(_, score)| score * sign).max();
+                    let played = scores.iter().find(|&&(m, _)| m == mv).map(|&(_, score)| score * sign);
+                    if played < best {
+                        mistakes += 1;
+                    }
+                    moves += 1;
+                }
+                mover = mover.next_player();
+            }
+            GameSummary { won: results.wins > 0, mistakes, moves }
+        })
+    }
+
+    /// Returns the score of the current position from O's point of view,
+    /// searching as deep as the config of `player` allows, without playing.
+    pub fn evaluation(&self, player: CheckBox) -> i8 {
+        self.evaluation_job(player, CancelToken::new())()
+    }
+
+    /// Like `evaluation`, but as a job to run on another thread, which stops
+    /// searching if `cancel` is cancelled.
+    pub fn evaluation_job(&self, player: CheckBox, cancel: CancelToken) -> Job<i8> {
+        let config = self.player_config(player);
+        let max_depth = config.search_config(S::depth_limit()).depth();
+        let mut tree = MiniMaxTree::from_state(self.state().clone(), self.player());
+        tree.set_evaluator(self.evaluators[side(player)].clone());
+        tree.set_cancel_token(Some(cancel));
+        Box::new(move || tree.evaluate(max_depth))
+    }
+
+    /// Returns the line of play the engine expects from here, searching as
+    /// deep as the game allows, or nothing if the game is over.
+    pub fn principal_variation(&mut self) -> Vec<S::Move> {
+        match self.tree.find_move_with_pv(S::depth_limit()) {
+            Some((_, pv, _)) => pv,
+            None => vec![],
+        }
+    }
+
+    /// Lets the engine play a move for the current player.
+    ///
+    /// Returns the move that was played, or `None` if the engine didn't
+    /// move.
+    pub fn play_engine_move<R>(&mut self, rng: &mut R) -> Option<S::Move>
+        where R: Rng + ?Sized,
+    {
+        if self.adjudication.is_some() {
+            return None;
+        }
+        let search = self.engine_search();
+        let (index, handicapped) = search.choose(&mut self.tree, rng)?;
+        Some(self.play_engine_choice(&search, index, handicapped))
+    }
+
+    /// Returns how the engine chooses the move of the current player.
+    fn engine_search(&self) -> EngineSearch<S> {
+        let side = side(self.player());
+        let config = self.players[side].clone();
+        EngineSearch {
+            max_depth: config.search_config(S::depth_limit()).depth(),
+            config,
+            evaluator: self.evaluators[side].clone(),
+            node_budget: self.node_budget,
+            adaptive: self.adaptive,
+            // Players alternate, so these are the first moves of each side.
+            opening: self.moves.len() < 2,
+        }
+    }
+
+    /// Plays the move with `index` in the tree, which the engine chose with
+    /// `search`.
+    fn play_engine_choice(&mut self, search: &EngineSearch<S>, index: usize, handicapped: bool) -> S::Move {
+        let mv = self.tree.move_for_index(index);
+        let stats = self.tree.search_stats();
+        log::log(Level::Info, "game", "engine moved", &[
+            ("player", &self.player().dump_char()),
+            ("move", &format_args!("{:?}", mv)),
+            ("difficulty", &search.config.difficulty.name()),
+            ("depth", &search.max_depth),
+            ("reached", &stats.max_depth),
+            ("nodes", &stats.nodes),
+            ("cutoffs", &stats.cutoffs),
+            ("book", &stats.book),
+            ("handicapped", &handicapped),
+        ]);
+        self.tree.choose_with_index(index);
+
+        self.push_move(mv);
+        self.undone.clear();
+        mv
+    }
+
+    /// Returns a job that chooses the move of the engine for the current
+    /// player on another thread, with the random numbers of `rng`, for
+    /// `finish_thinking` to play it.
+    ///
+    /// The job searches the tree of the game, so the game searches from
+    /// scratch until the move is played. Playing or undoing a move, or the
+    /// game ending meanwhile, makes `finish_thinking` ignore the move.
+    pub fn think(&mut self, mut rng: Box<dyn RngCore + Send>, cancel: CancelToken) -> Job<EngineReply> {
+        let search = self.engine_search();
+        let plies = self.moves.len();
+        let scratch = new_tree(self.state().clone(), self.player());
+        let mut tree = mem::replace(&mut self.tree, scratch);
+        tree.set_cancel_token(Some(cancel));
+        self.thinking = self.adjudication.is_none();
+        Box::new(move || {
+            let choice = search.choose(&mut tree, &mut *rng);
+            EngineReply(Box::new(Thought { tree, search, choice, plies }))
+        })
+    }
+
+    /// Plays the move the engine chose in `reply`, unless it's from another
+    /// game, it was cancelled, or the game changed since it started
+    /// thinking.
+    ///
+    /// Returns the move that was played, if any.
+    pub fn finish_thinking(&mut self, reply: EngineReply) -> Option<S::Move> {
+        let thought = match reply.0.downcast::<Thought<S>>() {
+            Ok(thought) => *thought,
+            Err(..) => return None,
+        };
+        let Thought { mut tree, search, choice, plies } = thought;
+        if !self.thinking || plies != self.moves.len() || tree.is_cancelled() {
+            return None;
+        }
+        self.thinking = false;
+        tree.set_cancel_token(None);
+        self.tree = tree;
+        let (index, handicapped) = choice?;
+        Some(self.play_engine_choice(&search, index, handicapped))
+    }
+
+    /// Undoes the last move, if any.
+    ///
+    /// Returns whether a move was undone.
+    pub fn undo(&mut self) -> bool {
+        if self.adjudication.is_some() {
+            return false;
+        }
+        let last = match self.moves.pop() {
+            Some(m) => m,
+            None => return false,
+        };
+        self.undone.push(last);
+        self.history.pop();
+        self.thinking = false;
+        let plies = self.moves.len();
+        self.timed_out.retain(|&ply| ply <= plies);
+
+        // The tree only knows how to go forward, so replay the game up to
+        // this point.
+        //
+        // TODO(emilio): We could keep the previous nodes around instead, but
+        // the trees are cheap enough to rebuild.
+        self.tree = new_tree(self.history[0].clone(), self.first_player);
+        for &mv in &self.moves {
+            self.tree.choose(mv).expect("Replayed moves should be legal");
+        }
+        true
+    }
+
+    /// Redoes the last undone move, if any.
+    ///
+    /// Returns whether a move was redone.
+    pub fn redo(&mut self) -> bool {
+        if self.adjudication.is_some() {
+            return false;
+        }
+        let mv = match self.undone.pop() {
+            Some(m) => m,
+            None => return false,
+        };
+        self.tree.choose(mv).expect("Redone moves should be legal");
+        self.push_move(mv);
+        true
+    }
+}
+
+/// A saved game, read and upgraded to the current version of the format,
+/// but not replayed yet.
+#[derive(Debug)]
+pub struct SavedGame {
+    version: u32,
+    fields: Fields,
+    /// The lines before the hash, which the hash of older versions is of.
+    record: String,
+}
+
+impl SavedGame {
+    /// Reads a game in the save format from `src`.
+    pub fn read<R>(src: R) -> Result<Self, LoadError>
+        where R: BufRead,
+    {
+        let mut lines = src.lines();
+        let header = match lines.next() {
+            Some(line) => line?,
+            None => return Err(LoadError::NotASavedGame),
+        };
+        let mut record = format!("{}\n", header);
+
+        let mut header = header.split_whitespace();
+        if header.next() != Some(SAVE_FORMAT_MAGIC) {
+            return Err(LoadError::NotASavedGame);
+        }
+        let version = header.next()
+            .and_then(|v| v.parse::<u32>().ok())
+            .ok_or(LoadError::NotASavedGame)?;
+
+        let mut fields = Fields::new();
+        for line in lines {
+            let line = line?;
+            if !line.starts_with("hash ") {
+                record.push_str(&line);
+                record.push('\n');
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut parts = line.splitn(2, ' ');
+            let name = parts.next().unwrap().to_owned();
+            let value = parts.next().unwrap_or("").trim().to_owned();
+            fields.insert(name, value);
+        }
+
+        migrate(version, &mut fields)?;
+        Ok(SavedGame { version, fields, record })
+    }
+
+    /// Returns the variant the game is of.
+    pub fn variant(&self) -> Result<Variant, LoadError> {
+        self.fields.get("variant")
+            .and_then(|id| Variant::from_id(id))
+            .ok_or(LoadError::InvalidField("variant"))
+    }
+}
+
+/// Saving and loading.
+impl<S: Savable> Game<S> {
+    /// Loads a game saved with `save`.
+    pub fn load<P>(path: P) -> Result<Self, LoadError>
+        where P: AsRef<Path>,
+    {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a game in the save format from `src`.
+    pub fn read<R>(src: R) -> Result<Self, LoadError>
+        where R: BufRead,
+    {
+        Self::from_saved(&SavedGame::read(src)?)
+    }
+
+    /// Replays the game `saved` has, which needs to be of this variant.
+    pub fn from_saved(saved: &SavedGame) -> Result<Self, LoadError> {
+        if saved.variant()? != S::variant() {
+            return Err(LoadError::InvalidField("variant"));
+        }
+        let fields = &saved.fields;
+
+        let first_player = fields.get("first-player")
+            .and_then(|v| v.chars().next())
+            .and_then(CheckBox::from_dump_char)
+            .and_then(|p| if p == CheckBox::Empty { None } else { Some(p) })
+            .ok_or(LoadError::InvalidField("first-player"))?;
+
+        let players = [("player-x", CheckBox::X), ("player-o", CheckBox::O)];
+        let mut configs = vec![];
+        for &(name, player) in &players {
+            let config = fields.get(name)
+                .and_then(|v| PlayerConfig::from_spec(v).ok())
+                .ok_or(LoadError::InvalidField(name))?;
+            configs.push((name, player, config));
+        }
+
+        let seed = fields.get("seed")
+            .and_then(|v| v.parse::<u64>().ok())
+            .ok_or(LoadError::InvalidField("seed"))?;
+
+        let time_control = |name| fields.get(name).map(|v| {
+            TimeControl::parse(v).ok_or(LoadError::InvalidField(name))
+        });
+        let time_controls = match (time_control("time-x"), time_control("time-o")) {
+            (Some(x), Some(o)) => Some([x?, o?]),
+            (None, None) => None,
+            (Some(..), None) => return Err(LoadError::InvalidField("time-o")),
+            (None, Some(..)) => return Err(LoadError::InvalidField("time-x")),
+        };
+
+        let mut game = match fields.get("position") {
+            Some(position) => {
+                let (state, player) = S::from_set_up_notation(position)
+                    .ok_or(LoadError::InvalidField("position"))?;
+                if player != first_player {
+                    return Err(LoadError::InvalidField("position"));
+                }
+                Game::from_state(state, first_player)
+            }
+            None => Game::new(first_player),
+        };
+        for (name, player, config) in configs {
+            game.set_player_config(player, config).map_err(|_| LoadError::InvalidField(name))?;
+        }
+        game.set_seed(seed);
+        game.set_time_controls(time_controls);
+        if let Some(policy) = fields.get("on-timeout") {
+            let policy = TimeoutPolicy::from_name(policy).ok_or(LoadError::InvalidField("on-timeout"))?;
+            game.set_timeout_policy(policy);
+        }
+
+        let moves = fields.get("moves").ok_or(LoadError::InvalidField("moves"))?;
+        for m in moves.split_whitespace() {
+            let mv = S::read_move(m).ok_or(LoadError::InvalidField("moves"))?;
+            if game.choose(mv).is_err() {
+                return Err(LoadError::IllegalMove(m.to_owned()));
+            }
+        }
+
+        if let Some(timed_out) = fields.get("timed-out") {
+            for ply in timed_out.split_whitespace() {
+                match ply.parse::<usize>() {
+                    Ok(ply) if ply > game.timed_out.last().cloned().unwrap_or(0) && ply <= game.moves.len() => {
+                        game.timed_out.push(ply);
+                    }
+                    _ => return Err(LoadError::InvalidField("timed-out")),
+                }
+            }
+        }
+
+        if let Some(result) = fields.get("result") {
+            match GameResult::from_name(result) {
+                Some(result @ GameResult::TimeForfeit(..)) |
+                Some(result @ GameResult::Resigned(..)) |
+                Some(result @ GameResult::DrawAgreed) if game.result() == GameResult::InProgress => {
+                    game.adjudicate(result);
+                }
+                _ => return Err(LoadError::InvalidField("result")),
+            }
+        }
+
+        // Version 1 had no hash to check. The hashes of other older versions
+        // are of the record as it was written then, rather than as this
+        // version would write it.
+        if saved.version >= 2 {
+            let hash = fields.get("hash").ok_or(LoadError::InvalidField("hash"))?;
+            let expected = if saved.version == SAVE_FORMAT_VERSION {
+                game.record_hash()
+            } else {
+                format!("{:016x}", fnv1a(saved.record.as_bytes()))
+            };
+            if *hash != expected {
+                return Err(LoadError::HashMismatch);
+            }
+        }
+
+        Ok(game)
+    }
+
+    /// Saves the game to `path`, so that it can be resumed with `load`.
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+        where P: AsRef<Path>,
+    {
+        let mut file = File::create(path)?;
+        self.write(&mut file)
+    }
+
+    /// Writes the game in the save format to `dest`.
+    pub fn write<W>(&self, dest: &mut W) -> io::Result<()>
+        where W: Write,
+    {
+        let record = self.record();
+        write!(dest, "{}", record)?;
+        writeln!(dest, "hash {}", self.record_hash())
+    }
+
+    /// Returns everything needed to replay the game exactly, in the save
+    /// format.
+    fn record(&self) -> String {
+        let mut record = format!("{} {}\n", SAVE_FORMAT_MAGIC, SAVE_FORMAT_VERSION);
+        record.push_str(&format!("variant {}\n", S::variant().id()));
+        record.push_str(&format!("first-player {}\n", self.first_player.dump_char()));
+        // Only for set up positions, so that the other records stay the same.
+        if let Some(position) = self.history[0].set_up_notation(self.first_player) {
+            record.push_str(&format!("position {}\n", position));
+        }
+        record.push_str(&format!("player-x {}\n", self.player_config(CheckBox::X).to_spec()));
+        record.push_str(&format!("player-o {}\n", self.player_config(CheckBox::O).to_spec()));
+        record.push_str(&format!("seed {}\n", self.seed));
+        if let Some([x, o]) = self.time_controls {
+            record.push_str(&format!("time-x {}\ntime-o {}\n", x, o));
+            record.push_str(&format!("on-timeout {}\n", self.timeout_policy.name()));
+        }
+        record.push_str("moves");
+        for &mv in &self.moves {
+            record.push(' ');
+            record.push_str(&S::write_move(mv));
+        }
+        record.push('\n');
+        if !self.timed_out.is_empty() {
+            let plies = self.timed_out.iter().map(|ply| ply.to_string()).collect::<Vec<_>>();
+            record.push_str(&format!("timed-out {}\n", plies.join(" ")));
+        }
+        if let Some(result) = self.adjudication {
+            record.push_str(&format!("result {}\n", result.name()));
+        }
+        record
+    }
+
+    /// Returns a hash of the moves and settings of the game, which is the
+    /// same for games that replay the same.
+    pub fn record_hash(&self) -> String {
+        format!("{:016x}", fnv1a(self.record().as_bytes()))
+    }
+}
+
+/// A position of any variant, as the GUI shows it.
+pub trait Position {
+    /// Returns the number of columns and rows of the grid.
+    fn grid_size(&self) -> (usize, usize);
+    /// Returns the mark in column `x` and row `y`.
+    fn mark(&self, x: usize, y: usize) -> CheckBox;
+    /// Returns the text to show in the square in column `x` and row `y`
+    /// besides its mark, if any.
+    fn square_label(&self, x: usize, y: usize) -> Option<String>;
+    /// Returns the squares of the line that won the game, if any.
+    fn winning_squares(&self) -> Vec<(usize, usize)>;
+    /// Returns a rendering of the position in an equivalent game, if any.
+    fn mirror(&self) -> Option<String>;
+}
+
+/// A position of a game in its history.
+struct HistoryPosition<'a, S: 'a>(&'a S);
+
+impl<'a, S: GridState> Position for HistoryPosition<'a, S> {
+    fn grid_size(&self) -> (usize, usize) {
+        self.0.grid_size()
+    }
+
+    fn mark(&self, x: usize, y: usize) -> CheckBox {
+        self.0.mark(x, y)
+    }
+
+    fn square_label(&self, x: usize, y: usize) -> Option<String> {
+        self.0.square_label(x, y)
+    }
+
+    fn winning_squares(&self) -> Vec<(usize, usize)> {
+        self.0.winning_squares()
+    }
+
+    fn mirror(&self) -> Option<String> {
+        self.0.mirror()
+    }
+}
+
+/// Returns the name of the square in column `x` and row `y`, like "b3" for
+/// the second column of the third row.
+pub fn square_name(x: usize, y: usize) -> String {
+    format!("{}{}", (b'a' + x as u8) as char, y + 1)
+}
+
+/// Returns who played the move that led from `before` to `after`, and the
+/// name of the square they marked.
+pub fn name_move<S: GridState>(before: &S, after: &S) -> (CheckBox, String) {
+    let (cols, rows) = after.grid_size();
+    for x in 0..cols {
+        for y in 0..rows {
+            let mark = after.mark(x, y);
+            if mark != before.mark(x, y) {
+                let name = after.square_label(x, y).unwrap_or_else(|| square_name(x, y));
+                return (mark, name);
+            }
+        }
+    }
+    unreachable!("Moves should mark a square")
+}
+
+/// A game of any variant, as the GUI sees it: a grid of squares that the
+/// player clicks, and to which the engine replies.
+pub trait Board {
+    /// Returns the number of columns and rows of the grid.
+    fn grid_size(&self) -> (usize, usize);
+    /// Returns the size of the sub-grids the grid is visually split in, if
+    /// any.
+    fn block_size(&self) -> Option<usize>;
+    /// Returns the mark in column `x` and row `y`.
+    fn mark(&self, x: usize, y: usize) -> CheckBox;
+    /// Returns whether clicking the square in column `x` and row `y` plays a
+    /// move.
+    fn is_playable(&self, x: usize, y: usize) -> bool;
+    fn result(&self) -> GameResult;
+    fn player(&self) -> CheckBox;
+    /// Returns the squares that play the move the engine recommends to
+    /// `player`, if any.
+    fn hint_squares(&self, player: CheckBox) -> Vec<(usize, usize)>;
+    /// Like `hint_squares`, within `node_budget` nodes, see
+    /// `Game::hint_within_budget`.
+    fn hint_squares_within_budget(&self, player: CheckBox, node_budget: usize) -> Vec<(usize, usize)>;
+    /// Like `hint_squares`, as a job to run on another thread, see
+    /// `Game::hint_job`.
+    fn hint_job(&self, player: CheckBox, cancel: CancelToken) -> Job<Vec<(usize, usize)>>;
+    /// Returns the score of the current position from O's point of view,
+    /// searching as deep as the config of `player` allows.
+    fn evaluation(&self, player: CheckBox) -> i8;
+    /// Like `evaluation`, as a job to run on another thread, see
+    /// `Game::evaluation_job`.
+    fn evaluation_job(&self, player: CheckBox, cancel: CancelToken) -> Job<i8>;
+    /// Returns the outcome of the current position with best play, if the
+    /// game is small enough to solve.
+    fn solve(&self) -> Option<Solution>;
+    /// Counts how every game where the player to move plays the square in
+    /// column `x` and row `y` ends, with its other move if `other_mark` is
+    /// set, or returns `None` if the game is too big to count them.
+    fn tally_click(&self, x: usize, y: usize, other_mark: bool) -> Result<Option<Tally>, MoveError>;
+    /// Returns the squares where `player` would win the game right away,
+    /// whether or not it's their turn.
+    fn immediate_threats(&self, player: CheckBox) -> Vec<(usize, usize)>;
+    /// Returns the line of play the engine expects from here: who plays
+    /// each move, and the square they mark.
+    fn principal_variation(&mut self) -> Vec<(CheckBox, String)>;
+    /// Searches the current position `depth` levels, and returns the tree of
+    /// the search, up to `levels` levels below it, as a Graphviz graph.
+    fn search_tree_dot(&mut self, depth: usize, levels: usize) -> String;
+    /// Plays the move for the square in column `x` and row `y`.
+    fn click(&mut self, x: usize, y: usize) -> Result<(), MoveError>;
+    /// Plays the other move for the square in column `x` and row `y`, in
+    /// games that have one.
+    fn click_other(&mut self, x: usize, y: usize) -> Result<(), MoveError>;
+    /// Plays the move that marks the square called `name` with `mark`, as
+    /// `move_names` names them.
+    fn play_named(&mut self, mark: CheckBox, name: &str) -> Result<(), MoveError>;
+    /// Returns whether the engine moved.
+    fn play_engine_move(&mut self, rng: &mut dyn RngCore) -> bool;
+    /// Returns a job that chooses the move of the engine for the player to
+    /// move on another thread, see `Game::think`.
+    fn think(&mut self, rng: Box<dyn RngCore + Send>, cancel: CancelToken) -> Job<EngineReply>;
+    /// Plays the move of `reply`, returning whether it did, see
+    /// `Game::finish_thinking`.
+    fn finish_thinking(&mut self, reply: EngineReply) -> bool;
+    fn undo(&mut self) -> bool;
+    fn redo(&mut self) -> bool;
+    fn difficulty(&self) -> Difficulty;
+    fn set_difficulty(&mut self, difficulty: Difficulty);
+    fn set_randomize(&mut self, randomize: bool);
+    fn player_config(&self, player: CheckBox) -> PlayerConfig;
+    fn set_player_config(&mut self, player: CheckBox, config: PlayerConfig) -> Result<(), String>;
+    fn set_node_budget(&mut self, node_budget: usize);
+    fn set_opponent_model(&mut self, model: &OpponentModel);
+    /// Summarizes how `player` played this game so far, for the opponent
+    /// model.
+    fn summary(&self, player: CheckBox) -> GameSummary;
+    fn summary_job(&self, player: CheckBox) -> Job<GameSummary>;
+    fn seed(&self) -> u64;
+    fn set_seed(&mut self, seed: u64);
+    fn time_controls(&self) -> Option<[TimeControl; 2]>;
+    fn set_time_controls(&mut self, controls: Option<[TimeControl; 2]>);
+    fn timeout_policy(&self) -> TimeoutPolicy;
+    fn set_timeout_policy(&mut self, policy: TimeoutPolicy);
+    fn timed_out(&self) -> Vec<usize>;
+    fn mark_timed_out(&mut self);
+    fn set_game_over_hook(&mut self, hook: GameOverHook);
+    /// Ends the game because `player` ran out of time.
+    fn run_out_of_time(&mut self, player: CheckBox);
+    /// Returns whether a move was played for `player`, see
+    /// `Game::time_out`.
+    fn time_out(&mut self, player: CheckBox) -> bool;
+    fn resign(&mut self, player: CheckBox);
+    /// Offers a draw from `player`, returning whether it was offered.
+    fn offer_draw(&mut self, player: CheckBox) -> bool;
+    /// Returns the player whose draw offer is waiting for an answer, if any.
+    fn draw_offer(&self) -> Option<CheckBox>;
+    /// Answers the draw offer of the opponent of `player`, returning whether
+    /// there was one.
+    fn answer_draw_offer(&mut self, player: CheckBox, accept: bool) -> bool;
+    /// Returns whether the engine, playing `player`, would take a draw now.
+    fn engine_accepts_draw(&self, player: CheckBox) -> bool;
+    fn warm_up(&self, cancel: CancelToken) -> WarmUpJob;
+    fn adopt_warm_tree(&mut self, tree: WarmTree) -> bool;
+    /// Returns a job that scores every move of the current position,
+    /// searching as deep as the game allows, and solves it if it's small
+    /// enough, to run on another thread.
+    fn analyze(&self, cancel: CancelToken) -> AnalysisJob;
+    fn describe(&self) -> String;
+    /// Returns the number of moves played so far.
+    fn plies(&self) -> usize;
+    /// Returns the position after the first `ply` moves.
+    fn position<'a>(&'a self, ply: usize) -> Box<dyn Position + 'a>;
+    /// Returns who played each move so far, and the square they marked.
+    fn move_names(&self) -> Vec<(CheckBox, String)>;
+    /// Writes the game in the save format to `dest`, see `Game::write`.
+    fn write_record(&self, dest: &mut dyn Write) -> io::Result<()>;
+    fn record_hash(&self) -> String;
+    /// Runs a console command against the game, see `commands::execute`.
+    fn execute(&mut self, line: &str) -> Result<String, String>;
+    /// Returns a job that runs a console command that only searches, to run
+    /// on another thread, see `commands::search_job`.
+    fn command_job(&self, line: &str) -> Option<Job<Result<String, String>>>;
+}
+
+/// Names the moves of `line`, played from `state` with `player` to move.
+fn name_line<S: GridState>(state: &S, player: CheckBox, line: &[S::Move]) -> Vec<(CheckBox, String)> {
+    let mut state = state.clone();
+    let mut player = player;
+    line.iter().map(|&mv| {
+        let (_, next) = state.successors(player).into_iter()
+            .find(|&(m, _)| m == mv)
+            .expect("The line of play should be legal");
+        let name = name_move(&state, &next);
+        state = next;
+        player = player.next_player();
+        name
+    }).collect()
+}
+
+/// Returns the squares of the grid that play `mv`.
+fn squares_for_move<S: GridState>(state: &S, mv: S::Move) -> Vec<(usize, usize)> {
+    let (cols, rows) = state.grid_size();
+    let mut squares = vec![];
+    for x in 0..cols {
+        for y in 0..rows {
+            if state.move_for_square(x, y) == Some(mv) ||
+                state.other_move_for_square(x, y) == Some(mv) {
+                squares.push((x, y));
+            }
+        }
+    }
+    squares
+}
+
+/// Returns the move `for_square` says the square in column `x` and row `y`
+/// plays, or why there's none.
+fn move_for_click<S, F>(game: &Game<S>, x: usize, y: usize, for_square: F) -> Result<S::Move, MoveError>
+    where S: GridState,
+          F: FnOnce(&S, usize, usize) -> Option<S::Move>,
+{
+    let state = game.state();
+    let (cols, rows) = state.grid_size();
+    if x >= cols || y >= rows {
+        return Err(MoveError::OutOfBounds);
+    }
+    if let Some(mv) = for_square(state, x, y) {
+        return Ok(mv);
+    }
+    Err(if game.result() != GameResult::InProgress {
+        MoveError::GameOver
+    } else if state.mark(x, y) != CheckBox::Empty {
+        MoveError::Occupied
+    } else {
+        MoveError::Illegal
+    })
+}
+
+impl<S: GridState + Savable> Board for Game<S> {
+    fn grid_size(&self) -> (usize, usize) {
+        self.state().grid_size()
+    }
+
+    fn block_size(&self) -> Option<usize> {
+        self.state().block_size()
+    }
+
+    fn mark(&self, x: usize, y: usize) -> CheckBox {
+        self.state().mark(x, y)
+    }
+
+    fn is_playable(&self, x: usize, y: usize) -> bool {
+        self.state().move_for_square(x, y).is_some()
+    }
+
+    fn result(&self) -> GameResult {
+        Game::result(self)
+    }
+
+    fn player(&self) -> CheckBox {
+        Game::player(self)
+    }
+
+    fn hint_squares(&self, player: CheckBox) -> Vec<(usize, usize)> {
+        match self.hint(player) {
+            Some(mv) => squares_for_move(self.state(), mv),
+            None => vec![],
+        }
+    }
+
+    fn hint_squares_within_budget(&self, player: CheckBox, node_budget: usize) -> Vec<(usize, usize)> {
+        match self.hint_within_budget(player, node_budget) {
+            Some(mv) => squares_for_move(self.state(), mv),
+            None => vec![],
+        }
+    }
+
+    fn hint_job(&self, player: CheckBox, cancel: CancelToken) -> Job<Vec<(usize, usize)>> {
+        let job = Game::hint_job(self, player, cancel);
+        let state = self.state().clone();
+        Box::new(move || match job() {
+            Some(mv) => squares_for_move(&state, mv),
+            None => vec![],
+        })
+    }
+
+    fn evaluation(&self, player: CheckBox) -> i8 {
+        Game::evaluation(self, player)
+    }
+
+    fn evaluation_job(&self, player: CheckBox, cancel: CancelToken) -> Job<i8> {
+        Game::evaluation_job(self, player, cancel)
+    }
+
+    fn solve(&self) -> Option<Solution> {
+        if S::is_solvable() { Some(self.tree.solve()) } else { None }
+    }
+
+    fn tally_click(&self, x: usize, y: usize, other_mark: bool) -> Result<Option<Tally>, MoveError> {
+        let mv = if other_mark {
+            move_for_click(self, x, y, S::other_move_for_square)?
+        } else {
+            move_for_click(self, x, y, S::move_for_square)?
+        };
+        if !S::is_solvable() {
+            return Ok(None);
+        }
+        Ok(self.tree.tally_move(mv))
+    }
+
+    fn immediate_threats(&self, player: CheckBox) -> Vec<(usize, usize)> {
+        let mut squares = vec![];
+        for mv in self.tree.immediate_threats(player) {
+            for square in squares_for_move(self.state(), mv) {
+                if !squares.contains(&square) {
+                    squares.push(square);
+                }
+            }
+        }
+        squares
+    }
+
+    fn principal_variation(&mut self) -> Vec<(CheckBox, String)> {
+        let pv = Game::principal_variation(self);
+        name_line(self.state(), self.player(), &pv)
+    }
+
+    fn search_tree_dot(&mut self, depth: usize, levels: usize) -> String {
+        let depth = SearchConfig::clamped(depth, S::depth_limit()).depth();
+        self.tree.find_best_move(depth);
+        let mut dot = String::new();
+        self.tree.write_dot(levels, &mut dot).expect("Writing to a string can't fail");
+        dot
+    }
+
+    fn click(&mut self, x: usize, y: usize) -> Result<(), MoveError> {
+        let mv = move_for_click(self, x, y, S::move_for_square)?;
+        self.choose(mv)
+    }
+
+    fn click_other(&mut self, x: usize, y: usize) -> Result<(), MoveError> {
+        let mv = move_for_click(self, x, y, S::other_move_for_square)?;
+        self.choose(mv)
+    }
+
+    fn play_named(&mut self, mark: CheckBox, name: &str) -> Result<(), MoveError> {
+        if self.result() != GameResult::InProgress {
+            return Err(MoveError::GameOver);
+        }
+        let state = self.state().clone();
+        let mv = state.successors(self.player()).into_iter()
+            .find(|(_, next)| {
+                let (m, n) = name_move(&state, next);
+                m == mark && n == name
+            })
+            .map(|(mv, _)| mv)
+            .ok_or(MoveError::Illegal)?;
+        self.choose(mv)
+    }
+
+    fn play_engine_move(&mut self, rng: &mut dyn RngCore) -> bool {
+        Game::play_engine_move(self, rng).is_some()
+    }
+
+    fn think(&mut self, rng: Box<dyn RngCore + Send>, cancel: CancelToken) -> Job<EngineReply> {
+        Game::think(self, rng, cancel)
+    }
+
+    fn finish_thinking(&mut self, reply: EngineReply) -> bool {
+        Game::finish_thinking(self, reply).is_some()
+    }
+
+    fn undo(&mut self) -> bool {
+        Game::undo(self)
+    }
+
+    fn redo(&mut self) -> bool {
+        Game::redo(self)
+    }
+
+    fn difficulty(&self) -> Difficulty {
+        Game::difficulty(self)
+    }
+
+    fn set_difficulty(&mut self, difficulty: Difficulty) {
+        Game::set_difficulty(self, difficulty)
+    }
+
+    fn set_randomize(&mut self, randomize: bool) {
+        Game::set_randomize(self, randomize)
+    }
+
+    fn player_config(&self, player: CheckBox) -> PlayerConfig {
+        Game::player_config(self, player).clone()
+    }
+
+    fn set_player_config(&mut self, player: CheckBox, config: PlayerConfig) -> Result<(), String> {
+        Game::set_player_config(self, player, config)
+    }
+
+    fn set_node_budget(&mut self, node_budget: usize) {
+        Game::set_node_budget(self, node_budget)
+    }
+
+    fn set_opponent_model(&mut self, model: &OpponentModel) {
+        Game::set_opponent_model(self, model)
+    }
+
+    fn summary(&self, player: CheckBox) -> GameSummary {
+        Game::summary(self, player)
+    }
+
+    fn summary_job(&self, player: CheckBox) -> Job<GameSummary> {
+        Game::summary_job(self, player)
+    }
+
+    fn seed(&self) -> u64 {
+        Game::seed(self)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        Game::set_seed(self, seed)
+    }
+
+    fn time_controls(&self) -> Option<[TimeControl; 2]> {
+        Game::time_controls(self)
+    }
+
+    fn set_time_controls(&mut self, controls: Option<[TimeControl; 2]>) {
+        Game::set_time_controls(self, controls)
+    }
+
+    fn timeout_policy(&self) -> TimeoutPolicy {
+        Game::timeout_policy(self)
+    }
+
+    fn set_timeout_policy(&mut self, policy: TimeoutPolicy) {
+        Game::set_timeout_policy(self, policy)
+    }
+
+    fn timed_out(&self) -> Vec<usize> {
+        Game::timed_out(self).to_vec()
+    }
+
+    fn mark_timed_out(&mut self) {
+        Game::mark_timed_out(self)
+    }
+
+    fn set_game_over_hook(&mut self, hook: GameOverHook) {
+        Game::set_game_over_hook(self, hook)
+    }
+
+    fn run_out_of_time(&mut self, player: CheckBox) {
+        Game::run_out_of_time(self, player)
+    }
+
+    fn time_out(&mut self, player: CheckBox) -> bool {
+        Game::time_out(self, player).is_some()
+    }
+
+    fn resign(&mut self, player: CheckBox) {
+        Game::resign(self, player)
+    }
+
+    fn offer_draw(&mut self, player: CheckBox) -> bool {
+        Game::offer_draw(self, player)
+    }
+
+    fn draw_offer(&self) -> Option<CheckBox> {
+        Game::draw_offer(self)
+    }
+
+    fn answer_draw_offer(&mut self, player: CheckBox, accept: bool) -> bool {
+        Game::answer_draw_offer(self, player, accept)
+    }
+
+    fn engine_accepts_draw(&self, player: CheckBox) -> bool {
+        Game::engine_accepts_draw(self, player)
+    }
+
+    fn warm_up(&self, cancel: CancelToken) -> WarmUpJob {
+        Game::warm_up(self, cancel)
+    }
+
+    fn adopt_warm_tree(&mut self, tree: WarmTree) -> bool {
+        Game::adopt_warm_tree(self, tree)
+    }
+
+    fn analyze(&self, cancel: CancelToken) -> AnalysisJob {
+        let player = self.player();
+        let mut tree = self.engine_tree(player);
+        tree.set_cancel_token(Some(cancel));
+        Box::new(move || {
+            let mut square_scores = vec![];
+            for (mv, score) in tree.evaluate_all_moves(S::depth_limit()) {
+                // Scores are for what a click plays.
+                for (x, y) in squares_for_move(tree.state(), mv) {
+                    if tree.state().move_for_square(x, y) == Some(mv) {
+                        square_scores.push(((x, y), score));
+                    }
+                }
+            }
+            let pv = tree.find_move_with_pv(S::depth_limit())
+                .map_or_else(Vec::new, |(_, pv, _)| pv);
+            if tree.is_cancelled() {
+                return None;
+            }
+            // Only solvable games are small enough to prove a draw, and
+            // then the line of play is the one to hold it along.
+            let solution = if S::is_solvable() { Some(tree.solve()) } else { None };
+            let exact_moves_to_draw = match solution {
+                Some(Solution::Draw) => tree.exact_moves_to_draw(&pv),
+                _ => None,
+            };
+            Some(Analysis {
+                square_scores,
+                solution,
+                principal_variation: name_line(tree.state(), player, &pv),
+                exact_moves_to_draw,
+            })
+        })
+    }
+
+    fn describe(&self) -> String {
+        Game::describe(self)
+    }
+
+    fn plies(&self) -> usize {
+        self.moves.len()
+    }
+
+    fn position<'a>(&'a self, ply: usize) -> Box<dyn Position + 'a> {
+        Box::new(HistoryPosition(&self.history[ply]))
+    }
+
+    fn move_names(&self) -> Vec<(CheckBox, String)> {
+        self.history.windows(2).map(|w| name_move(&w[0], &w[1])).collect()
+    }
+
+    fn write_record(&self, mut dest: &mut dyn Write) -> io::Result<()> {
+        Game::write(self, &mut dest)
+    }
+
+    fn record_hash(&self) -> String {
+        Game::record_hash(self)
+    }
+
+    fn execute(&mut self, line: &str) -> Result<String, String> {
+        commands::execute(self, line)
+    }
+
+    fn command_job(&self, line: &str) -> Option<Job<Result<String, String>>> {
+        commands::search_job(self, line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use variant::{AnyGame, Variant};
+
+    #[test]
+    fn reports_each_game_once() {
+        let results = Rc::new(RefCell::new(vec![]));
+        let mut game = Game::<State>::new(CheckBox::X);
+        {
+            let results = results.clone();
+            game.set_game_over_hook(Box::new(move |r| results.borrow_mut().push(r)));
+        }
+
+        // X wins down the first column.
+        for &mv in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+            game.choose(mv).unwrap();
+        }
+        assert!(results.borrow().is_empty());
+        game.choose((0, 2)).unwrap();
+        assert_eq!(*results.borrow(), [GameResult::XWins]);
+
+        assert!(game.undo());
+        assert!(game.redo());
+        assert!(game.undo());
+        game.choose((2, 2)).unwrap();
+        game.choose((1, 2)).unwrap();
+        assert_eq!(*results.borrow(), [GameResult::XWins]);
+    }
+
+    #[test]
+    fn ends_when_out_of_time() {
+        let results = Rc::new(RefCell::new(vec![]));
+        let mut game = Game::<State>::new(CheckBox::X);
+        {
+            let results = results.clone();
+            game.set_game_over_hook(Box::new(move |r| results.borrow_mut().push(r)));
+        }
+        game.choose((1, 1)).unwrap();
+        game.run_out_of_time(CheckBox::O);
+        assert_eq!(game.result(), GameResult::TimeForfeit(CheckBox::O));
+        assert_eq!(*results.borrow(), [GameResult::TimeForfeit(CheckBox::O)]);
+        assert!(game.choose((0, 0)).is_err());
+        assert!(game.play_engine_move(&mut ::rand::thread_rng()).is_none());
+        assert!(!game.undo());
+
+        // Only the first way the game ends counts.
+        game.run_out_of_time(CheckBox::X);
+        assert_eq!(game.result(), GameResult::TimeForfeit(CheckBox::O));
+    }
+
+    #[test]
+    fn moves_for_whoever_runs_out_of_time() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        game.set_time_controls(Some([TimeControl::from_secs(1, 0); 2]));
+        game.set_timeout_policy(TimeoutPolicy::GreedyMove);
+        for &mv in &[(0, 0), (1, 1), (2, 2), (1, 0), (0, 2)] {
+            game.choose(mv).unwrap();
+        }
+        // Only on their turn.
+        assert_eq!(game.time_out(CheckBox::X), None);
+        assert_eq!(game.time_out(CheckBox::O), Some((1, 2)));
+        assert_eq!(game.timed_out(), &[6]);
+        assert_eq!(game.result(), GameResult::OWins);
+
+        let mut saved = vec![];
+        game.write(&mut saved).unwrap();
+        let saved = String::from_utf8(saved).unwrap();
+        assert!(saved.contains("\non-timeout greedy-move\n"), "{}", saved);
+        assert!(saved.contains("\ntimed-out 6\n"), "{}", saved);
+        let loaded = Game::<State>::read(saved.as_bytes()).unwrap();
+        assert_eq!(loaded.timeout_policy(), TimeoutPolicy::GreedyMove);
+        assert_eq!(loaded.timed_out(), game.timed_out());
+        assert!(Game::<State>::read(saved.replace("timed-out 6", "timed-out 7").as_bytes()).is_err());
+
+        assert!(game.undo());
+        assert!(game.timed_out().is_empty());
+
+        game.set_timeout_policy(TimeoutPolicy::Forfeit);
+        assert_eq!(game.time_out(CheckBox::O), None);
+        assert_eq!(game.result(), GameResult::TimeForfeit(CheckBox::O));
+        assert!(Game::<State>::read(saved.replace("greedy-move", "skip-turn").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn ends_by_resigning_or_agreeing_to_draw() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        game.choose((1, 1)).unwrap();
+        assert!(game.offer_draw(CheckBox::O));
+        assert!(!game.offer_draw(CheckBox::X));
+        // Only the other player can answer, and moving withdraws it.
+        assert!(!game.answer_draw_offer(CheckBox::O, true));
+        game.choose((0, 0)).unwrap();
+        assert_eq!(game.draw_offer(), None);
+
+        assert!(game.offer_draw(CheckBox::X));
+        assert!(game.answer_draw_offer(CheckBox::O, false));
+        assert_eq!(game.result(), GameResult::InProgress);
+        assert!(game.offer_draw(CheckBox::X));
+        assert!(game.answer_draw_offer(CheckBox::O, true));
+        assert_eq!(game.result(), GameResult::DrawAgreed);
+        assert!(!game.undo());
+        let reload = |game: &Game| {
+            let mut saved = vec![];
+            game.write(&mut saved).unwrap();
+            Game::<State>::read(&saved[..]).unwrap()
+        };
+        let loaded = reload(&game);
+        assert_eq!(loaded.result(), GameResult::DrawAgreed);
+        // Which the hash tells from the game going on.
+        let mut unfinished = Game::<State>::new(CheckBox::X);
+        unfinished.choose((1, 1)).unwrap();
+        unfinished.choose((0, 0)).unwrap();
+        assert_eq!(loaded.record_hash(), game.record_hash());
+        assert_ne!(loaded.record_hash(), unfinished.record_hash());
+
+        let mut game = Game::<State>::new(CheckBox::X);
+        game.resign(CheckBox::X);
+        assert_eq!(game.result(), GameResult::Resigned(CheckBox::X));
+        assert!(game.choose((1, 1)).is_err());
+        assert!(!game.offer_draw(CheckBox::O));
+        let mut loaded = reload(&game);
+        assert_eq!(loaded.result(), GameResult::Resigned(CheckBox::X));
+        assert!(loaded.choose((1, 1)).is_err());
+        assert_eq!(loaded.record_hash(), game.record_hash());
+
+        // Only results off the board are saved, and only for games that
+        // weren't over on it.
+        let mut saved = vec![];
+        Game::<State>::new(CheckBox::X).write(&mut saved).unwrap();
+        let saved = String::from_utf8(saved).unwrap();
+        let saved = saved.replace("\nhash ", "\nresult x-wins\nhash ");
+        match Game::<State>::read(saved.as_bytes()) {
+            Err(LoadError::InvalidField("result")) => {}
+            other => panic!("Expected an invalid result, got {:?}", other),
+        }
+
+        // The engine takes a draw when it can't win, but not when it can.
+        let mut game = Game::<State>::new(CheckBox::X);
+        assert!(game.engine_accepts_draw(CheckBox::O));
+        game.choose((0, 0)).unwrap();
+        game.choose((1, 0)).unwrap();
+        game.choose((1, 1)).unwrap();
+        game.choose((0, 1)).unwrap();
+        assert!(!game.engine_accepts_draw(CheckBox::X));
+        assert!(game.engine_accepts_draw(CheckBox::O));
+    }
+
+    #[test]
+    fn hashes_the_record() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        game.set_seed(42);
+        game.choose((1, 1)).unwrap();
+        game.choose((0, 2)).unwrap();
+        let mut saved = vec![];
+        game.write(&mut saved).unwrap();
+        let saved = String::from_utf8(saved).unwrap();
+        assert!(saved.ends_with(&format!("hash {}\n", game.record_hash())));
+
+        let loaded = Game::<State>::read(saved.as_bytes()).unwrap();
+        assert_eq!(loaded.seed(), 42);
+        assert_eq!(loaded.record_hash(), game.record_hash());
+
+        // And with the time of each player.
+        game.set_time_controls(Some([TimeControl::from_secs(30, 5), TimeControl::from_secs(1, 1)]));
+        let mut saved = vec![];
+        game.write(&mut saved).unwrap();
+        let saved = String::from_utf8(saved).unwrap();
+        assert!(saved.contains("\ntime-x 30+5\ntime-o 1+1\n"));
+        let loaded = Game::<State>::read(saved.as_bytes()).unwrap();
+        assert_eq!(loaded.time_controls(), game.time_controls());
+        assert_eq!(loaded.record_hash(), game.record_hash());
+        let untimed = saved.replace("time-o 1+1\n", "");
+        assert!(Game::<State>::read(untimed.as_bytes()).is_err());
+
+        let tampered = saved.replace("moves 1,1 0,2", "moves 1,1 2,2");
+        match Game::<State>::read(tampered.as_bytes()) {
+            Err(LoadError::HashMismatch) => {}
+            other => panic!("Expected a hash mismatch, got {:?}", other),
+        }
+
+        // Files from before there were hashes still load.
+        let old = "tic-tac-minimax-save 1\nfirst-player X\ndifficulty Perfect\nmoves 1,1\n";
+        assert_eq!(Game::<State>::read(old.as_bytes()).unwrap().seed(), 0);
+
+        // And so do the ones hashed by older versions, which are still
+        // checked.
+        let old = "tic-tac-minimax-save 2\nfirst-player X\ndifficulty Perfect\n\
+                   randomize false\nseed 7\nmoves 1,1 0,2\n";
+        let old = format!("{}hash {:016x}\n", old, fnv1a(old.as_bytes()));
+        let loaded = AnyGame::read(old.as_bytes()).unwrap();
+        assert_eq!(loaded.variant(), Variant::TicTacToe);
+        assert_eq!(loaded.board().plies(), 2);
+        assert!(AnyGame::read(old.replace("seed 7", "seed 8").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn saves_every_variant() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for &variant in Variant::all().iter() {
+            let mut game = variant.new_game(CheckBox::X);
+            game.board_mut().set_difficulty(Difficulty::Easy);
+            game.board_mut().set_randomize(true);
+            for _ in 0..3 {
+                game.board_mut().play_engine_move(&mut rng);
+            }
+            if variant == Variant::Wild {
+                game.board_mut().click_other(1, 1).unwrap();
+            }
+
+            let mut saved = vec![];
+            game.board().write_record(&mut saved).unwrap();
+            let saved = String::from_utf8(saved).unwrap();
+            assert!(saved.contains(&format!("\nvariant {}\n", variant.id())));
+            let loaded = AnyGame::read(saved.as_bytes()).unwrap();
+            assert_eq!(loaded.variant(), variant);
+            assert_eq!(loaded.board().move_names(), game.board().move_names());
+            assert_eq!(loaded.board().record_hash(), game.board().record_hash());
+        }
+
+        // A game of one variant isn't one of another.
+        let saved = "tic-tac-minimax-save 7\nvariant connect4\nfirst-player X\n";
+        match Game::<State>::read(saved.as_bytes()) {
+            Err(LoadError::InvalidField("variant")) => {}
+            other => panic!("Expected an invalid variant, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn starts_from_set_up_positions() {
+        let (state, player) = State::from_notation("XX_/_O_/___ O").unwrap();
+        let mut game = Game::from_state(state.clone(), player);
+        game.choose((2, 0)).unwrap();
+        assert!(game.undo());
+        assert_eq!(*game.state(), state);
+        assert_eq!(game.player(), CheckBox::O);
+        let warm = game.warm_up(CancelToken::new())();
+        assert!(game.adopt_warm_tree(warm));
+        // O has to block.
+        assert_eq!(game.play_engine_move(&mut ::rand::thread_rng()), Some((2, 0)));
+
+        let mut saved = vec![];
+        game.write(&mut saved).unwrap();
+        let saved = String::from_utf8(saved).unwrap();
+        assert!(saved.contains("\nposition XX_/_O_/___ O\n"));
+        let loaded = Game::<State>::read(saved.as_bytes()).unwrap();
+        assert_eq!(loaded.position(0).mark(1, 0), CheckBox::X);
+        assert_eq!(loaded.state(), game.state());
+        assert_eq!(loaded.record_hash(), game.record_hash());
+
+        let impossible = saved.replace("XX_/_O_/___ O", "XXX/_O_/___ O");
+        assert!(Game::<State>::read(impossible.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn records_the_history() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        game.choose((1, 1)).unwrap();
+        game.choose((0, 2)).unwrap();
+        game.choose((2, 0)).unwrap();
+        assert!(game.undo());
+
+        assert_eq!(game.plies(), 2);
+        assert_eq!(game.position(0).mark(1, 1), CheckBox::Empty);
+        assert_eq!(game.position(1).mark(1, 1), CheckBox::X);
+        assert_eq!(game.position(1).mark(0, 2), CheckBox::Empty);
+        assert_eq!(game.move_names(), [(CheckBox::X, "b2".to_owned()),
+                                       (CheckBox::O, "a3".to_owned())]);
+    }
+
+    #[test]
+    fn adopts_warm_trees_of_the_same_game() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        let warm = game.warm_up(CancelToken::new())();
+        game.choose((1, 1)).unwrap();
+        assert!(game.adopt_warm_tree(warm));
+        assert_eq!(game.state().get(1, 1), CheckBox::X);
+        assert_eq!(game.player(), CheckBox::O);
+        assert!(game.play_engine_move(&mut ::rand::thread_rng()).is_some());
+
+        let other_player = Game::<State>::new(CheckBox::O).warm_up(CancelToken::new())();
+        assert!(!game.adopt_warm_tree(other_player));
+        let other_game = Game::<::number_scrabble::NumberScrabbleState>::new(CheckBox::X)
+            .warm_up(CancelToken::new())();
+        assert!(!game.adopt_warm_tree(other_game));
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let cancelled = Game::<State>::new(CheckBox::X).warm_up(cancel)();
+        assert!(!game.adopt_warm_tree(cancelled));
+        let (state, player) = State::from_notation("X__/_O_/___ X").unwrap();
+        let other_position = Game::from_state(state, player).warm_up(CancelToken::new())();
+        assert!(!game.adopt_warm_tree(other_position));
+        assert_eq!(game.plies(), 2);
+    }
+
+    #[test]
+    fn thinks_on_another_thread() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        game.set_difficulty(Difficulty::Perfect);
+        game.choose((0, 0)).unwrap();
+        let job = game.think(Box::new(StdRng::seed_from_u64(0)), CancelToken::new());
+        let reply = ::std::thread::spawn(job).join().unwrap();
+        // Only a center reply doesn't lose against a corner opening.
+        assert_eq!(game.finish_thinking(reply), Some((1, 1)));
+        assert_eq!(game.moves, [(0, 0), (1, 1)]);
+
+        // Not after the game changed.
+        let reply = game.think(Box::new(StdRng::seed_from_u64(0)), CancelToken::new())();
+        game.choose((2, 2)).unwrap();
+        assert_eq!(game.finish_thinking(reply), None);
+        let reply = game.think(Box::new(StdRng::seed_from_u64(0)), CancelToken::new())();
+        game.undo();
+        assert_eq!(game.finish_thinking(reply), None);
+        let cancel = CancelToken::new();
+        let job = game.think(Box::new(StdRng::seed_from_u64(0)), cancel.clone());
+        cancel.cancel();
+        assert_eq!(game.finish_thinking(job()), None);
+        assert_eq!(game.moves, [(0, 0), (1, 1)]);
+
+        // Nor in another game.
+        let reply = game.think(Box::new(StdRng::seed_from_u64(0)), CancelToken::new())();
+        let mut other = Game::<::connect_four::ConnectFourState>::new(CheckBox::X);
+        assert_eq!(other.finish_thinking(reply), None);
+    }
+
+    #[test]
+    fn analyzes_on_another_thread() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        for &mv in &[(0, 0), (1, 1), (0, 1)] {
+            game.choose(mv).unwrap();
+        }
+        let job = Board::analyze(&game, CancelToken::new());
+        let analysis = ::std::thread::spawn(job).join().unwrap().unwrap();
+        assert_eq!(analysis.square_scores.len(), 6);
+        assert_eq!(analysis.solution, Some(Solution::Draw));
+        assert_eq!(analysis.principal_variation[0], (CheckBox::O, "a3".to_owned()));
+        assert_eq!(analysis.exact_moves_to_draw, Some(2));
+
+        let cancel = CancelToken::new();
+        let job = Board::analyze(&game, cancel.clone());
+        cancel.cancel();
+        assert_eq!(job(), None);
+
+        // Too big to prove anything.
+        let game = Game::<::connect_four::ConnectFourState>::new(CheckBox::X);
+        let analysis = Board::analyze(&game, CancelToken::new())().unwrap();
+        assert_eq!(analysis.solution, None);
+        assert_eq!(analysis.exact_moves_to_draw, None);
+    }
+
+    #[test]
+    fn plays_each_side_as_configured() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        let mut config = PlayerConfig::default();
+        config.handicap.no_center_opening = true;
+        game.set_player_config(CheckBox::O, config).unwrap();
+
+        // The center is the only reply to a corner that doesn't lose.
+        game.choose((0, 0)).unwrap();
+        let mut rng = ::rand::thread_rng();
+        assert_ne!(game.play_engine_move(&mut rng), Some((1, 1)));
+        while game.play_engine_move(&mut rng).is_some() {}
+        assert_eq!(game.result(), GameResult::XWins);
+
+        let positional = PlayerConfig { evaluator: "positional".to_owned(), ..PlayerConfig::default() };
+        assert!(game.set_player_config(CheckBox::X, positional.clone()).is_ok());
+        let mut connect_four = Game::<::connect_four::ConnectFourState>::new(CheckBox::X);
+        assert!(connect_four.set_player_config(CheckBox::X, positional.clone()).is_err());
+
+        // Saved games keep how each side played.
+        let mut saved = vec![];
+        game.write(&mut saved).unwrap();
+        let loaded = Game::<State>::read(&saved[..]).unwrap();
+        assert_eq!(*loaded.player_config(CheckBox::X), positional);
+        assert!(loaded.player_config(CheckBox::O).handicap.no_center_opening);
+        assert_eq!(loaded.record_hash(), game.record_hash());
+    }
+
+    #[test]
+    fn summarizes_how_each_side_played() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        // Playing an edge loses against a corner opening.
+        for &mv in &[(0, 0), (1, 0), (1, 1), (2, 2)] {
+            game.choose(mv).unwrap();
+        }
+        assert_eq!(game.summary(CheckBox::X), GameSummary { won: false, mistakes: 0, moves: 2 });
+        assert_eq!(game.summary(CheckBox::O), GameSummary { won: false, mistakes: 1, moves: 2 });
+
+        let mut rng = ::rand::thread_rng();
+        while game.play_engine_move(&mut rng).is_some() {}
+        assert_eq!(game.result(), GameResult::XWins);
+        let x = game.summary(CheckBox::X);
+        assert!(x.won);
+        assert_eq!(x.mistakes, 0);
+        assert!(!game.summary(CheckBox::O).won);
+    }
+
+    #[test]
+    fn adapts_to_the_opponent() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        let config = PlayerConfig { difficulty: Difficulty::Adaptive, ..PlayerConfig::default() };
+        game.set_player_config(CheckBox::O, config).unwrap();
+        let mut model = OpponentModel::default();
+        for _ in 0..10 {
+            model.record(GameSummary { won: true, mistakes: 0, moves: 5 });
+        }
+        game.set_opponent_model(&model);
+
+        // At full strength, the center is the only reply to a corner that
+        // doesn't lose.
+        game.choose((0, 0)).unwrap();
+        let mut rng = ::rand::thread_rng();
+        assert_eq!(game.play_engine_move(&mut rng), Some((1, 1)));
+    }
+
+    #[test]
+    fn explains_illegal_clicks() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        game.click(1, 1).unwrap();
+        assert_eq!(game.click(1, 1), Err(MoveError::Occupied));
+        assert_eq!(game.click(3, 0), Err(MoveError::OutOfBounds));
+        assert_eq!(game.choose((1, 1)), Err(MoveError::Occupied));
+        assert_eq!(game.choose((0, 3)), Err(MoveError::OutOfBounds));
+
+        for &(x, y) in &[(0, 0), (1, 0), (0, 1), (1, 2)] {
+            game.click(x, y).unwrap();
+        }
+        assert_eq!(game.result(), GameResult::XWins);
+        assert_eq!(game.click(2, 2), Err(MoveError::GameOver));
+        assert_eq!(game.choose((2, 2)), Err(MoveError::GameOver));
+    }
+
+    #[test]
+    fn finds_immediate_threats() {
+        let mut game = Game::<State>::new(CheckBox::X);
+        for &(x, y) in &[(0, 0), (1, 1), (1, 0)] {
+            game.click(x, y).unwrap();
+        }
+        // X wins on c1, and O has to block it, whoever is to move.
+        assert_eq!(game.immediate_threats(CheckBox::X), [(2, 0)]);
+        assert!(game.immediate_threats(CheckBox::O).is_empty());
+        game.click(0, 2).unwrap();
+        assert_eq!(game.immediate_threats(CheckBox::O), [(2, 0)]);
+    }
+}
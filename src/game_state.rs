@@ -0,0 +1,265 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! What the minimax engine needs to know about a game.
+
+use state::CheckBox;
+use std::fmt;
+use variant::Variant;
+
+/// The outcome of a game at a given state.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum GameResult {
+    XWins,
+    OWins,
+    Draw,
+    InProgress,
+    /// The given player ran out of time, which only games with a clock
+    /// end with, not states.
+    TimeForfeit(CheckBox),
+    /// The given player resigned, which like the rest ends games, not
+    /// states.
+    Resigned(CheckBox),
+    /// The players agreed to a draw.
+    DrawAgreed,
+}
+
+impl GameResult {
+    /// A name for the result, like the records and saved games have.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            GameResult::XWins => "x-wins",
+            GameResult::OWins => "o-wins",
+            GameResult::Draw => "draw",
+            GameResult::TimeForfeit(CheckBox::X) => "x-out-of-time",
+            GameResult::TimeForfeit(_) => "o-out-of-time",
+            GameResult::Resigned(CheckBox::X) => "x-resigned",
+            GameResult::Resigned(_) => "o-resigned",
+            GameResult::DrawAgreed => "draw-agreed",
+            GameResult::InProgress => "*",
+        }
+    }
+
+    /// The inverse of `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "x-wins" => GameResult::XWins,
+            "o-wins" => GameResult::OWins,
+            "draw" => GameResult::Draw,
+            "x-out-of-time" => GameResult::TimeForfeit(CheckBox::X),
+            "o-out-of-time" => GameResult::TimeForfeit(CheckBox::O),
+            "x-resigned" => GameResult::Resigned(CheckBox::X),
+            "o-resigned" => GameResult::Resigned(CheckBox::O),
+            "draw-agreed" => GameResult::DrawAgreed,
+            "*" => GameResult::InProgress,
+            _ => return None,
+        })
+    }
+}
+
+/// Why a move couldn't be played.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MoveError {
+    /// The game is over already.
+    GameOver,
+    /// The square is marked already.
+    Occupied,
+    /// The move is off the board.
+    OutOfBounds,
+    /// The move breaks some other rule of the game.
+    Illegal,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            MoveError::GameOver => "the game is over",
+            MoveError::Occupied => "that square is taken",
+            MoveError::OutOfBounds => "that's off the board",
+            MoveError::Illegal => "that's not a legal move",
+        })
+    }
+}
+
+/// The state of a two-player game where X and O take turns, which the engine
+/// can search.
+///
+/// O is the maximizing player, so positive scores are good for O, and
+/// negative scores are good for X.
+///
+/// States and moves are `Send`, so that games can be searched away from the
+/// main thread, and states can be compared, to tell whether a search was of
+/// the same position.
+pub trait GameState: Clone + PartialEq + fmt::Debug + Send + 'static {
+    /// A move in this game.
+    type Move: Copy + Eq + fmt::Debug + Send;
+
+    /// The state at the start of the game.
+    fn initial() -> Self;
+
+    /// Returns the player that won the game, or `CheckBox::Empty` if nobody
+    /// did (yet).
+    fn winner(&self) -> CheckBox;
+
+    /// Returns all the moves `player` can make, along with the states they
+    /// lead to.
+    ///
+    /// This must be empty if the game is over.
+    fn successors(&self, player: CheckBox) -> Vec<(Self::Move, Self)>;
+
+    /// Returns a heuristic score of this state.
+    ///
+    /// Wins are 10 for O and -10 for X, and all the other states must score
+    /// strictly in between.
+    fn evaluate(&self) -> i8 {
+        self.winner() as i8
+    }
+
+    /// Returns how early `mv` should be searched, lower is earlier. Searching
+    /// the best moves first makes alpha-beta pruning more effective.
+    fn move_priority(&self, _mv: Self::Move) -> u8 {
+        0
+    }
+
+    /// Returns a known best move for `player` in this state, if any, so the
+    /// engine doesn't need to search it.
+    fn book_move(&self, _player: CheckBox) -> Option<Self::Move> {
+        None
+    }
+
+    /// Returns why `mv`, which isn't among the successors of this state,
+    /// can't be played, if the game isn't over.
+    fn move_error(&self, _mv: Self::Move) -> MoveError {
+        MoveError::Illegal
+    }
+
+    /// Returns whether `mv` takes the center of the board, which handicaps
+    /// can keep the engine away from.
+    fn is_center_move(&self, _mv: Self::Move) -> bool {
+        false
+    }
+
+    /// The maximum number of levels the engine should search in this game.
+    ///
+    /// Deeper searches than this would take too long to be playable.
+    fn depth_limit() -> usize;
+
+    /// Whether the whole game is small enough to search every line of play
+    /// to the end while playing.
+    fn is_solvable() -> bool {
+        false
+    }
+
+    /// Dumps this state, with each line indented by `indent` spaces.
+    fn dump<W>(&self, indent: usize, dest: &mut W) -> fmt::Result
+        where W: fmt::Write;
+
+    /// Returns the outcome of the game at this state.
+    fn result(&self) -> GameResult {
+        match self.winner() {
+            CheckBox::X => GameResult::XWins,
+            CheckBox::O => GameResult::OWins,
+            CheckBox::Empty if self.successors(CheckBox::X).is_empty() => {
+                GameResult::Draw
+            }
+            CheckBox::Empty => GameResult::InProgress,
+        }
+    }
+
+    /// Returns whether the game is over at this state.
+    fn is_over(&self) -> bool {
+        self.result() != GameResult::InProgress
+    }
+}
+
+/// A game state that can be shown and played on a grid of squares, like the
+/// GUI does.
+pub trait GridState: GameState {
+    /// Returns the number of columns and rows of the grid.
+    fn grid_size(&self) -> (usize, usize);
+
+    /// Returns the size of the sub-grids the grid is visually split in, if
+    /// any.
+    fn block_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns the mark in column `x` and row `y`.
+    fn mark(&self, x: usize, y: usize) -> CheckBox;
+
+    /// Returns the text to show in the square in column `x` and row `y`
+    /// besides its mark, if any.
+    fn square_label(&self, _x: usize, _y: usize) -> Option<String> {
+        None
+    }
+
+    /// Returns the move to play when the square in column `x` and row `y` is
+    /// clicked, if any.
+    fn move_for_square(&self, x: usize, y: usize) -> Option<Self::Move>;
+
+    /// Returns the other move to play from the square in column `x` and row
+    /// `y`, in games where a square can play more than one, if any.
+    fn other_move_for_square(&self, _x: usize, _y: usize) -> Option<Self::Move> {
+        None
+    }
+
+    /// Returns the squares of the line that won the game, if any.
+    fn winning_squares(&self) -> Vec<(usize, usize)>;
+
+    /// Returns a rendering of this position in an equivalent game, to show
+    /// alongside the grid, if any.
+    fn mirror(&self) -> Option<String> {
+        None
+    }
+}
+
+/// A game state that can be saved, see `Game::save`.
+pub trait Savable: GameState {
+    /// Returns the variant whose states these are.
+    fn variant() -> Variant;
+
+    /// Writes `mv` as the `moves` field of the save format has it, without
+    /// spaces.
+    fn write_move(mv: Self::Move) -> String;
+
+    /// The inverse of `write_move`. Whether the move is on the board is up
+    /// to the game.
+    fn read_move(s: &str) -> Option<Self::Move>;
+
+    /// Returns the notation of this position with `player` to move, to save
+    /// games that start from it, or `None` if it's the initial position or
+    /// the game has no notation.
+    fn set_up_notation(&self, _player: CheckBox) -> Option<String> {
+        None
+    }
+
+    /// The inverse of `set_up_notation`, which rejects positions that can't
+    /// come up in a game.
+    fn from_set_up_notation(_notation: &str) -> Option<(Self, CheckBox)> {
+        None
+    }
+}
+
+/// Parses a pair of numbers written as `x,y`, like the save format writes
+/// squares.
+pub fn parse_pair(s: &str) -> Option<(usize, usize)> {
+    let mut numbers = s.splitn(2, ',').map(|n| n.trim().parse::<usize>().ok());
+    match (numbers.next(), numbers.next()) {
+        (Some(Some(x)), Some(Some(y))) => Some((x, y)),
+        _ => None,
+    }
+}
@@ -0,0 +1,161 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The trees the engine searches, as Graphviz graphs, to see why it plays
+//! what it plays.
+
+use minimax::SearchConfig;
+use state::CheckBox;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use variant::Variant;
+
+pub const USAGE: &str = "\
+usage: tic-tac-minimax tree [options]
+
+  --variant <name>     tictactoe (default), ultimate, connect4, scrabble,
+                       misere, wild, notakto, cube or qubic
+  --moves <squares>    play the squares first, as column,row separated by
+                       spaces, like \"1,1 0,2\"
+  --depth <n>          search <n> levels (default: 4)
+  --levels <n>         write the first <n> levels of the tree (default: 3)
+  --output <path>      write to <path> (default: the standard output), as
+                       SVG if it ends in .svg, which needs Graphviz' `dot`
+
+Searched nodes show the board and their score from O's point of view, and
+the ones the search pruned before looking at them are dashed.";
+
+#[derive(Clone, Debug)]
+struct Options {
+    variant: Variant,
+    moves: Vec<(usize, usize)>,
+    depth: usize,
+    levels: usize,
+    output: Option<String>,
+}
+
+fn parse_square(square: &str) -> Option<(usize, usize)> {
+    let mut coords = square.splitn(2, ',').map(|c| c.trim().parse::<usize>().ok());
+    match (coords.next(), coords.next()) {
+        (Some(Some(x)), Some(Some(y))) => Some((x, y)),
+        _ => None,
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        variant: Variant::TicTacToe,
+        moves: vec![],
+        depth: 4,
+        levels: 3,
+        output: None,
+    };
+
+    let mut depth = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+        let invalid = || format!("invalid value for {}: {}", arg, value);
+        match arg.as_str() {
+            "--variant" => {
+                options.variant = Variant::from_id(value)
+                    .ok_or_else(|| format!("unknown variant: {}", value))?;
+            }
+            "--moves" => {
+                options.moves = value.split_whitespace()
+                    .map(|square| parse_square(square).ok_or_else(|| format!("not a square: {}", square)))
+                    .collect::<Result<_, _>>()?;
+            }
+            "--depth" => depth = Some(value.clone()),
+            "--levels" => options.levels = value.parse::<usize>().map_err(|_| invalid())?,
+            "--output" => options.output = Some(value.clone()),
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+
+    // The limit depends on the variant, which may come after the depth.
+    if let Some(depth) = depth {
+        options.depth = SearchConfig::parse(&depth, options.variant.depth_limit())
+            .map_err(|err| err.to_string())?
+            .depth();
+    }
+    Ok(options)
+}
+
+/// Lays out `dot` with Graphviz, returning the SVG it draws.
+pub fn to_svg(dot: &str) -> io::Result<Vec<u8>> {
+    let mut child = Command::new("dot")
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().expect("Should have piped the input").write_all(dot.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("dot failed: {}", output.status)));
+    }
+    Ok(output.stdout)
+}
+
+/// Returns the graph of the search of the position `options` ask for.
+fn search_tree(options: &Options) -> Result<String, String> {
+    let mut game = options.variant.new_game(CheckBox::X);
+    let board = game.board_mut();
+    for &(x, y) in &options.moves {
+        board.click(x, y).map_err(|err| format!("can't play {},{}: {}", x, y, err))?;
+    }
+    Ok(board.search_tree_dot(options.depth, options.levels))
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    if args.iter().any(|arg| arg == "--help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let options = parse_options(args)?;
+    let dot = search_tree(&options)?;
+    let result = match options.output {
+        Some(ref path) if path.ends_with(".svg") => {
+            let svg = to_svg(&dot).map_err(|err| format!("couldn't run Graphviz: {}", err))?;
+            File::create(path).and_then(|mut file| file.write_all(&svg))
+        }
+        Some(ref path) => File::create(path).and_then(|mut file| file.write_all(dot.as_bytes())),
+        None => io::stdout().write_all(dot.as_bytes()),
+    };
+    result.map_err(|err| format!("couldn't write the tree: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_the_tree_of_a_position() {
+        let args = ["--moves", "1,1 0,0", "--depth", "20", "--levels", "1"];
+        let options = parse_options(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>()).unwrap();
+        assert_eq!(options.depth, 9);
+        let dot = search_tree(&options).unwrap();
+        // The position and the seven replies to it.
+        assert_eq!(dot.lines().filter(|l| l.contains(" -> ")).count(), 7);
+
+        let args = ["--moves", "1,1 1,1"];
+        let options = parse_options(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>()).unwrap();
+        assert!(search_tree(&options).is_err());
+    }
+}
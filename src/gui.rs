@@ -0,0 +1,3108 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The GTK frontend.
+
+use tic_tac_minimax::{
+    bench,
+    clock,
+    config,
+    effects,
+    recorder,
+    report,
+    terminal,
+};
+
+use tic_tac_minimax::adaptive::OpponentModel;
+use tic_tac_minimax::app_config::AppConfig;
+use tic_tac_minimax::clock::{Clock, TimeControl, TimeoutPolicy};
+use tic_tac_minimax::config::Config;
+use tic_tac_minimax::driver::{GameDriver, GameEvent};
+use tic_tac_minimax::effects::{Cue, Effects, Frame};
+use tic_tac_minimax::frontend::Click;
+use tic_tac_minimax::game::{Analysis, Board, Game, LoadError};
+use tic_tac_minimax::game_state::{GameResult, MoveError};
+use tic_tac_minimax::i18n::{tr, tr_format};
+use tic_tac_minimax::log::{self, Filter, Level};
+use tic_tac_minimax::minimax::{CancelToken, Difficulty, SearchConfig, Solution};
+use tic_tac_minimax::network::{Event, Message, Network};
+use tic_tac_minimax::observer::{Sink, Snapshot};
+use tic_tac_minimax::spectator::Spectator;
+use tic_tac_minimax::player::PlayerConfig;
+use tic_tac_minimax::puzzle::{Goal, Progress, Puzzle, Verdict};
+use tic_tac_minimax::recorder::Record;
+use tic_tac_minimax::scoreboard::{Scoreboard, Tally};
+use tic_tac_minimax::settings::Settings;
+use tic_tac_minimax::state::{CheckBox, State};
+use tic_tac_minimax::theme::{ColorScheme, Theme};
+use tic_tac_minimax::variant::{AnyGame, Variant};
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use gio::{ApplicationExt, ApplicationExtManual, ApplicationFlags};
+use gtk::{AspectFrameExt, BoxExt, Cast, ClipboardExt, ComboBoxExt, ComboBoxTextExt, CssProviderExt, DialogExt, EditableSignals, EntryExt, ExpanderExt, FileChooserExt, FrameExt, GridExt, GtkWindowExt, LabelExt, OrientableExt, ProgressBarExt, RevealerExt, SpinButtonExt, StyleContextExt, TextBufferExt, TextViewExt, ToggleButtonExt, WidgetExt, ContainerExt, ButtonExt};
+
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::env;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::PathBuf;
+use std::process;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often to look for what the other player did in network games.
+const NETWORK_POLL_MS: u32 = 100;
+
+/// How often to look for new observers when nothing happens.
+const OBSERVER_POLL_MS: u32 = 250;
+
+/// For how long the engine thinks about each move in `Difficulty::Auto`.
+const AUTO_MOVE_TIME: Duration = Duration::from_millis(200);
+
+/// How often to look for whether the engine warmed up.
+const WARM_UP_POLL_MS: u32 = 50;
+
+/// How often the clocks are updated.
+const CLOCK_TICK_MS: u32 = 100;
+
+/// For how long the clocks can go without updating before it's taken as
+/// the computer having been asleep, like with the lid closed.
+const ASLEEP_AFTER: Duration = Duration::from_secs(2);
+
+/// How often the engine log shows what was logged since, and how many lines
+/// of it it keeps.
+const LOG_POLL_MS: u32 = 250;
+const LOG_LINES: i32 = 500;
+
+/// For how long a hint is shown.
+const HINT_DURATION_MS: u32 = 1000;
+
+/// How often the evaluation bar moves towards a new score, and how much of
+/// the bar it moves each time.
+const EVAL_BAR_FRAME_MS: u32 = 16;
+const EVAL_BAR_STEP: f64 = 0.04;
+
+/// For how long the reason a click couldn't be played is shown, and the
+/// clicked square is red.
+const ILLEGAL_MESSAGE_MS: u32 = 1500;
+const ILLEGAL_FLASH_MS: u32 = 400;
+
+/// For how long a new mark stays small before growing, enough to draw a
+/// frame of it.
+const PLACED_DURATION_MS: u32 = 50;
+
+/// How often the winning line flashes, and how many times.
+const FLASH_INTERVAL_MS: u32 = 250;
+const FLASHES: u32 = 3;
+
+/// For how long the main loop can be busy before the UI feels unresponsive,
+/// about a frame at 60Hz.
+const MAIN_LOOP_BUDGET: Duration = Duration::from_millis(16);
+
+thread_local! {
+    /// What to do with the results of the jobs running on other threads once
+    /// they finish, see `run_in_background`. Each returns whether its job is
+    /// still running.
+    static BACKGROUND_JOBS: RefCell<Vec<Box<dyn FnMut() -> bool>>> = RefCell::new(vec![]);
+}
+
+/// Runs `job` on another thread, and then `done` with its result on the main
+/// loop, unless `cancel` was cancelled by then.
+fn run_in_background<T, F>(cancel: CancelToken, job: Box<dyn FnOnce() -> T + Send>, done: F)
+    where T: Send + 'static,
+          F: FnOnce(T) + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // The app may be gone by now.
+        let _ = sender.send(job());
+        glib::idle_add(|| {
+            finish_background_jobs();
+            glib::Continue(false)
+        });
+    });
+    let mut done = Some(done);
+    BACKGROUND_JOBS.with(|jobs| jobs.borrow_mut().push(Box::new(move || {
+        match receiver.try_recv() {
+            Ok(result) => {
+                if !cancel.is_cancelled() {
+                    let done = done.take().expect("Jobs should finish once");
+                    within_budget("background job", || done(result));
+                }
+                false
+            }
+            Err(mpsc::TryRecvError::Empty) => true,
+            Err(mpsc::TryRecvError::Disconnected) => false,
+        }
+    })));
+}
+
+/// Hands the results of the jobs that finished to whoever waits for them.
+fn finish_background_jobs() {
+    // Taken out, since handling a result may start another job.
+    let jobs = BACKGROUND_JOBS.with(|jobs| mem::take(&mut *jobs.borrow_mut()));
+    let running = jobs.into_iter().filter_map(|mut job| if job() { Some(job) } else { None })
+        .collect::<Vec<_>>();
+    BACKGROUND_JOBS.with(|jobs| jobs.borrow_mut().extend(running));
+}
+
+/// Highlights the buttons of `grid` for `squares` for a moment, like a hint.
+fn highlight_squares(grid: &gtk::Grid, squares: &[(usize, usize)]) {
+    let buttons = squares.iter().map(|&(x, y)| {
+        grid.get_child_at(x as i32, y as i32)
+            .expect("Grid should match the board size")
+    }).collect::<Vec<_>>();
+
+    for button in &buttons {
+        button.get_style_context()
+            .expect("Buttons should have a style context")
+            .add_class("hint");
+    }
+
+    gtk::timeout_add(HINT_DURATION_MS, move || {
+        for button in &buttons {
+            button.get_style_context()
+                .expect("Buttons should have a style context")
+                .remove_class("hint");
+        }
+        gtk::Continue(false)
+    });
+}
+
+/// Appends the command in `line` and its `result` to the scrollback of
+/// `console_output`.
+fn append_command(console_output: &gtk::TextView, line: &str, result: Result<String, String>) {
+    let output = match result {
+        Ok(output) => output,
+        Err(err) => format!("error: {}", err),
+    };
+
+    let buffer = console_output.get_buffer()
+        .expect("Text views should have a buffer");
+    let mut text = format!("> {}\n", line);
+    if !output.is_empty() {
+        text.push_str(&output);
+        if !output.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+    buffer.insert(&mut buffer.get_end_iter(), &text);
+    if let Some(mark) = buffer.get_insert() {
+        buffer.place_cursor(&buffer.get_end_iter());
+        console_output.scroll_to_mark(&mark, 0.0, false, 0.0, 0.0);
+    }
+}
+
+/// Describes `analysis` in the analysis pane, for a grid with `marks`, row by
+/// row.
+fn analysis_text(analysis: &Analysis, marks: &[Vec<CheckBox>], maximizing: bool, theme: &Theme) -> String {
+    let mut text = String::new();
+    for (y, row) in marks.iter().enumerate() {
+        for (x, &mark) in row.iter().enumerate() {
+            let cell = match analysis.square_scores.iter().find(|s| s.0 == (x, y)) {
+                Some(&(_, score)) => {
+                    // Scores are from O's point of view.
+                    let score = if maximizing { score } else { -score };
+                    match score {
+                        10 => "W".to_owned(),
+                        -10 => "L".to_owned(),
+                        score => score.to_string(),
+                    }
+                }
+                None => match mark {
+                    CheckBox::Empty => "_".to_owned(),
+                    mark => theme.glyph(mark).to_owned(),
+                },
+            };
+            text.push_str(&format!("{:>3}", cell));
+        }
+        text.push('\n');
+    }
+    text.push_str(tr("\nW wins and L loses with best play, 0 draws.\n\
+                   Other numbers are how good the move looks."));
+    match analysis.solution {
+        Some(Solution::Win(1)) => {
+            text.push_str(tr("\n\nSolved: you can win, in 1 move counting both sides."))
+        }
+        Some(Solution::Win(plies)) => text.push_str(&tr_format(
+            "\n\nSolved: you can win, in {} moves counting both sides.", &[&plies])),
+        Some(Solution::Loss(1)) => {
+            text.push_str(tr("\n\nSolved: you lose against best play, in 1 move counting both sides."))
+        }
+        Some(Solution::Loss(plies)) => text.push_str(&tr_format(
+            "\n\nSolved: you lose against best play, in {} moves counting both sides.", &[&plies])),
+        Some(Solution::Draw) => text.push_str(tr("\n\nSolved: it's a draw.")),
+        None => {}
+    }
+    let pv = &analysis.principal_variation;
+    if !pv.is_empty() {
+        let moves = pv.iter()
+            .map(|&(mark, ref square)| format!("{} {}", theme.glyph(mark), square))
+            .collect::<Vec<_>>();
+        text.push_str(&tr_format("\n\nExpected line: {}.", &[&escape_markup(&moves.join(", "))]));
+    }
+    if let Some(exact_moves) = analysis.exact_moves_to_draw {
+        text.push_str(&match exact_moves {
+            1 => tr("\n\nThe best you can get is a draw, 1 exact move required.").to_owned(),
+            _ => tr_format("\n\nThe best you can get is a draw, {} exact moves required.", &[&exact_moves]),
+        });
+    }
+    text
+}
+
+/// Runs `f`, and logs it if it took longer than the main loop budget.
+///
+/// Searches and file access run on other threads, see `run_in_background`,
+/// so this is to catch what still doesn't.
+fn within_budget<F, R>(what: &str, f: F) -> R
+    where F: FnOnce() -> R,
+{
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    if elapsed > MAIN_LOOP_BUDGET {
+        log::log(Level::Warn, "gui", "main loop stalled", &[
+            ("in", &what),
+            ("for", &format_args!("{:?}", elapsed)),
+        ]);
+    }
+    result
+}
+
+/// Sets the name screen readers use for `widget`.
+///
+/// TODO(emilio): Use the safe bindings once gtk-rs has ATK ones.
+fn set_accessible_name(widget: &gtk::Widget, name: &str) {
+    use glib::translate::ToGlibPtr;
+
+    unsafe {
+        let accessible = gtk_sys::gtk_widget_get_accessible(widget.to_glib_none().0);
+        if !accessible.is_null() {
+            atk_sys::atk_object_set_name(accessible, name.to_glib_none().0);
+        }
+    }
+}
+
+/// Returns the square the key for `digit` plays: the one labeled with it,
+/// if any, or else the one at that position reading a 3x3 grid from the top
+/// left.
+fn square_for_digit(board: &dyn Board, digit: usize) -> Option<(usize, usize)> {
+    let (cols, rows) = board.grid_size();
+    let position = board.position(board.plies());
+    let label = digit.to_string();
+    for x in 0..cols {
+        for y in 0..rows {
+            if position.square_label(x, y).as_ref() == Some(&label) {
+                return Some((x, y));
+            }
+        }
+    }
+    if (cols, rows) == (3, 3) {
+        return Some(((digit - 1) % 3, (digit - 1) / 3));
+    }
+    None
+}
+
+/// Describes what `puzzle` asks for, like "X to move and win in 2".
+fn puzzle_goal(puzzle: &Puzzle, theme: &Theme) -> String {
+    let glyph = theme.glyph(puzzle.player);
+    match puzzle.goal {
+        Goal::Win(1) => tr_format("{} to move and win", &[&glyph]),
+        Goal::Win(moves) => tr_format("{} to move and win in {}", &[&glyph, &moves]),
+        Goal::Draw => tr_format("{} to move: find the only move that doesn't lose", &[&glyph]),
+    }
+}
+
+/// Escapes `text` to use it in Pango markup.
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// How the inputs of the settings dialog look when what they have won't be
+/// used as is.
+const WARNING_CSS: &str = "spinbutton.warning { color: #cc0000; }";
+
+/// Marks `input` with the `warning` class, and says why in its tooltip, if
+/// the depth it has isn't one the engine searches in a game that goes up to
+/// `depth_limit` levels deep. Zero means as deep as the difficulty goes.
+fn validate_depth(input: &gtk::SpinButton, depth_limit: usize) {
+    let text = input.get_text().unwrap_or_default();
+    let warning = match SearchConfig::parse(&text, depth_limit) {
+        _ if text.trim() == "0" => None,
+        Ok(ref config) if config.is_clamped() => {
+            Some(tr_format("The game is only {} levels deep", &[&depth_limit]))
+        }
+        Ok(_) => None,
+        Err(err) => Some(err.to_string()),
+    };
+    let style = match input.get_style_context() {
+        Some(style) => style,
+        None => return,
+    };
+    match warning {
+        Some(warning) => {
+            style.add_class("warning");
+            input.set_tooltip_text(Some(&*warning));
+        }
+        None => {
+            style.remove_class("warning");
+            input.set_tooltip_text(None);
+        }
+    }
+}
+
+/// Returns an input for the depth of a player config, validated as it
+/// changes.
+fn depth_input(depth: Option<usize>, depth_limit: usize, css: &gtk::CssProvider) -> gtk::SpinButton {
+    let max_depth = Variant::all().iter().map(|v| v.depth_limit()).max().unwrap();
+    let input = gtk::SpinButton::new_with_range(0.0, max_depth as f64, 1.0);
+    input.set_numeric(true);
+    input.set_value(depth.unwrap_or(0) as f64);
+    if let Some(style) = input.get_style_context() {
+        style.add_provider(css, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+    }
+    input.connect_changed(move |input| validate_depth(input, depth_limit));
+    validate_depth(&input, depth_limit);
+    input
+}
+
+/// How the settings describe `policy`.
+fn timeout_policy_label(policy: TimeoutPolicy) -> &'static str {
+    match policy {
+        TimeoutPolicy::Forfeit => tr("They lose the game"),
+        TimeoutPolicy::GreedyMove => tr("The engine moves for them, quickly"),
+    }
+}
+
+/// The extra space between the sub-grids of the grid, if any.
+const BLOCK_SPACING: i32 = 6 /* px */;
+
+/// The size of the marks relative to their squares.
+const MARK_SCALE: f64 = 0.4;
+
+/// Animates the squares of the grid, with the classes of `Theme::css`.
+struct Animations {
+    grid: Rc<RefCell<gtk::Grid>>,
+}
+
+impl Animations {
+    fn style(&self, x: usize, y: usize) -> Option<gtk::StyleContext> {
+        self.grid.borrow().get_child_at(x as i32, y as i32)?.get_style_context()
+    }
+}
+
+impl Effects for Animations {
+    fn play(&self, cue: &Cue) {
+        match *cue {
+            Cue::Placed(x, y) => {
+                let style = match self.style(x, y) {
+                    Some(style) => style,
+                    None => return,
+                };
+                style.add_class("placed");
+                gtk::timeout_add(PLACED_DURATION_MS, move || {
+                    style.remove_class("placed");
+                    gtk::Continue(false)
+                });
+            }
+            Cue::Won(ref squares) => {
+                let styles = squares.iter().filter_map(|&(x, y)| self.style(x, y)).collect::<Vec<_>>();
+                let mut toggles = 0;
+                gtk::timeout_add(FLASH_INTERVAL_MS, move || {
+                    toggles += 1;
+                    for style in &styles {
+                        if toggles % 2 == 1 {
+                            style.add_class("flash");
+                        } else {
+                            style.remove_class("flash");
+                        }
+                    }
+                    gtk::Continue(toggles < FLASHES * 2)
+                });
+            }
+            Cue::Drawn => {}
+        }
+    }
+}
+
+/// Plays the sounds of the desktop sound theme, if `canberra-gtk-play` is
+/// around to play them.
+struct Sounds;
+
+impl Effects for Sounds {
+    fn play(&self, cue: &Cue) {
+        let id = match *cue {
+            Cue::Placed(..) => "button-pressed",
+            Cue::Won(..) => "complete",
+            Cue::Drawn => "dialog-information",
+        };
+        // Missing sounds are no big deal.
+        if let Ok(mut child) = process::Command::new("canberra-gtk-play").args(["--id", id]).spawn() {
+            thread::spawn(move || child.wait());
+        }
+    }
+}
+
+/// The evaluation bar beside the board: how much of it is O's is how good
+/// the position is for O, and the label under it says by how much.
+#[derive(Clone)]
+struct EvalBar {
+    container: gtk::Box,
+    bar: gtk::ProgressBar,
+    label: gtk::Label,
+    /// The fraction of the bar that's O's once it stops moving.
+    target: Rc<Cell<f64>>,
+    /// Whether the bar is moving towards `target` already.
+    moving: Rc<Cell<bool>>,
+}
+
+impl EvalBar {
+    fn new() -> Self {
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 5 /* px */);
+        let bar = gtk::ProgressBar::new();
+        bar.set_orientation(gtk::Orientation::Vertical);
+        // O's part grows from the bottom.
+        bar.set_inverted(true);
+        bar.set_fraction(0.5);
+        let label = gtk::Label::new(None);
+        container.pack_start(&bar, /* expand = */ true, /* fill = */ true, 0);
+        container.pack_start(&label, /* expand = */ false, /* fill = */ true, 0);
+        bar.show();
+        label.show();
+        // Only shown when the settings say so.
+        container.set_no_show_all(true);
+        EvalBar {
+            container,
+            bar,
+            label,
+            target: Rc::new(Cell::new(0.5)),
+            moving: Rc::new(Cell::new(false)),
+        }
+    }
+
+    /// Moves the bar to `score`, from O's point of view, a bit every frame
+    /// rather than all at once.
+    fn show_score(&self, score: i8, theme: &Theme) {
+        let win = CheckBox::O as i8;
+        self.label.set_text(&match score {
+            0 => tr("Even").to_owned(),
+            score if score == win => tr_format("{} wins with best play", &[&theme.glyph(CheckBox::O)]),
+            score if score == -win => tr_format("{} wins with best play", &[&theme.glyph(CheckBox::X)]),
+            score if score > 0 => format!("{} +{}", theme.glyph(CheckBox::O), score),
+            score => format!("{} +{}", theme.glyph(CheckBox::X), -score),
+        });
+
+        self.target.set((score + win) as f64 / (2 * win) as f64);
+        if self.moving.get() {
+            return;
+        }
+        self.moving.set(true);
+        let (bar, target, moving) = (self.bar.clone(), self.target.clone(), self.moving.clone());
+        gtk::timeout_add(EVAL_BAR_FRAME_MS, move || {
+            let fraction = bar.get_fraction();
+            let target = target.get();
+            let step = (target - fraction).clamp(-EVAL_BAR_STEP, EVAL_BAR_STEP);
+            bar.set_fraction(fraction + step);
+            let done = step.abs() < EVAL_BAR_STEP;
+            moving.set(!done);
+            gtk::Continue(!done)
+        });
+    }
+}
+
+/// What the windows of the GUI share: the configuration file, and what
+/// doesn't depend on the game being played.
+struct Session {
+    application: gtk::Application,
+    config: RefCell<Config>,
+    /// Where to save the configuration, if anywhere.
+    config_path: Option<PathBuf>,
+    /// The results of all the windows. Shared with the game over hooks.
+    scoreboard: Rc<RefCell<Scoreboard>>,
+    progress: RefCell<Progress>,
+    /// How the human plays, for `Difficulty::Adaptive`.
+    opponent: RefCell<OpponentModel>,
+    /// The number of nodes the engine can search in `AUTO_MOVE_TIME` on
+    /// this machine.
+    node_budget: usize,
+    /// The engine log events that aren't shown yet, from whichever thread
+    /// logged them.
+    log_events: mpsc::Receiver<String>,
+    /// The windows that are open.
+    windows: RefCell<Vec<Rc<App>>>,
+}
+
+impl Session {
+    /// Saves `settings` with what all the windows share, like the results,
+    /// if the configuration is saved anywhere.
+    fn save_config(&self, settings: &Settings) -> io::Result<()> {
+        let path = match self.config_path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let mut config = self.config.borrow_mut();
+        settings.write_to(&mut config);
+        self.scoreboard.borrow().write_to(&mut config);
+        self.progress.borrow().write_to(&mut config);
+        self.opponent.borrow().write_to(&mut config);
+        config.save(path)
+    }
+
+    /// Shows what the engine logged since the last call in the engine log of
+    /// every window.
+    fn tail_log(&self) {
+        let text = self.log_events.try_iter().map(|line| line + "\n").collect::<String>();
+        if text.is_empty() {
+            return;
+        }
+        for window in self.windows.borrow().iter() {
+            window.append_log(&text);
+        }
+    }
+}
+
+/// A window, with its game.
+struct App {
+    session: Rc<Session>,
+    game: RefCell<AnyGame>,
+    settings: RefCell<Settings>,
+    /// The results of the games of this window. Shared with the game over
+    /// hook of the current game.
+    results: Rc<RefCell<Tally>>,
+    /// The last puzzle the user picked, as of the position they're solving
+    /// it from.
+    puzzle: RefCell<Option<Puzzle>>,
+    /// The number of moves of the position being replayed, or `None` if
+    /// showing the current one.
+    replay: Cell<Option<usize>>,
+    /// The random numbers of the engine, and what they were seeded with at
+    /// the start of the current game, to reproduce it.
+    rng: RefCell<StdRng>,
+    seed: Cell<u64>,
+    /// Whether the engine is still searching the start of the game in the
+    /// background.
+    warming_up: Cell<bool>,
+    /// Cancels the warm up that's running, if any.
+    warm_up_cancel: RefCell<Option<CancelToken>>,
+    /// Cancels the searches about the current position, like the analysis
+    /// and the hints, once it changes.
+    position_cancel: RefCell<CancelToken>,
+    /// Cancels the search of the engine for its move, if it's thinking.
+    engine_cancel: RefCell<Option<CancelToken>>,
+    /// The time the players have left, if the game is played with a clock.
+    clock: RefCell<Option<Clock>>,
+    /// When the clocks were last updated.
+    clock_ticked: Cell<Instant>,
+    /// Whether the opponent model learned from the current game already.
+    learned: Cell<bool>,
+    /// Whether the window was closed, to stop updating it.
+    closed: Cell<bool>,
+    /// Who to stream the game to, if anyone.
+    observer: RefCell<Option<Box<dyn Sink>>>,
+    /// The other player, if playing over the network rather than against
+    /// the engine.
+    network: RefCell<Option<Network>>,
+    /// Our mark in the network game, once there's one.
+    network_mark: Cell<Option<CheckBox>>,
+    /// Whether the engine moves first in games against it.
+    engine_first: bool,
+    /// The style of the grid buttons, from the theme.
+    css: gtk::CssProvider,
+    /// The size of the marks, to fit the squares. This goes over the theme.
+    scale_css: gtk::CssProvider,
+    /// The number of columns and rows of the grid.
+    grid_size: Cell<(usize, usize)>,
+    /// The size of the squares the marks are scaled for, in pixels.
+    cell_size: Cell<i32>,
+    /// What to do when something happens on the board, from the settings.
+    effects: RefCell<Vec<Box<dyn Effects>>>,
+    /// The board as last shown, to tell what happened since, or `None` if
+    /// it was a replay.
+    last_frame: RefCell<Option<Frame>>,
+
+    window: gtk::Window,
+    new_window_button: gtk::Button,
+    restart_button: gtk::Button,
+    undo_button: gtk::Button,
+    redo_button: gtk::Button,
+    hint_button: gtk::Button,
+    resign_button: gtk::Button,
+    /// Offers a draw, or accepts the one the opponent offers.
+    draw_button: gtk::Button,
+    /// Makes clicks count how the games after a move end, rather than play
+    /// it.
+    explore_button: gtk::ToggleButton,
+    save_button: gtk::Button,
+    load_button: gtk::Button,
+    export_button: gtk::Button,
+    import_button: gtk::Button,
+    setup_button: gtk::Button,
+    puzzle_button: gtk::Button,
+    settings_button: gtk::Button,
+    report_button: gtk::Button,
+    container: gtk::Box,
+    board_box: gtk::Box,
+    /// Keeps the squares of the grid square.
+    grid_frame: gtk::AspectFrame,
+    grid: Rc<RefCell<gtk::Grid>>,
+    /// Shows the position in an equivalent game, for the variants that have
+    /// one.
+    mirror_label: gtk::Label,
+    status_label: gtk::Label,
+    scoreboard_label: gtk::Label,
+    clock_label: gtk::Label,
+    network_label: gtk::Label,
+    replay_box: gtk::Box,
+    back_button: gtk::Button,
+    forward_button: gtk::Button,
+    /// The moves so far, each a link to the position after it.
+    moves_label: gtk::Label,
+    analysis: gtk::Expander,
+    analysis_label: gtk::Label,
+    eval_bar: EvalBar,
+    variant_input: gtk::ComboBoxText,
+    difficulty_input: gtk::ComboBoxText,
+
+    console_button: gtk::ToggleButton,
+    console: gtk::Revealer,
+    console_output: gtk::TextView,
+    console_input: gtk::Entry,
+
+    log_button: gtk::ToggleButton,
+    log: gtk::Revealer,
+    log_output: gtk::TextView,
+}
+
+impl App {
+    /// Opens a window playing with `settings`, streaming its games to
+    /// `observer` and playing them over `network`, if given.
+    fn open(
+        session: &Rc<Session>,
+        settings: Settings,
+        engine_first: bool,
+        observer: Option<Box<dyn Sink>>,
+        network: Option<Network>)
+    {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_title(tr("Tic tac toe"));
+        window.set_default_size(420, 560);
+        window.set_application(&session.application);
+
+        let button = gtk::Button::new_with_label(tr("Restart"));
+
+        let first_player = if engine_first { settings.human.next_player() } else { settings.human };
+        let game = settings.variant.new_game(first_player);
+
+        let app = Rc::new(App {
+            session: session.clone(),
+            game: RefCell::new(game),
+            settings: RefCell::new(settings),
+            results: Rc::new(RefCell::new(Tally::default())),
+            puzzle: RefCell::new(None),
+            replay: Cell::new(None),
+            network: RefCell::new(network),
+            network_mark: Cell::new(None),
+            engine_first,
+            // Reseeded for every game.
+            rng: RefCell::new(StdRng::seed_from_u64(0)),
+            seed: Cell::new(0),
+            warming_up: Cell::new(false),
+            warm_up_cancel: RefCell::new(None),
+            position_cancel: RefCell::new(CancelToken::new()),
+            engine_cancel: RefCell::new(None),
+            clock: RefCell::new(None),
+            clock_ticked: Cell::new(Instant::now()),
+            learned: Cell::new(false),
+            closed: Cell::new(false),
+            observer: RefCell::new(observer),
+            css: gtk::CssProvider::new(),
+            scale_css: gtk::CssProvider::new(),
+            grid_size: Cell::new((3, 3)),
+            cell_size: Cell::new(0),
+            effects: RefCell::new(vec![]),
+            last_frame: RefCell::new(None),
+
+            window,
+            new_window_button: gtk::Button::new_with_label(tr("New window")),
+            restart_button: button,
+            undo_button: gtk::Button::new_with_label(tr("Undo")),
+            redo_button: gtk::Button::new_with_label(tr("Redo")),
+            hint_button: gtk::Button::new_with_label(tr("Hint")),
+            resign_button: gtk::Button::new_with_label(tr("Resign")),
+            draw_button: gtk::Button::new_with_label(tr("Offer draw")),
+            explore_button: gtk::ToggleButton::new_with_label(tr("Explore")),
+            save_button: gtk::Button::new_with_label(tr("Save")),
+            load_button: gtk::Button::new_with_label(tr("Load")),
+            export_button: gtk::Button::new_with_label(tr("Export record")),
+            import_button: gtk::Button::new_with_label(tr("Import record")),
+            setup_button: gtk::Button::new_with_label(tr("Set up position")),
+            puzzle_button: gtk::Button::new_with_label(tr("Puzzles")),
+            settings_button: gtk::Button::new_with_label(tr("Settings")),
+            report_button: gtk::Button::new_with_label(tr("Report a problem")),
+            container: gtk::Box::new(gtk::Orientation::Vertical, 10 /* px */),
+            board_box: gtk::Box::new(gtk::Orientation::Horizontal, 10 /* px */),
+            grid_frame: gtk::AspectFrame::new(None, 0.5, 0.5, 1.0, /* obey_child = */ false),
+            // Replaced by one that fits the board on init.
+            grid: Rc::new(RefCell::new(gtk::Grid::new())),
+            mirror_label: gtk::Label::new(None),
+            status_label: gtk::Label::new(None),
+            scoreboard_label: gtk::Label::new(None),
+            clock_label: gtk::Label::new(None),
+            network_label: gtk::Label::new(None),
+            replay_box: gtk::Box::new(gtk::Orientation::Horizontal, 5 /* px */),
+            back_button: gtk::Button::new_with_label("◀"),
+            forward_button: gtk::Button::new_with_label("▶"),
+            moves_label: gtk::Label::new(None),
+            analysis: gtk::Expander::new(tr("Analysis")),
+            analysis_label: gtk::Label::new(None),
+            eval_bar: EvalBar::new(),
+
+            console_button: gtk::ToggleButton::new_with_label(tr("Console")),
+            console: gtk::Revealer::new(),
+            console_output: gtk::TextView::new(),
+            console_input: gtk::Entry::new(),
+            log_button: gtk::ToggleButton::new_with_label(tr("Engine log")),
+            log: gtk::Revealer::new(),
+            log_output: gtk::TextView::new(),
+            variant_input: gtk::ComboBoxText::new(),
+            difficulty_input: gtk::ComboBoxText::new(),
+        });
+
+        session.windows.borrow_mut().push(app.clone());
+        App::init(app);
+    }
+
+    /// Opens another window, with the settings of this one.
+    fn open_new_window(&self) {
+        let settings = self.settings.borrow().clone();
+        App::open(&self.session, settings, self.engine_first, None, None);
+    }
+
+    fn init(app: Rc<Self>) {
+        let box_ = &app.container;
+        app.grid_frame.set_shadow_type(gtk::ShadowType::None);
+        app.grid_frame.add(&*app.grid.borrow());
+        app.board_box.pack_start(&app.grid_frame, /* expand = */ true, /* fill = */ true, 0);
+        app.board_box.pack_start(&app.eval_bar.container, /* expand = */ false, /* fill = */ true, 0);
+        app.board_box.pack_start(&app.mirror_label, /* expand = */ false, /* fill = */ true, 0);
+        app.mirror_label.set_no_show_all(true);
+        box_.pack_start(&app.board_box, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.status_label, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.scoreboard_label, /* expand = */ false, /* fill = */ true, 0);
+        box_.pack_start(&app.clock_label, /* expand = */ false, /* fill = */ true, 0);
+        app.clock_label.set_no_show_all(true);
+        box_.pack_start(&app.network_label, /* expand = */ false, /* fill = */ true, 0);
+        app.network_label.set_no_show_all(true);
+        app.replay_box.pack_start(&app.back_button, /* expand = */ false, /* fill = */ true, 0);
+        app.replay_box.pack_start(&app.moves_label, /* expand = */ true, /* fill = */ true, 0);
+        app.replay_box.pack_start(&app.forward_button, /* expand = */ false, /* fill = */ true, 0);
+        app.moves_label.set_line_wrap(true);
+        box_.pack_start(&app.replay_box, /* expand = */ false, /* fill = */ true, 0);
+        box_.pack_start(&app.analysis, /* expand = */ false, /* fill = */ true, 0);
+        box_.pack_start(&app.new_window_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.restart_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.undo_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.redo_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.hint_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.resign_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.draw_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.explore_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.save_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.load_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.export_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.import_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.setup_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.puzzle_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.settings_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.report_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.variant_input, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.difficulty_input, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.console_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.console, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.log_button, /* expand = */ true, /* fill = */ true, 0);
+        box_.pack_start(&app.log, /* expand = */ true, /* fill = */ true, 0);
+        app.window.add(box_);
+
+        {
+            let console_box = gtk::Box::new(gtk::Orientation::Vertical, 5 /* px */);
+            let scrolled = gtk::ScrolledWindow::new(None, None);
+            scrolled.set_size_request(-1, 150);
+            scrolled.add(&app.console_output);
+            console_box.pack_start(&scrolled, /* expand = */ true, /* fill = */ true, 0);
+            console_box.pack_start(&app.console_input, /* expand = */ false, /* fill = */ true, 0);
+            app.console.add(&console_box);
+
+            app.console_output.set_editable(false);
+            app.console_output.set_cursor_visible(false);
+            app.console_output.set_monospace(true);
+            app.console_input.set_placeholder_text(tr("Type \"help\" for a list of commands"));
+        }
+
+        {
+            let scrolled = gtk::ScrolledWindow::new(None, None);
+            scrolled.set_size_request(-1, 150);
+            scrolled.add(&app.log_output);
+            app.log.add(&scrolled);
+
+            app.log_output.set_editable(false);
+            app.log_output.set_cursor_visible(false);
+            app.log_output.set_monospace(true);
+        }
+
+        app.analysis.add(&app.analysis_label);
+
+        {
+            let settings = app.settings.borrow();
+            for variant in Variant::all().iter() {
+                app.variant_input.append_text(variant.name());
+            }
+            let index = Variant::all().iter().position(|v| *v == settings.variant).unwrap();
+            app.variant_input.set_active(index as i32);
+
+            for difficulty in Difficulty::all().iter() {
+                app.difficulty_input.append_text(difficulty.name());
+            }
+            let index = Difficulty::all().iter().position(|d| *d == settings.engine.difficulty).unwrap();
+            app.difficulty_input.set_active(index as i32);
+        }
+
+        {
+            let app_clone = app.clone();
+            app.window.connect_key_press_event(move |_, event| {
+                // Leave the digits alone when typing commands, and the
+                // clipboard to the text.
+                if app_clone.console_input.has_focus() || app_clone.log_output.has_focus() {
+                    return gtk::Inhibit(false);
+                }
+                let keyval = event.get_keyval();
+                if event.get_state().contains(gdk::ModifierType::CONTROL_MASK) {
+                    match keyval {
+                        0x4e | 0x6e => app_clone.open_new_window(), // N.
+                        0x43 | 0x63 => app_clone.copy_position(), // C.
+                        0x56 | 0x76 => within_budget("paste", || App::paste_position(&app_clone)), // V.
+                        _ => return gtk::Inhibit(false),
+                    }
+                    return gtk::Inhibit(true);
+                }
+                let digit = match keyval {
+                    0x31..=0x39 => keyval - 0x30, // 1 to 9.
+                    0xffb1..=0xffb9 => keyval - 0xffb0, // Keypad 1 to 9.
+                    _ => return gtk::Inhibit(false),
+                };
+                let square = square_for_digit(app_clone.game.borrow().board(), digit as usize);
+                if let Some((x, y)) = square {
+                    within_budget("move", || App::handle_click(&app_clone, x, y, /* other_mark = */ false));
+                }
+                gtk::Inhibit(true)
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.window.connect_delete_event(move |_, _| {
+                if let Some(ref mut network) = *app_clone.network.borrow_mut() {
+                    network.close("closed the window");
+                }
+                app_clone.save_config();
+                app_clone.cancel_warm_up();
+                app_clone.stop_thinking();
+                // The application quits once it has no windows left.
+                app_clone.closed.set(true);
+                app_clone.session.windows.borrow_mut().retain(|window| !Rc::ptr_eq(window, &app_clone));
+                gtk::Inhibit(false)
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.window.connect_focus_out_event(move |_, _| {
+                App::pause_clock(&app_clone, true);
+                gtk::Inhibit(false)
+            });
+            let app_clone = app.clone();
+            app.window.connect_focus_in_event(move |_, _| {
+                App::pause_clock(&app_clone, false);
+                gtk::Inhibit(false)
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.new_window_button.connect_clicked(move |_| app_clone.open_new_window());
+        }
+
+        {
+            let app_clone = app.clone();
+            app.restart_button.connect_clicked(move |_| within_budget("restart", || {
+                {
+                    let mut game = app_clone.game.borrow_mut();
+                    *game = game.variant().new_game(app_clone.first_player());
+                }
+                app_clone.watch_game();
+                App::let_engine_move(&app_clone, None);
+                App::warm_up(&app_clone);
+                app_clone.update_grid();
+            }));
+        }
+
+        {
+            let app_clone = app.clone();
+            app.variant_input.connect_changed(move |_| within_budget("variant change", || {
+                let variant = app_clone.variant();
+                app_clone.settings.borrow_mut().variant = variant;
+                {
+                    let mut game = app_clone.game.borrow_mut();
+                    if game.variant() == variant {
+                        return;
+                    }
+                    *game = variant.new_game(app_clone.first_player());
+                }
+                app_clone.watch_game();
+                App::let_engine_move(&app_clone, None);
+                App::warm_up(&app_clone);
+                App::rebuild_grid(&app_clone);
+                app_clone.update_grid();
+            }));
+        }
+
+        {
+            let app_clone = app.clone();
+            app.difficulty_input.connect_changed(move |_| {
+                let difficulty = app_clone.difficulty();
+                app_clone.settings.borrow_mut().engine.difficulty = difficulty;
+                app_clone.configure_players();
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.analysis.connect_property_expanded_notify(move |_| {
+                within_budget("analysis", || app_clone.update_analysis());
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.console_button.connect_toggled(move |button| {
+                app_clone.console.set_reveal_child(button.get_active());
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.log_button.connect_toggled(move |button| {
+                app_clone.log.set_reveal_child(button.get_active());
+            });
+        }
+
+
+        {
+            let app_clone = app.clone();
+            app.console_input.connect_activate(move |entry| {
+                let line = entry.get_text().unwrap_or(String::new());
+                entry.set_text("");
+                within_budget("console command", || app_clone.run_command(&line));
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.save_button.connect_clicked(move |_| {
+                app_clone.save();
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.load_button.connect_clicked(move |_| {
+                App::load(&app_clone);
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.export_button.connect_clicked(move |_| {
+                app_clone.export_record();
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.import_button.connect_clicked(move |_| {
+                App::import_record(&app_clone);
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.setup_button.connect_clicked(move |_| {
+                let position = match app_clone.game.borrow_mut().tic_tac_toe() {
+                    Some(game) => (game.state().clone(), game.player()),
+                    None => (State::initial(), app_clone.human()),
+                };
+                App::set_up_position(&app_clone, position);
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.resign_button.connect_clicked(move |_| {
+                app_clone.resign();
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.draw_button.connect_clicked(move |_| {
+                app_clone.offer_or_accept_draw();
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.puzzle_button.connect_clicked(move |_| {
+                app_clone.pick_puzzle();
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.settings_button.connect_clicked(move |_| {
+                app_clone.edit_settings();
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.report_button.connect_clicked(move |_| {
+                app_clone.report_problem();
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.back_button.connect_clicked(move |_| {
+                let plies = app_clone.game.borrow().board().plies();
+                let ply = app_clone.replay.get().unwrap_or(plies);
+                if ply > 0 {
+                    app_clone.show_ply(ply - 1);
+                }
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.forward_button.connect_clicked(move |_| {
+                if let Some(ply) = app_clone.replay.get() {
+                    app_clone.show_ply(ply + 1);
+                }
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.moves_label.connect_activate_link(move |_, uri| {
+                if let Ok(ply) = uri.parse::<usize>() {
+                    app_clone.show_ply(ply);
+                }
+                gtk::Inhibit(true)
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.undo_button.connect_clicked(move |_| {
+                within_budget("undo", || app_clone.undo());
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.redo_button.connect_clicked(move |_| {
+                within_budget("redo", || app_clone.redo());
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.hint_button.connect_clicked(move |_| {
+                within_budget("hint", || app_clone.hint());
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.explore_button.connect_toggled(move |_| {
+                app_clone.update_grid();
+            });
+        }
+
+        if app.network.borrow().is_some() {
+            // Both sides need to agree on the game, so nothing but the moves
+            // can change it.
+            app.restart_button.set_sensitive(false);
+            app.undo_button.set_sensitive(false);
+            app.redo_button.set_sensitive(false);
+            app.load_button.set_sensitive(false);
+            app.import_button.set_sensitive(false);
+            app.setup_button.set_sensitive(false);
+            app.puzzle_button.set_sensitive(false);
+            app.variant_input.set_sensitive(false);
+            app.difficulty_input.set_sensitive(false);
+            app.console_button.set_sensitive(false);
+            app.network_label.show();
+
+            let app_clone = app.clone();
+            gtk::timeout_add(NETWORK_POLL_MS, move || {
+                if app_clone.closed.get() {
+                    return gtk::Continue(false);
+                }
+                within_budget("network", || App::poll_network(&app_clone));
+                gtk::Continue(true)
+            });
+        }
+
+        if app.observer.borrow().is_some() {
+            let app_clone = app.clone();
+            gtk::timeout_add(OBSERVER_POLL_MS, move || {
+                if app_clone.closed.get() {
+                    return gtk::Continue(false);
+                }
+                app_clone.notify_observer();
+                gtk::Continue(true)
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            app.grid_frame.connect_size_allocate(move |_, allocation| {
+                let (cols, rows) = app_clone.grid_size.get();
+                let cell_size = cmp::min(allocation.width / cols as i32, allocation.height / rows as i32);
+                if cell_size != app_clone.cell_size.get() {
+                    // Restyling the squares while sizing them would size them
+                    // all over again.
+                    let app_clone = app_clone.clone();
+                    gtk::idle_add(move || {
+                        app_clone.scale_marks(cell_size);
+                        gtk::Continue(false)
+                    });
+                }
+            });
+        }
+
+        {
+            let app_clone = app.clone();
+            gtk::timeout_add(CLOCK_TICK_MS, move || {
+                if app_clone.closed.get() {
+                    return gtk::Continue(false);
+                }
+                App::tick_clock(&app_clone);
+                gtk::Continue(true)
+            });
+        }
+
+        app.apply_theme();
+        app.apply_effects();
+        app.watch_game();
+        App::let_engine_move(&app, None);
+        App::warm_up(&app);
+        App::rebuild_grid(&app);
+        app.update_grid();
+        app.window.show_all();
+    }
+
+    /// Searches the start of the game on another thread, and hands the
+    /// result to the engine when it's done, so that its first reply is as
+    /// quick as the rest.
+    ///
+    /// Cancels the warm up that was running, if any, since it was for a game
+    /// that's gone.
+    fn warm_up(app: &Rc<Self>) {
+        app.cancel_warm_up();
+        let cancel = CancelToken::new();
+        *app.warm_up_cancel.borrow_mut() = Some(cancel.clone());
+        let job = app.game.borrow().board().warm_up(cancel.clone());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // The app may be gone by now.
+            let _ = sender.send(job());
+        });
+        app.warming_up.set(true);
+
+        let app_clone = app.clone();
+        gtk::timeout_add(WARM_UP_POLL_MS, move || {
+            // Another warm up replaced this one.
+            if cancel.is_cancelled() {
+                return gtk::Continue(false);
+            }
+            match receiver.try_recv() {
+                Ok(tree) => {
+                    // A game of another variant keeps searching on its own.
+                    app_clone.game.borrow_mut().board_mut().adopt_warm_tree(tree);
+                }
+                Err(mpsc::TryRecvError::Empty) => return gtk::Continue(true),
+                Err(mpsc::TryRecvError::Disconnected) => {}
+            }
+            app_clone.warming_up.set(false);
+            app_clone.update_grid();
+            gtk::Continue(false)
+        });
+    }
+
+    /// Stops the search of the warm up that's running, if any.
+    fn cancel_warm_up(&self) {
+        if let Some(cancel) = self.warm_up_cancel.borrow_mut().take() {
+            cancel.cancel();
+        }
+        self.warming_up.set(false);
+    }
+
+    /// Makes the clock run for whoever has to move on `board`, if there's a
+    /// clock.
+    fn run_clock(&self, board: &dyn Board) {
+        if let Some(ref mut clock) = *self.clock.borrow_mut() {
+            let player = match board.result() {
+                GameResult::InProgress => board.player(),
+                _ => CheckBox::Empty,
+            };
+            clock.run(player, Instant::now());
+        }
+    }
+
+    /// Pauses the time of the human while the window is in the background,
+    /// if there's a clock and it stops for them, or makes it run again.
+    fn pause_clock(app: &Rc<Self>, paused: bool) {
+        if let Some(ref mut clock) = *app.clock.borrow_mut() {
+            if !clock.pauses_when_away(app.human()) {
+                return;
+            }
+            if paused {
+                clock.pause(app.human(), Instant::now());
+            } else {
+                clock.resume(app.human(), Instant::now());
+            }
+        }
+        App::tick_clock(app);
+    }
+
+    /// Shows the time left, and does what the game says to whoever has to
+    /// move if they ran out of it, see `Game::time_out`.
+    fn tick_clock(app: &Rc<Self>) {
+        let now = Instant::now();
+        let ticked = app.clock_ticked.replace(now);
+        let (text, flagged) = match *app.clock.borrow_mut() {
+            Some(ref mut clock) => {
+                let settings = app.settings.borrow();
+                // Nobody could think while the computer was asleep.
+                let pauses = clock.pauses_when_away(CheckBox::X) || clock.pauses_when_away(CheckBox::O);
+                if pauses && now.duration_since(ticked) > ASLEEP_AFTER {
+                    for &player in &[CheckBox::X, CheckBox::O] {
+                        if !clock.is_paused(player) {
+                            clock.pause(player, ticked);
+                            clock.resume(player, now);
+                        }
+                    }
+                }
+                let theme = &settings.theme;
+                let mut text = format!("{} {}  {} {}",
+                                       theme.glyph(CheckBox::X), clock::format(clock.left(CheckBox::X, now)),
+                                       theme.glyph(CheckBox::O), clock::format(clock.left(CheckBox::O, now)));
+                if clock.is_paused(app.human()) {
+                    text.push_str(tr(" (paused)"));
+                }
+                // Someone the engine moves for stays out of time, which only
+                // matters on their turn.
+                let game = app.game.borrow();
+                let board = game.board();
+                let flagged = match board.result() {
+                    GameResult::InProgress if clock.is_out_of_time(board.player(), now) => Some(board.player()),
+                    _ => None,
+                };
+                (text, flagged)
+            }
+            None => return,
+        };
+        app.clock_label.set_text(&text);
+        if let Some(player) = flagged {
+            app.stop_thinking();
+            let moved = app.game.borrow_mut().board_mut().time_out(player);
+            if moved {
+                App::let_engine_move(app, None);
+            }
+            app.update_grid();
+        }
+    }
+
+    /// Plays the square in column `x` and row `y`, with the other mark of
+    /// the square if `other_mark` is set, in the games that have one.
+    fn handle_click(app: &Rc<Self>, x: usize, y: usize, other_mark: bool) {
+        // Past positions are only for looking at, and the engine is about to
+        // move in the one it's thinking about.
+        if app.replay.get().is_some() || app.thinking() {
+            return;
+        }
+
+        if app.explore_button.get_active() {
+            app.explore(x, y, other_mark);
+            return;
+        }
+
+        if app.network.borrow().is_some() {
+            // TODO(emilio): The network protocol only knows about squares, so
+            // there's no way to send the other mark yet.
+            if !other_mark {
+                app.play_network_move(x, y);
+            }
+            return;
+        }
+
+        let puzzle = if other_mark { None } else { app.current_puzzle() };
+        let verdict = match puzzle {
+            Some(ref puzzle) => match puzzle.check((x, y)) {
+                Ok(verdict) => Some(verdict),
+                Err(err) => {
+                    app.show_illegal_move(x, y, err);
+                    return;
+                }
+            },
+            None => None,
+        };
+        // The trainer would give the answer of puzzles away.
+        if verdict.is_none() && app.settings.borrow().trainer && !app.confirm_move(x, y) {
+            return;
+        }
+
+        {
+            let mut game = app.game.borrow_mut();
+            let mut rng = app.rng.borrow_mut();
+            // The engine thinks on another thread, see `let_engine_move`.
+            let mut driver = GameDriver::new(game.board_mut(), &mut *rng);
+            if let Err(err) = driver.submit(Click { x, y, other_mark }) {
+                app.show_illegal_move(x, y, err);
+                return;
+            }
+            while let Some(event) = driver.next() {
+                if let GameEvent::MoveMade(..) = event {
+                    app.run_clock(driver.board());
+                }
+            }
+        }
+
+        App::let_engine_move(app, puzzle.and_then(|puzzle| verdict.map(|verdict| (puzzle, verdict))));
+        app.update_grid();
+    }
+
+    /// Tells the user how the move they just played does for `puzzle`, or
+    /// moves on to the rest of it, after the reply of the engine.
+    fn judge_puzzle_move(&self, puzzle: &Puzzle, verdict: Verdict) {
+        let message = match verdict {
+            Verdict::Right => {
+                if let Some(game) = self.game.borrow_mut().tic_tac_toe() {
+                    *self.puzzle.borrow_mut() = Some(puzzle.continued(game.state().clone(), game.player()));
+                }
+                return;
+            }
+            Verdict::Solved => {
+                self.session.progress.borrow_mut().record(puzzle);
+                self.save_config();
+                tr("Solved!")
+            }
+            Verdict::TooSlow => tr("That still wins, but not in time. Undo to try again."),
+            Verdict::Wrong => tr("That's not it. Undo to try again."),
+        };
+        self.inform(message);
+    }
+
+    /// The puzzle the user is solving, if the game is at its position.
+    fn current_puzzle(&self) -> Option<Puzzle> {
+        let puzzle = self.puzzle.borrow().clone()?;
+        if self.replay.get().is_some() {
+            return None;
+        }
+        let mut game = self.game.borrow_mut();
+        let game = game.tic_tac_toe()?;
+        if *game.state() == puzzle.state && game.player() == puzzle.player {
+            Some(puzzle)
+        } else {
+            None
+        }
+    }
+
+    /// Shows how every game where the player to move plays the square in
+    /// column `x` and row `y` ends, instead of playing it.
+    fn explore(&self, x: usize, y: usize, other_mark: bool) {
+        let game = self.game.borrow();
+        let board = game.board();
+        let tally = match board.tally_click(x, y, other_mark) {
+            Ok(tally) => tally,
+            Err(err) => return self.show_illegal_move(x, y, err),
+        };
+        let tally = match tally {
+            Some(tally) => tally,
+            None => {
+                self.status_label.set_text(tr("This game is too big to count every way it can go."));
+                return;
+            }
+        };
+
+        let settings = self.settings.borrow();
+        let theme = &settings.theme;
+        let games = tally.games();
+        let percent = |n: u64| 100. * n as f64 / games as f64;
+        self.status_label.set_text(&format!(
+            "Of the {} games after {} plays there, {} wins {} ({:.0}%), {} wins {} ({:.0}%) and {} ({:.0}%) are draws.",
+            games, theme.glyph(board.player()),
+            theme.glyph(CheckBox::X), tally.x_wins, percent(tally.x_wins),
+            theme.glyph(CheckBox::O), tally.o_wins, percent(tally.o_wins),
+            tally.draws, percent(tally.draws)));
+    }
+
+    /// Says why the square in column `x` and row `y` couldn't be played, and
+    /// flashes it red, for a moment.
+    fn show_illegal_move(&self, x: usize, y: usize, err: MoveError) {
+        let previous = self.status_label.get_text().unwrap_or_default();
+        let message = tr_format("Can't play there: {}.", &[&err]);
+        self.status_label.set_text(&message);
+        let label = self.status_label.clone();
+        gtk::timeout_add(ILLEGAL_MESSAGE_MS, move || {
+            // Unless something else was said since.
+            if label.get_text().as_ref() == Some(&message) {
+                label.set_text(&previous);
+            }
+            gtk::Continue(false)
+        });
+
+        let style = match self.grid.borrow().get_child_at(x as i32, y as i32) {
+            Some(button) => button.get_style_context(),
+            None => return,
+        };
+        if let Some(style) = style {
+            style.add_class("illegal");
+            gtk::timeout_add(ILLEGAL_FLASH_MS, move || {
+                style.remove_class("illegal");
+                gtk::Continue(false)
+            });
+        }
+    }
+
+    /// Plays a move in a network game, if it's our turn, and sends it to the
+    /// other player.
+    fn play_network_move(&self, x: usize, y: usize) {
+        let mark = match self.network_mark.get() {
+            Some(mark) => mark,
+            None => return,
+        };
+        {
+            let mut network = self.network.borrow_mut();
+            let network = network.as_mut().unwrap();
+            let mut game = self.game.borrow_mut();
+            let board = game.board_mut();
+            if !network.is_connected() || board.player() != mark {
+                return;
+            }
+            // Moving turns down the draw they offered, if they did.
+            let offered = board.draw_offer() == Some(mark.next_player());
+            if let Err(err) = board.click(x, y) {
+                self.show_illegal_move(x, y, err);
+                return;
+            }
+            if offered {
+                network.send(&Message::AnswerDraw(false));
+            }
+            network.send(&Message::Move(x, y));
+        }
+        self.update_grid();
+    }
+
+    /// Handles what the other player did in a network game.
+    fn poll_network(app: &Rc<Self>) {
+        let events = match *app.network.borrow_mut() {
+            Some(ref mut network) => network.poll(),
+            None => return,
+        };
+        if events.is_empty() {
+            return;
+        }
+
+        for event in events {
+            let error = match event {
+                Event::Connected(_) => {
+                    // A guest joined, start a game with them.
+                    let variant = app.variant();
+                    let mark = app.settings.borrow().human;
+                    app.network_mark.set(Some(mark));
+                    app.start_network_game(variant, mark);
+                    app.network.borrow_mut().as_mut().unwrap().send(&Message::Hello(variant, mark));
+                    None
+                }
+                Event::Message(Message::Hello(variant, host_mark)) => {
+                    if app.network.borrow().as_ref().unwrap().is_host() || app.network_mark.get().is_some() {
+                        Some("the opponent said hello twice")
+                    } else {
+                        app.network_mark.set(Some(host_mark.next_player()));
+                        App::start_network_game_and_grid(app, variant, host_mark);
+                        None
+                    }
+                }
+                Event::Message(Message::Move(x, y)) => {
+                    let mut game = app.game.borrow_mut();
+                    let board = game.board_mut();
+                    let theirs = app.network_mark.get().map(|m| m.next_player());
+                    if theirs == Some(board.player()) && board.click(x, y).is_ok() {
+                        None
+                    } else {
+                        Some("the opponent played an illegal move")
+                    }
+                }
+                Event::Message(Message::Resign) => {
+                    if let Some(mark) = app.network_mark.get() {
+                        app.game.borrow_mut().board_mut().resign(mark.next_player());
+                    }
+                    None
+                }
+                Event::Message(Message::OfferDraw) => {
+                    let mut game = app.game.borrow_mut();
+                    let board = game.board_mut();
+                    match app.network_mark.get() {
+                        // If the offers crossed, both sides agree.
+                        Some(mark) if board.draw_offer() == Some(mark) => {
+                            board.answer_draw_offer(mark.next_player(), true);
+                        }
+                        Some(mark) => {
+                            board.offer_draw(mark.next_player());
+                        }
+                        None => {}
+                    }
+                    None
+                }
+                Event::Message(Message::AnswerDraw(accept)) => {
+                    let mut game = app.game.borrow_mut();
+                    let board = game.board_mut();
+                    match app.network_mark.get() {
+                        Some(mark) if board.answer_draw_offer(mark.next_player(), accept) => None,
+                        _ => Some("the opponent answered a draw offer nobody made"),
+                    }
+                }
+                Event::Message(Message::Bye(_)) | Event::Closed(_) => None,
+            };
+            if let Some(error) = error {
+                app.network.borrow_mut().as_mut().unwrap().close(error);
+                break;
+            }
+        }
+        app.update_grid();
+    }
+
+    /// Replaces the game with a new network game of `variant`, in which the
+    /// host plays `host_mark` and moves first.
+    fn start_network_game(&self, variant: Variant, host_mark: CheckBox) {
+        *self.game.borrow_mut() = variant.new_game(host_mark);
+        // So that switching doesn't start yet another game.
+        let index = Variant::all().iter().position(|v| *v == variant).unwrap();
+        self.variant_input.set_active(index as i32);
+        self.watch_game();
+    }
+
+    /// Like `start_network_game`, rebuilding the grid in case the host plays
+    /// another variant.
+    fn start_network_game_and_grid(app: &Rc<Self>, variant: Variant, host_mark: CheckBox) {
+        app.start_network_game(variant, host_mark);
+        App::rebuild_grid(app);
+    }
+
+    /// Takes back the last engine reply together with the human move that
+    /// preceded it, so that it's the human's turn again.
+    fn undo(&self) {
+        self.stop_thinking();
+        let human = self.human();
+        self.replay.set(None);
+        {
+            let mut game = self.game.borrow_mut();
+            let board = game.board_mut();
+            if board.undo() {
+                while board.player() != human && board.undo() {}
+            }
+        }
+
+        self.update_grid();
+    }
+
+    /// Replays the human move and the engine reply that were last undone.
+    fn redo(&self) {
+        self.stop_thinking();
+        let human = self.human();
+        self.replay.set(None);
+        {
+            let mut game = self.game.borrow_mut();
+            let board = game.board_mut();
+            if board.redo() {
+                while board.player() != human && board.redo() {}
+            }
+        }
+
+        self.update_grid();
+    }
+
+    /// Highlights the square the engine would play as the human for a
+    /// moment, once a search on another thread finds it.
+    fn hint(&self) {
+        let human = self.human();
+        let cancel = self.position_cancel.borrow().clone();
+        let job = {
+            let game = self.game.borrow();
+            let board = game.board();
+            if board.player() != human || self.replay.get().is_some() {
+                return;
+            }
+            board.hint_job(human, cancel.clone())
+        };
+        let grid = self.grid.borrow().clone();
+        run_in_background(cancel, job, move |squares| highlight_squares(&grid, &squares));
+    }
+
+    /// Highlights `squares` for a moment, like a hint.
+    fn highlight_squares(&self, squares: &[(usize, usize)]) {
+        highlight_squares(&self.grid.borrow(), squares);
+    }
+
+    /// Asks the human whether they really want to click the square in column
+    /// `x` and row `y`, when it misses a win or leaves a win to the opponent.
+    ///
+    /// Returns whether to play the move.
+    ///
+    /// TODO(emilio): The threats are squares, so with the other mark a click
+    /// on a winning square counts as winning.
+    fn confirm_move(&self, x: usize, y: usize) -> bool {
+        let (message, squares) = {
+            let game = self.game.borrow();
+            let board = game.board();
+            let human = board.player();
+            if board.result() != GameResult::InProgress {
+                return true;
+            }
+            let wins = board.immediate_threats(human);
+            let blocks = board.immediate_threats(human.next_player());
+            if !wins.is_empty() {
+                if wins.contains(&(x, y)) {
+                    return true;
+                }
+                (tr("You can win right now. Are you sure?"), wins)
+            } else if !blocks.is_empty() && !blocks.contains(&(x, y)) {
+                (tr("Your opponent wins next unless you block them. Are you sure?"), blocks)
+            } else {
+                return true;
+            }
+        };
+
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.window),
+            gtk::DialogFlags::MODAL,
+            gtk::MessageType::Question,
+            gtk::ButtonsType::None,
+            message,
+        );
+        dialog.add_button(tr("_Show why"), gtk::ResponseType::Help.into());
+        dialog.add_button(tr("_Cancel"), gtk::ResponseType::No.into());
+        dialog.add_button(tr("_Play it"), gtk::ResponseType::Yes.into());
+        let response = dialog.run();
+        dialog.destroy();
+
+        let play: i32 = gtk::ResponseType::Yes.into();
+        let show_why: i32 = gtk::ResponseType::Help.into();
+        if response == show_why {
+            self.highlight_squares(&squares);
+        }
+        response == play
+    }
+
+    /// Appends `text` to the engine log, keeping the last `LOG_LINES` lines.
+    fn append_log(&self, text: &str) {
+        let buffer = self.log_output.get_buffer()
+            .expect("Text views should have a buffer");
+        buffer.insert(&mut buffer.get_end_iter(), text);
+        // The text ends with a newline, so the last line is empty.
+        let extra = buffer.get_line_count() - 1 - LOG_LINES;
+        if extra > 0 {
+            buffer.delete(&mut buffer.get_start_iter(), &mut buffer.get_iter_at_line(extra));
+        }
+        if let Some(mark) = buffer.get_insert() {
+            buffer.place_cursor(&buffer.get_end_iter());
+            self.log_output.scroll_to_mark(&mark, 0.0, false, 0.0, 0.0);
+        }
+    }
+
+    /// Runs a console command, and appends it and its output to the
+    /// console scrollback.
+    ///
+    /// Searches run on another thread, and show up once they're done.
+    fn run_command(&self, line: &str) {
+        let job = self.game.borrow().board().command_job(line);
+        if let Some(job) = job {
+            let console_output = self.console_output.clone();
+            let line = line.to_owned();
+            run_in_background(CancelToken::new(), job, move |result| {
+                append_command(&console_output, &line, result);
+            });
+            return;
+        }
+        let result = self.game.borrow_mut().board_mut().execute(line);
+        append_command(&self.console_output, line, result);
+
+        // The command may have changed the game or its settings. Keep the
+        // difficulty it set, rather than the one of the settings.
+        let difficulty = self.game.borrow().board().difficulty();
+        let index = Difficulty::all().iter().position(|d| *d == difficulty).unwrap();
+        self.difficulty_input.set_active(index as i32);
+        self.settings.borrow_mut().engine.difficulty = difficulty;
+        self.watch_game();
+        self.update_grid();
+    }
+
+    /// Asks the user for a file to save to or load from, in a dialog titled
+    /// `title`.
+    fn choose_file(&self, action: gtk::FileChooserAction, title: &str) -> Option<PathBuf> {
+        let accept = match action {
+            gtk::FileChooserAction::Save => tr("_Save"),
+            _ => tr("_Open"),
+        };
+
+        let dialog = gtk::FileChooserDialog::with_buttons(
+            Some(title),
+            Some(&self.window),
+            action,
+            &[(tr("_Cancel"), gtk::ResponseType::Cancel),
+              (accept, gtk::ResponseType::Accept)],
+        );
+        dialog.set_do_overwrite_confirmation(true);
+
+        let response = dialog.run();
+        let file = dialog.get_filename();
+        dialog.destroy();
+
+        let accepted: i32 = gtk::ResponseType::Accept.into();
+        if response == accepted { file } else { None }
+    }
+
+    fn save(&self) {
+        let path = match self.choose_file(gtk::FileChooserAction::Save, tr("Save game")) {
+            Some(path) => path,
+            None => return,
+        };
+
+        let mut contents = vec![];
+        self.game.borrow().board().write_record(&mut contents)
+            .expect("Writing to memory shouldn't fail");
+        let status_label = self.status_label.clone();
+        run_in_background(CancelToken::new(), Box::new(move || fs::write(&path, contents)), move |result| {
+            if let Err(err) = result {
+                status_label.set_text(&tr_format("Couldn't save the game: {}", &[&err]));
+            }
+        });
+    }
+
+    fn load(app: &Rc<Self>) {
+        let path = match app.choose_file(gtk::FileChooserAction::Open, tr("Load game")) {
+            Some(path) => path,
+            None => return,
+        };
+
+        let app_clone = app.clone();
+        run_in_background(CancelToken::new(), Box::new(move || fs::read(&path)), move |contents| {
+            let app = app_clone;
+            let game = match contents.map_err(LoadError::from).and_then(|contents| AnyGame::read(&*contents)) {
+                Ok(game) => game,
+                Err(err) => {
+                    app.status_label.set_text(&tr_format("Couldn't load the game: {}", &[&err]));
+                    return;
+                }
+            };
+
+            // Switch to the variant of the game before replacing it, so that
+            // the switch doesn't start a new one afterwards.
+            let variant = Variant::all().iter().position(|v| *v == game.variant()).unwrap();
+            app.variant_input.set_active(variant as i32);
+
+            let human = app.human();
+            let engine = game.board().player_config(human.next_player());
+            let hints = game.board().player_config(human);
+            let difficulty = engine.difficulty;
+            let seed = game.board().seed();
+            let time_controls = game.board().time_controls();
+            let timeout_policy = game.board().timeout_policy();
+            *app.game.borrow_mut() = game;
+            app.watch_game();
+            // Keep playing like the saved game would have.
+            app.reseed(seed);
+            if time_controls.is_some() {
+                app.start_clock(time_controls, timeout_policy);
+            }
+            {
+                let mut settings = app.settings.borrow_mut();
+                settings.engine = engine;
+                settings.hints = hints;
+            }
+            // This ends up configuring the players of the game too, like they
+            // already are.
+            let index = Difficulty::all().iter().position(|d| *d == difficulty).unwrap();
+            app.difficulty_input.set_active(index as i32);
+            app.update_grid();
+        });
+    }
+
+    /// Writes the record of the finished game to a file of the user's choice.
+    fn export_record(&self) {
+        let record = {
+            let game = self.game.borrow();
+            if game.board().result() == GameResult::InProgress {
+                self.status_label.set_text(tr("Only finished games can be exported."));
+                return;
+            }
+            let human = self.human();
+            let opponent = if self.network.borrow().is_some() {
+                "Opponent".to_owned()
+            } else {
+                format!("tic-tac-minimax ({})", self.difficulty().name())
+            };
+            let (x, o) = if human == CheckBox::X { ("Human", &*opponent) } else { (&*opponent, "Human") };
+            Record::of_game(&game, x, o, &recorder::today())
+        };
+
+        let path = match self.choose_file(gtk::FileChooserAction::Save, tr("Export game record")) {
+            Some(path) => path,
+            None => return,
+        };
+        let mut contents = vec![];
+        record.write(&mut contents).expect("Writing to memory shouldn't fail");
+        let status_label = self.status_label.clone();
+        run_in_background(CancelToken::new(), Box::new(move || fs::write(&path, contents)), move |result| {
+            if let Err(err) = result {
+                status_label.set_text(&tr_format("Couldn't export the game: {}", &[&err]));
+            }
+        });
+    }
+
+    /// Reads a game record of the user's choice, and shows it from the
+    /// start.
+    fn import_record(app: &Rc<Self>) {
+        let path = match app.choose_file(gtk::FileChooserAction::Open, tr("Import game record")) {
+            Some(path) => path,
+            None => return,
+        };
+        let app_clone = app.clone();
+        run_in_background(CancelToken::new(), Box::new(move || fs::read_to_string(&path)), move |text| {
+            let app = app_clone;
+            let game = text.map_err(|err| err.to_string()).and_then(|text| {
+                Record::parse(&text).and_then(|r| r.replay()).map_err(|err| err.to_string())
+            });
+            let game = match game {
+                Ok(game) => game,
+                Err(err) => {
+                    app.status_label.set_text(&tr_format("Couldn't import the game: {}", &[&err]));
+                    return;
+                }
+            };
+
+            // Like `load`, switch first so that the switch doesn't start a new
+            // game afterwards.
+            let variant = Variant::all().iter().position(|v| *v == game.variant()).unwrap();
+            app.variant_input.set_active(variant as i32);
+            let time_controls = game.board().time_controls();
+            let timeout_policy = game.board().timeout_policy();
+            *app.game.borrow_mut() = game;
+            app.watch_game();
+            // The time it was played with, rather than the one of the
+            // settings.
+            app.start_clock(time_controls, timeout_policy);
+            app.show_ply(0);
+        });
+    }
+
+    /// Lets the user place and clear marks to build a tic-tac-toe position,
+    /// starting from `position`, and then play or analyze from it.
+    fn set_up_position(app: &Rc<Self>, (state, player): (State, CheckBox)) {
+        let state = Rc::new(RefCell::new(state));
+
+        let dialog = gtk::Dialog::new_with_buttons(
+            Some(tr("Set up position")),
+            Some(&app.window),
+            gtk::DialogFlags::MODAL,
+            &[(tr("_Cancel"), gtk::ResponseType::Cancel.into()),
+              (tr("_Analyze"), gtk::ResponseType::Apply.into()),
+              (tr("_Play"), gtk::ResponseType::Ok.into())],
+        );
+
+        let settings = app.settings.borrow().clone();
+        let theme = settings.theme.clone();
+        let glyph = move |mark| theme.glyph(mark).to_owned();
+        let hint = gtk::Label::new(Some(tr("Click a square to mark it X, then O, then clear it.")));
+        hint.set_xalign(0.0);
+        let squares = gtk::Grid::new();
+        squares.set_row_homogeneous(true);
+        squares.set_column_homogeneous(true);
+        for x in 0..3 {
+            for y in 0..3 {
+                let button = gtk::Button::new_with_label(&glyph(state.borrow().get(x, y)));
+                button.set_size_request(60, 60);
+                let state = state.clone();
+                let glyph = glyph.clone();
+                button.connect_clicked(move |button| {
+                    let mut state = state.borrow_mut();
+                    let mark = match state.get(x, y) {
+                        CheckBox::Empty => CheckBox::X,
+                        CheckBox::X => CheckBox::O,
+                        CheckBox::O => CheckBox::Empty,
+                    };
+                    state.set(x, y, mark);
+                    button.set_label(&glyph(mark));
+                });
+                squares.attach(&button, x as i32, y as i32, 1, 1);
+            }
+        }
+        let to_move = gtk::ComboBoxText::new();
+        to_move.append_text(&format!("{} to move", settings.theme.glyph(CheckBox::X)));
+        to_move.append_text(&format!("{} to move", settings.theme.glyph(CheckBox::O)));
+        to_move.set_active(if player == CheckBox::X { 0 } else { 1 });
+        let error = gtk::Label::new(None);
+        error.set_xalign(0.0);
+
+        let content = dialog.get_content_area();
+        content.pack_start(&hint, /* expand = */ false, /* fill = */ true, 5);
+        content.pack_start(&squares, /* expand = */ true, /* fill = */ true, 5);
+        content.pack_start(&to_move, /* expand = */ false, /* fill = */ true, 5);
+        content.pack_start(&error, /* expand = */ false, /* fill = */ true, 5);
+        dialog.show_all();
+
+        let play: i32 = gtk::ResponseType::Ok.into();
+        let analyze: i32 = gtk::ResponseType::Apply.into();
+        let (response, player) = loop {
+            let response = dialog.run();
+            if response != play && response != analyze {
+                dialog.destroy();
+                return;
+            }
+            let player = if to_move.get_active() == 1 { CheckBox::O } else { CheckBox::X };
+            match state.borrow().validate(player) {
+                Ok(()) => break (response, player),
+                Err(err) => error.set_text(&tr_format("This can't come up in a game: {}.", &[&err])),
+            }
+        };
+        dialog.destroy();
+
+        // Like `load`, switch first so that the switch doesn't start a new
+        // game afterwards, nor warm one up.
+        let variant = Variant::all().iter().position(|v| *v == Variant::TicTacToe).unwrap();
+        app.variant_input.set_active(variant as i32);
+        app.cancel_warm_up();
+        let state = state.borrow().clone();
+        *app.game.borrow_mut() = AnyGame::TicTacToe(Game::from_state(state, player));
+        app.watch_game();
+
+        if response == analyze {
+            app.analysis.set_expanded(true);
+        } else {
+            App::let_engine_move(app, None);
+        }
+        app.update_grid();
+    }
+
+    /// Copies the position of the game to the clipboard, as a diagram, and
+    /// in tic-tac-toe also as the notation `paste_position` reads.
+    fn copy_position(&self) {
+        let mut game = self.game.borrow_mut();
+        let diagram = terminal::diagram(game.board());
+        let text = match game.tic_tac_toe() {
+            Some(game) => format!("{}\n\n{}", game.state().to_notation(game.player()), diagram),
+            None => diagram,
+        };
+        gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&text);
+    }
+
+    /// Sets up the tic-tac-toe position in the clipboard, like
+    /// `X_O/_X_/__O X`, to play or analyze from it.
+    fn paste_position(app: &Rc<Self>) {
+        // Like the setup button, which network games disable.
+        if !app.setup_button.get_sensitive() {
+            return;
+        }
+        let text = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).wait_for_text().unwrap_or_default();
+        match State::from_pasted(&text) {
+            Ok(position) => App::set_up_position(app, position),
+            Err(err) => app.inform(&tr_format("The clipboard has no position: {}.", &[&err])),
+        }
+    }
+
+    /// Asks whether to resign, and resigns the game if so.
+    fn resign(&self) {
+        let human = self.human();
+        if self.game.borrow().board().result() != GameResult::InProgress ||
+            !self.confirm(tr("Resign this game?"), tr("_Resign"))
+        {
+            return;
+        }
+        self.game.borrow_mut().board_mut().resign(human);
+        if let Some(ref mut network) = *self.network.borrow_mut() {
+            network.send(&Message::Resign);
+        }
+        self.update_grid();
+    }
+
+    /// Asks whether to offer a draw, or to take the one the opponent
+    /// offers, and does so.
+    ///
+    /// The engine answers offers right away, over the network they're
+    /// answered whenever the other player does.
+    fn offer_or_accept_draw(&self) {
+        let human = self.human();
+        let offer = self.game.borrow().board().draw_offer();
+        if offer == Some(human.next_player()) {
+            if !self.confirm(tr("Accept the draw your opponent offers?"), tr("_Accept")) {
+                return;
+            }
+            self.game.borrow_mut().board_mut().answer_draw_offer(human, true);
+            if let Some(ref mut network) = *self.network.borrow_mut() {
+                network.send(&Message::AnswerDraw(true));
+            }
+            self.update_grid();
+            return;
+        }
+        if offer.is_some() || !self.confirm(tr("Offer your opponent a draw?"), tr("_Offer")) {
+            return;
+        }
+
+        if let Some(ref mut network) = *self.network.borrow_mut() {
+            if self.game.borrow_mut().board_mut().offer_draw(human) {
+                network.send(&Message::OfferDraw);
+            }
+        } else {
+            let declined = {
+                let mut game = self.game.borrow_mut();
+                let mut rng = self.rng.borrow_mut();
+                let mut driver = GameDriver::new(game.board_mut(), &mut *rng);
+                driver.set_engine(human.next_player(), true);
+                driver.offer_draw(human);
+                // Just the answer: it was the human's turn, so the engine
+                // has nothing else to do.
+                let mut declined = false;
+                for event in driver {
+                    match event {
+                        GameEvent::DrawOffered(..) => {}
+                        GameEvent::DrawDeclined(..) => declined = true,
+                        _ => break,
+                    }
+                }
+                declined
+            };
+            if declined {
+                self.inform(tr("The engine turns the draw down, it thinks it can win."));
+            }
+        }
+        self.update_grid();
+    }
+
+    /// Asks the user to confirm `question`, with `action` for the button
+    /// that does it.
+    fn confirm(&self, question: &str, action: &str) -> bool {
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.window),
+            gtk::DialogFlags::MODAL,
+            gtk::MessageType::Question,
+            gtk::ButtonsType::None,
+            question,
+        );
+        dialog.add_button(tr("_Cancel"), gtk::ResponseType::No.into());
+        dialog.add_button(action, gtk::ResponseType::Yes.into());
+        let response = dialog.run();
+        dialog.destroy();
+        let yes: i32 = gtk::ResponseType::Yes.into();
+        response == yes
+    }
+
+    /// Tells the user `message`, until they close it.
+    fn inform(&self, message: &str) {
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.window),
+            gtk::DialogFlags::MODAL,
+            gtk::MessageType::Info,
+            gtk::ButtonsType::Ok,
+            message,
+        );
+        dialog.run();
+        dialog.destroy();
+    }
+
+    /// Lets the user pick a puzzle, and starts a game from it.
+    fn pick_puzzle(&self) {
+        let human = self.human();
+        let puzzles = Puzzle::all().into_iter().map(|p| p.for_player(human)).collect::<Vec<_>>();
+
+        let dialog = gtk::Dialog::new_with_buttons(
+            Some(tr("Puzzles")),
+            Some(&self.window),
+            gtk::DialogFlags::MODAL,
+            &[(tr("_Cancel"), gtk::ResponseType::Cancel.into()),
+              (tr("_Play"), gtk::ResponseType::Ok.into())],
+        );
+
+        let list = gtk::ComboBoxText::new();
+        let solved = {
+            let progress = self.session.progress.borrow();
+            let theme = &self.settings.borrow().theme;
+            for puzzle in &puzzles {
+                let check = if progress.is_solved(puzzle) { "✓ " } else { "" };
+                list.append_text(&format!("{}{}: {}", check, puzzle.title, puzzle_goal(puzzle, theme)));
+            }
+            // The first one left to solve.
+            let next = puzzles.iter().position(|p| !progress.is_solved(p)).unwrap_or(0);
+            list.set_active(next as i32);
+            progress.solved()
+        };
+        let count = gtk::Label::new(Some(&*tr_format("Solved {} of {}.", &[&solved, &puzzles.len()])));
+        count.set_xalign(0.0);
+
+        let content = dialog.get_content_area();
+        content.pack_start(&list, /* expand = */ false, /* fill = */ true, 5);
+        content.pack_start(&count, /* expand = */ false, /* fill = */ true, 5);
+        dialog.show_all();
+
+        let response = dialog.run();
+        let index = list.get_active();
+        dialog.destroy();
+        let play: i32 = gtk::ResponseType::Ok.into();
+        if response != play || index < 0 {
+            return;
+        }
+        let puzzle = puzzles[index as usize].clone();
+
+        // Like `set_up_position`.
+        let variant = Variant::all().iter().position(|v| *v == Variant::TicTacToe).unwrap();
+        self.variant_input.set_active(variant as i32);
+        self.cancel_warm_up();
+        *self.game.borrow_mut() = AnyGame::TicTacToe(Game::from_state(puzzle.state.clone(), puzzle.player));
+        *self.puzzle.borrow_mut() = Some(puzzle);
+        self.watch_game();
+        self.update_grid();
+    }
+
+    /// Lets the user change the theme and their mark, and saves the settings
+    /// if they accept.
+    fn edit_settings(&self) {
+        let dialog = gtk::Dialog::new_with_buttons(
+            Some(tr("Settings")),
+            Some(&self.window),
+            gtk::DialogFlags::MODAL,
+            &[(tr("_Cancel"), gtk::ResponseType::Cancel.into()),
+              (tr("_OK"), gtk::ResponseType::Ok.into())],
+        );
+
+        let settings = self.settings.borrow().clone();
+        let theme = &settings.theme;
+        let human = gtk::ComboBoxText::new();
+        human.append_text(theme.glyph(CheckBox::X));
+        human.append_text(theme.glyph(CheckBox::O));
+        human.set_active(if settings.human == CheckBox::X { 0 } else { 1 });
+        let randomize = gtk::CheckButton::new();
+        randomize.set_active(settings.engine.randomize);
+        let no_center_opening = gtk::CheckButton::new();
+        no_center_opening.set_active(settings.engine.handicap.no_center_opening);
+        let sounds = gtk::CheckButton::new();
+        sounds.set_active(settings.sounds);
+        let trainer = gtk::CheckButton::new();
+        trainer.set_active(settings.trainer);
+        let eval_bar = gtk::CheckButton::new();
+        eval_bar.set_active(settings.eval_bar);
+        let clock_seconds = gtk::SpinButton::new_with_range(
+            0.0,
+            Settings::MAX_CLOCK_SECONDS as f64,
+            10.0,
+        );
+        clock_seconds.set_value(settings.clock_seconds as f64);
+        let clock_input = |seconds: u32| {
+            let input = gtk::SpinButton::new_with_range(0.0, Settings::MAX_CLOCK_SECONDS as f64, 1.0);
+            input.set_value(seconds as f64);
+            input
+        };
+        let clock_increment = clock_input(settings.clock_increment_seconds);
+        let engine_clock_seconds = clock_input(settings.engine_clock_seconds);
+        let engine_clock_increment = clock_input(settings.engine_clock_increment_seconds);
+        let pause_clock = gtk::CheckButton::new();
+        pause_clock.set_active(settings.pause_clock_when_away);
+        // It only means something with a clock.
+        pause_clock.set_sensitive(settings.clock_seconds > 0);
+        {
+            let pause_clock = pause_clock.clone();
+            clock_seconds.connect_changed(move |input| {
+                pause_clock.set_sensitive(input.get_value_as_int() > 0);
+            });
+        }
+        let timeout_policy = gtk::ComboBoxText::new();
+        for &policy in TimeoutPolicy::all().iter() {
+            timeout_policy.append_text(timeout_policy_label(policy));
+        }
+        let active = TimeoutPolicy::all().iter().position(|p| *p == settings.timeout_policy).unwrap();
+        timeout_policy.set_active(active as i32);
+        let x_glyph = gtk::Entry::new();
+        x_glyph.set_text(&theme.x_glyph);
+        let o_glyph = gtk::Entry::new();
+        o_glyph.set_text(&theme.o_glyph);
+        let font_size = gtk::SpinButton::new_with_range(
+            Theme::MIN_FONT_SIZE as f64,
+            Theme::MAX_FONT_SIZE as f64,
+            1.0,
+        );
+        font_size.set_value(theme.font_size as f64);
+        let colors = gtk::ComboBoxText::new();
+        for scheme in ColorScheme::all().iter() {
+            colors.append_text(scheme.name());
+        }
+        let active = ColorScheme::all().iter().position(|s| *s == theme.colors).unwrap();
+        colors.set_active(active as i32);
+        let warning_css = gtk::CssProvider::new();
+        warning_css.load_from_data(WARNING_CSS.as_bytes()).expect("Invalid warning CSS");
+        let depth_limit = settings.variant.depth_limit();
+        let engine_depth = depth_input(settings.engine.depth, depth_limit, &warning_css);
+        let hints_depth = depth_input(settings.hints.depth, depth_limit, &warning_css);
+
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(5);
+        grid.set_column_spacing(10);
+        let rows: [(&str, &gtk::Widget); 18] = [
+            (tr("Play as"), human.upcast_ref()),
+            (tr("Levels the engine searches (0 for the difficulty's)"), engine_depth.upcast_ref()),
+            (tr("Levels the hints search (0 for as deep as the game goes)"), hints_depth.upcast_ref()),
+            (tr("Vary the engine's moves"), randomize.upcast_ref()),
+            (tr("Never let the engine open in the center"), no_center_opening.upcast_ref()),
+            (tr("Play sounds"), sounds.upcast_ref()),
+            (tr("Warn about missed wins and blocks"), trainer.upcast_ref()),
+            (tr("Show how good the position is beside the board"), eval_bar.upcast_ref()),
+            (tr("Your seconds, from the next game (0 for no clock)"), clock_seconds.upcast_ref()),
+            (tr("Seconds you get after each move"), clock_increment.upcast_ref()),
+            (tr("The engine's seconds (0 for as many as yours)"), engine_clock_seconds.upcast_ref()),
+            (tr("Seconds the engine gets after each move"), engine_clock_increment.upcast_ref()),
+            (tr("Stop your clock while the window is in the background"), pause_clock.upcast_ref()),
+            (tr("When someone runs out of time"), timeout_policy.upcast_ref()),
+            (tr("X mark"), x_glyph.upcast_ref()),
+            (tr("O mark"), o_glyph.upcast_ref()),
+            (tr("Font size"), font_size.upcast_ref()),
+            (tr("Colors"), colors.upcast_ref()),
+        ];
+        for (i, &(name, widget)) in rows.iter().enumerate() {
+            let label = gtk::Label::new(Some(name));
+            label.set_xalign(0.0);
+            grid.attach(&label, 0, i as i32, 1, 1);
+            grid.attach(widget, 1, i as i32, 1, 1);
+        }
+        dialog.get_content_area().pack_start(&grid, /* expand = */ true, /* fill = */ true, 10);
+        dialog.show_all();
+
+        let response = dialog.run();
+        let accepted: i32 = gtk::ResponseType::Ok.into();
+        if response == accepted {
+            let mut settings = settings;
+            let restart = {
+                let human = if human.get_active() == 1 { CheckBox::O } else { CheckBox::X };
+                let changed = human != settings.human;
+                settings.human = human;
+                changed
+            };
+            // Depths deeper than the game goes are kept, the engine clamps
+            // them, so that they still apply to deeper variants.
+            let depth = |input: &gtk::SpinButton| match input.get_value_as_int() {
+                depth if depth > 0 => Some(depth as usize),
+                _ => None,
+            };
+            settings.engine.depth = depth(&engine_depth);
+            settings.hints.depth = depth(&hints_depth);
+            settings.engine.randomize = randomize.get_active();
+            settings.engine.handicap.no_center_opening = no_center_opening.get_active();
+            settings.sounds = sounds.get_active();
+            settings.trainer = trainer.get_active();
+            settings.eval_bar = eval_bar.get_active();
+            settings.clock_seconds = clock_seconds.get_value_as_int() as u32;
+            settings.clock_increment_seconds = clock_increment.get_value_as_int() as u32;
+            settings.engine_clock_seconds = engine_clock_seconds.get_value_as_int() as u32;
+            settings.engine_clock_increment_seconds = engine_clock_increment.get_value_as_int() as u32;
+            settings.pause_clock_when_away = pause_clock.get_active();
+            if let Some(ref mut clock) = *self.clock.borrow_mut() {
+                clock.set_pauses_when_away(self.human(), settings.pause_clock_when_away);
+            }
+            if let Some(&policy) = TimeoutPolicy::all().get(timeout_policy.get_active() as usize) {
+                settings.timeout_policy = policy;
+            }
+            let theme = &mut settings.theme;
+            // Empty glyphs would make the marks invisible, keep the old ones.
+            let glyph = |entry: &gtk::Entry| entry.get_text().filter(|t| !t.is_empty());
+            if let Some(glyph) = glyph(&x_glyph) {
+                theme.x_glyph = glyph;
+            }
+            if let Some(glyph) = glyph(&o_glyph) {
+                theme.o_glyph = glyph;
+            }
+            theme.font_size = font_size.get_value_as_int() as u32;
+            if let Some(scheme) = colors.get_active_text().and_then(|n| ColorScheme::from_name(&n)) {
+                theme.colors = scheme;
+            }
+            *self.settings.borrow_mut() = settings;
+            self.configure_players();
+            // Who moves first goes with the mark, so a new mark needs a new
+            // game.
+            if restart && self.network.borrow().is_none() {
+                self.restart_button.clicked();
+            }
+            self.apply_theme();
+            self.apply_effects();
+            self.update_grid();
+            self.save_config();
+        }
+        dialog.destroy();
+    }
+
+    /// Shows a bug report of the current game and settings, to copy into an
+    /// issue.
+    fn report_problem(&self) {
+        let report = {
+            let mut config = self.session.config.borrow().clone();
+            self.settings.borrow().write_to(&mut config);
+            report::bug_report(&self.game.borrow(), self.seed.get(), &config)
+        };
+
+        let dialog = gtk::Dialog::new_with_buttons(
+            Some(tr("Report a problem")),
+            Some(&self.window),
+            gtk::DialogFlags::MODAL,
+            &[(tr("_Close"), gtk::ResponseType::Close.into())],
+        );
+        let hint = gtk::Label::new(Some(tr("Please paste this in the issue describing the problem:")));
+        hint.set_xalign(0.0);
+        let text = gtk::TextView::new();
+        text.set_editable(false);
+        text.set_monospace(true);
+        let buffer = text.get_buffer().expect("Text views should have a buffer");
+        buffer.set_text(&report);
+        buffer.select_range(&buffer.get_start_iter(), &buffer.get_end_iter());
+        let scrolled = gtk::ScrolledWindow::new(None, None);
+        scrolled.set_size_request(450, 300);
+        scrolled.add(&text);
+
+        let content = dialog.get_content_area();
+        content.pack_start(&hint, /* expand = */ false, /* fill = */ true, 5);
+        content.pack_start(&scrolled, /* expand = */ true, /* fill = */ true, 5);
+        dialog.show_all();
+        dialog.run();
+        dialog.destroy();
+    }
+
+    /// Restyles the grid after the theme changes.
+    fn apply_theme(&self) {
+        let css = self.settings.borrow().theme.css();
+        self.css.load_from_data(css.as_bytes()).expect("Invalid grid CSS");
+        // The font size of the theme is the smallest the marks can get.
+        let cell_size = self.cell_size.replace(0);
+        if cell_size > 0 {
+            self.scale_marks(cell_size);
+        }
+    }
+
+    /// Makes the marks fit in squares of `cell_size` pixels.
+    fn scale_marks(&self, cell_size: i32) {
+        if self.cell_size.replace(cell_size) == cell_size {
+            return;
+        }
+        // Points are 4/3 of a pixel at the usual 96 DPI.
+        let min = self.settings.borrow().theme.font_size as f64 * 4.0 / 3.0;
+        let size = (cell_size as f64 * MARK_SCALE).max(min).round();
+        let css = format!("button {{ font-size: {}px; }}\nbutton.placed {{ font-size: {}px; }}\n",
+                          size, (size / 2.0).round());
+        self.scale_css.load_from_data(css.as_bytes()).expect("Invalid scale CSS");
+    }
+
+    /// Picks the effects after the settings change.
+    fn apply_effects(&self) {
+        let mut effects: Vec<Box<dyn Effects>> = vec![Box::new(Animations { grid: self.grid.clone() })];
+        if self.settings.borrow().sounds {
+            effects.push(Box::new(Sounds));
+        }
+        *self.effects.borrow_mut() = effects;
+    }
+
+    /// Plays the effects of what happened on the board since it was last
+    /// shown.
+    fn play_effects(&self, frame: Option<Frame>) {
+        let cues = match (self.last_frame.borrow().as_ref(), frame.as_ref()) {
+            (before, Some(after)) => effects::cues(before, after),
+            (_, None) => vec![],
+        };
+        *self.last_frame.borrow_mut() = frame;
+        for cue in &cues {
+            for effects in self.effects.borrow().iter() {
+                effects.play(cue);
+            }
+        }
+    }
+
+    /// Saves the settings to the configuration file, if there's one.
+    fn save_config(&self) {
+        if let Err(err) = self.session.save_config(&self.settings.borrow()) {
+            self.status_label.set_text(&tr_format("Couldn't save the settings: {}", &[&err]));
+        }
+    }
+
+    /// Shows the position after the first `ply` moves, going back to the
+    /// current one if `ply` is the number of moves played.
+    fn show_ply(&self, ply: usize) {
+        let plies = self.game.borrow().board().plies();
+        self.replay.set(if ply < plies { Some(ply) } else { None });
+        self.update_grid();
+    }
+
+    /// Sends the current position to the observers, if any.
+    fn notify_observer(&self) {
+        if let Some(ref mut observer) = *self.observer.borrow_mut() {
+            let game = self.game.borrow();
+            let board = game.board();
+            let plies = board.plies();
+            observer.update(Snapshot::new(game.variant(), &*board.position(plies), plies, board.result()));
+        }
+    }
+
+    /// Makes the current game update the scoreboard when it's over and play
+    /// with a fresh seed, stops replaying or thinking about the previous one,
+    /// and tells the observers about it.
+    fn watch_game(&self) {
+        self.stop_thinking();
+        self.replay.set(None);
+        self.learned.set(false);
+        self.reseed(rand::random());
+        self.configure_players();
+        self.game.borrow_mut().board_mut().set_node_budget(self.session.node_budget);
+        if let Some(ref mut observer) = *self.observer.borrow_mut() {
+            observer.reset();
+        }
+        // Both sides of a network game would need to agree on the time, so
+        // only games against the engine have a clock.
+        let controls = match self.settings.borrow().time_controls() {
+            _ if self.network.borrow().is_some() => None,
+            Some((human, engine)) if self.human() == CheckBox::X => Some([human, engine]),
+            Some((human, engine)) => Some([engine, human]),
+            None => None,
+        };
+        let policy = self.settings.borrow().timeout_policy;
+        self.start_clock(controls, policy);
+        let scoreboard = self.session.scoreboard.clone();
+        let results = self.results.clone();
+        let human = self.human();
+        self.game.borrow_mut().board_mut().set_game_over_hook(Box::new(move |result| {
+            scoreboard.borrow_mut().record(result, human);
+            results.borrow_mut().record(result, human);
+        }));
+    }
+
+    /// Plays the game with the time of `controls` for X and O, in that order,
+    /// or without a clock, and with `policy` for whoever runs out of it.
+    ///
+    /// The time of the human stops while they're away if the settings say
+    /// so.
+    fn start_clock(&self, controls: Option<[TimeControl; 2]>, policy: TimeoutPolicy) {
+        {
+            let mut game = self.game.borrow_mut();
+            let board = game.board_mut();
+            board.set_time_controls(controls);
+            board.set_timeout_policy(policy);
+        }
+        let pauses = self.settings.borrow().pause_clock_when_away;
+        *self.clock.borrow_mut() = controls.map(|controls| {
+            let mut clock = Clock::with_time_controls(controls);
+            clock.set_pauses_when_away(self.human(), pauses);
+            clock
+        });
+        self.clock_label.set_visible(controls.is_some());
+    }
+
+    /// Makes the engine play against the human, and hint the moves of the
+    /// human, like the settings say.
+    fn configure_players(&self) {
+        let human = self.human();
+        let settings = self.settings.borrow();
+        let mut game = self.game.borrow_mut();
+        let board = game.board_mut();
+        board.set_opponent_model(&self.session.opponent.borrow());
+        for &(player, config) in &[(human, &settings.hints), (human.next_player(), &settings.engine)] {
+            if let Err(err) = board.set_player_config(player, config.clone()) {
+                // Like the positional evaluator in a game other than
+                // tic-tac-toe.
+                eprintln!("{}, using the default one", err);
+                let config = PlayerConfig { evaluator: "default".to_owned(), ..config.clone() };
+                board.set_player_config(player, config).expect("Every game has the default evaluator");
+            }
+        }
+    }
+
+    /// Makes the engine play with random numbers from `seed` from now on.
+    fn reseed(&self, seed: u64) {
+        self.seed.set(seed);
+        *self.rng.borrow_mut() = StdRng::seed_from_u64(seed);
+        self.game.borrow_mut().board_mut().set_seed(seed);
+    }
+
+    /// The mark the human plays with.
+    fn human(&self) -> CheckBox {
+        self.network_mark.get().unwrap_or(self.settings.borrow().human)
+    }
+
+    /// The mark of whoever moves first in new games: the host in network
+    /// games, and otherwise the human, unless the engine was asked to.
+    fn first_player(&self) -> CheckBox {
+        match (self.network_mark.get(), &*self.network.borrow()) {
+            (Some(mark), Some(network)) if !network.is_host() => mark.next_player(),
+            (_, &Some(..)) => self.human(),
+            _ if self.engine_first => self.human().next_player(),
+            _ => self.human(),
+        }
+    }
+
+    /// Lets the engine think of its move on another thread if it's its turn,
+    /// and plays it once it's done, until it's the human's turn again.
+    ///
+    /// Then tells the user how the move they played does for the puzzle of
+    /// `puzzle`, if any, like `judge_puzzle_move`.
+    fn let_engine_move(app: &Rc<Self>, puzzle: Option<(Puzzle, Verdict)>) {
+        app.stop_thinking();
+        let job = {
+            let mut game = app.game.borrow_mut();
+            let board = game.board_mut();
+            if app.network.borrow().is_some() || app.replay.get().is_some() ||
+               board.result() != GameResult::InProgress || board.player() == app.human() {
+                None
+            } else {
+                // Within the time it has left.
+                if let Some(ref clock) = *app.clock.borrow() {
+                    let time = clock.move_time(board.player(), Instant::now()).min(AUTO_MOVE_TIME);
+                    let budget = app.session.node_budget as f64 * bench::seconds(time) / bench::seconds(AUTO_MOVE_TIME);
+                    board.set_node_budget((budget as usize).max(1));
+                }
+                let rng = StdRng::from_rng(&mut *app.rng.borrow_mut())
+                    .expect("Seeding from another generator shouldn't fail");
+                let cancel = CancelToken::new();
+                *app.engine_cancel.borrow_mut() = Some(cancel.clone());
+                Some((cancel.clone(), board.think(Box::new(rng), cancel)))
+            }
+        };
+
+        let (cancel, job) = match job {
+            Some(job) => job,
+            None => {
+                if let Some((puzzle, verdict)) = puzzle {
+                    app.judge_puzzle_move(&puzzle, verdict);
+                }
+                return;
+            }
+        };
+        let app_clone = app.clone();
+        run_in_background(cancel, job, move |reply| {
+            app_clone.engine_cancel.borrow_mut().take();
+            let moved = app_clone.game.borrow_mut().board_mut().finish_thinking(reply);
+            if moved {
+                App::let_engine_move(&app_clone, puzzle);
+            } else if let Some((puzzle, verdict)) = puzzle {
+                app_clone.judge_puzzle_move(&puzzle, verdict);
+            }
+            app_clone.update_grid();
+        });
+    }
+
+    /// Stops the search of the move of the engine, if it's thinking of one.
+    fn stop_thinking(&self) {
+        if let Some(cancel) = self.engine_cancel.borrow_mut().take() {
+            cancel.cancel();
+        }
+    }
+
+    /// Whether the engine is thinking of its move.
+    fn thinking(&self) -> bool {
+        self.engine_cancel.borrow().is_some()
+    }
+
+    fn variant(&self) -> Variant {
+        let all = Variant::all();
+        let active = self.variant_input.get_active();
+        if active < 0 {
+            return all[0];
+        }
+        all[active as usize]
+    }
+
+    fn difficulty(&self) -> Difficulty {
+        let all = Difficulty::all();
+        let active = self.difficulty_input.get_active();
+        if active < 0 {
+            return all[all.len() - 1];
+        }
+        all[active as usize]
+    }
+
+    /// Shows the score of every move of the player to move in the analysis
+    /// pane, if it's expanded, once a search on another thread finds them.
+    fn update_analysis(&self) {
+        // Searching every move is slow, don't bother unless it's shown.
+        if !self.analysis.get_expanded() {
+            return;
+        }
+
+        let cancel = self.position_cancel.borrow().clone();
+        let game = self.game.borrow();
+        let board = game.board();
+        let job = board.analyze(cancel.clone());
+        let maximizing = board.player() as i8 > 0;
+        let (cols, rows) = board.grid_size();
+        let marks = (0..rows).map(|y| (0..cols).map(|x| board.mark(x, y)).collect())
+            .collect::<Vec<Vec<_>>>();
+        let theme = self.settings.borrow().theme.clone();
+        let label = self.analysis_label.clone();
+        label.set_text(tr("Analyzing…"));
+        run_in_background(cancel, job, move |analysis| {
+            let analysis = match analysis {
+                Some(analysis) => analysis,
+                None => return,
+            };
+            if analysis.square_scores.is_empty() {
+                label.set_text(tr("The game is over."));
+                return;
+            }
+            let text = analysis_text(&analysis, &marks, maximizing, &theme);
+            label.set_markup(&format!("<tt>{}</tt>", text));
+        });
+    }
+
+    /// Shows the score of the current position in the evaluation bar, if
+    /// the settings show it, searching as deep as the hints do on another
+    /// thread.
+    fn update_eval_bar(&self) {
+        let settings = self.settings.borrow();
+        if !settings.eval_bar {
+            self.eval_bar.container.hide();
+            return;
+        }
+        let cancel = self.position_cancel.borrow().clone();
+        let job = self.game.borrow().board().evaluation_job(self.human(), cancel.clone());
+        let eval_bar = self.eval_bar.clone();
+        let theme = settings.theme.clone();
+        run_in_background(cancel, job, move |score| {
+            eval_bar.show_score(score, &theme);
+        });
+        self.eval_bar.container.show();
+    }
+
+    /// Tells the opponent model how the human played once a game against
+    /// the adaptive engine is over, so that the next one adapts to it.
+    ///
+    /// Grading the moves of the human searches, so it's done on another
+    /// thread.
+    fn learn_from_game(&self) {
+        if self.learned.get() || self.network.borrow().is_some() {
+            return;
+        }
+        if self.settings.borrow().engine.difficulty != Difficulty::Adaptive {
+            return;
+        }
+        let job = {
+            let game = self.game.borrow();
+            let board = game.board();
+            if board.result() == GameResult::InProgress {
+                return;
+            }
+            board.summary_job(self.human())
+        };
+        self.learned.set(true);
+        let session = self.session.clone();
+        let settings = self.settings.borrow().clone();
+        let label = self.status_label.clone();
+        // Even if the game changes meanwhile.
+        run_in_background(CancelToken::new(), job, move |summary| {
+            session.opponent.borrow_mut().record(summary);
+            if let Err(err) = session.save_config(&settings) {
+                label.set_text(&tr_format("Couldn't save the settings: {}", &[&err]));
+            }
+        });
+    }
+
+    fn update_grid(&self) {
+        // The searches about the position shown are useless now.
+        self.position_cancel.replace(CancelToken::new()).cancel();
+        self.learn_from_game();
+        self.update_analysis();
+        self.update_eval_bar();
+        let puzzle = self.current_puzzle();
+
+        let game = self.game.borrow();
+        let board = game.board();
+        // Whatever changed the board may have changed who's thinking.
+        self.run_clock(board);
+        let plies = board.plies();
+        let position = board.position(self.replay.get().unwrap_or(plies));
+        let (cols, rows) = position.grid_size();
+        let winning_squares = position.winning_squares();
+        let in_progress = self.replay.get().is_none() && board.result() == GameResult::InProgress;
+
+        // Only point out the playable squares when the rules restrict them.
+        let restricted = in_progress && (0..cols).any(|x| (0..rows).any(|y| {
+            board.mark(x, y) == CheckBox::Empty && !board.is_playable(x, y)
+        }));
+
+        let settings = self.settings.borrow();
+        let theme = &settings.theme;
+        let grid = self.grid.borrow();
+        for x in 0..cols {
+            for y in 0..rows {
+                let button = grid.get_child_at(x as i32, y as i32)
+                    .expect("Grid should match the board size")
+                    .downcast::<gtk::Button>()
+                    .expect("No button? Pshaw!");
+                let glyph = theme.glyph(position.mark(x, y));
+                let label = match position.square_label(x, y) {
+                    Some(label) if position.mark(x, y) == CheckBox::Empty => label,
+                    Some(label) => format!("{} ({})", label, glyph),
+                    None => glyph.to_owned(),
+                };
+                button.set_label(&label);
+
+                // Like "row 2 column 3, empty".
+                let mut name = tr_format("row {} column {}", &[&(y + 1), &(x + 1)]);
+                if let Some(label) = position.square_label(x, y) {
+                    name.push_str(&format!(", {}", label));
+                }
+                name.push_str(match position.mark(x, y) {
+                    CheckBox::Empty => tr(", empty"),
+                    CheckBox::X => ", X",
+                    CheckBox::O => ", O",
+                });
+                if winning_squares.contains(&(x, y)) {
+                    name.push_str(tr(", winning"));
+                }
+                set_accessible_name(button.upcast_ref(), &name);
+
+                let style = button.get_style_context()
+                    .expect("Buttons should have a style context");
+                if winning_squares.contains(&(x, y)) {
+                    style.add_class("winning");
+                } else {
+                    style.remove_class("winning");
+                }
+                if restricted && board.is_playable(x, y) {
+                    style.add_class("playable");
+                } else {
+                    style.remove_class("playable");
+                }
+            }
+        }
+
+        // Replays only go over what already happened.
+        let frame = match self.replay.get() {
+            Some(..) => None,
+            None => Some(Frame::new(&*position, board.result())),
+        };
+        self.play_effects(frame);
+
+        match position.mirror() {
+            Some(text) => {
+                self.mirror_label.set_markup(&format!("<tt>{}</tt>", text));
+                self.mirror_label.show();
+            }
+            None => self.mirror_label.hide(),
+        }
+
+        let network = self.network.borrow();
+        if let Some(ref network) = *network {
+            self.network_label.set_text(&network.status());
+        }
+        let waiting = network.is_some() && board.player() != self.human();
+        let mut status = match (self.replay.get(), board.result()) {
+            (Some(ply), _) => tr_format("Replaying move {} of {}.", &[&ply, &plies]),
+            (None, GameResult::InProgress) if waiting => tr("Their turn.").to_owned(),
+            (None, GameResult::InProgress) if self.thinking() => tr("The engine is thinking…").to_owned(),
+            (None, GameResult::XWins) => tr_format("{} wins!", &[&theme.glyph(CheckBox::X)]),
+            (None, GameResult::OWins) => tr_format("{} wins!", &[&theme.glyph(CheckBox::O)]),
+            (None, GameResult::Draw) => tr("It's a draw.").to_owned(),
+            (None, GameResult::TimeForfeit(loser)) => {
+                tr_format("{} ran out of time.", &[&theme.glyph(loser)])
+            }
+            (None, GameResult::Resigned(loser)) => tr_format("{} resigned.", &[&theme.glyph(loser)]),
+            (None, GameResult::DrawAgreed) => tr("It's a draw by agreement.").to_owned(),
+            (None, GameResult::InProgress) if self.explore_button.get_active() => {
+                tr("Exploring: click a square to count how the games after it end.").to_owned()
+            }
+            (None, GameResult::InProgress) => match puzzle {
+                Some(ref puzzle) => tr_format("Puzzle: {}.", &[&puzzle_goal(puzzle, theme)]),
+                None => tr("Your turn.").to_owned(),
+            },
+        };
+        // To tell whether a replay of the game matches it.
+        if self.replay.get().is_none() && board.result() != GameResult::InProgress {
+            status.push_str(&tr_format(" Record {}.", &[&board.record_hash()]));
+        }
+        if self.warming_up.get() && board.result() == GameResult::InProgress {
+            status.push_str(tr(" Warming up the engine…"));
+        }
+        let offer = board.draw_offer();
+        if self.replay.get().is_none() {
+            if offer == Some(self.human()) {
+                status.push_str(tr(" You offered a draw."));
+            } else if offer.is_some() {
+                status.push_str(tr(" Your opponent offers a draw."));
+            }
+        }
+        let playing = board.result() == GameResult::InProgress;
+        self.resign_button.set_sensitive(playing);
+        self.draw_button.set_sensitive(playing && offer != Some(self.human()));
+        self.draw_button.set_label(if offer.is_some() && offer != Some(self.human()) {
+            tr("Accept draw")
+        } else {
+            tr("Offer draw")
+        });
+        self.status_label.set_text(&status);
+
+        // Like "1. X b2, O a1 2. X c3", with the move that led to the shown
+        // position in bold.
+        let shown = self.replay.get().unwrap_or(plies);
+        let mut moves = String::new();
+        for (i, (mark, name)) in board.move_names().into_iter().enumerate() {
+            if i % 2 == 0 {
+                if i != 0 {
+                    moves.push(' ');
+                }
+                moves.push_str(&format!("{}. ", i / 2 + 1));
+            } else {
+                moves.push_str(", ");
+            }
+            let text = escape_markup(&format!("{} {}", theme.glyph(mark), name));
+            let text = if i + 1 == shown { format!("<b>{}</b>", text) } else { text };
+            moves.push_str(&format!("<a href=\"{}\">{}</a>", i + 1, text));
+        }
+        self.moves_label.set_markup(&moves);
+        self.back_button.set_sensitive(shown > 0);
+        self.forward_button.set_sensitive(shown < plies);
+
+        self.notify_observer();
+
+        let scoreboard = self.session.scoreboard.borrow();
+        self.scoreboard_label.set_text(&format!(
+            "This window: {}.\nAll time: {}.", self.results.borrow(), scoreboard.all_time));
+    }
+
+    /// Replaces the grid with one that fits the current board, with the
+    /// sub-grids of the board spaced apart.
+    fn rebuild_grid(app: &Rc<Self>) {
+        let (cols, rows, block_size) = {
+            let game = app.game.borrow();
+            let board = game.board();
+            let (cols, rows) = board.grid_size();
+            (cols, rows, board.block_size())
+        };
+
+        let grid = gtk::Grid::new();
+        // Every square the same size, as big as the window allows.
+        grid.set_row_homogeneous(true);
+        grid.set_column_homogeneous(true);
+        for x in 0..cols {
+            for y in 0..rows {
+                let button = gtk::Button::new();
+                button.set_hexpand(true);
+                button.set_vexpand(true);
+                let style = button.get_style_context()
+                    .expect("Buttons should have a style context");
+                style.add_provider(&app.css, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+                style.add_provider(&app.scale_css, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION + 1);
+                if let Some(block_size) = block_size {
+                    if x != 0 && x % block_size == 0 {
+                        button.set_margin_start(BLOCK_SPACING);
+                    }
+                    if y != 0 && y % block_size == 0 {
+                        button.set_margin_top(BLOCK_SPACING);
+                    }
+                }
+
+                let app_clone = app.clone();
+                button.connect_clicked(move |_| {
+                    within_budget("move", || App::handle_click(&app_clone, x, y, /* other_mark = */ false));
+                });
+                // Right clicks place the other mark in wild tic-tac-toe.
+                let app_clone = app.clone();
+                button.connect_button_press_event(move |_, event| {
+                    if event.get_button() != 3 {
+                        return gtk::Inhibit(false);
+                    }
+                    within_budget("move", || App::handle_click(&app_clone, x, y, /* other_mark = */ true));
+                    gtk::Inhibit(true)
+                });
+                grid.attach(&button, x as i32, y as i32, 1, 1);
+            }
+        }
+
+        let old_grid = app.grid.replace(grid);
+        app.grid_frame.remove(&old_grid);
+        let grid = app.grid.borrow();
+        app.grid_frame.add(&*grid);
+        app.grid_frame.set_property_ratio(cols as f32 / rows as f32);
+        app.grid_size.set((cols, rows));
+        grid.show_all();
+        // So that the arrow keys move around the grid right away.
+        if let Some(first) = grid.get_child_at(0, 0) {
+            first.grab_focus();
+        }
+    }
+}
+
+/// Runs the GUI with `app_config` until all its windows are closed,
+/// streaming the games of the first one to `observer` and playing them over
+/// `network`, if given.
+pub fn run(app_config: AppConfig, observer: Option<Box<dyn Sink>>, network: Option<Network>) {
+    gtk::init().expect("Failed to initialize GTK.");
+    // Not unique, so that every run plays its own games, like the ones over
+    // the network.
+    let application = gtk::Application::new(None, ApplicationFlags::NON_UNIQUE)
+        .expect("Failed to initialize GTK.");
+
+    let AppConfig { config, config_path, settings, engine_first, .. } = app_config;
+    let node_budget = (bench::nodes_per_second() * bench::seconds(AUTO_MOVE_TIME)) as usize;
+
+    // The engine log shows what the engine decided even when the
+    // environment doesn't ask to log it.
+    if env::var_os(log::ENV_VAR).is_none() {
+        log::set_filter(Filter::everything(Level::Debug));
+    }
+    log::keep_recent();
+    let (log_sender, log_events) = mpsc::channel();
+    log::add_sink(Box::new(move |event| {
+        let _ = log_sender.send(event.to_string());
+    }));
+
+    let session = Rc::new(Session {
+        application: application.clone(),
+        scoreboard: Rc::new(RefCell::new(Scoreboard::from_config(&config))),
+        progress: RefCell::new(Progress::from_config(&config)),
+        opponent: RefCell::new(OpponentModel::from_config(&config)),
+        config: RefCell::new(config),
+        config_path,
+        node_budget,
+        log_events,
+        windows: RefCell::new(vec![]),
+    });
+
+    {
+        let session = session.clone();
+        gtk::timeout_add(LOG_POLL_MS, move || {
+            session.tail_log();
+            gtk::Continue(true)
+        });
+    }
+
+    // Only the first window streams and plays over the network.
+    let first_window = RefCell::new(Some((settings, observer, network)));
+    application.connect_activate(move |_| {
+        if let Some((settings, observer, network)) = first_window.borrow_mut().take() {
+            App::open(&session, settings, engine_first, observer, network);
+        }
+    });
+    // The arguments are for us, not for GTK.
+    application.run(&[]);
+}
+
+/// Shows the game `spectator` watches until its window is closed, without
+/// letting the user play.
+pub fn spectate(spectator: Spectator) {
+    gtk::init().expect("Failed to initialize GTK.");
+
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title(&tr_format("Spectating {}", &[&spectator.address()]));
+    window.set_default_size(420, 460);
+    window.connect_delete_event(|_, _| {
+        gtk::main_quit();
+        gtk::Inhibit(false)
+    });
+
+    let theme = config::default_path()
+        .and_then(|path| Config::load(&path).ok())
+        .map(|config| Theme::from_config(&config))
+        .unwrap_or_default();
+    let box_ = gtk::Box::new(gtk::Orientation::Vertical, 5);
+    let grid = gtk::Grid::new();
+    grid.set_row_homogeneous(true);
+    grid.set_column_homogeneous(true);
+    box_.pack_start(&grid, /* expand = */ true, /* fill = */ true, 0);
+    let status = gtk::Label::new(Some(tr("Waiting for the game…")));
+    box_.pack_start(&status, /* expand = */ false, /* fill = */ true, 5);
+    window.add(&box_);
+    window.show_all();
+
+    let spectator = RefCell::new(spectator);
+    let shown = RefCell::new(None::<Snapshot>);
+    gtk::timeout_add(OBSERVER_POLL_MS, move || {
+        let mut spectator = spectator.borrow_mut();
+        match spectator.poll() {
+            Ok(true) => {}
+            Ok(false) => return gtk::Continue(true),
+            Err(err) => {
+                status.set_text(&tr_format("Disconnected: {}", &[&err]));
+                return gtk::Continue(false);
+            }
+        }
+        let snapshot = match spectator.snapshot() {
+            Some(snapshot) => snapshot.clone(),
+            None => return gtk::Continue(true),
+        };
+
+        let (cols, rows) = snapshot.grid_size();
+        let resized = shown.borrow().as_ref().map(|s| s.grid_size()) != Some((cols, rows));
+        if resized {
+            for child in grid.get_children() {
+                grid.remove(&child);
+            }
+            for x in 0..cols {
+                for y in 0..rows {
+                    grid.attach(&gtk::Label::new(None), x as i32, y as i32, 1, 1);
+                }
+            }
+            grid.show_all();
+        }
+        for x in 0..cols {
+            for y in 0..rows {
+                if let Some(label) = grid.get_child_at(x as i32, y as i32)
+                    .and_then(|child| child.downcast::<gtk::Label>().ok())
+                {
+                    label.set_text(theme.glyph(snapshot.mark(x, y)));
+                }
+            }
+        }
+        status.set_text(&match snapshot.result() {
+            GameResult::InProgress => tr_format("{}, move {}.", &[&snapshot.variant().name(), &(snapshot.plies() + 1)]),
+            GameResult::XWins => tr_format("{} wins!", &[&theme.glyph(CheckBox::X)]),
+            GameResult::OWins => tr_format("{} wins!", &[&theme.glyph(CheckBox::O)]),
+            GameResult::Draw => tr("It's a draw.").to_owned(),
+            GameResult::TimeForfeit(player) => tr_format("{} ran out of time.", &[&theme.glyph(player)]),
+            GameResult::Resigned(player) => tr_format("{} resigned.", &[&theme.glyph(player)]),
+            GameResult::DrawAgreed => tr("It's a draw by agreement.").to_owned(),
+        });
+        *shown.borrow_mut() = Some(snapshot);
+        gtk::Continue(true)
+    });
+
+    gtk::main();
+}
@@ -0,0 +1,210 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Translations of the strings of the interface.
+//!
+//! The translations are gettext catalogs in `po/`, built into the binary,
+//! one per language, like `po/es.po`. Strings without a translation are
+//! shown in English. To translate a new string, wrap it in `tr`, or in
+//! `tr_format` if it has `{}` placeholders, and add it to the catalogs.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// The catalogs there are, by language.
+const CATALOGS: [(&str, &str); 1] = [
+    ("es", include_str!("../po/es.po")),
+];
+
+/// The translations of a language, from the English strings.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+/// Reads the contents of the quoted string `line` starts with.
+fn parse_quoted(line: &str) -> Option<String> {
+    let line = line.trim();
+    if !line.starts_with('"') || !line.ends_with('"') || line.len() < 2 {
+        return None;
+    }
+    let mut ret = String::new();
+    let mut chars = line[1..line.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            ret.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => ret.push('\n'),
+            't' => ret.push('\t'),
+            c => ret.push(c),
+        }
+    }
+    Some(ret)
+}
+
+impl Catalog {
+    /// Parses a gettext catalog, of which only `msgid` and `msgstr` entries
+    /// with no plurals nor contexts are supported.
+    pub fn parse(po: &str) -> Result<Self, String> {
+        let mut messages = HashMap::new();
+        // The entry being read, and whether its translation is.
+        let mut id = None;
+        let mut text: Option<String> = None;
+        for (number, line) in po.lines().enumerate() {
+            let line = line.trim();
+            let error = || format!("line {}: expected a string: {}", number + 1, line);
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(quoted) = line.strip_prefix("msgid ") {
+                if let (Some(id), Some(text)) = (id.take(), text.take()) {
+                    messages.insert(id, text);
+                }
+                id = Some(parse_quoted(quoted).ok_or_else(error)?);
+            } else if let Some(quoted) = line.strip_prefix("msgstr ") {
+                if id.is_none() || text.is_some() {
+                    return Err(format!("line {}: msgstr without a msgid", number + 1));
+                }
+                text = Some(parse_quoted(quoted).ok_or_else(error)?);
+            } else {
+                // A string continues the last msgid or msgstr.
+                let more = parse_quoted(line).ok_or_else(error)?;
+                match (text.as_mut(), id.as_mut()) {
+                    (Some(text), _) => text.push_str(&more),
+                    (None, Some(id)) => id.push_str(&more),
+                    (None, None) => return Err(error()),
+                }
+            }
+        }
+        if let (Some(id), Some(text)) = (id, text) {
+            messages.insert(id, text);
+        }
+        // The header, and the strings nobody translated yet.
+        messages.retain(|id, text| !id.is_empty() && !text.is_empty());
+        Ok(Self {
+            messages,
+        })
+    }
+
+    /// Returns the catalog built in for `locale`, like `es_ES.UTF-8`, if
+    /// any.
+    pub fn for_locale(locale: &str) -> Option<Self> {
+        // Without the encoding nor the modifier, and then without the
+        // country.
+        let locale = locale.split(['.', '@']).next().unwrap_or("");
+        let language = locale.split('_').next().unwrap_or("");
+        let po = CATALOGS.iter()
+            .find(|&&(name, _)| name == locale)
+            .or_else(|| CATALOGS.iter().find(|&&(name, _)| name == language))?
+            .1;
+        Some(Self::parse(po).expect("Built-in catalogs should parse"))
+    }
+
+    /// Returns the translation of `msgid`, or `msgid` if there's none.
+    pub fn get<'a>(&'a self, msgid: &'a str) -> &'a str {
+        self.messages.get(msgid).map_or(msgid, |text| text.as_str())
+    }
+}
+
+/// Returns the locale of the user, from the environment like gettext does.
+pub fn locale_from_env() -> Option<String> {
+    for var in &["LANGUAGE", "LC_ALL", "LC_MESSAGES", "LANG"] {
+        let value = match env::var(var) {
+            Ok(value) => value,
+            Err(..) => continue,
+        };
+        // `LANGUAGE` is a list of preferences.
+        let locale = value.split(':').next().unwrap_or("").trim().to_owned();
+        match &*locale {
+            "" => continue,
+            // The default locale, which is English.
+            "C" | "POSIX" => return None,
+            _ => return Some(locale),
+        }
+    }
+    None
+}
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Translates the interface to `locale` from now on, or leaves it in
+/// English if there's no catalog for it.
+///
+/// Only the first call has any effect, the interface doesn't change its
+/// language once it has shown some string.
+pub fn init(locale: Option<&str>) {
+    let catalog = locale.and_then(Catalog::for_locale).unwrap_or_default();
+    let _ = CATALOG.set(catalog);
+}
+
+/// Returns the translation of `msgid` to the language of the interface.
+pub fn tr(msgid: &'static str) -> &'static str {
+    match CATALOG.get() {
+        Some(catalog) => catalog.get(msgid),
+        None => msgid,
+    }
+}
+
+/// Like `tr`, replacing each `{}` of the translation with the next of
+/// `args`.
+pub fn tr_format(msgid: &'static str, args: &[&dyn fmt::Display]) -> String {
+    let mut args = args.iter();
+    let mut parts = tr(msgid).split("{}");
+    let mut ret = parts.next().unwrap_or("").to_owned();
+    for part in parts {
+        if let Some(arg) = args.next() {
+            ret.push_str(&arg.to_string());
+        }
+        ret.push_str(part);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_with_the_catalogs() {
+        let po = "# A comment.\nmsgid \"\"\nmsgstr \"Content-Type: text/plain; charset=UTF-8\\n\"\n\n\
+                  msgid \"Undo\"\nmsgstr \"Deshacer\"\n\n\
+                  msgid \"{} wins!\"\nmsgstr \"\"\n\"¡Gana \"\n\"{}!\"\n\n\
+                  msgid \"Untranslated\"\nmsgstr \"\"\n";
+        let catalog = Catalog::parse(po).unwrap();
+        assert_eq!(catalog.get("Undo"), "Deshacer");
+        assert_eq!(catalog.get("{} wins!"), "¡Gana {}!");
+        assert_eq!(catalog.get("Untranslated"), "Untranslated");
+        assert_eq!(catalog.get(""), "");
+        assert!(Catalog::parse("msgstr \"Deshacer\"\n").is_err());
+        assert!(Catalog::parse("msgid Undo\n").is_err());
+
+        let spanish = Catalog::for_locale("es_ES.UTF-8").unwrap();
+        assert_eq!(spanish.get("Undo"), "Deshacer");
+        assert!(Catalog::for_locale("xx_XX").is_none());
+
+        for &(language, _) in &CATALOGS {
+            assert!(Catalog::for_locale(language).is_some());
+        }
+
+        // Before `init`, and in the tests, everything is in English.
+        assert_eq!(tr_format("Replaying move {} of {}.", &[&1, &5]), "Replaying move 1 of 5.");
+    }
+}
@@ -0,0 +1,101 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The engine behind the game, to play tic-tac-toe and friends from other
+//! programs.
+//!
+//! Most programs only need the prelude:
+//!
+//! ```
+//! extern crate tic_tac_minimax;
+//!
+//! use tic_tac_minimax::prelude::*;
+//!
+//! let mut game = Game::<State>::new(CheckBox::X);
+//! game.set_difficulty(Difficulty::Perfect);
+//! let mut rng = tic_tac_minimax::rand::thread_rng();
+//! while game.state().result() == GameResult::InProgress {
+//!     game.play_engine_move(&mut rng);
+//! }
+//! // Perfect play always draws.
+//! assert_eq!(game.state().result(), GameResult::Draw);
+//! ```
+//!
+//! See the `examples` directory for more.
+
+// Re-exported for the random number generators the engine takes.
+pub extern crate rand;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+
+pub mod adaptive;
+pub mod app_config;
+pub mod bench;
+pub mod book;
+pub mod clock;
+pub mod commands;
+pub mod config;
+pub mod connect_four;
+pub mod cube;
+pub mod driver;
+pub mod effects;
+pub mod evaluator;
+pub mod ffi;
+pub mod frontend;
+pub mod fuzzing;
+pub mod game;
+pub mod game_state;
+pub mod graphviz;
+pub mod i18n;
+pub mod log;
+pub mod minimax;
+pub mod network;
+pub mod number_scrabble;
+pub mod observer;
+pub mod player;
+pub mod protocol;
+pub mod puzzle;
+#[cfg(test)]
+mod reference;
+pub mod recorder;
+pub mod report;
+pub mod rules;
+pub mod scoreboard;
+pub mod selfplay;
+pub mod selftest;
+pub mod settings;
+pub mod spectator;
+pub mod state;
+pub mod terminal;
+pub mod theme;
+pub mod tournament;
+pub mod ultimate;
+pub mod variant;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+/// The types to play and search games with.
+pub mod prelude {
+    pub use driver::{GameDriver, GameEvent};
+    pub use evaluator::{DefaultEvaluator, Evaluator};
+    pub use game::{Board, Game};
+    pub use game_state::{GameResult, GameState, GridState};
+    pub use minimax::{Difficulty, MiniMaxTree, Solution};
+    pub use player::{Handicap, PlayerConfig};
+    pub use state::{CheckBox, State};
+    pub use variant::{AnyGame, Variant};
+}
@@ -0,0 +1,346 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Structured events about what the engine decides, to find out afterwards
+//! why it played a move.
+//!
+//! Nothing is logged unless `TIC_TAC_MINIMAX_LOG` asks for it, with a level
+//! for every target, for some of them, or both:
+//!
+//! ```text
+//! TIC_TAC_MINIMAX_LOG=info
+//! TIC_TAC_MINIMAX_LOG=search=trace,game=info
+//! TIC_TAC_MINIMAX_LOG=debug,book=off
+//! ```
+//!
+//! The targets are `search`, for what each search found, `book`, for the
+//! moves of the opening book, `game`, for the moves and the results, and
+//! `gui`, for when the GUI stops responding for longer than a frame.
+//! Events are written to the standard error, like:
+//!
+//! ```text
+//! INFO game: engine moved player=O move=(1, 1) nodes=2315 cutoffs=402
+//! ```
+//!
+//! The GUI also keeps the last [`RECENT_EVENTS`](constant.RECENT_EVENTS.html)
+//! events, for bug reports.
+
+use std::collections::VecDeque;
+use std::env;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The variable with the filter.
+pub const ENV_VAR: &str = "TIC_TAC_MINIMAX_LOG";
+
+/// How much an event matters, from most to least.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub fn all() -> [Level; 5] {
+        [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+
+    /// Parses a level of a filter, where `off` is `None`.
+    fn parse(name: &str) -> Result<Option<Self>, String> {
+        if name == "off" {
+            return Ok(None);
+        }
+        Self::all().iter().cloned()
+            .find(|level| level.name() == name)
+            .map(Some)
+            .ok_or_else(|| format!("unknown log level `{}`", name))
+    }
+}
+
+/// Which events to log: up to a level for each target, and up to another
+/// one for the rest.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Filter {
+    default: Option<Level>,
+    targets: Vec<(String, Option<Level>)>,
+}
+
+impl Filter {
+    /// Parses a comma-separated list of levels like `search=debug`, or just
+    /// `debug` for the targets not in the list.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut filter = Filter::default();
+        for directive in text.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let mut parts = directive.splitn(2, '=');
+            let first = parts.next().unwrap_or("");
+            match parts.next() {
+                Some(level) => {
+                    let level = Level::parse(level.trim())?;
+                    filter.targets.retain(|(target, _)| target != first.trim());
+                    filter.targets.push((first.trim().to_owned(), level));
+                }
+                None => filter.default = Level::parse(first)?,
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Logs up to `level` for every target.
+    pub fn everything(level: Level) -> Self {
+        Self {
+            default: Some(level),
+            targets: vec![],
+        }
+    }
+
+    /// Returns whether nothing gets through.
+    pub fn is_off(&self) -> bool {
+        self.max_level().is_none()
+    }
+
+    pub fn enabled(&self, target: &str, level: Level) -> bool {
+        let max = match self.targets.iter().find(|&(t, _)| t == target) {
+            Some(&(_, max)) => max,
+            None => self.default,
+        };
+        max.is_some_and(|max| level <= max)
+    }
+
+    /// The most verbose level any target logs.
+    fn max_level(&self) -> Option<Level> {
+        self.targets.iter().map(|&(_, level)| level).chain(Some(self.default)).max()?
+    }
+}
+
+/// Something the engine did, with what's known about it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Event {
+    pub level: Level,
+    /// What the event is about, like `search`.
+    pub target: &'static str,
+    pub message: &'static str,
+    /// The details, by name, already formatted.
+    pub fields: Vec<(&'static str, String)>,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}: {}", self.level.name().to_uppercase(), self.target, self.message)?;
+        for &(name, ref value) in &self.fields {
+            if value.is_empty() || value.contains(char::is_whitespace) && !value.starts_with('(') {
+                write!(f, " {}={:?}", name, value)?;
+            } else {
+                write!(f, " {}={}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where the events go.
+pub type Sink = Box<dyn FnMut(&Event) + Send>;
+
+struct Logger {
+    filter: Filter,
+    sinks: Vec<Sink>,
+}
+
+static LOGGER: Mutex<Logger> = Mutex::new(Logger {
+    filter: Filter {
+        default: None,
+        targets: Vec::new(),
+    },
+    sinks: Vec::new(),
+});
+
+/// One more than the most verbose level of the filter, or zero if it's
+/// off, so that events that won't be logged cost no lock.
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets up logging to the standard error as `TIC_TAC_MINIMAX_LOG` says, if
+/// it's set.
+///
+/// Returns an error if the filter doesn't parse, logging nothing then.
+pub fn init_from_env() -> Result<(), String> {
+    let text = match env::var(ENV_VAR) {
+        Ok(text) => text,
+        Err(..) => return Ok(()),
+    };
+    let filter = Filter::parse(&text).map_err(|err| format!("{}: {}", ENV_VAR, err))?;
+    set_filter(filter);
+    add_sink(Box::new(|event| eprintln!("{}", event)));
+    Ok(())
+}
+
+/// Returns what's being logged.
+pub fn filter() -> Filter {
+    LOGGER.lock().unwrap().filter.clone()
+}
+
+/// Logs what `filter` lets through from now on.
+pub fn set_filter(filter: Filter) {
+    let max = filter.max_level().map_or(0, |level| level as usize + 1);
+    LOGGER.lock().unwrap().filter = filter;
+    MAX_LEVEL.store(max, Ordering::Relaxed);
+}
+
+/// Sends the events that get through the filter to `sink` too, from any
+/// thread.
+///
+/// The sink can't log anything itself.
+pub fn add_sink(sink: Sink) {
+    LOGGER.lock().unwrap().sinks.push(sink);
+}
+
+/// How many events [`keep_recent`](fn.keep_recent.html) keeps.
+pub const RECENT_EVENTS: usize = 200;
+
+/// The last events that got through the filter, dropping the oldest ones
+/// past `capacity`.
+struct Recent {
+    capacity: usize,
+    events: VecDeque<Event>,
+}
+
+impl Recent {
+    fn push(&mut self, event: &Event) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event.clone());
+    }
+}
+
+static RECENT: Mutex<Recent> = Mutex::new(Recent {
+    capacity: 0,
+    events: VecDeque::new(),
+});
+
+/// Keeps the last [`RECENT_EVENTS`](constant.RECENT_EVENTS.html) events that
+/// get through the filter from now on, for [`recent`](fn.recent.html).
+///
+/// Calling it again does nothing.
+pub fn keep_recent() {
+    {
+        let mut recent = RECENT.lock().unwrap();
+        if recent.capacity != 0 {
+            return;
+        }
+        recent.capacity = RECENT_EVENTS;
+    }
+    add_sink(Box::new(|event| RECENT.lock().unwrap().push(event)));
+}
+
+/// Returns the events kept since [`keep_recent`](fn.keep_recent.html), the
+/// oldest first.
+pub fn recent() -> Vec<Event> {
+    RECENT.lock().unwrap().events.iter().cloned().collect()
+}
+
+/// Returns whether an event about `target` at `level` would be logged, to
+/// skip computing what it'd say otherwise.
+pub fn enabled(target: &str, level: Level) -> bool {
+    if level as usize >= MAX_LEVEL.load(Ordering::Relaxed) {
+        return false;
+    }
+    LOGGER.lock().unwrap().filter.enabled(target, level)
+}
+
+/// Logs `message` about `target` with `fields`, if the filter lets it
+/// through.
+pub fn log(
+    level: Level,
+    target: &'static str,
+    message: &'static str,
+    fields: &[(&'static str, &dyn fmt::Display)])
+{
+    if level as usize >= MAX_LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut logger = LOGGER.lock().unwrap();
+    if !logger.filter.enabled(target, level) || logger.sinks.is_empty() {
+        return;
+    }
+    let event = Event {
+        level,
+        target,
+        message,
+        fields: fields.iter().map(|&(name, value)| (name, value.to_string())).collect(),
+    };
+    for sink in logger.sinks.iter_mut() {
+        sink(&event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_by_target() {
+        let filter = Filter::parse("info, search=trace ,book=off").unwrap();
+        assert!(filter.enabled("game", Level::Info));
+        assert!(!filter.enabled("game", Level::Debug));
+        assert!(filter.enabled("search", Level::Trace));
+        assert!(!filter.enabled("book", Level::Error));
+        assert_eq!(filter.max_level(), Some(Level::Trace));
+
+        assert!(Filter::parse("").unwrap().is_off());
+        assert!(Filter::parse("search=off").unwrap().is_off());
+        assert!(Filter::parse("search=loud").is_err());
+
+        let event = Event {
+            level: Level::Info,
+            target: "game",
+            message: "engine moved",
+            fields: vec![("move", "(1, 1)".to_owned()), ("reason", "a book move".to_owned())],
+        };
+        assert_eq!(event.to_string(), "INFO game: engine moved move=(1, 1) reason=\"a book move\"");
+    }
+
+    #[test]
+    fn keeps_the_last_events() {
+        let event = |message| Event {
+            level: Level::Debug,
+            target: "game",
+            message,
+            fields: vec![],
+        };
+        let mut recent = Recent { capacity: 2, events: VecDeque::new() };
+        recent.push(&event("first"));
+        recent.push(&event("second"));
+        recent.push(&event("third"));
+        assert_eq!(recent.events, [event("second"), event("third")]);
+    }
+}
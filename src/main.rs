@@ -15,148 +15,285 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+#[cfg(feature = "gui")]
+extern crate atk_sys;
+#[cfg(feature = "gui")]
+extern crate gdk;
+#[cfg(feature = "gui")]
+extern crate gio;
+#[cfg(feature = "gui")]
+extern crate glib;
+#[cfg(feature = "gui")]
 extern crate gtk;
+#[cfg(feature = "gui")]
+extern crate gtk_sys;
+extern crate rand;
+extern crate tic_tac_minimax;
 
-mod minimax;
-mod state;
+#[cfg(feature = "gui")]
+mod gui;
 
-use minimax::MiniMaxTree;
-use state::CheckBox;
+use tic_tac_minimax::{
+    app_config,
+    bench,
+    frontend,
+    graphviz,
+    i18n,
+    log,
+    protocol,
+    selfplay,
+    selftest,
+    spectator,
+    terminal,
+    tournament,
+};
 
-use gtk::{BoxExt, Cast, EntryExt, GridExt, GtkWindowExt, WidgetExt, ContainerExt, ButtonExt};
+use app_config::AppConfig;
+use spectator::Spectator;
+use terminal::Terminal;
 
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::env;
+use std::io;
+use std::process;
 
-struct App {
-    tree: RefCell<MiniMaxTree>,
-
-    window: gtk::Window,
-    restart_button: gtk::Button,
-    grid: gtk::Grid,
-    depth_input: gtk::Entry,
+/// Returns the config the options in `args` ask for, or exits if they're
+/// wrong.
+fn app_config(args: &[String]) -> AppConfig {
+    if args.iter().any(|arg| arg == "--help") {
+        println!("{}", app_config::USAGE);
+        process::exit(0);
+    }
+    AppConfig::from_args(args).unwrap_or_else(|err| {
+        eprintln!("error: {}\n\n{}", err, app_config::USAGE);
+        process::exit(2);
+    })
 }
 
-impl App {
-    fn init(app: Rc<Self>) {
-        let box_ = gtk::Box::new(gtk::Orientation::Vertical, 10 /* px */);
-        box_.pack_start(&app.grid, /* expand = */ true, /* fill = */ true, 0);
-        box_.pack_start(&app.restart_button, /* expand = */ true, /* fill = */ true, 0);
-        box_.pack_start(&app.depth_input, /* expand = */ true, /* fill = */ true, 0);
-        app.window.add(&box_);
-
-        app.depth_input.set_placeholder_text("Max depth");
-
-        app.window.connect_delete_event(|_, _| {
-            gtk::main_quit();
-            gtk::Inhibit(false)
-        });
-
-        {
-            let app_clone = app.clone();
-            app.restart_button.connect_clicked(move |_| {
-                // TODO(randomize?).
-                *app_clone.tree.borrow_mut() = MiniMaxTree::new(CheckBox::X);
-                app_clone.update_grid();
-            });
+/// Plays a game against the engine on stdin and stdout.
+fn play_in_terminal(app_config: AppConfig) {
+    let settings = &app_config.settings;
+    let mut game = settings.variant.new_game(app_config.first_player());
+    let human = settings.human;
+    for &(player, config) in &[(human, &settings.hints), (human.next_player(), &settings.engine)] {
+        if let Err(err) = game.board_mut().set_player_config(player, config.clone()) {
+            eprintln!("{}, using the default one", err);
         }
+    }
 
-        for x in 0..3 {
-            for y in 0..3 {
-                let app = app.clone();
-                let button = app.grid.get_child_at(x, y)
-                    .expect("Grid should be 3x3")
-                    .downcast::<gtk::Button>()
-                    .expect("No button? Pshaw!");
+    let stdin = io::stdin();
+    let mut terminal = Terminal::new(stdin.lock(), io::stdout());
+    frontend::play(&mut terminal, &mut game, settings.human, &mut rand::thread_rng());
+}
 
-                let x = x as usize;
-                let y = y as usize;
+/// Returns how many of `args` are the network or observer option the GUI
+/// was asked for, if any, counting the binary.
+fn gui_option_len(args: &[String]) -> usize {
+    let len = match args.get(1).map(|arg| arg.as_str()) {
+        Some("--listen") | Some("--connect") | Some("--broadcast") | Some("--broadcast-public") => 3,
+        // The socket is optional.
+        Some("observe") if args.get(2).is_some_and(|arg| !arg.starts_with("--")) => 3,
+        Some("observe") => 2,
+        _ => 1,
+    };
+    len.min(args.len())
+}
 
-                button.connect_clicked(move |_| {
-                    app.handle_click(x, y);
-                });
-            }
-        }
+#[cfg(feature = "gui")]
+fn run_gui(args: &[String]) {
+    use std::path::PathBuf;
+    use tic_tac_minimax::network::Network;
+    use tic_tac_minimax::observer::{self, Observer, Sink};
+    use tic_tac_minimax::spectator::Broadcaster;
 
-        app.window.show_all();
+    let (args, options) = args.split_at(gui_option_len(args));
+    let app_config = app_config(options);
+    if app_config.mode == app_config::Mode::Text {
+        if let Some(arg) = args.get(1) {
+            eprintln!("{} needs the GUI, drop `--mode text`.", arg);
+            process::exit(2);
+        }
+        return play_in_terminal(app_config);
     }
 
-    fn handle_click(&self, x: usize, y: usize) {
-        use std::cmp;
-
-        {
-            let mut tree = self.tree.borrow_mut();
-            if tree.choose(x, y).is_err() {
-                // TODO(emilio): Suggest an error? meh.
-                return;
+    let mut observer: Option<Box<dyn Sink>> = None;
+    let mut network = None;
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("--listen") => {
+            let port = args.get(2).and_then(|p| p.parse::<u16>().ok()).unwrap_or_else(|| {
+                eprintln!("usage: tic-tac-minimax --listen <port>");
+                process::exit(2);
+            });
+            match Network::listen(port) {
+                Ok(n) => network = Some(n),
+                Err(err) => {
+                    eprintln!("Couldn't listen on port {}: {}", port, err);
+                    process::exit(1);
+                }
             }
-
-            let max_depth = self.depth_input.get_text().and_then(|s| {
-                s.parse::<usize>().ok()
-            }).unwrap_or(4);
-
-            let max_depth = cmp::max(max_depth, 1);
-
-            // Now play as the opponent.
-            if let Some(index) = tree.find_move_index(max_depth) {
-                tree.choose_with_index(index);
+        }
+        Some("--connect") => {
+            let address = args.get(2).unwrap_or_else(|| {
+                eprintln!("usage: tic-tac-minimax --connect <host>:<port>");
+                process::exit(2);
+            });
+            match Network::connect(address) {
+                Ok(n) => network = Some(n),
+                Err(err) => {
+                    eprintln!("Couldn't connect to {}: {}", address, err);
+                    process::exit(1);
+                }
             }
         }
-
-        self.update_grid();
-    }
-
-    fn update_grid(&self) {
-        let tree = self.tree.borrow();
-        let state = tree.state();
-
-        for x in 0..3 {
-            for y in 0..3 {
-                let button = self.grid.get_child_at(x, y)
-                    .expect("Grid should be 3x3")
-                    .downcast::<gtk::Button>()
-                    .expect("No button? Pshaw!");
-                let label = match state.get(x as usize, y as usize) {
-                    CheckBox::Empty => " ",
-                    CheckBox::X => "X",
-                    CheckBox::O => "O",
-                };
-                button.set_label(label);
+        Some("observe") => {
+            let path = args.get(2).map(PathBuf::from).unwrap_or_else(observer::default_path);
+            match Observer::bind(&path) {
+                Ok(o) => {
+                    eprintln!("Streaming the game to {}", o.path().display());
+                    observer = Some(Box::new(o));
+                }
+                Err(err) => {
+                    eprintln!("Couldn't listen at {}: {}", path.display(), err);
+                    process::exit(1);
+                }
             }
         }
-    }
-
-    fn build_grid() -> gtk::Grid {
-        let grid = gtk::Grid::new();
-        for x in 0..3 {
-            for y in 0..3 {
-                let button = gtk::Button::new();
-                grid.attach(&button, x, y, 1, 1);
+        Some(option @ "--broadcast") | Some(option @ "--broadcast-public") => {
+            let port = args.get(2).and_then(|p| p.parse::<u16>().ok()).unwrap_or_else(|| {
+                eprintln!("usage: tic-tac-minimax {} <port>", option);
+                process::exit(2);
+            });
+            let public = option == "--broadcast-public";
+            match Broadcaster::bind(port, public) {
+                Ok(b) if public => {
+                    eprintln!("Anyone who can reach this machine can watch at ws://<host>:{}", b.port());
+                    observer = Some(Box::new(b));
+                }
+                Ok(b) => {
+                    eprintln!("Spectators can watch at ws://localhost:{}", b.port());
+                    observer = Some(Box::new(b));
+                }
+                Err(err) => {
+                    eprintln!("Couldn't listen on port {}: {}", port, err);
+                    process::exit(1);
+                }
             }
         }
-        grid
+        _ => {}
     }
+    gui::run(app_config, observer, network);
 }
 
-fn main() {
-    gtk::init().expect("Failed to initialize GTK.");
-
-    let window = gtk::Window::new(gtk::WindowType::Toplevel);
-    window.set_title("Tic tac toe");
-    window.set_default_size(350, 70);
+/// Shows the game broadcast at `url` until it's over, read-only.
+fn spectate(url: Option<&String>) {
+    let url = url.unwrap_or_else(|| {
+        eprintln!("usage: tic-tac-minimax --spectate ws://<host>:<port>");
+        process::exit(2);
+    });
+    let spectator = Spectator::connect(url).unwrap_or_else(|err| {
+        eprintln!("Couldn't spectate {}: {}", url, err);
+        process::exit(1);
+    });
+    show_spectated(spectator);
+}
 
-    let button = gtk::Button::new_with_label("Restart");
+#[cfg(feature = "gui")]
+fn show_spectated(spectator: Spectator) {
+    gui::spectate(spectator);
+}
 
-    let app = Rc::new(App {
-        tree: RefCell::new(minimax::MiniMaxTree::new(CheckBox::X)),
+/// Prints the board every time it changes.
+#[cfg(not(feature = "gui"))]
+fn show_spectated(mut spectator: Spectator) {
+    use std::thread;
+    use std::time::Duration;
+    use tic_tac_minimax::game_state::GameResult;
 
-        window: window,
-        restart_button: button,
-        grid: App::build_grid(),
-        depth_input: gtk::Entry::new(),
-    });
+    loop {
+        match spectator.poll() {
+            Ok(false) => thread::sleep(Duration::from_millis(100)),
+            Ok(true) => {
+                let snapshot = match spectator.snapshot() {
+                    Some(snapshot) => snapshot,
+                    None => continue,
+                };
+                let (cols, rows) = snapshot.grid_size();
+                println!("\n{}, after {} moves:", snapshot.variant().name(), snapshot.plies());
+                for y in 0..rows {
+                    let row = (0..cols).map(|x| snapshot.mark(x, y).dump_char().to_string()).collect::<Vec<_>>();
+                    println!("  {}", row.join(" "));
+                }
+                if snapshot.result() != GameResult::InProgress {
+                    println!("{:?}", snapshot.result());
+                }
+            }
+            Err(err) => {
+                println!("Disconnected: {}", err);
+                return;
+            }
+        }
+    }
+}
 
-    App::init(app);
+/// Without GTK there's only the terminal, so the GUI options make no sense.
+#[cfg(not(feature = "gui"))]
+fn run_gui(args: &[String]) {
+    if gui_option_len(args) > 1 {
+        eprintln!("{} needs the GUI, and this build has none. Try `text`.", args[1]);
+        process::exit(2);
+    }
+    play_in_terminal(app_config(&args[1..]));
+}
 
-    gtk::main();
+fn main() {
+    let locale = i18n::locale_from_env();
+    i18n::init(locale.as_deref());
+    if let Err(err) = log::init_from_env() {
+        eprintln!("{}, logging nothing", err);
+    }
+    let args = env::args().collect::<Vec<_>>();
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("selftest") => {
+            let passed = selftest::run();
+            process::exit(if passed { 0 } else { 1 });
+        }
+        Some("bench") => {
+            if let Err(err) = bench::run(&args[2..]) {
+                eprintln!("error: {}\n\n{}", err, bench::USAGE);
+                process::exit(2);
+            }
+            process::exit(0);
+        }
+        Some("selfplay") => {
+            if let Err(err) = selfplay::run(&args[2..]) {
+                eprintln!("error: {}\n\n{}", err, selfplay::USAGE);
+                process::exit(2);
+            }
+            process::exit(0);
+        }
+        Some("tournament") => {
+            if let Err(err) = tournament::run(&args[2..]) {
+                eprintln!("error: {}\n\n{}", err, tournament::USAGE);
+                process::exit(2);
+            }
+            process::exit(0);
+        }
+        Some("tree") => {
+            if let Err(err) = graphviz::run(&args[2..]) {
+                eprintln!("error: {}\n\n{}", err, graphviz::USAGE);
+                process::exit(2);
+            }
+            process::exit(0);
+        }
+        Some("--protocol") => {
+            let stdin = io::stdin();
+            if let Err(err) = protocol::run(stdin.lock(), &mut io::stdout()) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+            process::exit(0);
+        }
+        Some("text") => play_in_terminal(app_config(&args[2..])),
+        Some("--spectate") => spectate(args.get(2)),
+        _ => run_gui(&args),
+    }
 }
@@ -17,181 +17,1269 @@
 
 //! An implementation of the minimax algorithm.
 
+use evaluator::{DefaultEvaluator, Evaluator};
+use game_state::{GameResult, GameState, MoveError};
+use log::{self, Level};
+use rand::Rng;
+use rand::distributions::WeightedIndex;
 use state::State;
 use state::CheckBox;
+use std::cmp;
 use std::fmt;
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
+/// More than any score, and its negation less than any score.
+const INFINITY: i8 = i8::MAX;
+
+/// Returns 1 for O and -1 for X, to turn scores from O's point of view into
+/// scores from `player`'s point of view and back.
+fn sign(player: CheckBox) -> i8 {
+    player as i8 / CheckBox::O as i8
+}
+
+/// How hard the engine tries to win.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Perfect,
+    /// As deep as the machine can search in a reasonable time.
+    Auto,
+    /// As strong as it takes for the human to win about as often as they'd
+    /// like, see `adaptive`.
+    Adaptive,
+}
+
+impl Difficulty {
+    /// All the difficulty levels, from easiest to hardest, followed by
+    /// `Auto` and `Adaptive`.
+    pub fn all() -> [Difficulty; 6] {
+        [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard, Difficulty::Perfect, Difficulty::Auto,
+         Difficulty::Adaptive]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Perfect => "Perfect",
+            Difficulty::Auto => "Auto",
+            Difficulty::Adaptive => "Adaptive",
+        }
+    }
+
+    /// The inverse of `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Difficulty::all().iter().cloned().find(|d| d.name() == name)
+    }
+
+    /// The number of levels the engine looks ahead.
+    ///
+    /// This is further limited by `GameState::depth_limit`.
+    pub fn max_depth(&self) -> usize {
+        match *self {
+            Difficulty::Easy => 1,
+            Difficulty::Medium => 2,
+            Difficulty::Hard => 3,
+            // As deep as the game allows, or the node budget for `Auto`, or
+            // the strength for `Adaptive`.
+            Difficulty::Perfect | Difficulty::Auto | Difficulty::Adaptive => usize::MAX,
+        }
+    }
+
+    /// The probability of not playing the best move that was found.
+    fn mistake_probability(&self) -> f64 {
+        match *self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Medium => 0.25,
+            Difficulty::Hard | Difficulty::Perfect | Difficulty::Auto => 0.0,
+            // The strength says instead.
+            Difficulty::Adaptive => 0.0,
+        }
+    }
+}
+
+/// Why a depth can't be searched.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum DepthError {
+    /// The given text isn't a number.
+    NotANumber(String),
+    /// The depth is less than `SearchConfig::MIN_DEPTH`.
+    TooShallow(usize),
+}
+
+impl fmt::Display for DepthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DepthError::NotANumber(ref text) => write!(f, "not a depth: {}", text),
+            DepthError::TooShallow(depth) => write!(f, "not a depth: {}", depth),
+        }
+    }
+}
+
+/// A depth that's fine to search, which is how every depth the user asks
+/// for should get to the engine.
+///
+/// Depths of less than a level are refused, and depths deeper than a game
+/// goes are clamped to its depth limit rather than refused, since they'd
+/// search the same.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SearchConfig {
+    depth: usize,
+    /// Whether the depth asked for was deeper than the game goes.
+    clamped: bool,
+}
+
+impl SearchConfig {
+    pub const MIN_DEPTH: usize = 1;
+
+    /// Searches `depth` levels of a game that goes up to `depth_limit`
+    /// levels deep, or returns why not.
+    pub fn new(depth: usize, depth_limit: usize) -> Result<Self, DepthError> {
+        if depth < Self::MIN_DEPTH {
+            return Err(DepthError::TooShallow(depth));
+        }
+        Ok(Self {
+            depth: cmp::min(depth, depth_limit),
+            clamped: depth > depth_limit,
+        })
+    }
+
+    /// Like `new`, for a depth as the user typed it.
+    pub fn parse(text: &str, depth_limit: usize) -> Result<Self, DepthError> {
+        let depth = text.trim().parse::<usize>()
+            .map_err(|_| DepthError::NotANumber(text.trim().to_owned()))?;
+        Self::new(depth, depth_limit)
+    }
+
+    /// Like `new`, searching at least `MIN_DEPTH` levels rather than
+    /// refusing shallower depths.
+    pub fn clamped(depth: usize, depth_limit: usize) -> Self {
+        Self::new(cmp::max(depth, Self::MIN_DEPTH), depth_limit)
+            .expect("The depth should be deep enough")
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns whether the depth asked for was deeper than the game goes.
+    pub fn is_clamped(&self) -> bool {
+        self.clamped
+    }
+}
+
+/// How well the scores of a search predict the next one.
+///
+/// After the opponent moves, the previous search already scored the replies
+/// in the new position, and those scores are used to search the most
+/// promising replies first.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct WarmStartStats {
+    /// The number of searches that started with the replies already scored.
+    pub warm_searches: usize,
+    /// The number of those in which the best reply according to the previous
+    /// search remained the best.
+    pub previous_best_kept: usize,
+}
+
+/// What a search did.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    /// The number of nodes the search visited.
+    pub nodes: usize,
+    /// The number of nodes whose remaining children alpha-beta pruning
+    /// skipped.
+    pub cutoffs: usize,
+    /// The deepest ply below the current position the search reached.
+    pub max_depth: usize,
+    /// How long the search took, or zero where there's no clock, like on
+    /// wasm.
+    pub elapsed: Duration,
+    /// The number of visited nodes whose children an earlier search had
+    /// generated already. There's no transposition table, the tree itself is
+    /// what's kept between searches.
+    pub tt_hits: usize,
+    /// The number of visited nodes whose score an earlier search had found
+    /// already, so that their children weren't searched again.
+    pub score_hits: usize,
+    /// Whether the move came from the opening book, without searching.
+    pub book: bool,
+}
+
+impl SearchStats {
+    /// Adds the stats of a later search to these.
+    fn add(&mut self, other: &SearchStats) {
+        self.nodes += other.nodes;
+        self.cutoffs += other.cutoffs;
+        self.max_depth = cmp::max(self.max_depth, other.max_depth);
+        self.elapsed += other.elapsed;
+        self.tt_hits += other.tt_hits;
+        self.score_hits += other.score_hits;
+        self.book |= other.book;
+    }
+}
+
+/// A node the search is done with, for tools that show the search.
+#[derive(Debug)]
+pub struct TraceNode<'a, S: GameState + 'a> {
+    pub state: &'a S,
+    /// The player to move.
+    pub player: CheckBox,
+    /// The move that led to the node.
+    pub mv: S::Move,
+    /// How many plies below the current position the node is.
+    pub ply: usize,
+    /// The window the node was searched with, and its score, from the point
+    /// of view of `player`. With pruning, scores above `beta` are only a
+    /// lower bound.
+    pub alpha: i8,
+    pub beta: i8,
+    pub score: i8,
+}
+
+/// A function the search calls with every node it visits, children before
+/// their parents.
+pub type Tracer<S> = Box<dyn FnMut(&TraceNode<S>) + Send>;
+
+struct TraceHook<S: GameState>(Tracer<S>);
+
+impl<S: GameState> fmt::Debug for TraceHook<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TraceHook")
+    }
+}
+
+/// How many nodes a search visits between looks at whether it was
+/// cancelled.
+const CANCEL_CHECK_NODES: usize = 1024;
+
+/// Stops the searches of the trees that have it when cancelled, from any
+/// thread.
+///
+/// Once cancelled it stays so, and every search that has it stops.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// What a search for a move found.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SearchOutcome {
+    /// The index of the move to play, as `move_for_index` takes it.
+    Found(usize),
+    /// There's nothing to search: the game is over, or the search had no
+    /// depth.
+    NoMove,
+    /// The search was cancelled before it finished.
+    Cancelled,
+}
+
+impl SearchOutcome {
+    /// Returns the index of the move found, if any.
+    pub fn index(self) -> Option<usize> {
+        match self {
+            SearchOutcome::Found(index) => Some(index),
+            SearchOutcome::NoMove | SearchOutcome::Cancelled => None,
+        }
+    }
+}
+
+/// The children of nodes that were dropped, to store the children of new
+/// nodes in without allocating.
+#[derive(Debug)]
+struct NodePool<S: GameState> {
+    free: Vec<Vec<MiniMaxNode<S>>>,
+}
+
+impl<S: GameState> Default for NodePool<S> {
+    fn default() -> Self {
+        Self { free: vec![] }
+    }
+}
+
+impl<S: GameState> NodePool<S> {
+    /// The most vectors to keep around. Past this they're freed as usual.
+    const MAX_FREE: usize = 4096;
+
+    /// Returns an empty vector to store children in.
+    fn alloc(&mut self) -> Vec<MiniMaxNode<S>> {
+        self.free.pop().unwrap_or_default()
+    }
+
+    /// Drops `children` and everything below them, keeping their vectors.
+    fn recycle(&mut self, mut children: Vec<MiniMaxNode<S>>) {
+        for child in children.iter_mut() {
+            if let Some(grandchildren) = child.children.take() {
+                self.recycle(grandchildren);
+            }
+        }
+        if self.free.len() < Self::MAX_FREE {
+            children.clear();
+            self.free.push(children);
+        }
+    }
+}
+
+/// Returns the time now, if there's a clock to tell.
+fn now() -> Option<Instant> {
+    if cfg!(target_arch = "wasm32") { None } else { Some(Instant::now()) }
+}
+
+/// What a search carries down the tree.
+struct Search<S: GameState> {
+    evaluator: Arc<dyn Evaluator<S>>,
+    prune: bool,
+    /// How many plies below the current position the node being searched
+    /// is.
+    ply: usize,
+    stats: SearchStats,
+    tracer: Option<TraceHook<S>>,
+    start: Option<Instant>,
+    cancel: Option<CancelToken>,
+    /// Whether the search saw that it was cancelled, after which the scores
+    /// it returns mean nothing.
+    cancelled: bool,
+    pool: NodePool<S>,
+    /// The number of positions so far where the search stopped before the
+    /// game was over, and what it was when the search of the innermost node
+    /// being searched started.
+    horizon_leaves: usize,
+    subtree_horizon_leaves: usize,
+}
+
+impl<S: GameState> Search<S> {
+    /// Returns whether the search was cancelled, looking at the token only
+    /// every so often.
+    fn check_cancelled(&mut self) -> bool {
+        if !self.cancelled && self.stats.nodes.is_multiple_of(CANCEL_CHECK_NODES) {
+            self.cancelled = self.cancel.as_ref().is_some_and(|c| c.is_cancelled());
+        }
+        self.cancelled
+    }
+}
+
+/// What a score returned by a search says about the real score.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Bound {
+    Exact,
+    /// The real score is at least this.
+    Lower,
+    /// The real score is at most this.
+    Upper,
+}
+
+/// The score a search found for a node, which the searches after it can
+/// reuse instead of searching the node again.
+#[derive(Copy, Clone, Debug)]
+struct CachedScore {
+    score: i8,
+    bound: Bound,
+    /// How many levels below the node the search looked.
+    depth: usize,
+    /// Whether every line of play below the node ended within those levels,
+    /// so that deeper searches would find the same.
+    complete: bool,
+}
+
+impl CachedScore {
+    /// Returns the score a search of `depth` levels in the window from
+    /// `alpha` to `beta` can return instead of searching, if any.
+    ///
+    /// The score is only reused if such a search would find the same one,
+    /// or a bound that makes it just as irrelevant to the parent, so reusing
+    /// it never changes the moves the engine plays.
+    fn reusable(&self, depth: usize, alpha: i8, beta: i8) -> Option<i8> {
+        if depth != self.depth && !(self.complete && depth > self.depth) {
+            return None;
+        }
+        match self.bound {
+            Bound::Exact => Some(self.score),
+            Bound::Lower if self.score >= beta => Some(self.score),
+            Bound::Upper if self.score <= alpha => Some(self.score),
+            _ => None,
+        }
+    }
+}
+
+/// The outcome of a position with best play, for the player to move, and in
+/// how many plies the game ends if somebody wins: as soon as possible for the
+/// winner, and as late as possible for the loser.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Solution {
+    Win(usize),
+    Draw,
+    Loss(usize),
+}
+
+impl Solution {
+    /// The solution for the player that moved into a position with this
+    /// solution.
+    fn before_move(self) -> Self {
+        match self {
+            Solution::Win(plies) => Solution::Loss(plies + 1),
+            Solution::Draw => Solution::Draw,
+            Solution::Loss(plies) => Solution::Win(plies + 1),
+        }
+    }
+
+    /// Orders solutions from worst to best for the player to move.
+    fn rank(self) -> (u8, isize) {
+        match self {
+            Solution::Loss(plies) => (0, plies as isize),
+            Solution::Draw => (1, 0),
+            Solution::Win(plies) => (2, -(plies as isize)),
+        }
+    }
+}
+
+/// Searches every line of play from `state`, with `player` to move.
+fn solve_state<S: GameState>(state: &S, player: CheckBox) -> Solution {
+    match state.winner() {
+        CheckBox::Empty => {}
+        winner if winner == player => return Solution::Win(0),
+        _ => return Solution::Loss(0),
+    }
+    state.successors(player).iter()
+        .map(|(_, s)| solve_state(s, player.next_player()).before_move())
+        .max_by_key(|s| s.rank())
+        .unwrap_or(Solution::Draw)
+}
+
+/// How the games from a position end, counting every line of play to the
+/// end, whether it's any good or not.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+pub struct Tally {
+    pub x_wins: u64,
+    pub o_wins: u64,
+    pub draws: u64,
+}
+
+impl Tally {
+    /// The number of different games.
+    pub fn games(&self) -> u64 {
+        self.x_wins + self.o_wins + self.draws
+    }
+
+    fn add(&mut self, other: Tally) {
+        self.x_wins += other.x_wins;
+        self.o_wins += other.o_wins;
+        self.draws += other.draws;
+    }
+}
+
+/// Counts how every line of play from `state` ends, with `player` to move.
+fn tally_state<S: GameState>(state: &S, player: CheckBox) -> Tally {
+    let mut tally = Tally::default();
+    match state.winner() {
+        CheckBox::X => tally.x_wins = 1,
+        CheckBox::O => tally.o_wins = 1,
+        CheckBox::Empty => {
+            let successors = state.successors(player);
+            if successors.is_empty() {
+                tally.draws = 1;
+            }
+            for (_, next) in successors {
+                tally.add(tally_state(&next, player.next_player()));
+            }
+        }
+    }
+    tally
+}
+
+/// The search tree of a game.
+///
+/// The search is a negamax: nodes are scored from the point of view of their
+/// player to move. The public methods use scores from O's point of view
+/// though, like `GameState::evaluate`.
 #[derive(Debug)]
-pub struct MiniMaxTree {
-    current_state: MiniMaxNode,
+pub struct MiniMaxTree<S: GameState = State> {
+    current_state: MiniMaxNode<S>,
+    /// How the positions where the search stops are scored.
+    evaluator: Arc<dyn Evaluator<S>>,
+    warm_start_stats: WarmStartStats,
+    /// What the last search did.
+    stats: SearchStats,
+    tracer: Option<TraceHook<S>>,
+    cancel: Option<CancelToken>,
+    /// The most nodes to keep below the current one between moves, if
+    /// there's a limit.
+    node_limit: Option<usize>,
+    pool: NodePool<S>,
 }
 
-impl MiniMaxTree {
-    pub fn new(player: CheckBox) -> Self {
-        Self {
-            current_state: MiniMaxNode {
-                state: State::initial(),
-                player: player,
-                children: None,
-            },
-        }
+impl<S: GameState> MiniMaxTree<S> {
+    /// How many levels below the current node are kept between moves, no
+    /// matter the node limit.
+    pub const MIN_RETAINED_LEVELS: usize = 2;
+
+    pub fn new(player: CheckBox) -> Self {
+        Self::from_state(S::initial(), player)
+    }
+
+    /// Returns a tree starting from `state`, with `player` to move.
+    pub fn from_state(state: S, player: CheckBox) -> Self {
+        Self {
+            current_state: MiniMaxNode::new(state, player, None),
+            evaluator: Arc::new(DefaultEvaluator),
+            warm_start_stats: WarmStartStats::default(),
+            stats: SearchStats::default(),
+            tracer: None,
+            cancel: None,
+            node_limit: None,
+            pool: NodePool::default(),
+        }
+    }
+
+    /// Makes the search score the positions where it stops with `evaluator`.
+    pub fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator<S>>) {
+        self.evaluator = evaluator;
+        // Scores of the old evaluator aren't what searches would find now.
+        self.current_state.forget_scores();
+    }
+
+    /// Makes the searches call `tracer` with every node they visit, or stop
+    /// calling anything if `None`.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer<S>>) {
+        self.tracer = tracer.map(TraceHook);
+    }
+
+    /// Makes the searches stop early once `cancel` is cancelled, or never if
+    /// `None`.
+    ///
+    /// `find_move_index` tells it was cancelled, but the other searches
+    /// return made up scores or no move then, so whoever cancels should
+    /// ignore them.
+    pub fn set_cancel_token(&mut self, cancel: Option<CancelToken>) {
+        self.cancel = cancel;
+    }
+
+    /// Makes the tree keep at most `node_limit` nodes between moves, dropping
+    /// the deepest levels it searched first, or all of them if `None`.
+    ///
+    /// The children and grandchildren of the current node are always kept,
+    /// since the next search needs them anyway.
+    pub fn set_node_limit(&mut self, node_limit: Option<usize>) {
+        self.node_limit = node_limit;
+        self.prune();
+    }
+
+    /// Returns the number of nodes the tree keeps, counting the current one.
+    pub fn retained_nodes(&self) -> usize {
+        self.current_state.count()
+    }
+
+    /// Drops the deepest levels of the tree until it's within the node
+    /// limit, if there's one.
+    fn prune(&mut self) {
+        let limit = match self.node_limit {
+            Some(limit) => limit,
+            None => return,
+        };
+        let mut levels = vec![];
+        self.current_state.count_levels(0, &mut levels);
+        let mut total = 0;
+        let mut kept_levels = 0;
+        for (level, &nodes) in levels.iter().enumerate() {
+            total += nodes;
+            if total > limit && level > Self::MIN_RETAINED_LEVELS {
+                break;
+            }
+            kept_levels = level;
+        }
+        if kept_levels + 1 < levels.len() {
+            self.current_state.prune_below(kept_levels, &mut self.pool);
+        }
+    }
+
+    /// Returns whether the searches of the tree are cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|c| c.is_cancelled())
+    }
+
+    /// Returns the number of nodes the last search visited.
+    pub fn nodes_visited(&self) -> usize {
+        self.stats.nodes
+    }
+
+    /// Returns what the last search did.
+    pub fn search_stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    /// Starts a search of the current position, which `finish_search`
+    /// ends.
+    fn start_search(&mut self, prune: bool) -> Search<S> {
+        Search {
+            evaluator: self.evaluator.clone(),
+            prune,
+            ply: 0,
+            stats: SearchStats::default(),
+            tracer: self.tracer.take(),
+            start: now(),
+            cancel: self.cancel.clone(),
+            cancelled: false,
+            pool: std::mem::take(&mut self.pool),
+            horizon_leaves: 0,
+            subtree_horizon_leaves: 0,
+        }
+    }
+
+    /// Ends `search`, returning what it did.
+    fn finish_search(&mut self, search: Search<S>) -> SearchStats {
+        self.tracer = search.tracer;
+        self.pool = search.pool;
+        let mut stats = search.stats;
+        if let Some(start) = search.start {
+            stats.elapsed = start.elapsed();
+        }
+        stats
+    }
+
+    /// Returns how often the previous search predicted the best move.
+    #[allow(dead_code)] // This is just for reporting.
+    pub fn warm_start_stats(&self) -> WarmStartStats {
+        self.warm_start_stats
+    }
+
+    /// Ensures we have computed at least up to `depth` levels in the tree.
+    #[allow(dead_code)] // This is just for debugging.
+    pub fn dump<W>(&self, dest: &mut W) -> fmt::Result
+        where W: fmt::Write,
+    {
+        self.dump_up_to(usize::MAX, dest)
+    }
+
+    /// Dumps the computed tree, but only up to `levels` levels below the
+    /// current state.
+    pub fn dump_up_to<W>(&self, levels: usize, dest: &mut W) -> fmt::Result
+        where W: fmt::Write,
+    {
+        self.current_state.dump(0, levels, &*self.evaluator, dest)
+    }
+
+    /// Writes the computed tree, up to `levels` levels below the current
+    /// state, as a Graphviz graph.
+    ///
+    /// Nodes show their board and their score in the last search that
+    /// reached them, from the point of view of O. Nodes that search pruned
+    /// before looking at them are dashed.
+    pub fn write_dot<W>(&self, levels: usize, dest: &mut W) -> fmt::Result
+        where W: fmt::Write,
+    {
+        writeln!(dest, "digraph search {{")?;
+        writeln!(dest, "  node [shape=box, fontname=monospace];")?;
+        self.current_state.write_dot(&mut 0, levels, dest)?;
+        writeln!(dest, "}}")
+    }
+
+    /// Returns the current state of the game.
+    pub fn state(&self) -> &S {
+        &self.current_state.state
+    }
+
+    /// Returns the player that has to move.
+    pub fn player(&self) -> CheckBox {
+        self.current_state.player
+    }
+
+    /// Plays `mv` as the current player.
+    ///
+    /// Returns an error if the move is not legal, like when the square was
+    /// not empty, or the game is over.
+    pub fn choose(&mut self, mv: S::Move) -> Result<(), MoveError> {
+        let index = match self.current_state.ensure_children().iter().position(|child| {
+            child.mv == Some(mv)
+        }) {
+            Some(index) => index,
+            None if self.state().is_over() => return Err(MoveError::GameOver),
+            None => return Err(self.state().move_error(mv)),
+        };
+
+        self.choose_with_index(index);
+        Ok(())
+    }
+
+    /// Returns the move with the given index.
+    pub fn move_for_index(&self, index: usize) -> S::Move {
+        let children = self.current_state.children.as_ref()
+            .expect("Should have computed the children");
+        children[index].mv.unwrap()
+    }
+
+    pub fn choose_with_index(&mut self, index: usize) {
+        let mut current_state = self.current_state.take();
+        self.current_state = current_state.ensure_children()[index].take();
+        // The other moves are gone for good.
+        if let Some(siblings) = current_state.children.take() {
+            self.pool.recycle(siblings);
+        }
+        self.prune();
+    }
+
+    /// Returns the best move for `player` in the current state, searching up
+    /// to `max_depth` levels, without changing the tree.
+    ///
+    /// `player` doesn't need to be the one to move. Returns `None` if the game
+    /// is already over, or if `max_depth` is zero.
+    pub fn best_move_for(&self, player: CheckBox, max_depth: usize) -> Option<S::Move> {
+        let mut scratch = self.scratch(player);
+        let index = scratch.find_move_index(max_depth).index()?;
+        Some(scratch.move_for_index(index))
+    }
+
+    /// Returns the score of the current state with best play, from O's point
+    /// of view, searching up to `max_depth` levels, without changing the
+    /// tree.
+    ///
+    /// States where the game is over, and searches of zero levels, score as
+    /// the evaluator says.
+    pub fn evaluate(&self, max_depth: usize) -> i8 {
+        if !self.current_state.state.is_over() && max_depth > 0 {
+            let mut scratch = self.scratch(self.player());
+            if let Some((_, score)) = scratch.find_best_move(max_depth) {
+                if !scratch.is_cancelled() {
+                    return score;
+                }
+            }
+        }
+        self.evaluator.evaluate(self.state())
+    }
+
+    /// Returns how many nodes a search of the current state up to
+    /// `max_depth` levels would visit without alpha-beta pruning, without
+    /// changing the tree.
+    pub fn nodes_without_pruning(&self, max_depth: usize) -> usize {
+        let mut scratch = self.scratch(self.player());
+        let order = scratch.current_state.search_order();
+        let mut search = scratch.start_search(/* prune = */ false);
+        scratch.find_move_index_internal(max_depth, &order, &mut search);
+        scratch.finish_search(search).nodes
+    }
+
+    /// Returns a new tree for the current state, with `player` to move.
+    fn scratch(&self, player: CheckBox) -> Self {
+        let mut scratch = MiniMaxTree::from_state(self.state().clone(), player);
+        scratch.evaluator = self.evaluator.clone();
+        scratch.cancel = self.cancel.clone();
+        scratch
+    }
+
+    /// Finds a min/max move index for the next round.
+    ///
+    /// Searches that would reach as deep as the game allows play the book
+    /// move instead, if there's one, without visiting any node.
+    ///
+    /// Returns `NoMove` if the game is already over, or if `max_depth` is
+    /// zero.
+    pub fn find_move_index(
+        &mut self,
+        max_depth: usize)
+        -> SearchOutcome
+    {
+        if self.is_cancelled() {
+            return SearchOutcome::Cancelled;
+        }
+        if max_depth >= S::depth_limit() {
+            let player = self.current_state.player;
+            if let Some(mv) = self.current_state.state.book_move(player) {
+                let index = self.current_state.ensure_children().iter().position(|child| {
+                    child.mv == Some(mv)
+                });
+                if let Some(index) = index {
+                    self.stats = SearchStats::default();
+                    self.stats.book = true;
+                    log::log(Level::Debug, "book", "found a book move", &[
+                        ("player", &player.dump_char()),
+                        ("move", &format_args!("{:?}", mv)),
+                    ]);
+                    return SearchOutcome::Found(index);
+                }
+            }
+        }
+        match self.find_best_move(max_depth) {
+            Some((index, _)) => SearchOutcome::Found(index),
+            None if self.is_cancelled() => SearchOutcome::Cancelled,
+            None => SearchOutcome::NoMove,
+        }
+    }
+
+    /// Like `find_move_index`, but picking uniformly at random among all the
+    /// moves tying for the best score, rather than always the first one.
+    pub fn find_random_best_move_index<R>(
+        &mut self,
+        max_depth: usize,
+        rng: &mut R)
+        -> Option<usize>
+        where R: Rng + ?Sized,
+    {
+        if self.current_state.state.is_over() || max_depth == 0 {
+            return None;
+        }
+
+        let scores = self.child_scores(max_depth);
+        let best = scores.iter().cloned().max().expect("Should have at least a move");
+        let ties = scores.iter().enumerate()
+            .filter(|&(_, &score)| score == best)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let index = ties[rng.gen_range(0..ties.len())];
+        if log::enabled("search", Level::Debug) {
+            let stats = self.stats;
+            log::log(Level::Debug, "search", "found a move", &[
+                ("player", &self.player().dump_char()),
+                ("move", &format_args!("{:?}", self.move_for_index(index))),
+                ("score", &(best * sign(self.player()))),
+                ("ties", &ties.len()),
+                ("depth", &max_depth),
+                ("reached", &stats.max_depth),
+                ("nodes", &stats.nodes),
+                ("cutoffs", &stats.cutoffs),
+                ("score_hits", &stats.score_hits),
+                ("elapsed", &format_args!("{:?}", stats.elapsed)),
+            ]);
+        }
+        Some(index)
+    }
+
+    /// Finds a move index searching deeper and deeper, up to `max_depth`
+    /// levels, for as long as the next search is expected to keep the total
+    /// within `node_budget` nodes. At least one level is always searched.
+    ///
+    /// Each search starts from the scores of the previous one, so searching
+    /// all the depths costs little more than searching the last one.
+    pub fn find_move_index_within_budget(
+        &mut self,
+        node_budget: usize,
+        max_depth: usize)
+        -> Option<usize>
+    {
+        let mut best = None;
+        let mut total = SearchStats::default();
+        let mut previous_nodes = 0;
+        for depth in 1..max_depth.saturating_add(1) {
+            best = Some(self.find_move_index(depth).index()?);
+            let nodes = self.stats.nodes;
+            total.add(&self.stats);
+            log::log(Level::Trace, "search", "deepened", &[
+                ("depth", &depth),
+                ("nodes", &nodes),
+                ("total", &total.nodes),
+                ("budget", &node_budget),
+            ]);
+            // The search reaches the end of the game already.
+            if nodes == previous_nodes {
+                break;
+            }
+            // Guess that the next search grows as much as this one did.
+            let growth = nodes as f64 / previous_nodes.max(1) as f64;
+            if total.nodes as f64 + nodes as f64 * growth > node_budget as f64 {
+                break;
+            }
+            previous_nodes = nodes;
+        }
+        self.stats = total;
+        best
+    }
+
+    /// Like `find_move_index`, but also returns the score of the move.
+    pub fn find_best_move(
+        &mut self,
+        max_depth: usize)
+        -> Option<(usize, i8)>
+    {
+        // The searches below update the scores, so compute the order of the
+        // root moves once to have both searches agree on it.
+        let order = self.current_state.search_order();
+        let previous_best = if self.current_state.children_scored() {
+            order.first().cloned()
+        } else {
+            None
+        };
+
+        let mut search = self.start_search(/* prune = */ true);
+        let move_pruning = self.find_move_index_internal(max_depth, &order, &mut search);
+        self.stats = self.finish_search(search);
+
+        if previous_best.is_some() && move_pruning.is_some() {
+            self.warm_start_stats.warm_searches += 1;
+            if previous_best == move_pruning.map(|(index, _)| index) {
+                self.warm_start_stats.previous_best_kept += 1;
+            }
+        }
+
+        if let Some((index, score)) = move_pruning {
+            if log::enabled("search", Level::Debug) {
+                let stats = self.stats;
+                log::log(Level::Debug, "search", "found a move", &[
+                    ("player", &self.player().dump_char()),
+                    ("move", &format_args!("{:?}", self.move_for_index(index))),
+                    ("score", &score),
+                    ("depth", &max_depth),
+                    ("reached", &stats.max_depth),
+                    ("nodes", &stats.nodes),
+                    ("cutoffs", &stats.cutoffs),
+                    ("score_hits", &stats.score_hits),
+                    ("elapsed", &format_args!("{:?}", stats.elapsed)),
+                ]);
+            }
+        }
+
+        move_pruning
+    }
+
+    /// Like `find_best_move`, but also returns what the search did.
+    pub fn find_best_move_with_stats(
+        &mut self,
+        max_depth: usize)
+        -> Option<(usize, i8, SearchStats)>
+    {
+        let (index, score) = self.find_best_move(max_depth)?;
+        Some((index, score, self.stats))
+    }
+
+    /// Like `find_best_move`, but also returns the principal variation: the
+    /// line of play the search expects if both players play their best,
+    /// starting with the move, and as long as the search looked ahead.
+    ///
+    /// The line comes from searching each of its positions in turn, so it
+    /// costs a bit more than the search of the move alone.
+    pub fn find_move_with_pv(
+        &mut self,
+        max_depth: usize)
+        -> Option<(S::Move, Vec<S::Move>, i8)>
+    {
+        let (index, score) = self.find_best_move(max_depth)?;
+        let mv = self.move_for_index(index);
+
+        let mut pv = vec![mv];
+        let mut scratch = self.scratch(self.player());
+        scratch.choose(mv).expect("Searched moves should be legal");
+        for depth in (1..max_depth).rev() {
+            let index = match scratch.find_best_move(depth) {
+                Some((index, _)) => index,
+                None => break,
+            };
+            pv.push(scratch.move_for_index(index));
+            scratch.choose_with_index(index);
+        }
+        Some((mv, pv, score))
+    }
+
+    /// Finds a move whose score is proven to be at most `margin` worse than
+    /// the best one, searching up to `max_depth` levels.
+    ///
+    /// This searches the moves after the best one so far only to prove they
+    /// aren't better by more than `margin`, which is cheaper the larger the
+    /// margin is, and stops as soon as no move can be better by more than
+    /// that. A margin of zero finds the best move, like `find_best_move`.
+    ///
+    /// Returns the index of the move and its exact score, or `None` if the
+    /// game is already over, or if `max_depth` is zero.
+    pub fn find_move_within_margin(
+        &mut self,
+        max_depth: usize,
+        margin: i8)
+        -> Option<(usize, i8)>
+    {
+        self.stats = SearchStats::default();
+
+        if self.current_state.state.is_over() || max_depth == 0 {
+            return None;
+        }
+
+        let sign = sign(self.current_state.player);
+        // Nothing scores better than a win.
+        let unbeatable = CheckBox::O as i8 - margin;
+
+        let order = self.current_state.search_order();
+        let mut search = self.start_search(/* prune = */ true);
+        let children = self.current_state.ensure_children();
+        let mut best: Option<(usize, i8)> = None;
+        for &i in &order {
+            // Only look for moves better than the best one by more than the
+            // margin. Scores out of the window are just bounds, but fail-soft
+            // searches make those within it exact.
+            let alpha = match best {
+                None => -INFINITY,
+                Some((_, best)) => best.saturating_add(margin),
+            };
+
+            let score = -children[i].negamax(max_depth - 1, -INFINITY, -alpha, &mut search);
+
+            if score > alpha && score < INFINITY {
+                best = Some((i, score));
+                if score >= unbeatable {
+                    break;
+                }
+            }
+        }
+        self.stats = self.finish_search(search);
+
+        best.map(|(index, score)| (index, score * sign))
+    }
+
+    /// Searches the current position both with and without alpha-beta
+    /// pruning, and returns whether both searches chose the same move, with
+    /// pruning visiting no more nodes.
+    ///
+    /// The tests check this on random positions of every game, this allows
+    /// checking it on others, in any build.
+    pub fn pruning_agrees(&mut self, max_depth: usize) -> bool {
+        let order = self.current_state.search_order();
+
+        let mut search = self.start_search(/* prune = */ true);
+        let move_pruning = self.find_move_index_internal(max_depth, &order, &mut search);
+        let pruning = self.finish_search(search);
+
+        let mut search = self.start_search(/* prune = */ false);
+        let move_without_pruning = self.find_move_index_internal(max_depth, &order, &mut search);
+        let without_pruning = self.finish_search(search);
+
+        move_pruning == move_without_pruning && pruning.nodes <= without_pruning.nodes
     }
 
-    /// Ensures we have computed at least up to `depth` levels in the tree.
-    #[allow(dead_code)] // This is just for debugging.
-    pub fn dump<W>(&self, dest: &mut W) -> fmt::Result
-        where W: fmt::Write,
+    /// Finds a move index for the next round, making mistakes as often as
+    /// `difficulty` says, and searching no deeper than it and `max_depth`.
+    ///
+    /// Mistakes are picked at random among all the moves, with better moves
+    /// being more likely, so not all the mistakes are outright blunders.
+    ///
+    /// Returns `None` if the game is already over.
+    pub fn find_move_index_with_difficulty<R>(
+        &mut self,
+        difficulty: Difficulty,
+        max_depth: usize,
+        randomize_ties: bool,
+        rng: &mut R)
+        -> Option<usize>
+        where R: Rng + ?Sized,
     {
-        self.current_state.dump(0, dest)
+        let max_depth = cmp::min(difficulty.max_depth(), max_depth);
+        self.find_move_index_with_mistakes(max_depth, difficulty.mistake_probability(), randomize_ties, rng)
     }
 
-    /// Returns the current state of the game.
-    pub fn state(&self) -> &State {
-        &self.current_state.state
+    /// Like `find_move_index_with_difficulty`, but making mistakes with the
+    /// given probability, and searching no deeper than `max_depth`.
+    pub fn find_move_index_with_mistakes<R>(
+        &mut self,
+        max_depth: usize,
+        mistake_probability: f64,
+        randomize_ties: bool,
+        rng: &mut R)
+        -> Option<usize>
+        where R: Rng + ?Sized,
+    {
+        let max_depth = cmp::min(max_depth, S::depth_limit());
+        let best_move = if randomize_ties {
+            self.find_random_best_move_index(max_depth, rng)?
+        } else {
+            self.find_move_index(max_depth).index()?
+        };
+        if !rng.gen_bool(mistake_probability) {
+            return Some(best_move);
+        }
+
+        // Scores are in the [-10, 10] range, make them positive weights.
+        let weights = self.child_scores(max_depth).into_iter().map(|score| score as i32 + 11);
+
+        let distribution =
+            WeightedIndex::new(weights).expect("Should have at least a move");
+        let index = rng.sample(distribution);
+        log::log(Level::Debug, "search", "rolled a mistake", &[
+            ("probability", &mistake_probability),
+            ("best", &format_args!("{:?}", self.move_for_index(best_move))),
+            ("chosen", &format_args!("{:?}", self.move_for_index(index))),
+        ]);
+        Some(index)
     }
 
-    /// Toggles the square at (x, y).
+    /// If the best the current player can get is a draw, returns how many of
+    /// their moves along `pv` are the only one that keeps the draw.
+    ///
+    /// `pv` is the line of play expected from the current node, like the one
+    /// `find_move_with_pv` finds. The moves of the current player along it
+    /// are checked with the solver, so like `solve`, this is only fast
+    /// enough for small games.
     ///
-    /// Returns an error if the square was not empty.
-    pub fn choose(&mut self, x: usize, y: usize) -> Result<(), ()> {
-        if self.current_state.state.get(x, y) != CheckBox::Empty ||
-            self.current_state.score() != 0 {
-            return Err(());
+    /// Returns `None` if the game isn't a draw with best play, or if `pv`
+    /// doesn't reach the end of the game to prove it.
+    pub fn exact_moves_to_draw(&self, pv: &[S::Move]) -> Option<usize> {
+        let defender = self.player();
+        let mut state = self.state().clone();
+        let mut player = defender;
+        let mut exact_moves = 0;
+        for &mv in pv {
+            let mut successors = state.successors(player);
+            let index = successors.iter().position(|&(m, _)| m == mv)?;
+            if player == defender {
+                let solutions = successors.iter()
+                    .map(|(_, next)| solve_state(next, player.next_player()))
+                    .collect::<Vec<_>>();
+                // The defender can do better than a draw, or the line
+                // doesn't hold it.
+                if solutions.iter().any(|&s| matches!(s, Solution::Loss(..))) ||
+                    solutions[index] != Solution::Draw {
+                    return None;
+                }
+                if solutions.iter().filter(|&&s| s == Solution::Draw).count() == 1 {
+                    exact_moves += 1;
+                }
+            }
+            state = successors.swap_remove(index).1;
+            player = player.next_player();
         }
 
-        let current_player = self.current_state.player;
-        let mut current_state = self.current_state.take();
-        let new_state = current_state.ensure_children().iter_mut().find(|s| {
-            s.state.get(x, y) == current_player
-        });
-
-        let new_state = match new_state {
-            None => {
-                // If we couldn't find a state here, it means that the game is
-                // over, compute it from the subsequents states.
-                //
-                // NOTE(emilio): We don't really need to iterate this, but seems
-                // cheap enough.
-                let s = self.current_state.state.subsequent_states(current_player).find(|s| {
-                    s.get(x, y) == current_player
-                }).unwrap();
-                assert_ne!(s.score(), 0);
-                MiniMaxNode::new(s, current_player.next_player())
-            }
-            Some(mut new_state) => new_state.take(),
-        };
-
-        self.current_state = new_state;
+        if state.result() != GameResult::Draw {
+            return None;
+        }
+        Some(exact_moves)
+    }
 
-        Ok(())
+    /// Searches every line of play from the current state to the end of the
+    /// game, without changing the tree.
+    ///
+    /// There's no depth limit nor pruning, so this is only fast enough for
+    /// small games.
+    pub fn solve(&self) -> Solution {
+        solve_state(self.state(), self.player())
     }
 
-    pub fn choose_with_index(&mut self, index: usize) {
-        let mut current_state = self.current_state.take();
-        let new_state = &mut current_state.ensure_children()[index];
-        self.current_state = new_state.take();
+    /// Returns the moves that win the game right away for `player`, whether
+    /// or not it's their turn, without searching.
+    pub fn immediate_threats(&self, player: CheckBox) -> Vec<S::Move> {
+        self.state().successors(player).into_iter()
+            .filter(|(_, state)| state.winner() == player)
+            .map(|(mv, _)| mv)
+            .collect()
     }
 
-    /// Finds a min/max move index for the next round.
+    /// Counts how every game from the current state ends, without changing
+    /// the tree.
     ///
-    /// Returns `None` if the game is already over, or if `max_depth` is zero.
-    pub fn find_move_index(
-        &mut self,
-        max_depth: usize)
-        -> Option<usize>
-    {
-        let mut nodes_visited_pruning = 0;
-        let move_pruning = self.find_move_index_internal(
-            max_depth,
-            /* prune = */ true,
-            &mut nodes_visited_pruning);
+    /// Like `solve`, this is only fast enough for small games.
+    pub fn tally(&self) -> Tally {
+        tally_state(self.state(), self.player())
+    }
 
-        if cfg!(debug_assertions) {
-            let mut nodes_visited_without_pruning = 0;
-            let move_without_pruning = self.find_move_index_internal(
-                max_depth,
-                /* prune = */ false,
-                &mut nodes_visited_without_pruning,
-            );
+    /// Counts how every game where the current player plays `mv` ends, or
+    /// returns `None` if `mv` isn't legal.
+    pub fn tally_move(&self, mv: S::Move) -> Option<Tally> {
+        let player = self.player();
+        self.state().successors(player).into_iter()
+            .find(|&(m, _)| m == mv)
+            .map(|(_, next)| tally_state(&next, player.next_player()))
+    }
 
-            // This is the whole point of it!
-            assert_eq!(move_pruning, move_without_pruning);
-            assert!(nodes_visited_pruning <= nodes_visited_without_pruning);
+    /// Returns each move of the current player along with its score,
+    /// searching up to `max_depth` levels from the current node.
+    ///
+    /// Returns no moves if the game is over, or if `max_depth` is zero.
+    pub fn evaluate_all_moves(&mut self, max_depth: usize) -> Vec<(S::Move, i8)> {
+        if self.current_state.state.is_over() || max_depth == 0 {
+            return vec![];
         }
+        let sign = sign(self.current_state.player);
+        let scores = self.child_scores(max_depth);
+        scores.into_iter().enumerate().map(|(i, score)| {
+            (self.move_for_index(i), score * sign)
+        }).collect()
+    }
 
-        move_pruning
+    /// Computes the exact score of each of the children of the current node,
+    /// from the point of view of the player to move, searching up to
+    /// `max_depth` levels from the current node.
+    fn child_scores(&mut self, max_depth: usize) -> Vec<i8> {
+        let mut search = self.start_search(/* prune = */ true);
+        let scores = self.current_state.ensure_children().iter_mut().map(|child| {
+            // Use a full window for every child, so that the scores are exact
+            // rather than bounds.
+            -child.negamax(max_depth - 1, -INFINITY, INFINITY, &mut search)
+        }).collect();
+        self.stats = self.finish_search(search);
+        scores
     }
 
     fn find_move_index_internal(
         &mut self,
         max_depth: usize,
-        prune: bool,
-        nodes_visited: &mut usize,
-    ) -> Option<usize>
+        order: &[usize],
+        search: &mut Search<S>,
+    ) -> Option<(usize, i8)>
     {
-        use std::i8;
-        *nodes_visited = 0;
-
-        if self.current_state.score() != 0 || max_depth == 0 {
+        if self.current_state.state.is_over() || max_depth == 0 {
             // It's over already, or we didn't have any chances of computing it.
             return None;
         }
 
-        let maximizing = self.current_state.player as i8 > 0;
-
-        let mut best = if maximizing { i8::MIN } else { i8::MAX };
+        let sign = sign(self.current_state.player);
+        let mut best = -INFINITY;
         let mut best_move = None;
 
-        let mut alpha = i8::MIN;
-        let mut beta = i8::MAX;
-
-        for (i, child) in self.current_state.ensure_children().iter_mut().enumerate() {
-            let child_score = child.minimax(
-                max_depth - 1,
-                alpha,
-                beta,
-                prune,
-                nodes_visited
-            );
-
-            let child_is_best_so_far = if maximizing {
-                child_score > best
-            } else {
-                child_score < best
-            };
+        let children = self.current_state.ensure_children();
+        for &i in order {
+            let child_score = -children[i].negamax(max_depth - 1, -INFINITY, -best, search);
+            if search.cancelled {
+                return None;
+            }
 
-            if child_is_best_so_far {
+            if child_score > best {
                 best = child_score;
-                if maximizing {
-                    alpha = best;
-                } else {
-                    beta = best;
-                }
                 best_move = Some(i);
             }
         }
 
-        best_move
+        best_move.map(|index| (index, best * sign))
     }
 }
 
 #[derive(Debug)]
-struct MiniMaxNode {
+struct MiniMaxNode<S: GameState> {
     /// The state this node represents.
-    state: State,
+    state: S,
     /// The player that has to move.
     player: CheckBox,
+    /// The move that led to this state, if any.
+    mv: Option<S::Move>,
     /// The children of the node. This will be `None` when they haven't been
     /// computed yet.
-    children: Option<Box<[MiniMaxNode]>>,
+    children: Option<Vec<MiniMaxNode<S>>>,
+    /// The score of this node in the last search that reached it, if any.
+    ///
+    /// This is only used to decide in which order to search the children,
+    /// since it may be a bound, or come from a shallower search.
+    last_score: Option<i8>,
+    /// The score of the last search that searched the children of this
+    /// node, and how, for the searches after it to reuse.
+    cached: Option<CachedScore>,
 }
 
-impl MiniMaxNode {
-    pub fn new(state: State, player: CheckBox) -> Self {
+impl<S: GameState> MiniMaxNode<S> {
+    pub fn new(state: S, player: CheckBox, mv: Option<S::Move>) -> Self {
         Self {
-            state: state,
-            player: player,
+            state,
+            player,
+            mv,
             children: None,
+            last_score: None,
+            cached: None,
         }
     }
 
@@ -200,86 +1288,301 @@ impl MiniMaxNode {
         Self {
             state: self.state.clone(),
             player: self.player,
+            mv: self.mv,
             children: self.children.take(),
+            last_score: self.last_score,
+            cached: self.cached,
         }
     }
 
-    fn minimax(
-        &mut self,
-        max_depth: usize,
-        mut alpha: i8,
-        mut beta: i8,
-        prune: bool,
-        nodes_visited: &mut usize,
-    ) -> i8 {
-        use std::{cmp, i8};
-        *nodes_visited += 1;
-
-        if max_depth == 0 {
-            return self.score();
+    /// Forgets the scores this subtree can reuse from earlier searches.
+    fn forget_scores(&mut self) {
+        self.cached = None;
+        if let Some(ref mut children) = self.children {
+            for child in children.iter_mut() {
+                child.forget_scores();
+            }
         }
+    }
 
-        if self.ensure_children().is_empty() {
-            return self.state.score();
+    /// Returns whether all the children of this node have been scored by a
+    /// previous search.
+    fn children_scored(&self) -> bool {
+        match self.children {
+            Some(ref children) => {
+                !children.is_empty() &&
+                    children.iter().all(|c| c.last_score.is_some())
+            }
+            None => false,
         }
+    }
 
-        let maximizing = self.player as i8 > 0;
+    /// Returns the indices of the children in the order they should be
+    /// searched: best first according to the last search, and in the order
+    /// they were generated otherwise.
+    fn search_order(&mut self) -> Vec<usize> {
         let children = self.ensure_children();
 
-        let mut best = if maximizing { i8::MIN } else { i8::MAX };
-        for child in children {
-            let val = child.minimax(
-                max_depth - 1,
+        let mut order = (0..children.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| {
+            match children[i].last_score {
+                // The worst for the child is the best for us.
+                Some(score) => (false, score),
+                // Children that were never scored go last.
+                None => (true, 0),
+            }
+        });
+        order
+    }
+
+    /// Returns the score of this node from the point of view of its player
+    /// to move, searching up to `max_depth` levels below it.
+    ///
+    /// With pruning, scores of `beta` or more are only a lower bound of the
+    /// real score.
+    fn negamax(
+        &mut self,
+        max_depth: usize,
+        alpha: i8,
+        beta: i8,
+        search: &mut Search<S>,
+    ) -> i8 {
+        search.ply += 1;
+        search.stats.max_depth = cmp::max(search.stats.max_depth, search.ply);
+        let score = self.negamax_internal(max_depth, alpha, beta, search);
+        // Whatever a cancelled search returns shouldn't order later ones.
+        if !search.cancelled {
+            self.last_score = Some(score);
+        }
+        if let Some(ref mut tracer) = search.tracer {
+            (tracer.0)(&TraceNode {
+                state: &self.state,
+                player: self.player,
+                mv: self.mv.expect("Only children are searched"),
+                ply: search.ply,
                 alpha,
                 beta,
-                prune,
-                nodes_visited
-            );
+                score,
+            });
+        }
+        search.ply -= 1;
+        score
+    }
+
+    fn negamax_internal(
+        &mut self,
+        max_depth: usize,
+        alpha: i8,
+        beta: i8,
+        search: &mut Search<S>,
+    ) -> i8 {
+        search.stats.nodes += 1;
+        if search.check_cancelled() {
+            return 0;
+        }
+
+        if max_depth == 0 {
+            // Telling whether the game goes on is slow, so don't bother once
+            // the parent stopped elsewhere already.
+            if search.horizon_leaves != search.subtree_horizon_leaves || !self.state.is_over() {
+                search.horizon_leaves += 1;
+            }
+            return self.score(&*search.evaluator);
+        }
+
+        if self.children.is_some() {
+            search.stats.tt_hits += 1;
+        }
+        // Searches without pruning are there to check the ones with it, so
+        // they don't take shortcuts.
+        let reusable = self.cached.filter(|_| search.prune).and_then(|cached| {
+            cached.reusable(max_depth, alpha, beta).map(|score| (score, cached.complete))
+        });
+        if let Some((score, complete)) = reusable {
+            search.stats.score_hits += 1;
+            if !complete {
+                search.horizon_leaves += 1;
+            }
+            return score;
+        }
+        if self.ensure_children_in(&mut search.pool).is_empty() {
+            return self.score(&*search.evaluator);
+        }
 
-            best = if maximizing {
-                cmp::max(val, best)
+        let horizon_leaves = search.horizon_leaves;
+        let outer_horizon_leaves = mem::replace(&mut search.subtree_horizon_leaves, horizon_leaves);
+        let score = self.search_children(max_depth, alpha, beta, search);
+        search.subtree_horizon_leaves = outer_horizon_leaves;
+        if !search.cancelled {
+            // Fail-soft scores out of the window are bounds.
+            let bound = if score <= alpha {
+                Bound::Upper
+            } else if score >= beta {
+                Bound::Lower
             } else {
-                cmp::min(val, best)
+                Bound::Exact
             };
+            self.cached = Some(CachedScore {
+                score,
+                bound,
+                depth: max_depth,
+                complete: search.horizon_leaves == horizon_leaves,
+            });
+        }
+        score
+    }
 
-            if maximizing {
-                if best > beta && prune {
-                    return best;
-                }
-                alpha = cmp::max(best, alpha);
-            } else {
-                if best < alpha && prune {
-                    return best;
-                }
-                beta = cmp::min(best, beta);
+    /// Searches the children of this node, which must have some, for
+    /// `negamax`.
+    fn search_children(
+        &mut self,
+        max_depth: usize,
+        mut alpha: i8,
+        beta: i8,
+        search: &mut Search<S>,
+    ) -> i8 {
+        let order = self.search_order();
+        let children = self.ensure_children();
+
+        let mut best = -INFINITY;
+        for i in order {
+            let val = -children[i].negamax(max_depth - 1, -beta, -alpha, search);
+            if search.cancelled {
+                return 0;
+            }
+
+            best = cmp::max(val, best);
+            if best > beta && search.prune {
+                search.stats.cutoffs += 1;
+                return best;
             }
+            alpha = cmp::max(best, alpha);
         }
         best
     }
 
     /// Ensures to have computed the children states for this state.
-    fn ensure_children(&mut self) -> &mut [MiniMaxNode] {
+    fn ensure_children(&mut self) -> &mut [MiniMaxNode<S>] {
+        self.ensure_children_in(&mut NodePool::default())
+    }
+
+    /// Like `ensure_children`, storing them in a vector from `pool`.
+    fn ensure_children_in(&mut self, pool: &mut NodePool<S>) -> &mut [MiniMaxNode<S>] {
         if self.children.is_none() {
-            let children =
-                if self.score() != 0 {
-                    // This is a game over state, so just prune here.
-                    vec![].into_boxed_slice()
-                } else {
-                    self.state.subsequent_states(self.player).map(|s| {
-                        MiniMaxNode::new(s, self.player.next_player())
-                    }).collect::<Vec<_>>().into_boxed_slice()
-                };
+            // This is empty for game over states, so we just prune there.
+            let next_player = self.player.next_player();
+            let mut children = pool.alloc();
+            children.extend(self.state.successors(self.player).into_iter().map(|(mv, s)| {
+                MiniMaxNode::new(s, next_player, Some(mv))
+            }));
+
+            // Search the most promising moves first, so that we prune more.
+            let state = &self.state;
+            children.sort_by_key(|child| state.move_priority(child.mv.unwrap()));
 
             self.children = Some(children);
         }
         self.children.as_mut().unwrap()
     }
 
-    fn score(&self) -> i8 {
-        self.state.score()
+    /// Returns the number of nodes of the subtree of this node.
+    fn count(&self) -> usize {
+        1 + self.children.as_ref().map_or(0, |children| children.iter().map(|c| c.count()).sum())
+    }
+
+    /// Adds the number of nodes at each level of this subtree to `levels`,
+    /// this node being at `level`.
+    fn count_levels(&self, level: usize, levels: &mut Vec<usize>) {
+        if levels.len() <= level {
+            levels.push(0);
+        }
+        levels[level] += 1;
+        if let Some(ref children) = self.children {
+            for child in children.iter() {
+                child.count_levels(level + 1, levels);
+            }
+        }
+    }
+
+    /// Drops the nodes more than `levels` levels below this one.
+    fn prune_below(&mut self, levels: usize, pool: &mut NodePool<S>) {
+        if levels == 0 {
+            if let Some(children) = self.children.take() {
+                pool.recycle(children);
+            }
+            return;
+        }
+        if let Some(ref mut children) = self.children {
+            for child in children.iter_mut() {
+                child.prune_below(levels - 1, pool);
+            }
+        }
+    }
+
+    /// Returns the heuristic score of this node from the point of view of
+    /// its player to move.
+    fn score(&self, evaluator: &dyn Evaluator<S>) -> i8 {
+        evaluator.evaluate(&self.state) * sign(self.player)
+    }
+
+    /// Writes the Graphviz nodes and edges of this subtree, numbering its
+    /// nodes from `next_id`, and returns the number of this node.
+    fn write_dot<W>(&self, next_id: &mut usize, levels: usize, dest: &mut W) -> Result<usize, fmt::Error>
+        where W: fmt::Write
+    {
+        let id = *next_id;
+        *next_id += 1;
+
+        let mut board = String::new();
+        self.state.dump(0, &mut board)?;
+        let mut label = String::new();
+        for line in board.lines() {
+            for c in line.chars() {
+                if c == '"' || c == '\\' {
+                    label.push('\\');
+                }
+                label.push(c);
+            }
+            // Left-justified lines, so that the rows of the board line up.
+            label.push_str("\\l");
+        }
+        match self.last_score {
+            Some(score) => {
+                label.push_str(&format!("score: {}\\l", score * sign(self.player)));
+                writeln!(dest, "  n{} [label=\"{}\"];", id, label)?;
+            }
+            // The root is never scored itself.
+            None if id == 0 => writeln!(dest, "  n{} [label=\"{}\"];", id, label)?,
+            None => writeln!(dest, "  n{} [label=\"{}\", style=dashed, fontcolor=gray];", id, label)?,
+        }
+
+        if levels == 0 {
+            return Ok(id);
+        }
+        if let Some(ref children) = self.children {
+            for child in children.iter() {
+                let child_id = child.write_dot(next_id, levels - 1, dest)?;
+                let style = if child.last_score.is_some() { "" } else { ", style=dashed" };
+                writeln!(
+                    dest,
+                    "  n{} -> n{} [label=\"{:?}\"{}];",
+                    id,
+                    child_id,
+                    child.mv.expect("Children have a move"),
+                    style,
+                )?;
+            }
+        }
+        Ok(id)
     }
 
-    pub fn dump<W>(&self, indent: usize, dest: &mut W) -> fmt::Result
+    pub fn dump<W>(
+        &self,
+        indent: usize,
+        levels: usize,
+        evaluator: &dyn Evaluator<S>,
+        dest: &mut W)
+        -> fmt::Result
         where W: fmt::Write
     {
         self.state.dump(indent, dest)?;
@@ -287,14 +1590,477 @@ impl MiniMaxNode {
             dest.write_char(' ')?;
         }
 
-        writeln!(dest, "score: {}", self.score())?;
+        writeln!(dest, "score: {}", evaluator.evaluate(&self.state))?;
+
+        if levels == 0 {
+            return Ok(());
+        }
 
         if let Some(ref children) = self.children {
             for child in children.iter() {
-                child.dump(indent + 1, dest)?;
+                child.dump(indent + 1, levels - 1, evaluator, dest)?;
             }
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use connect_four::ConnectFourState;
+    use cube::{CubeState, QubicState};
+    use number_scrabble::NumberScrabbleState;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use rules::{MisereState, NotaktoState, WildState};
+    use std::collections::{HashMap, HashSet};
+    use ultimate::UltimateState;
+
+    /// A board, as a key for the solver cache.
+    type Key = [i8; 9];
+
+    fn key(state: &State) -> Key {
+        let mut key = [0; 9];
+        for x in 0..3 {
+            for y in 0..3 {
+                key[x * 3 + y] = state.get(x, y) as i8;
+            }
+        }
+        key
+    }
+
+    /// A dumb exhaustive solver that knows the value of every position with
+    /// perfect play.
+    #[derive(Default)]
+    struct Solver {
+        values: HashMap<Key, i8>,
+    }
+
+    impl Solver {
+        fn value(&mut self, state: &State, player: CheckBox) -> i8 {
+            if let Some(value) = self.values.get(&key(state)) {
+                return *value;
+            }
+
+            let value = if state.is_over() {
+                state.score()
+            } else {
+                let values = state.subsequent_states(player).map(|s| {
+                    self.value(&s, player.next_player())
+                }).collect::<Vec<_>>();
+                if player as i8 > 0 {
+                    *values.iter().max().unwrap()
+                } else {
+                    *values.iter().min().unwrap()
+                }
+            };
+
+            self.values.insert(key(state), value);
+            value
+        }
+    }
+
+    /// A tree that asserts that every move the engine finds is optimal.
+    struct StrictTree<'a> {
+        tree: MiniMaxTree,
+        solver: &'a mut Solver,
+    }
+
+    impl<'a> StrictTree<'a> {
+        fn find_move_index(&mut self) -> Option<usize> {
+            let player = self.tree.player();
+            let state = self.tree.state().clone();
+            let index = self.tree.find_move_index(State::depth_limit()).index()?;
+
+            let expected = self.solver.value(&state, player);
+            let child = &self.tree.current_state.ensure_children()[index].state;
+            let actual = self.solver.value(child, player.next_player());
+            if actual != expected {
+                let mut dump = String::new();
+                state.dump(0, &mut dump).unwrap();
+                child.dump(0, &mut dump).unwrap();
+                panic!("Suboptimal move, expected {}, got {}:\n{}",
+                       expected, actual, dump);
+            }
+
+            Some(index)
+        }
+    }
+
+    /// Calls `f` with the moves leading to every reachable position.
+    fn for_each_position<F>(f: &mut F)
+        where F: FnMut(&[(usize, usize)]),
+    {
+        fn visit<F>(
+            state: &State,
+            player: CheckBox,
+            moves: &mut Vec<(usize, usize)>,
+            seen: &mut HashSet<Key>,
+            f: &mut F,
+        )
+            where F: FnMut(&[(usize, usize)]),
+        {
+            if !seen.insert(key(state)) {
+                return;
+            }
+            f(moves);
+            if state.score() != 0 {
+                return;
+            }
+            for s in state.subsequent_states(player) {
+                moves.push(state.changed_square(&s).unwrap());
+                visit(&s, player.next_player(), moves, seen, f);
+                moves.pop();
+            }
+        }
+
+        let mut seen = HashSet::new();
+        visit(&State::initial(), CheckBox::X, &mut vec![], &mut seen, f);
+    }
+
+    #[test]
+    fn plays_optimally_from_every_position() {
+        let mut solver = Solver::default();
+        let mut positions = 0;
+        for_each_position(&mut |moves| {
+            let mut tree = MiniMaxTree::new(CheckBox::X);
+            for &mv in moves {
+                tree.choose(mv).unwrap();
+            }
+            let mut strict = StrictTree { tree, solver: &mut solver };
+            strict.find_move_index();
+            positions += 1;
+        });
+
+        // All the distinct reachable positions, including the empty board.
+        assert_eq!(positions, 5478);
+    }
+
+    #[test]
+    fn plays_optimally_against_itself() {
+        let mut solver = Solver::default();
+        let mut strict = StrictTree {
+            tree: MiniMaxTree::new(CheckBox::X),
+            solver: &mut solver,
+        };
+        while let Some(index) = strict.find_move_index() {
+            strict.tree.choose_with_index(index);
+        }
+        assert_eq!(strict.tree.state().score(), 0);
+    }
+
+    #[test]
+    fn solves_every_position() {
+        let mut solver = Solver::default();
+        for_each_position(&mut |moves| {
+            let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+            for &mv in moves {
+                tree.choose(mv).unwrap();
+            }
+            let value = solver.value(tree.state(), tree.player()) * tree.player() as i8;
+            match tree.solve() {
+                Solution::Win(_) => assert!(value > 0, "{:?}", moves),
+                Solution::Draw => assert_eq!(value, 0, "{:?}", moves),
+                Solution::Loss(_) => assert!(value < 0, "{:?}", moves),
+            }
+        });
+
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        assert_eq!(tree.solve(), Solution::Draw);
+        // X can threaten two lines at once from here.
+        for &mv in &[(0, 0), (1, 0), (1, 1), (2, 2)] {
+            tree.choose(mv).unwrap();
+        }
+        assert_eq!(tree.solve(), Solution::Win(3));
+        tree.choose((0, 2)).unwrap();
+        assert_eq!(tree.solve(), Solution::Loss(2));
+    }
+
+    #[test]
+    fn keeps_the_tree_within_the_node_limit() {
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        tree.find_best_move(State::depth_limit());
+        let searched = tree.retained_nodes();
+
+        tree.set_node_limit(Some(100));
+        // The children and grandchildren are kept anyway.
+        assert_eq!(tree.retained_nodes(), 1 + 9 + 9 * 8);
+        let (best, score) = tree.find_best_move(State::depth_limit()).unwrap();
+
+        tree.choose_with_index(best);
+        assert!(!tree.pool.free.is_empty());
+        assert!(tree.retained_nodes() <= 100);
+        tree.set_node_limit(None);
+        let mut fresh = MiniMaxTree::<State>::new(CheckBox::X);
+        assert_eq!(fresh.find_best_move(State::depth_limit()).map(|(_, s)| s), Some(score));
+        assert_eq!(fresh.retained_nodes(), searched);
+    }
+
+    #[test]
+    fn stops_cancelled_searches() {
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        let cancel = CancelToken::new();
+        tree.set_cancel_token(Some(cancel.clone()));
+        // Cancel from inside the search, to stop it half way.
+        tree.set_tracer(Some(Box::new(move |_| cancel.cancel())));
+        assert_eq!(tree.find_best_move(State::depth_limit()), None);
+        assert!(tree.nodes_visited() <= CANCEL_CHECK_NODES);
+        assert_eq!(tree.find_move_index(State::depth_limit()), SearchOutcome::Cancelled);
+
+        tree.set_tracer(None);
+        tree.set_cancel_token(None);
+        assert!(tree.find_move_index(State::depth_limit()).index().is_some());
+    }
+
+    #[test]
+    fn validates_depths() {
+        assert_eq!(SearchConfig::new(0, 9), Err(DepthError::TooShallow(0)));
+        assert_eq!(SearchConfig::parse("deep", 9), Err(DepthError::NotANumber("deep".to_owned())));
+        assert_eq!(SearchConfig::parse(" 4", 9).map(|c| (c.depth(), c.is_clamped())), Ok((4, false)));
+        assert_eq!(SearchConfig::new(20, 9).map(|c| (c.depth(), c.is_clamped())), Ok((9, true)));
+        assert_eq!(SearchConfig::clamped(0, 9).depth(), SearchConfig::MIN_DEPTH);
+        assert_eq!(DepthError::TooShallow(0).to_string(), "not a depth: 0");
+    }
+
+    #[test]
+    fn tallies_every_game() {
+        let tree = MiniMaxTree::<State>::new(CheckBox::X);
+        let all = tree.tally();
+        assert_eq!(all, Tally { x_wins: 131184, o_wins: 77904, draws: 46080 });
+        assert_eq!(all.games(), 255168);
+
+        // The center wins X the most games and loses it the fewest.
+        let center = tree.tally_move((1, 1)).unwrap();
+        assert_eq!(center, Tally { x_wins: 15648, o_wins: 5616, draws: 4608 });
+        let corner = tree.tally_move((0, 0)).unwrap();
+        assert_eq!(corner, Tally { x_wins: 14652, o_wins: 7896, draws: 5184 });
+        assert_eq!(tree.tally_move((3, 3)), None);
+    }
+
+    #[test]
+    fn writes_the_tree_as_dot() {
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        tree.find_best_move(9).unwrap();
+        let mut dot = String::new();
+        tree.write_dot(4, &mut dot).unwrap();
+        assert!(dot.starts_with("digraph search {\n"));
+        assert!(dot.ends_with("}\n"));
+
+        // An edge to every node but the root.
+        let nodes = dot.lines().filter(|l| l.contains(" [label=") && !l.contains("->")).count();
+        let edges = dot.lines().filter(|l| l.contains("->")).count();
+        assert_eq!(edges, nodes - 1);
+        assert!(dot.contains("  n0 -> n1 [label=\"(1, 1)\"];"));
+        assert!(dot.contains("score: 0\\l"));
+        // With pruning, some replies deeper down are never looked at.
+        assert!(dot.contains("style=dashed"));
+    }
+
+    #[test]
+    fn reports_search_stats() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let traced = Arc::new(AtomicUsize::new(0));
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        {
+            let traced = traced.clone();
+            tree.set_tracer(Some(Box::new(move |node: &TraceNode<State>| {
+                assert!(node.ply >= 1 && node.ply <= 9);
+                traced.fetch_add(1, Ordering::SeqCst);
+            })));
+        }
+
+        let (_, _, stats) = tree.find_best_move_with_stats(9).unwrap();
+        assert_eq!(stats.nodes, tree.nodes_visited());
+        assert_eq!(stats.nodes, traced.load(Ordering::SeqCst));
+        assert_eq!(stats.max_depth, 9);
+        assert!(stats.cutoffs > 0);
+        assert_eq!(stats.tt_hits, 0);
+
+        // The second search walks the tree the first one built.
+        tree.set_tracer(None);
+        let (_, _, again) = tree.find_best_move_with_stats(9).unwrap();
+        assert!(again.tt_hits > 0);
+        assert_eq!(traced.load(Ordering::SeqCst), stats.nodes);
+    }
+
+    #[test]
+    fn reuses_the_scores_of_earlier_searches() {
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        tree.choose((0, 0)).unwrap();
+        let (index, _) = tree.find_best_move(9).unwrap();
+        tree.choose_with_index(index);
+        tree.choose((2, 2)).unwrap();
+
+        // The game ends within the levels the first search looked at, so its
+        // scores are as good as new ones.
+        let mut fresh = MiniMaxTree::<State>::from_state(tree.state().clone(), tree.player());
+        let expected = fresh.find_best_move(9);
+        assert_eq!(tree.find_best_move(9), expected);
+        assert!(tree.search_stats().score_hits > 0);
+        assert!(tree.nodes_visited() < fresh.nodes_visited());
+
+        // Searching again to the same depth only looks at the moves.
+        let mut tree = MiniMaxTree::<ConnectFourState>::new(CheckBox::X);
+        let expected = tree.find_best_move(6);
+        assert_eq!(tree.find_best_move(6), expected);
+        assert_eq!(tree.search_stats().score_hits, 7);
+        assert_eq!(tree.nodes_visited(), 7);
+
+        // Shallower searches don't reuse the deeper scores.
+        let mut fresh = MiniMaxTree::<ConnectFourState>::new(CheckBox::X);
+        assert_eq!(tree.find_best_move(4), fresh.find_best_move(4));
+        assert_eq!(tree.search_stats().score_hits, 0);
+
+        // Nor are the scores of another evaluator.
+        tree.set_evaluator(Arc::new(DefaultEvaluator));
+        tree.find_best_move(6).unwrap();
+        assert_eq!(tree.search_stats().score_hits, 0);
+    }
+
+    #[test]
+    fn reports_the_principal_variation() {
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        // X wins in three plies, with a fork.
+        for &mv in &[(0, 0), (1, 0), (1, 1), (2, 2)] {
+            tree.choose(mv).unwrap();
+        }
+        let (mv, pv, score) = tree.find_move_with_pv(9).unwrap();
+        assert_eq!(score, CheckBox::X as i8);
+        assert_eq!(pv.len(), 3);
+        assert_eq!(pv[0], mv);
+        for mv in pv {
+            tree.choose(mv).unwrap();
+        }
+        assert_eq!(tree.state().result(), GameResult::XWins);
+
+        // The line is as long as the search looked ahead.
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        assert_eq!(tree.find_move_with_pv(2).unwrap().1.len(), 2);
+        assert!(tree.find_move_with_pv(0).is_none());
+    }
+
+    #[test]
+    fn counts_exact_moves_to_draw() {
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        // O has to block at (0, 2), and then at (1, 0) after X blocks the
+        // diagonal.
+        for &mv in &[(0, 0), (1, 1), (0, 1)] {
+            tree.choose(mv).unwrap();
+        }
+        let pv = tree.find_move_with_pv(9).unwrap().1;
+        assert_eq!(tree.exact_moves_to_draw(&pv), Some(2));
+        // Not far enough to see the end of the game.
+        assert_eq!(tree.exact_moves_to_draw(&pv[..3]), None);
+
+        // Playing an edge loses against a corner opening.
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        tree.choose((0, 0)).unwrap();
+        tree.choose((1, 0)).unwrap();
+        let pv = tree.find_move_with_pv(9).unwrap().1;
+        assert_eq!(tree.exact_moves_to_draw(&pv), None);
+    }
+
+    #[test]
+    fn evaluates_without_moving() {
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        assert_eq!(tree.evaluate(9), 0);
+
+        // Playing an edge loses against a corner opening, and scores are from
+        // O's point of view.
+        tree.choose((0, 0)).unwrap();
+        tree.choose((1, 0)).unwrap();
+        let state = tree.state().clone();
+        assert_eq!(tree.evaluate(9), CheckBox::X as i8);
+        assert_eq!(tree.evaluate(0), state.evaluate());
+        assert_eq!(tree.state(), &state);
+        assert_eq!(tree.player(), CheckBox::X);
+
+        for &mv in &[(1, 1), (0, 1), (2, 2)] {
+            tree.choose(mv).unwrap();
+        }
+        assert!(tree.state().is_over());
+        assert_eq!(tree.evaluate(9), CheckBox::X as i8);
+    }
+
+    #[test]
+    fn searches_within_the_node_budget() {
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        tree.choose((0, 0)).unwrap();
+        tree.find_move_index_within_budget(1, 9).unwrap();
+        let shallow_nodes = tree.nodes_visited();
+        let mut full = MiniMaxTree::<State>::new(CheckBox::X);
+        full.choose((0, 0)).unwrap();
+        full.find_best_move(9).unwrap();
+        assert!(shallow_nodes < full.nodes_visited());
+
+        // Enough nodes to search the whole game play as well as that.
+        let index = tree.find_move_index_within_budget(usize::MAX, 9).unwrap();
+        let mv = tree.move_for_index(index);
+        let scores = tree.evaluate_all_moves(9);
+        let best = scores.iter().map(|s| s.1).max().unwrap();
+        assert_eq!(scores.iter().find(|s| s.0 == mv).unwrap().1, best);
+    }
+
+    /// Checks that pruning doesn't change the move the engine finds, and
+    /// doesn't visit more nodes, on `positions` random positions of up to
+    /// `max_plies` plies, searching `max_depth` levels.
+    fn check_pruning<S: GameState>(positions: usize, max_plies: usize, max_depth: usize) {
+        // Always the same positions, so that failures are reproducible.
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..positions {
+            let mut tree = MiniMaxTree::<S>::new(CheckBox::X);
+            for _ in 0..rng.gen_range(0..max_plies + 1) {
+                let successors = tree.state().successors(tree.player());
+                if successors.is_empty() {
+                    break;
+                }
+                let mv = successors[rng.gen_range(0..successors.len())].0;
+                tree.choose(mv).unwrap();
+            }
+
+            // Like `find_best_move`, both searches from the same order.
+            let order = tree.current_state.search_order();
+            let mut search = tree.start_search(/* prune = */ true);
+            let pruning = tree.find_move_index_internal(max_depth, &order, &mut search);
+            let nodes_pruning = tree.finish_search(search).nodes;
+            let mut search = tree.start_search(/* prune = */ false);
+            let without_pruning = tree.find_move_index_internal(max_depth, &order, &mut search);
+            let nodes_without_pruning = tree.finish_search(search).nodes;
+
+            assert_eq!(pruning, without_pruning, "In {:?}", tree.state());
+            assert!(nodes_pruning <= nodes_without_pruning, "In {:?}", tree.state());
+        }
+    }
+
+    #[test]
+    fn pruning_finds_the_same_moves() {
+        check_pruning::<State>(100, 6, 9);
+        check_pruning::<NumberScrabbleState>(20, 6, 9);
+        check_pruning::<MisereState>(20, 6, 9);
+        check_pruning::<NotaktoState>(20, 4, 9);
+        check_pruning::<WildState>(10, 5, 3);
+        check_pruning::<ConnectFourState>(10, 10, 4);
+        check_pruning::<UltimateState>(10, 10, 3);
+        check_pruning::<CubeState>(10, 6, 3);
+        check_pruning::<QubicState>(5, 10, 2);
+    }
+
+    #[test]
+    fn randomizes_among_the_best_moves() {
+        let pick = |seed| {
+            let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+            tree.choose((1, 1)).unwrap();
+            let index = tree.find_random_best_move_index(9, &mut StdRng::seed_from_u64(seed)).unwrap();
+            tree.move_for_index(index)
+        };
+
+        // Only the corners draw against a center opening.
+        let picks = (0..20).map(pick).collect::<HashSet<_>>();
+        let corners = [(0, 0), (0, 2), (2, 0), (2, 2)].iter().cloned().collect::<HashSet<_>>();
+        assert_eq!(picks, corners);
+        assert_eq!(pick(7), pick(7));
+    }
+}
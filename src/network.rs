@@ -0,0 +1,323 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Two players on different machines, over TCP.
+//!
+//! The protocol is line-based. Once a guest connects, the host says what
+//! they're playing, and then each side sends their moves as the squares they
+//! click:
+//!
+//! ```text
+//! hello 2 tictactoe X
+//! move 1,1
+//! move 0,2
+//! bye Closed the window
+//! ```
+//!
+//! The `2` is the version of the protocol, and `X` the mark of the host, who
+//! moves first. Neither side trusts the other's moves: they're played on the
+//! local board, and an illegal one ends the connection.
+//!
+//! Either side can also `resign`, or offer a draw with `draw offer`, which
+//! the other side answers with `draw accept` or `draw decline`. These came
+//! with the second version, so builds from before them refuse to play
+//! rather than failing once someone resigns.
+
+use state::CheckBox;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use variant::Variant;
+
+/// The version of the protocol this build speaks.
+const PROTOCOL_VERSION: u32 = 2;
+
+/// The longest line the opponent can send, far longer than any message.
+const MAX_LINE_BYTES: usize = 1024;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Message {
+    /// The game the host wants to play, and the host's mark.
+    Hello(Variant, CheckBox),
+    /// The sender clicked the square in column `x` and row `y`.
+    Move(usize, usize),
+    Resign,
+    OfferDraw,
+    /// The answer to a draw offer, which is whether it's accepted.
+    AnswerDraw(bool),
+    /// The sender is leaving, and why.
+    Bye(String),
+}
+
+impl Message {
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.trim().splitn(2, ' ');
+        let command = words.next()?;
+        let rest = words.next().unwrap_or("");
+        match command {
+            "hello" => {
+                let mut args = rest.split_whitespace();
+                if args.next()?.parse::<u32>().ok()? != PROTOCOL_VERSION {
+                    return None;
+                }
+                let variant = Variant::from_id(args.next()?)?;
+                let mark = match args.next()? {
+                    "X" => CheckBox::X,
+                    "O" => CheckBox::O,
+                    _ => return None,
+                };
+                Some(Message::Hello(variant, mark))
+            }
+            "move" => {
+                let mut coords = rest.splitn(2, ',').map(|c| c.trim().parse::<usize>().ok());
+                match (coords.next(), coords.next()) {
+                    (Some(Some(x)), Some(Some(y))) => Some(Message::Move(x, y)),
+                    _ => None,
+                }
+            }
+            "resign" if rest.is_empty() => Some(Message::Resign),
+            "draw" => match rest.trim() {
+                "offer" => Some(Message::OfferDraw),
+                "accept" => Some(Message::AnswerDraw(true)),
+                "decline" => Some(Message::AnswerDraw(false)),
+                _ => None,
+            },
+            "bye" => Some(Message::Bye(rest.to_owned())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Message::Hello(variant, mark) => {
+                write!(f, "hello {} {} {}", PROTOCOL_VERSION, variant.id(), mark.dump_char())
+            }
+            Message::Move(x, y) => write!(f, "move {},{}", x, y),
+            Message::Resign => write!(f, "resign"),
+            Message::OfferDraw => write!(f, "draw offer"),
+            Message::AnswerDraw(true) => write!(f, "draw accept"),
+            Message::AnswerDraw(false) => write!(f, "draw decline"),
+            Message::Bye(ref reason) => write!(f, "bye {}", reason),
+        }
+    }
+}
+
+/// Something that happened on the network.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Event {
+    /// A guest connected to the host, from the given address.
+    Connected(String),
+    Message(Message),
+    /// The connection is gone, and why.
+    Closed(String),
+}
+
+#[derive(Debug)]
+enum Connection {
+    /// Waiting for a guest on the given port.
+    Listening(TcpListener, u16),
+    /// Talking to the given address, with what's been read of the next
+    /// line.
+    Connected(TcpStream, String, Vec<u8>),
+    Closed(String),
+}
+
+/// The connection to the other player.
+#[derive(Debug)]
+pub struct Network {
+    connection: Connection,
+    /// Whether we're the host, rather than the guest.
+    host: bool,
+}
+
+impl Network {
+    /// Waits for a guest on `port`, on every interface.
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            connection: Connection::Listening(listener, port),
+            host: true,
+        })
+    }
+
+    /// Connects to a host at `address`, like `example.com:9999`.
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let mut last_err = io::Error::new(io::ErrorKind::NotFound, "no such host");
+        for addr in address.to_socket_addrs()? {
+            match TcpStream::connect(addr) {
+                Ok(stream) => {
+                    stream.set_nonblocking(true)?;
+                    let connection = Connection::Connected(stream, addr.to_string(), vec![]);
+                    return Ok(Self {
+                        connection,
+                        host: false,
+                    });
+                }
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.host
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(self.connection, Connection::Connected(..))
+    }
+
+    /// Describes the connection, for the user.
+    pub fn status(&self) -> String {
+        match self.connection {
+            Connection::Listening(_, port) => format!("Waiting for an opponent on port {}.", port),
+            Connection::Connected(_, ref addr, _) => format!("Playing against {}.", addr),
+            Connection::Closed(ref reason) => format!("Disconnected: {}", reason),
+        }
+    }
+
+    /// Sends `message` to the other player.
+    pub fn send(&mut self, message: &Message) {
+        let result = match self.connection {
+            Connection::Connected(ref mut stream, _, _) => writeln!(stream, "{}", message),
+            _ => return,
+        };
+        if let Err(err) = result {
+            self.connection = Connection::Closed(err.to_string());
+        }
+    }
+
+    /// Says bye to the other player, if connected, and stops listening.
+    pub fn close(&mut self, reason: &str) {
+        self.send(&Message::Bye(reason.to_owned()));
+        self.connection = Connection::Closed(reason.to_owned());
+    }
+
+    /// Returns what happened since the last poll, without blocking.
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut events = vec![];
+        if let Connection::Listening(ref listener, _) = self.connection {
+            match listener.accept() {
+                Ok((stream, addr)) => match stream.set_nonblocking(true) {
+                    Ok(()) => {
+                        events.push(Event::Connected(addr.to_string()));
+                        self.connection = Connection::Connected(stream, addr.to_string(), vec![]);
+                    }
+                    Err(err) => events.push(Event::Closed(err.to_string())),
+                },
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return events,
+                Err(err) => events.push(Event::Closed(err.to_string())),
+            }
+        }
+
+        let mut closed = None;
+        if let Connection::Connected(ref mut stream, _, ref mut buffer) = self.connection {
+            let mut chunk = [0; 512];
+            loop {
+                match stream.read(&mut chunk) {
+                    Ok(0) => {
+                        closed = Some("the opponent left".to_owned());
+                        break;
+                    }
+                    Ok(n) => {
+                        buffer.extend_from_slice(&chunk[..n]);
+                        // Rather than waiting forever for the end of it.
+                        let line_start = buffer.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+                        if buffer.len() - line_start > MAX_LINE_BYTES {
+                            closed = Some("the opponent sent a line too long".to_owned());
+                            break;
+                        }
+                    }
+                    Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    Err(err) => {
+                        closed = Some(err.to_string());
+                        break;
+                    }
+                }
+            }
+
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                let line = buffer.drain(..end + 1).collect::<Vec<_>>();
+                let message = String::from_utf8(line).ok().and_then(|l| Message::parse(&l));
+                match message {
+                    Some(Message::Bye(reason)) => {
+                        closed = Some(format!("the opponent left ({})", reason));
+                        break;
+                    }
+                    Some(message) => events.push(Event::Message(message)),
+                    None => {
+                        closed = Some("the opponent sent something unexpected".to_owned());
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let Some(reason) = closed {
+            events.push(Event::Closed(reason.clone()));
+            self.connection = Connection::Closed(reason);
+        } else if let Some(Event::Closed(reason)) = events.last() {
+            self.connection = Connection::Closed(reason.clone());
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = [
+            Message::Hello(Variant::ConnectFour, CheckBox::O),
+            Message::Move(3, 0),
+            Message::Resign,
+            Message::OfferDraw,
+            Message::AnswerDraw(false),
+            Message::Bye("Closed the window".to_owned()),
+        ];
+        for message in &messages {
+            assert_eq!(Message::parse(&message.to_string()).as_ref(), Some(message));
+        }
+        assert_eq!(Message::parse("hello 1 tictactoe X"), None);
+        assert_eq!(Message::parse("hello 3 tictactoe X"), None);
+        assert_eq!(Message::parse("move 1"), None);
+        assert_eq!(Message::parse("draw maybe"), None);
+        assert_eq!(Message::parse("takeback"), None);
+    }
+
+    #[test]
+    fn closes_on_lines_too_long() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let mut network = Network::connect(&listener.local_addr().unwrap().to_string()).unwrap();
+        let (mut host, _) = listener.accept().unwrap();
+        host.write_all(format!("{}\nmove {}", Message::Move(1, 1), "1".repeat(MAX_LINE_BYTES)).as_bytes()).unwrap();
+        let mut events = vec![];
+        while network.is_connected() {
+            events.extend(network.poll());
+        }
+        assert_eq!(events, [
+            Event::Message(Message::Move(1, 1)),
+            Event::Closed("the opponent sent a line too long".to_owned()),
+        ]);
+    }
+}
@@ -0,0 +1,236 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Number scrabble: players take turns picking numbers from 1 to 9, and the
+//! first one to hold three numbers summing to 15 wins.
+//!
+//! It is tic-tac-toe in disguise: lay the numbers out as a magic square, and
+//! the triples summing to 15 are exactly its rows, columns and diagonals.
+
+use game_state::{GameState, GridState, MoveError, Savable};
+use state::{self, CheckBox, State};
+use std::fmt;
+use variant::Variant;
+
+/// The magic square, by row and then column.
+const MAGIC_SQUARE: [[u8; 3]; 3] = [
+    [2, 7, 6],
+    [9, 5, 1],
+    [4, 3, 8],
+];
+
+/// Returns the number at column `x` and row `y` of the magic square.
+pub fn number_for_square(x: usize, y: usize) -> u8 {
+    MAGIC_SQUARE[y][x]
+}
+
+/// The inverse of `number_for_square`.
+pub fn square_for_number(number: u8) -> (usize, usize) {
+    for x in 0..3 {
+        for y in 0..3 {
+            if number_for_square(x, y) == number {
+                return (x, y);
+            }
+        }
+    }
+    panic!("{} is not a number of the game", number)
+}
+
+/// Returns whether three of `numbers` sum to 15.
+fn has_fifteen(numbers: &[u8]) -> bool {
+    numbers.iter().enumerate().any(|(i, a)| {
+        numbers[i + 1..].iter().enumerate().any(|(j, b)| {
+            numbers[i + j + 2..].iter().any(|c| a + b + c == 15)
+        })
+    })
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct NumberScrabbleState {
+    /// Who picked each number, starting from 1.
+    picks: [CheckBox; 9],
+}
+
+impl NumberScrabbleState {
+    /// Returns the numbers `player` picked, in increasing order.
+    pub fn numbers(&self, player: CheckBox) -> Vec<u8> {
+        (1..10).filter(|n| self.picks[*n as usize - 1] == player).collect()
+    }
+
+    /// Returns the number scrabble position that `state` is the tic-tac-toe
+    /// version of.
+    pub fn from_tic_tac_toe(state: &State) -> Self {
+        let mut picks = [CheckBox::Empty; 9];
+        for x in 0..3 {
+            for y in 0..3 {
+                picks[number_for_square(x, y) as usize - 1] = state.get(x, y);
+            }
+        }
+        Self { picks }
+    }
+
+    /// The inverse of `from_tic_tac_toe`.
+    pub fn to_tic_tac_toe(&self) -> State {
+        let mut state = State::initial();
+        for n in 1..10 {
+            let (x, y) = square_for_number(n);
+            state.set(x, y, self.picks[n as usize - 1]);
+        }
+        state
+    }
+
+    /// Renders the tic-tac-toe board, the magic square, and the numbers each
+    /// player picked, side by side.
+    pub fn side_by_side(&self) -> String {
+        let board = self.to_tic_tac_toe();
+        let mut ret = String::new();
+        for y in 0..3 {
+            let marks = (0..3).map(|x| board.get(x, y).dump_char().to_string());
+            let numbers = (0..3).map(|x| number_for_square(x, y).to_string());
+            ret.push_str(&marks.collect::<Vec<_>>().join(" "));
+            ret.push_str("    ");
+            ret.push_str(&numbers.collect::<Vec<_>>().join(" "));
+            match y {
+                0 => ret.push_str(&format!("    X: {:?}", self.numbers(CheckBox::X))),
+                1 => ret.push_str(&format!("    O: {:?}", self.numbers(CheckBox::O))),
+                _ => {}
+            }
+            ret.push('\n');
+        }
+        ret
+    }
+}
+
+impl GameState for NumberScrabbleState {
+    /// The number to pick.
+    type Move = u8;
+
+    fn initial() -> Self {
+        Self { picks: [CheckBox::Empty; 9] }
+    }
+
+    fn winner(&self) -> CheckBox {
+        if has_fifteen(&self.numbers(CheckBox::X)) {
+            CheckBox::X
+        } else if has_fifteen(&self.numbers(CheckBox::O)) {
+            CheckBox::O
+        } else {
+            CheckBox::Empty
+        }
+    }
+
+    fn successors(&self, player: CheckBox) -> Vec<(Self::Move, Self)> {
+        if self.winner() != CheckBox::Empty {
+            return vec![];
+        }
+        self.numbers(CheckBox::Empty).into_iter().map(|n| {
+            let mut ret = self.clone();
+            ret.picks[n as usize - 1] = player;
+            (n, ret)
+        }).collect()
+    }
+
+    /// Like in tic-tac-toe: the numbers in more triples first.
+    fn move_priority(&self, mv: Self::Move) -> u8 {
+        let (x, y) = square_for_number(mv);
+        state::square_priority(x, y)
+    }
+
+    fn move_error(&self, number: Self::Move) -> MoveError {
+        if !(1..=9).contains(&number) {
+            MoveError::OutOfBounds
+        } else if self.picks[number as usize - 1] != CheckBox::Empty {
+            MoveError::Occupied
+        } else {
+            MoveError::Illegal
+        }
+    }
+
+    fn is_center_move(&self, number: Self::Move) -> bool {
+        // The center of the magic square.
+        number == number_for_square(1, 1)
+    }
+
+    fn depth_limit() -> usize {
+        9
+    }
+
+    fn is_solvable() -> bool {
+        true
+    }
+
+    fn dump<W>(&self, indent: usize, dest: &mut W) -> fmt::Result
+        where W: fmt::Write,
+    {
+        for &player in &[CheckBox::X, CheckBox::O] {
+            for _ in 0..indent {
+                dest.write_char(' ')?;
+            }
+            writeln!(dest, "{}: {:?}", player.dump_char(), self.numbers(player))?;
+        }
+        Ok(())
+    }
+}
+
+/// The numbers are laid out in a single row, in order.
+impl GridState for NumberScrabbleState {
+    fn grid_size(&self) -> (usize, usize) {
+        (9, 1)
+    }
+
+    fn mark(&self, x: usize, _y: usize) -> CheckBox {
+        self.picks[x]
+    }
+
+    fn square_label(&self, x: usize, _y: usize) -> Option<String> {
+        Some((x + 1).to_string())
+    }
+
+    fn move_for_square(&self, x: usize, _y: usize) -> Option<Self::Move> {
+        if self.picks[x] == CheckBox::Empty && self.winner() == CheckBox::Empty {
+            Some(x as u8 + 1)
+        } else {
+            None
+        }
+    }
+
+    /// The numbers of the winning line of the tic-tac-toe version, which
+    /// might not be the only triple summing to 15, but is one of them.
+    fn winning_squares(&self) -> Vec<(usize, usize)> {
+        self.to_tic_tac_toe().winning_line().map_or(vec![], |line| {
+            line.iter().map(|&(x, y)| (number_for_square(x, y) as usize - 1, 0)).collect()
+        })
+    }
+
+    fn mirror(&self) -> Option<String> {
+        Some(self.side_by_side())
+    }
+}
+
+impl Savable for NumberScrabbleState {
+    fn variant() -> Variant {
+        Variant::NumberScrabble
+    }
+
+    fn write_move(number: Self::Move) -> String {
+        number.to_string()
+    }
+
+    fn read_move(s: &str) -> Option<Self::Move> {
+        s.parse().ok()
+    }
+}
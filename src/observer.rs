@@ -0,0 +1,477 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A stream of what happens in the game, for external tools to show it.
+//!
+//! Clients connect to a Unix socket and read one JSON object per line:
+//!
+//! ```text
+//! {"event":"game","variant":"tictactoe","cols":3,"rows":3,"ply":0,"marks":[null,...]}
+//! {"event":"diff","ply":1,"changes":[{"x":1,"y":1,"mark":"X"}]}
+//! {"event":"result","result":"x-wins"}
+//! ```
+//!
+//! A `game` event has the whole board, in rows, and is sent when a client
+//! connects and when a new game starts. Then every move or undo is a `diff`
+//! of the squares that changed, and a `result` is sent when the game ends.
+//!
+//! The same events go to the spectators of `spectator`, over a WebSocket.
+
+use game::Position;
+use game_state::GameResult;
+use state::CheckBox;
+use std::env;
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+use std::path::PathBuf;
+use variant::Variant;
+
+/// Where the snapshots of a game go, to tell someone else about it.
+pub trait Sink {
+    /// Tells what changed since the last update.
+    fn update(&mut self, snapshot: Snapshot);
+
+    /// Makes the next update start a new game, instead of being a diff of
+    /// the previous one.
+    fn reset(&mut self);
+}
+
+/// Returns where the socket goes if the user doesn't choose.
+pub fn default_path() -> PathBuf {
+    let dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => env::temp_dir(),
+    };
+    dir.join("tic-tac-minimax.sock")
+}
+
+fn mark_json(mark: CheckBox) -> &'static str {
+    match mark {
+        CheckBox::Empty => "null",
+        CheckBox::X => "\"X\"",
+        CheckBox::O => "\"O\"",
+    }
+}
+
+/// What an observer knows of a game at some point.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Snapshot {
+    variant: Variant,
+    cols: usize,
+    rows: usize,
+    /// The number of moves played.
+    ply: usize,
+    /// The marks of the board, in rows.
+    marks: Vec<CheckBox>,
+    result: GameResult,
+}
+
+impl Snapshot {
+    pub fn new(variant: Variant, position: &dyn Position, ply: usize, result: GameResult) -> Self {
+        let (cols, rows) = position.grid_size();
+        let mut marks = Vec::with_capacity(cols * rows);
+        for y in 0..rows {
+            for x in 0..cols {
+                marks.push(position.mark(x, y));
+            }
+        }
+        Self {
+            variant,
+            cols,
+            rows,
+            ply,
+            marks,
+            result,
+        }
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Returns the number of columns and rows of the grid.
+    pub fn grid_size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    /// Returns the mark in column `x` and row `y`.
+    pub fn mark(&self, x: usize, y: usize) -> CheckBox {
+        self.marks[y * self.cols + x]
+    }
+
+    /// Returns the number of moves played.
+    pub fn plies(&self) -> usize {
+        self.ply
+    }
+
+    pub fn result(&self) -> GameResult {
+        self.result
+    }
+
+    /// Returns what an observer knows after getting `event` from what it
+    /// knew `before`, or `None` if the event makes no sense then.
+    pub fn apply(before: Option<&Snapshot>, event: &str) -> Option<Self> {
+        match string_field(event, "event")? {
+            "game" => {
+                let mut snapshot = Snapshot {
+                    variant: Variant::from_id(string_field(event, "variant")?)?,
+                    cols: number_field(event, "cols")?,
+                    rows: number_field(event, "rows")?,
+                    ply: number_field(event, "ply")?,
+                    marks: vec![],
+                    result: GameResult::InProgress,
+                };
+                let marks = field(event, "marks")?;
+                for mark in list_items(marks)? {
+                    snapshot.marks.push(parse_mark(mark)?);
+                }
+                if snapshot.marks.len() != snapshot.cols * snapshot.rows {
+                    return None;
+                }
+                Some(snapshot)
+            }
+            "diff" => {
+                let mut snapshot = before?.clone();
+                snapshot.ply = number_field(event, "ply")?;
+                for change in list_items(field(event, "changes")?)? {
+                    let x = number_field(change, "x")?;
+                    let y = number_field(change, "y")?;
+                    if x >= snapshot.cols || y >= snapshot.rows {
+                        return None;
+                    }
+                    snapshot.marks[y * snapshot.cols + x] = parse_mark(field(change, "mark")?)?;
+                }
+                // Unless it says otherwise, a game that changes went on.
+                snapshot.result = GameResult::InProgress;
+                Some(snapshot)
+            }
+            "result" => {
+                let mut snapshot = before?.clone();
+                snapshot.result = match string_field(event, "result")? {
+                    "x-wins" => GameResult::XWins,
+                    "o-wins" => GameResult::OWins,
+                    "draw" => GameResult::Draw,
+                    "x-out-of-time" => GameResult::TimeForfeit(CheckBox::X),
+                    "o-out-of-time" => GameResult::TimeForfeit(CheckBox::O),
+                    "x-resigned" => GameResult::Resigned(CheckBox::X),
+                    "o-resigned" => GameResult::Resigned(CheckBox::O),
+                    "draw-agreed" => GameResult::DrawAgreed,
+                    _ => return None,
+                };
+                Some(snapshot)
+            }
+            _ => None,
+        }
+    }
+
+    fn game_event(&self) -> String {
+        let marks = self.marks.iter().map(|&m| mark_json(m)).collect::<Vec<_>>();
+        format!("{{\"event\":\"game\",\"variant\":\"{}\",\"cols\":{},\"rows\":{},\"ply\":{},\"marks\":[{}]}}",
+                self.variant.id(), self.cols, self.rows, self.ply, marks.join(","))
+    }
+
+    fn result_event(&self) -> Option<String> {
+        let result = match self.result {
+            GameResult::XWins => "x-wins",
+            GameResult::OWins => "o-wins",
+            GameResult::Draw => "draw",
+            GameResult::TimeForfeit(CheckBox::X) => "x-out-of-time",
+            GameResult::TimeForfeit(_) => "o-out-of-time",
+            GameResult::Resigned(CheckBox::X) => "x-resigned",
+            GameResult::Resigned(_) => "o-resigned",
+            GameResult::DrawAgreed => "draw-agreed",
+            GameResult::InProgress => return None,
+        };
+        Some(format!("{{\"event\":\"result\",\"result\":\"{}\"}}", result))
+    }
+}
+
+/// Returns the raw value of the field called `name` of the JSON object
+/// `json`, which is one of the events above.
+fn field<'a>(json: &'a str, name: &str) -> Option<&'a str> {
+    let json = json.trim();
+    if !json.starts_with('{') || !json.ends_with('}') {
+        return None;
+    }
+    let mut rest = &json[1..json.len() - 1];
+    while !rest.is_empty() {
+        let key_end = value_end(rest)?;
+        let key = &rest[..key_end];
+        if !key.starts_with('"') || !rest[key_end..].starts_with(':') {
+            return None;
+        }
+        rest = &rest[key_end + 1..];
+        let end = value_end(rest)?;
+        if key[1..key.len() - 1] == *name {
+            return Some(&rest[..end]);
+        }
+        rest = &rest[end..];
+        if rest.starts_with(',') {
+            rest = &rest[1..];
+        }
+    }
+    None
+}
+
+/// Returns where the JSON value at the start of `json` ends. The strings of
+/// the events have no escapes.
+fn value_end(json: &str) -> Option<usize> {
+    if let Some(string) = json.strip_prefix('"') {
+        return string.find('"').map(|i| i + 2);
+    }
+    if !json.starts_with('[') && !json.starts_with('{') {
+        return Some(json.find([',', ']', '}']).unwrap_or(json.len()));
+    }
+    let mut depth = 0;
+    let mut in_string = false;
+    for (i, c) in json.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '[' | '{' if !in_string => depth += 1,
+            ']' | '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn string_field<'a>(json: &'a str, name: &str) -> Option<&'a str> {
+    let value = field(json, name)?;
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        Some(&value[1..value.len() - 1])
+    } else {
+        None
+    }
+}
+
+fn number_field(json: &str, name: &str) -> Option<usize> {
+    field(json, name)?.parse().ok()
+}
+
+/// Returns the items of the JSON list `json`.
+fn list_items(json: &str) -> Option<Vec<&str>> {
+    if !json.starts_with('[') || !json.ends_with(']') {
+        return None;
+    }
+    let mut items = vec![];
+    let mut rest = &json[1..json.len() - 1];
+    while !rest.is_empty() {
+        let end = value_end(rest)?;
+        items.push(&rest[..end]);
+        rest = &rest[end..];
+        if rest.starts_with(',') {
+            rest = &rest[1..];
+        }
+    }
+    Some(items)
+}
+
+fn parse_mark(json: &str) -> Option<CheckBox> {
+    match json {
+        "null" => Some(CheckBox::Empty),
+        "\"X\"" => Some(CheckBox::X),
+        "\"O\"" => Some(CheckBox::O),
+        _ => None,
+    }
+}
+
+/// Returns the events that take an observer from `before` to `after`, or
+/// from nothing if `before` is `None`.
+pub fn events(before: Option<&Snapshot>, after: &Snapshot) -> Vec<String> {
+    let before = match before {
+        Some(before) if before.variant == after.variant && before.marks.len() == after.marks.len() => before,
+        _ => {
+            let mut events = vec![after.game_event()];
+            events.extend(after.result_event());
+            return events;
+        }
+    };
+
+    let mut events = vec![];
+    let changes = before.marks.iter().zip(after.marks.iter()).enumerate()
+        .filter(|&(_, (b, a))| b != a)
+        .map(|(i, (_, &mark))| {
+            format!("{{\"x\":{},\"y\":{},\"mark\":{}}}", i % after.cols, i / after.cols, mark_json(mark))
+        })
+        .collect::<Vec<_>>();
+    if !changes.is_empty() {
+        events.push(format!("{{\"event\":\"diff\",\"ply\":{},\"changes\":[{}]}}",
+                            after.ply, changes.join(",")));
+    }
+    if before.result != after.result {
+        events.extend(after.result_event());
+    }
+    events
+}
+
+/// Streams the events of the game to whoever connects to a socket.
+#[cfg(unix)]
+pub struct Observer {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<UnixStream>,
+    /// What the clients know so far, if anything.
+    last: Option<Snapshot>,
+}
+
+#[cfg(unix)]
+impl Observer {
+    /// Listens at `path`, replacing any socket that's already there.
+    pub fn bind<P>(path: P) -> io::Result<Self>
+        where P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        // A socket left behind by a crashed instance, but don't remove
+        // anything else.
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if metadata.file_type().is_socket() {
+                fs::remove_file(path)?;
+            }
+        }
+
+        let listener = UnixListener::bind(path)?;
+        // Never block the main loop waiting for clients.
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            path: path.to_owned(),
+            listener,
+            clients: vec![],
+            last: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+impl Sink for Observer {
+    fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// Sends the clients what changed since the last update, and says hi to
+    /// the new clients.
+    ///
+    /// Clients that don't keep up are dropped rather than slowing down the
+    /// game.
+    fn update(&mut self, snapshot: Snapshot) {
+        let lines = events(self.last.as_ref(), &snapshot);
+        self.clients.retain(|mut client| {
+            lines.iter().all(|line| writeln!(client, "{}", line).is_ok())
+        });
+
+        while let Ok((client, _)) = self.listener.accept() {
+            if client.set_nonblocking(true).is_err() {
+                continue;
+            }
+            let hello = events(None, &snapshot);
+            let mut client = client;
+            if hello.iter().all(|line| writeln!(client, "{}", line).is_ok()) {
+                self.clients.push(client);
+            }
+        }
+        self.last = Some(snapshot);
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Observer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_moves() {
+        let mut game = Variant::TicTacToe.new_game(CheckBox::X);
+        let snapshot = |game: &::variant::AnyGame| {
+            let board = game.board();
+            Snapshot::new(game.variant(), &*board.position(board.plies()), board.plies(), board.result())
+        };
+
+        let start = snapshot(&game);
+        let events = events(None, &start);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].starts_with("{\"event\":\"game\",\"variant\":\"tictactoe\",\"cols\":3,\"rows\":3,\"ply\":0,"));
+
+        for &(x, y) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+            game.board_mut().click(x, y).unwrap();
+        }
+        let before = snapshot(&game);
+        game.board_mut().click(0, 2).unwrap();
+        let after = snapshot(&game);
+        assert_eq!(super::events(Some(&before), &after), [
+            "{\"event\":\"diff\",\"ply\":5,\"changes\":[{\"x\":0,\"y\":2,\"mark\":\"X\"}]}",
+            "{\"event\":\"result\",\"result\":\"x-wins\"}",
+        ]);
+        assert!(super::events(Some(&after), &after).is_empty());
+    }
+
+    #[test]
+    fn replays_events() {
+        let mut game = Variant::Ultimate.new_game(CheckBox::X);
+        let snapshot = |game: &::variant::AnyGame| {
+            let board = game.board();
+            Snapshot::new(game.variant(), &*board.position(board.plies()), board.plies(), board.result())
+        };
+
+        let mut seen = None;
+        let mut last = None;
+        for &(x, y) in &[(4, 4), (3, 3), (0, 0)] {
+            let now = snapshot(&game);
+            for event in events(last.as_ref(), &now) {
+                seen = Snapshot::apply(seen.as_ref(), &event);
+            }
+            assert_eq!(seen.as_ref(), Some(&now));
+            last = Some(now);
+            game.board_mut().click(x, y).unwrap();
+        }
+
+        let mut game = Variant::TicTacToe.new_game(CheckBox::X);
+        for &(x, y) in &[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)] {
+            game.board_mut().click(x, y).unwrap();
+        }
+        let over = snapshot(&game);
+        let mut seen = None;
+        for event in events(None, &over) {
+            seen = Snapshot::apply(seen.as_ref(), &event);
+        }
+        assert_eq!(seen.map(|s| (s.result(), s.mark(0, 2))), Some((GameResult::XWins, CheckBox::X)));
+
+        assert_eq!(Snapshot::apply(None, "{\"event\":\"diff\",\"ply\":1,\"changes\":[]}"), None);
+        assert_eq!(Snapshot::apply(None, "{\"event\":\"game\",\"variant\":\"tictactoe\",\"cols\":3}"), None);
+    }
+}
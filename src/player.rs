@@ -0,0 +1,180 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! How the engine plays for each side of a game.
+
+use config::{Config, Value};
+use minimax::{Difficulty, SearchConfig};
+use std::cmp;
+
+/// Moves the engine refuses to play, to give weaker players a chance.
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+pub struct Handicap {
+    /// Never take the center with the first move of the side.
+    pub no_center_opening: bool,
+}
+
+/// How the engine searches for one side: for its moves when it plays that
+/// side, and for the hints of that side otherwise.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PlayerConfig {
+    pub difficulty: Difficulty,
+    /// The number of levels to search, if fewer than the difficulty does.
+    pub depth: Option<usize>,
+    /// Whether to pick at random among the moves tying for the best score,
+    /// rather than always playing the same.
+    pub randomize: bool,
+    /// The heuristic at the leaves of the search, see
+    /// `evaluator::evaluator_named`.
+    pub evaluator: String,
+    pub handicap: Handicap,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::Perfect,
+            depth: None,
+            randomize: false,
+            evaluator: "default".to_owned(),
+            handicap: Handicap::default(),
+        }
+    }
+}
+
+impl PlayerConfig {
+    /// The number of levels to search, before `GameState::depth_limit`.
+    pub fn max_depth(&self) -> usize {
+        cmp::min(self.difficulty.max_depth(), self.depth.unwrap_or(usize::MAX))
+    }
+
+    /// How deep to search with this config in a game that goes up to
+    /// `depth_limit` levels deep.
+    pub fn search_config(&self, depth_limit: usize) -> SearchConfig {
+        SearchConfig::clamped(self.max_depth(), depth_limit)
+    }
+
+    /// Overrides the fields that are in `section` of `config`, ignoring the
+    /// invalid ones.
+    pub fn update_from(&mut self, config: &Config, section: &str) {
+        if let Some(difficulty) = config.get_string(section, "difficulty").and_then(Difficulty::from_name) {
+            self.difficulty = difficulty;
+        }
+        match config.get_integer(section, "depth") {
+            // Zero is as deep as the difficulty goes.
+            Some(0) => self.depth = None,
+            Some(depth) if depth > 0 => self.depth = Some(depth as usize),
+            _ => {}
+        }
+        if let Some(randomize) = config.get_boolean(section, "randomize") {
+            self.randomize = randomize;
+        }
+        if let Some(evaluator) = config.get_string(section, "evaluator") {
+            self.evaluator = evaluator.to_owned();
+        }
+        if let Some(no_center) = config.get_boolean(section, "no-center-opening") {
+            self.handicap.no_center_opening = no_center;
+        }
+    }
+
+    /// Parses comma-separated settings like `depth=2,randomize=yes`, which
+    /// override the defaults.
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        let mut config = PlayerConfig::default();
+        for setting in spec.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let (key, value) = match setting.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(format!("expected a setting like depth=2, got {}", setting)),
+            };
+            let invalid = || format!("invalid value for {}: {}", key, value);
+            let boolean = || match value {
+                "yes" => Ok(true),
+                "no" => Ok(false),
+                _ => Err(invalid()),
+            };
+            match key {
+                "difficulty" => config.difficulty = Difficulty::from_name(value).ok_or_else(invalid)?,
+                "depth" => config.depth = Some(value.parse::<usize>().map_err(|_| invalid())?),
+                "randomize" => config.randomize = boolean()?,
+                "evaluator" => config.evaluator = value.to_owned(),
+                "no-center-opening" => config.handicap.no_center_opening = boolean()?,
+                _ => return Err(format!("unknown setting: {}", key)),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Returns all the settings, in the format `from_spec` reads.
+    pub fn to_spec(&self) -> String {
+        let yes_no = |b| if b { "yes" } else { "no" };
+        let mut spec = format!("difficulty={}", self.difficulty.name());
+        if let Some(depth) = self.depth {
+            spec.push_str(&format!(",depth={}", depth));
+        }
+        spec.push_str(&format!(",randomize={},evaluator={},no-center-opening={}",
+                               yes_no(self.randomize), self.evaluator,
+                               yes_no(self.handicap.no_center_opening)));
+        spec
+    }
+
+    pub fn write_to(&self, config: &mut Config, section: &str) {
+        config.set(section, "difficulty", Value::String(self.difficulty.name().to_owned()));
+        config.set(section, "depth", Value::Integer(self.depth.unwrap_or(0) as i64));
+        config.set(section, "randomize", Value::Boolean(self.randomize));
+        config.set(section, "evaluator", Value::String(self.evaluator.clone()));
+        config.set(section, "no-center-opening", Value::Boolean(self.handicap.no_center_opening));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_the_depth() {
+        let mut config = PlayerConfig {
+            difficulty: Difficulty::Medium,
+            depth: Some(5),
+            ..PlayerConfig::default()
+        };
+        assert_eq!(config.max_depth(), 2);
+
+        let mut file = Config::default();
+        config.write_to(&mut file, "engine");
+        file.set("engine", "depth", Value::Integer(0));
+        config.difficulty = Difficulty::Perfect;
+        config.update_from(&file, "engine");
+        assert_eq!(config.depth, None);
+        assert_eq!(config.max_depth(), Difficulty::Medium.max_depth());
+    }
+
+    #[test]
+    fn reads_the_specs_it_writes() {
+        let config = PlayerConfig {
+            depth: Some(3),
+            randomize: true,
+            handicap: Handicap { no_center_opening: true },
+            ..PlayerConfig::default()
+        };
+        assert_eq!(config.to_spec(),
+                   "difficulty=Perfect,depth=3,randomize=yes,evaluator=default,no-center-opening=yes");
+        assert_eq!(PlayerConfig::from_spec(&config.to_spec()), Ok(config));
+        assert_eq!(PlayerConfig::from_spec(""), Ok(PlayerConfig::default()));
+        assert!(PlayerConfig::from_spec("depth=two").is_err());
+        assert!(PlayerConfig::from_spec("speed=2").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A line protocol to drive the tic-tac-toe engine from other programs, in
+//! the spirit of UCI.
+//!
+//! Each command gets at most one line in reply:
+//!
+//! ```text
+//! isready                  readyok
+//! newgame                  (the empty board, with X to move)
+//! position XX__O____ O     (the marks in rows, and who's to move)
+//! go depth 2               bestmove 2 0 pv 2 0 0 2
+//! go                       bestmove 2 0 pv 2 0 0 2 ...
+//! quit
+//! ```
+//!
+//! Squares are `x y`, the column and the row, from zero. `go` without a
+//! depth searches the whole game, and replies `bestmove none` if the game is
+//! over. After `pv` come the squares of the line of play the engine expects,
+//! starting with its move. Anything that doesn't make sense gets an `error` reply, and leaves
+//! the position as it was.
+
+use game_state::{GameState, GridState};
+use minimax::{MiniMaxTree, SearchConfig};
+use state::{CheckBox, State};
+use std::io::{self, BufRead, Write};
+
+/// The position the engine is asked about.
+#[derive(Debug)]
+struct Session {
+    state: State,
+    player: CheckBox,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            state: State::initial(),
+            player: CheckBox::X,
+        }
+    }
+
+    /// Parses a `position` command's arguments.
+    fn position(args: &[&str]) -> Result<(State, CheckBox), String> {
+        let (marks, player) = match *args {
+            [marks, player] => (marks, player),
+            _ => return Err("usage: position <marks> <X|O>".to_owned()),
+        };
+        let player = match player {
+            "X" => CheckBox::X,
+            "O" => CheckBox::O,
+            _ => return Err(format!("not a player: {}", player)),
+        };
+        let marks = marks.chars().map(CheckBox::from_dump_char).collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("not marks: {}", marks))?;
+        let mut state = State::initial();
+        let (cols, rows) = state.grid_size();
+        if marks.len() != cols * rows {
+            return Err(format!("expected {} marks, got {}", cols * rows, marks.len()));
+        }
+        for (i, &mark) in marks.iter().enumerate() {
+            state.set(i % cols, i / cols, mark);
+        }
+        Ok((state, player))
+    }
+
+    /// Parses a `go` command's arguments into a search depth.
+    fn depth(args: &[&str]) -> Result<usize, String> {
+        match *args {
+            [] => Ok(State::depth_limit()),
+            ["depth", depth] => {
+                SearchConfig::parse(depth, State::depth_limit())
+                    .map(|config| config.depth())
+                    .map_err(|err| err.to_string())
+            }
+            _ => Err("usage: go [depth <n>]".to_owned()),
+        }
+    }
+
+    /// Handles a line of input, returning the reply, if any, or `None` if
+    /// the session is over.
+    fn handle(&mut self, line: &str) -> Option<Option<String>> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Some(None),
+        };
+        let reply = match command {
+            "isready" => Ok(Some("readyok".to_owned())),
+            "newgame" => {
+                *self = Session::new();
+                Ok(None)
+            }
+            "position" => Self::position(args).map(|(state, player)| {
+                self.state = state;
+                self.player = player;
+                None
+            }),
+            "go" => Self::depth(args).map(|depth| Some(self.go(depth))),
+            "quit" => return None,
+            _ => Err(format!("unknown command: {}", command)),
+        };
+        Some(reply.unwrap_or_else(|err| Some(format!("error {}", err))))
+    }
+
+    fn go(&self, depth: usize) -> String {
+        let mut tree = MiniMaxTree::from_state(self.state.clone(), self.player);
+        match tree.find_move_with_pv(depth) {
+            Some(((x, y), pv, _)) => {
+                let pv = pv.iter().map(|&(x, y)| format!("{} {}", x, y)).collect::<Vec<_>>();
+                format!("bestmove {} {} pv {}", x, y, pv.join(" "))
+            }
+            None => "bestmove none".to_owned(),
+        }
+    }
+}
+
+/// Answers the commands from `input` on `output` until `quit` or the end of
+/// the input.
+pub fn run<R, W>(input: R, output: &mut W) -> io::Result<()>
+    where R: BufRead,
+          W: Write,
+{
+    let mut session = Session::new();
+    for line in input.lines() {
+        match session.handle(&line?) {
+            Some(Some(reply)) => {
+                writeln!(output, "{}", reply)?;
+                // The other side waits for each reply before going on.
+                output.flush()?;
+            }
+            Some(None) => {}
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_commands() {
+        let input = "isready\n\
+                     position XX__O____ O\n\
+                     go depth 2\n\
+                     position XX__O___ O\n\
+                     go depth 0\n\
+                     position XXX_OO___ O\n\
+                     go\n\
+                     quit\n\
+                     go\n";
+        let mut output = vec![];
+        run(input.as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "readyok\n\
+                                                        bestmove 2 0 pv 2 0 0 2\n\
+                                                        error expected 9 marks, got 8\n\
+                                                        error not a depth: 0\n\
+                                                        bestmove none\n");
+    }
+}
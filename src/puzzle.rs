@@ -0,0 +1,278 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tic-tac-toe puzzles, like "X to move and win in 2", checked against the
+//! solver.
+//!
+//! The puzzles are in `puzzles/tictactoe.txt`, see there for the format.
+
+use config::{Config, Value};
+use game_state::{GameState, MoveError};
+use minimax::{MiniMaxTree, Solution};
+use state::{CheckBox, NotationError, PositionError, State};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// The section of the configuration file with the solved puzzles.
+const SECTION: &str = "puzzles";
+
+const PUZZLES: &str = include_str!("../puzzles/tictactoe.txt");
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Goal {
+    /// Win in at most this many moves of the player to move.
+    Win(usize),
+    /// Find the only move that doesn't lose.
+    Draw,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PuzzleError {
+    /// The line doesn't have the given field.
+    MissingField(&'static str),
+    InvalidPosition(NotationError),
+    /// The position can't come up in a game.
+    Impossible(PositionError),
+    InvalidGoal(String),
+    /// The solver doesn't agree with the goal, and says this instead.
+    WrongGoal(Solution),
+    /// More than one move reaches the goal, or none does.
+    Ambiguous,
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PuzzleError::MissingField(field) => write!(f, "missing {}", field),
+            PuzzleError::InvalidPosition(ref err) => write!(f, "invalid position: {}", err),
+            PuzzleError::Impossible(ref err) => write!(f, "impossible position: {}", err),
+            PuzzleError::InvalidGoal(ref goal) => write!(f, "invalid goal: {}", goal),
+            PuzzleError::WrongGoal(solution) => write!(f, "the solver says {:?}", solution),
+            PuzzleError::Ambiguous => write!(f, "the goal doesn't have a single answer"),
+        }
+    }
+}
+
+/// What a move does for the puzzle.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Verdict {
+    /// The move reaches the goal.
+    Solved,
+    /// The move keeps the win in time, but there's more to play.
+    Right,
+    /// The move still wins, but not in time.
+    TooSlow,
+    Wrong,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Puzzle {
+    /// Where the progress of the puzzle is saved.
+    pub id: String,
+    pub title: String,
+    pub state: State,
+    /// The player to move, who the puzzle is for.
+    pub player: CheckBox,
+    pub goal: Goal,
+}
+
+impl Puzzle {
+    /// Parses a line of the puzzles file, without checking the goal against
+    /// the solver, see `verify`.
+    pub fn parse(line: &str) -> Result<Self, PuzzleError> {
+        let mut fields = line.split_whitespace();
+        let id = fields.next().ok_or(PuzzleError::MissingField("id"))?;
+        let rows = fields.next().ok_or(PuzzleError::MissingField("position"))?;
+        let player = fields.next().ok_or(PuzzleError::MissingField("player"))?;
+        let (state, player) = State::from_notation(&format!("{} {}", rows, player))
+            .map_err(PuzzleError::InvalidPosition)?;
+        state.validate(player).map_err(PuzzleError::Impossible)?;
+        let goal = match fields.next().ok_or(PuzzleError::MissingField("goal"))? {
+            "win" => {
+                let moves = fields.next().ok_or(PuzzleError::MissingField("moves"))?;
+                match moves.parse::<usize>() {
+                    Ok(moves) if moves > 0 => Goal::Win(moves),
+                    _ => return Err(PuzzleError::InvalidGoal(format!("win {}", moves))),
+                }
+            }
+            "draw" => Goal::Draw,
+            goal => return Err(PuzzleError::InvalidGoal(goal.to_owned())),
+        };
+        let title = fields.collect::<Vec<_>>().join(" ");
+        if title.is_empty() {
+            return Err(PuzzleError::MissingField("title"));
+        }
+        Ok(Self {
+            id: id.to_owned(),
+            title,
+            state,
+            player,
+            goal,
+        })
+    }
+
+    /// Returns the puzzles that come with the program.
+    pub fn all() -> Vec<Self> {
+        PUZZLES.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| Self::parse(line).expect("The puzzles should parse"))
+            .collect()
+    }
+
+    /// Returns whether the solver agrees that the goal is the best the
+    /// player can do, and that there's a single move to reach it.
+    pub fn verify(&self) -> Result<(), PuzzleError> {
+        let tree = MiniMaxTree::from_state(self.state.clone(), self.player);
+        let solution = tree.solve();
+        let expected = match self.goal {
+            Goal::Win(moves) => Solution::Win(2 * moves - 1),
+            Goal::Draw => Solution::Draw,
+        };
+        if solution != expected {
+            return Err(PuzzleError::WrongGoal(solution));
+        }
+        let answers = self.state.successors(self.player).into_iter()
+            .filter(|&(mv, _)| matches!(self.check(mv), Ok(Verdict::Solved) | Ok(Verdict::Right)))
+            .count();
+        if answers != 1 {
+            return Err(PuzzleError::Ambiguous);
+        }
+        Ok(())
+    }
+
+    /// Checks `mv` of the player to move against the solver.
+    pub fn check(&self, mv: (usize, usize)) -> Result<Verdict, MoveError> {
+        let mut tree = MiniMaxTree::from_state(self.state.clone(), self.player);
+        tree.choose(mv)?;
+        // From the point of view of the opponent, who moves next.
+        Ok(match (self.goal, tree.solve()) {
+            (Goal::Win(_), Solution::Loss(0)) => Verdict::Solved,
+            (Goal::Win(moves), Solution::Loss(plies)) if plies < 2 * moves - 1 => Verdict::Right,
+            (Goal::Win(_), Solution::Loss(_)) => Verdict::TooSlow,
+            (Goal::Draw, Solution::Draw) => Verdict::Solved,
+            _ => Verdict::Wrong,
+        })
+    }
+
+    /// Returns the rest of the puzzle once a right move was answered, with
+    /// `player` to move in `state`.
+    pub fn continued(&self, state: State, player: CheckBox) -> Self {
+        let goal = match self.goal {
+            Goal::Win(moves) if moves > 1 => Goal::Win(moves - 1),
+            goal => goal,
+        };
+        Self {
+            state,
+            player,
+            goal,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the puzzle with the marks swapped, for `player` to solve.
+    pub fn for_player(&self, player: CheckBox) -> Self {
+        if player == self.player {
+            return self.clone();
+        }
+        let mut state = self.state.clone();
+        for x in 0..3 {
+            for y in 0..3 {
+                let mark = state.get(x, y);
+                if mark != CheckBox::Empty {
+                    state.set(x, y, mark.next_player());
+                }
+            }
+        }
+        Self {
+            state,
+            player,
+            ..self.clone()
+        }
+    }
+}
+
+/// The puzzles the user has solved.
+#[derive(Clone, Debug, Default)]
+pub struct Progress {
+    solved: BTreeSet<String>,
+}
+
+impl Progress {
+    pub fn from_config(config: &Config) -> Self {
+        let solved = Puzzle::all().into_iter()
+            .map(|puzzle| puzzle.id)
+            .filter(|id| config.get_boolean(SECTION, id) == Some(true))
+            .collect();
+        Self { solved }
+    }
+
+    pub fn write_to(&self, config: &mut Config) {
+        for id in &self.solved {
+            config.set(SECTION, id, Value::Boolean(true));
+        }
+    }
+
+    pub fn record(&mut self, puzzle: &Puzzle) {
+        self.solved.insert(puzzle.id.clone());
+    }
+
+    pub fn is_solved(&self, puzzle: &Puzzle) -> bool {
+        self.solved.contains(&puzzle.id)
+    }
+
+    pub fn solved(&self) -> usize {
+        self.solved.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_solver_agrees_with_the_puzzles() {
+        let puzzles = Puzzle::all();
+        for puzzle in &puzzles {
+            assert_eq!(puzzle.verify(), Ok(()), "{}", puzzle.id);
+            assert_eq!(puzzle.for_player(puzzle.player.next_player()).verify(), Ok(()), "{}", puzzle.id);
+        }
+
+        let fork = puzzles.iter().find(|p| p.id == "double-threat").unwrap();
+        assert_eq!(fork.check((1, 1)), Ok(Verdict::Right));
+        assert_eq!(fork.check((2, 1)), Ok(Verdict::Wrong));
+        assert_eq!(fork.check((0, 0)), Err(MoveError::Occupied));
+        let (state, player) = State::from_notation("OXO/XX_/_O_ X").unwrap();
+        let rest = fork.continued(state, player);
+        assert_eq!(rest.goal, Goal::Win(1));
+        assert_eq!(rest.check((2, 1)), Ok(Verdict::Solved));
+
+        assert_eq!(Puzzle::parse("a XX_/OO_/___ X win 0 Title"),
+                   Err(PuzzleError::InvalidGoal("win 0".to_owned())));
+        assert_eq!(Puzzle::parse("a XX_/OO_/___ X draw"), Err(PuzzleError::MissingField("title")));
+        assert_eq!(Puzzle::parse("a XX_/OO_/___ X draw Wrong").unwrap().verify(),
+                   Err(PuzzleError::WrongGoal(Solution::Win(1))));
+
+        let mut progress = Progress::default();
+        progress.record(fork);
+        let mut config = Config::default();
+        progress.write_to(&mut config);
+        let parsed = Progress::from_config(&config);
+        assert!(parsed.is_solved(fork));
+        assert_eq!(parsed.solved(), 1);
+    }
+}
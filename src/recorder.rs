@@ -0,0 +1,415 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Records of finished games, for people to read, in a format like the PGN
+//! of chess:
+//!
+//! ```text
+//! [Date "2017.03.14"]
+//! [Variant "tictactoe"]
+//! [First "X"]
+//! [X "Human"]
+//! [O "tic-tac-minimax (Perfect)"]
+//! [Result "x-wins"]
+//!
+//! 1. a1 b1 2. b2 c1 3. c3 x-wins
+//! ```
+//!
+//! The moves are numbered in pairs from the first player, and named like
+//! `Board::move_names` does. A move that marks a square with a mark other
+//! than the one of its player, like in wild tic-tac-toe, is written like
+//! `b2=O`.
+//!
+//! Games played with a clock have `XTime` and `OTime` headers too, with the
+//! seconds each side had for the whole game and after each move, like
+//! `[XTime "300+5"]`, and an `OnTimeout` one with what happens to whoever
+//! runs out of it, like `[OnTimeout "greedy-move"]`. The moves played for a
+//! player out of time are in a `TimedOut` header, like `[TimedOut "5 7"]`.
+
+use clock::{TimeControl, TimeoutPolicy};
+use game_state::{GameResult, MoveError};
+use state::CheckBox;
+use std::fmt;
+use std::io::{self, Write};
+use variant::{AnyGame, Variant};
+
+/// The longest line of moves `write` writes.
+const MAX_LINE_LENGTH: usize = 79;
+
+/// An error reading or replaying a record.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum RecordError {
+    /// The line with the given number is neither a header nor moves.
+    Syntax(usize),
+    /// A header is missing or malformed.
+    InvalidHeader(&'static str),
+    /// The move with the given name, counting from one, can't be played.
+    IllegalMove(usize, String),
+    /// The moves don't lead to the result of the record.
+    WrongResult,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecordError::Syntax(line) => write!(f, "syntax error on line {}", line),
+            RecordError::InvalidHeader(name) => {
+                write!(f, "missing or invalid header \"{}\"", name)
+            }
+            RecordError::IllegalMove(i, ref name) => write!(f, "illegal move {} ({})", i, name),
+            RecordError::WrongResult => write!(f, "the moves don't lead to the result"),
+        }
+    }
+}
+
+fn parse_mark(s: &str) -> Option<CheckBox> {
+    match s {
+        "X" => Some(CheckBox::X),
+        "O" => Some(CheckBox::O),
+        _ => None,
+    }
+}
+
+/// Returns the date `seconds` after the Unix epoch, in UTC, like
+/// `2017.03.14`.
+pub fn date(seconds: u64) -> String {
+    // Howard Hinnant's days-to-civil algorithm, for days since 1970-01-01.
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Returns today's date, like `date`, or the unknown date of PGN if there's
+/// no clock to tell.
+pub fn today() -> String {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        if let Ok(since_epoch) = SystemTime::now().duration_since(UNIX_EPOCH) {
+            return date(since_epoch.as_secs());
+        }
+    }
+    "????.??.??".to_owned()
+}
+
+/// Escapes the value of a header.
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Parses a header line like `[Name "value"]`.
+fn parse_header(line: &str) -> Option<(&str, String)> {
+    if !line.starts_with('[') || !line.ends_with(']') {
+        return None;
+    }
+    let line = &line[1..line.len() - 1];
+    let space = line.find(' ')?;
+    let (name, value) = (&line[..space], line[space..].trim());
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return None;
+    }
+
+    let mut ret = String::new();
+    let mut chars = value[1..value.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        ret.push(match c {
+            '"' => return None,
+            '\\' => chars.next()?,
+            c => c,
+        });
+    }
+    Some((name, ret))
+}
+
+/// Everything a record says about a game.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Record {
+    /// When it was played, like `date` writes it.
+    pub date: String,
+    pub variant: Variant,
+    pub first_player: CheckBox,
+    /// The names of the players of X and O.
+    pub x: String,
+    pub o: String,
+    /// The time X and O had, in that order, if they played with a clock.
+    pub time_controls: Option<[TimeControl; 2]>,
+    /// What happens when a player runs out of time.
+    pub timeout_policy: TimeoutPolicy,
+    /// The moves played for a player who ran out of time, counting from
+    /// one.
+    pub timed_out: Vec<usize>,
+    /// Who played each move, and the square they marked.
+    pub moves: Vec<(CheckBox, String)>,
+    pub result: GameResult,
+}
+
+impl Record {
+    /// Returns the record of `game`, played on `date` by `x` and `o`.
+    pub fn of_game(game: &AnyGame, x: &str, o: &str, date: &str) -> Self {
+        let board = game.board();
+        let plies = board.plies();
+        let first_player = if plies.is_multiple_of(2) { board.player() } else { board.player().next_player() };
+        Self {
+            date: date.to_owned(),
+            variant: game.variant(),
+            first_player,
+            x: x.to_owned(),
+            o: o.to_owned(),
+            time_controls: board.time_controls(),
+            timeout_policy: board.timeout_policy(),
+            timed_out: board.timed_out(),
+            moves: board.move_names(),
+            result: board.result(),
+        }
+    }
+
+    /// Writes the record in the format of the module docs.
+    pub fn write<W>(&self, dest: &mut W) -> io::Result<()>
+        where W: Write,
+    {
+        writeln!(dest, "[Date \"{}\"]", quote(&self.date))?;
+        writeln!(dest, "[Variant \"{}\"]", self.variant.id())?;
+        writeln!(dest, "[First \"{}\"]", self.first_player.dump_char())?;
+        writeln!(dest, "[X \"{}\"]", quote(&self.x))?;
+        writeln!(dest, "[O \"{}\"]", quote(&self.o))?;
+        if let Some([x, o]) = self.time_controls {
+            writeln!(dest, "[XTime \"{}\"]", x)?;
+            writeln!(dest, "[OTime \"{}\"]", o)?;
+            writeln!(dest, "[OnTimeout \"{}\"]", self.timeout_policy.name())?;
+        }
+        if !self.timed_out.is_empty() {
+            let plies = self.timed_out.iter().map(|ply| ply.to_string()).collect::<Vec<_>>();
+            writeln!(dest, "[TimedOut \"{}\"]", plies.join(" "))?;
+        }
+        writeln!(dest, "[Result \"{}\"]", self.result.name())?;
+        writeln!(dest)?;
+
+        let mut words = vec![];
+        let mut player = self.first_player;
+        for (i, &(mark, ref name)) in self.moves.iter().enumerate() {
+            if i % 2 == 0 {
+                words.push(format!("{}.", i / 2 + 1));
+            }
+            words.push(if mark == player { name.clone() } else { format!("{}={}", name, mark.dump_char()) });
+            player = player.next_player();
+        }
+        words.push(self.result.name().to_owned());
+
+        let mut line = String::new();
+        for word in words {
+            if !line.is_empty() && line.len() + 1 + word.len() > MAX_LINE_LENGTH {
+                writeln!(dest, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        writeln!(dest, "{}", line)
+    }
+
+    /// Reads a record that `write` wrote.
+    ///
+    /// The moves aren't checked until `replay`. Unknown headers are ignored.
+    pub fn parse(text: &str) -> Result<Self, RecordError> {
+        let mut date = None;
+        let mut variant = None;
+        let mut first_player = None;
+        let mut x = None;
+        let mut o = None;
+        let mut x_time = None;
+        let mut o_time = None;
+        let mut timeout_policy = TimeoutPolicy::Forfeit;
+        let mut timed_out = vec![];
+        let mut result = None;
+        let mut words = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if !line.starts_with('[') {
+                words.extend(line.split_whitespace());
+                continue;
+            }
+            let (name, value) = parse_header(line).ok_or(RecordError::Syntax(i + 1))?;
+            match name {
+                "Date" => date = Some(value),
+                "Variant" => {
+                    variant = Some(Variant::from_id(&value).ok_or(RecordError::InvalidHeader("Variant"))?);
+                }
+                "First" => {
+                    first_player = Some(parse_mark(&value).ok_or(RecordError::InvalidHeader("First"))?);
+                }
+                "X" => x = Some(value),
+                "O" => o = Some(value),
+                "XTime" => {
+                    x_time = Some(TimeControl::parse(&value).ok_or(RecordError::InvalidHeader("XTime"))?);
+                }
+                "OTime" => {
+                    o_time = Some(TimeControl::parse(&value).ok_or(RecordError::InvalidHeader("OTime"))?);
+                }
+                "OnTimeout" => {
+                    timeout_policy = TimeoutPolicy::from_name(&value).ok_or(RecordError::InvalidHeader("OnTimeout"))?;
+                }
+                "TimedOut" => {
+                    timed_out = value.split_whitespace()
+                        .map(|ply| ply.parse::<usize>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| RecordError::InvalidHeader("TimedOut"))?;
+                }
+                "Result" => {
+                    result = Some(GameResult::from_name(&value).ok_or(RecordError::InvalidHeader("Result"))?);
+                }
+                _ => {}
+            }
+        }
+
+        let first_player = first_player.ok_or(RecordError::InvalidHeader("First"))?;
+        let result = result.ok_or(RecordError::InvalidHeader("Result"))?;
+        let time_controls = match (x_time, o_time) {
+            (Some(x), Some(o)) => Some([x, o]),
+            (None, None) => None,
+            (Some(..), None) => return Err(RecordError::InvalidHeader("OTime")),
+            (None, Some(..)) => return Err(RecordError::InvalidHeader("XTime")),
+        };
+        // The moves end with the result, like in PGN.
+        if words.pop().and_then(GameResult::from_name) != Some(result) {
+            return Err(RecordError::WrongResult);
+        }
+        let mut moves = vec![];
+        let mut player = first_player;
+        for word in words {
+            // Move numbers.
+            if word.ends_with('.') {
+                continue;
+            }
+            let mut parts = word.splitn(2, '=');
+            let name = parts.next().unwrap().to_owned();
+            let mark = match parts.next() {
+                Some(mark) => match parse_mark(mark) {
+                    Some(mark) => mark,
+                    None => return Err(RecordError::IllegalMove(moves.len() + 1, word.to_owned())),
+                },
+                None => player,
+            };
+            moves.push((mark, name));
+            player = player.next_player();
+        }
+
+        Ok(Self {
+            date: date.ok_or(RecordError::InvalidHeader("Date"))?,
+            variant: variant.ok_or(RecordError::InvalidHeader("Variant"))?,
+            first_player,
+            x: x.ok_or(RecordError::InvalidHeader("X"))?,
+            o: o.ok_or(RecordError::InvalidHeader("O"))?,
+            time_controls,
+            timeout_policy,
+            timed_out,
+            moves,
+            result,
+        })
+    }
+
+    /// Plays the moves of the record in a new game of its variant, to look at
+    /// or to keep playing.
+    pub fn replay(&self) -> Result<AnyGame, RecordError> {
+        let mut game = self.variant.new_game(self.first_player);
+        {
+            let board = game.board_mut();
+            board.set_time_controls(self.time_controls);
+            board.set_timeout_policy(self.timeout_policy);
+            for (i, &(mark, ref name)) in self.moves.iter().enumerate() {
+                board.play_named(mark, name).map_err(|_: MoveError| {
+                    RecordError::IllegalMove(i + 1, name.clone())
+                })?;
+                if self.timed_out.contains(&(i + 1)) {
+                    board.mark_timed_out();
+                }
+            }
+            match self.result {
+                GameResult::TimeForfeit(loser) => board.run_out_of_time(loser),
+                GameResult::Resigned(loser) => board.resign(loser),
+                GameResult::DrawAgreed => {
+                    let player = board.player();
+                    board.offer_draw(player);
+                    board.answer_draw_offer(player.next_player(), true);
+                }
+                _ => {}
+            }
+            if board.result() != self.result {
+                return Err(RecordError::WrongResult);
+            }
+            if board.timed_out() != self.timed_out {
+                return Err(RecordError::InvalidHeader("TimedOut"));
+            }
+        }
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_what_it_writes() {
+        let mut game = Variant::Wild.new_game(CheckBox::X);
+        {
+            let board = game.board_mut();
+            board.click(1, 1).unwrap();
+            board.click_other(0, 1).unwrap();
+            board.click(2, 2).unwrap();
+            board.set_time_controls(Some([TimeControl::from_secs(30, 5), TimeControl::from_secs(1, 1)]));
+            board.set_timeout_policy(TimeoutPolicy::GreedyMove);
+            board.mark_timed_out();
+        }
+        let record = Record::of_game(&game, "Human", "tic-tac-minimax (\"Perfect\")", &date(1489449600));
+        let mut written = vec![];
+        record.write(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.starts_with("[Date \"2017.03.14\"]\n"), "{}", written);
+        assert!(written.contains("1. b2 a2=X 2. c3 *\n"), "{}", written);
+        assert!(written.contains("[O \"tic-tac-minimax (\\\"Perfect\\\")\"]\n"), "{}", written);
+        assert!(written.contains("[XTime \"30+5\"]\n[OTime \"1+1\"]\n[OnTimeout \"greedy-move\"]\n"), "{}", written);
+        assert!(written.contains("[TimedOut \"3\"]\n"), "{}", written);
+
+        let parsed = Record::parse(&written).unwrap();
+        assert_eq!(parsed, record);
+        let replayed = parsed.replay().unwrap();
+        assert_eq!(replayed.board().move_names(), game.board().move_names());
+        assert_eq!(replayed.board().time_controls(), record.time_controls);
+        assert_eq!(Record::of_game(&replayed, &record.x, &record.o, &record.date), record);
+
+        let tampered = written.replace("*", "draw");
+        assert_eq!(Record::parse(&tampered).unwrap().replay().err(), Some(RecordError::WrongResult));
+
+        // Games that end off the board replay to the same end.
+        for result in &["o-resigned", "draw-agreed"] {
+            let ended = Record::parse(&written.replace("*", result)).unwrap();
+            assert_eq!(ended.replay().unwrap().board().result(), ended.result);
+        }
+    }
+}
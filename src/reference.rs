@@ -0,0 +1,170 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A reference minimax implementation to test the real engine against.
+//!
+//! This is meant to be obviously correct rather than fast: there's no
+//! pruning, no move ordering and no caching, so don't make it smarter.
+
+use game_state::GameState;
+use state::CheckBox;
+
+/// Returns the minimax value of `state` with `player` to move, looking up to
+/// `max_depth` levels ahead.
+pub fn value<S: GameState>(state: &S, player: CheckBox, max_depth: usize) -> i8 {
+    let successors = state.successors(player);
+    if max_depth == 0 || successors.is_empty() {
+        return state.evaluate();
+    }
+
+    let values = successors.iter().map(|(_, s)| {
+        value(s, player.next_player(), max_depth - 1)
+    });
+    if player == CheckBox::O {
+        values.max().unwrap()
+    } else {
+        values.min().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use connect_four::ConnectFourState;
+    use minimax::MiniMaxTree;
+    use number_scrabble::NumberScrabbleState;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use state::State;
+    use ultimate::UltimateState;
+
+    /// Checks the engine against the reference on `positions` random
+    /// positions of up to `max_plies` plies, searching `max_depth` levels.
+    fn check_against_reference<S: GameState>(
+        positions: usize,
+        max_plies: usize,
+        max_depth: usize,
+    ) {
+        // Always the same positions, so that failures are reproducible.
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..positions {
+            let mut tree = MiniMaxTree::<S>::new(CheckBox::X);
+            for _ in 0..rng.gen_range(0..max_plies + 1) {
+                let successors = tree.state().successors(tree.player());
+                if successors.is_empty() {
+                    break;
+                }
+                let mv = successors[rng.gen_range(0..successors.len())].0;
+                tree.choose(mv).unwrap();
+            }
+
+            let state = tree.state().clone();
+            let player = tree.player();
+            let mut dump = String::new();
+            state.dump(0, &mut dump).unwrap();
+
+            let expected = value(&state, player, max_depth);
+            let successors = state.successors(player);
+            let (index, score) = match tree.find_best_move(max_depth) {
+                Some(best) => best,
+                None => {
+                    assert!(successors.is_empty(), "No move found in:\n{}", dump);
+                    continue;
+                }
+            };
+            assert_eq!(score, expected, "Wrong score in:\n{}", dump);
+
+            let mv = tree.move_for_index(index);
+            let chosen = &successors.iter().find(|s| s.0 == mv).unwrap().1;
+            assert_eq!(value(chosen, player.next_player(), max_depth - 1), expected,
+                       "{:?} isn't the best move in:\n{}", mv, dump);
+
+            for (mv, score) in tree.evaluate_all_moves(max_depth) {
+                let child = &successors.iter().find(|s| s.0 == mv).unwrap().1;
+                assert_eq!(score, value(child, player.next_player(), max_depth - 1),
+                           "Wrong score for {:?} in:\n{}", mv, dump);
+            }
+        }
+    }
+
+    /// Checks that the moves the engine finds within `margin` of the best one
+    /// are, according to the reference.
+    fn check_margin<S: GameState>(
+        positions: usize,
+        max_plies: usize,
+        max_depth: usize,
+        margin: i8,
+    ) {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..positions {
+            let mut tree = MiniMaxTree::<S>::new(CheckBox::X);
+            for _ in 0..rng.gen_range(0..max_plies + 1) {
+                let successors = tree.state().successors(tree.player());
+                if successors.is_empty() {
+                    break;
+                }
+                let mv = successors[rng.gen_range(0..successors.len())].0;
+                tree.choose(mv).unwrap();
+            }
+
+            let state = tree.state().clone();
+            let player = tree.player();
+            let (index, score) = match tree.find_move_within_margin(max_depth, margin) {
+                Some(found) => found,
+                None => continue,
+            };
+
+            let mv = tree.move_for_index(index);
+            let successors = state.successors(player);
+            let chosen = &successors.iter().find(|s| s.0 == mv).unwrap().1;
+            let actual = value(chosen, player.next_player(), max_depth - 1);
+            assert_eq!(score, actual, "Wrong score for {:?} in {:?}", mv, state);
+
+            let best = value(&state, player, max_depth);
+            let loss = if player == CheckBox::O { best - actual } else { actual - best };
+            assert!(loss <= margin, "{:?} loses {} in {:?}", mv, loss, state);
+        }
+    }
+
+    #[test]
+    fn moves_within_margin_are() {
+        for margin in 0..4 {
+            check_margin::<State>(30, 8, State::depth_limit(), margin * 5);
+            check_margin::<ConnectFourState>(10, 30, 4, margin);
+        }
+    }
+
+    #[test]
+    fn tic_tac_toe_matches_reference() {
+        check_against_reference::<State>(50, 8, State::depth_limit());
+    }
+
+    #[test]
+    fn ultimate_matches_reference() {
+        check_against_reference::<UltimateState>(50, 40, 2);
+    }
+
+    #[test]
+    fn connect_four_matches_reference() {
+        check_against_reference::<ConnectFourState>(30, 30, 4);
+    }
+
+    #[test]
+    fn number_scrabble_matches_reference() {
+        check_against_reference::<NumberScrabbleState>(30, 8, NumberScrabbleState::depth_limit());
+    }
+}
@@ -0,0 +1,76 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Bug reports: everything needed to reproduce a problem, as text to paste
+//! in an issue.
+
+use config::Config;
+use log;
+use std::env;
+use variant::AnyGame;
+
+/// Returns a report of the current game, which the engine played with random
+/// numbers from `seed`, and settings, and the recent log events if they're
+/// kept.
+pub fn bug_report(game: &AnyGame, seed: u64, config: &Config) -> String {
+    let mut report = format!(
+        "tic-tac-minimax {} ({} build, {})\n",
+        env!("CARGO_PKG_VERSION"),
+        if cfg!(debug_assertions) { "debug" } else { "release" },
+        env::consts::OS,
+    );
+
+    report.push_str(&format!("\n# Game: {}, seed {}\n", game.variant().name(), seed));
+    report.push_str(&game.board().describe());
+
+    report.push_str("\n# Saved game\n");
+    let mut saved = vec![];
+    game.board().write_record(&mut saved).expect("Writing to memory can't fail");
+    report.push_str(&String::from_utf8(saved).expect("Saved games are UTF-8"));
+
+    report.push_str("\n# Settings\n");
+    report.push_str(&config.to_string());
+
+    let events = log::recent();
+    if !events.is_empty() {
+        report.push_str("\n# Log\n");
+        for event in events {
+            report.push_str(&format!("{}\n", event));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use state::CheckBox;
+    use variant::Variant;
+
+    #[test]
+    fn reports_can_be_loaded() {
+        let mut game = Variant::ConnectFour.new_game(CheckBox::X);
+        game.board_mut().click(3, 0).unwrap();
+        game.board_mut().click(2, 0).unwrap();
+
+        let report = bug_report(&game, 0, &Config::default());
+        let saved = report.split("# Saved game\n").nth(1).unwrap();
+        let loaded = AnyGame::read(saved.as_bytes()).unwrap();
+        assert_eq!(loaded.variant(), Variant::ConnectFour);
+        assert_eq!(loaded.board().move_names(), game.board().move_names());
+    }
+}
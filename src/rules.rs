@@ -0,0 +1,342 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Tic-tac-toe with different rules: misère, wild and notakto.
+//!
+//! In misère tic-tac-toe, whoever completes a line of their mark loses. In
+//! wild tic-tac-toe, players place either mark, and whoever completes a line
+//! of either wins. In notakto, both players place X marks, and whoever
+//! completes a line loses.
+//!
+//! The rules are part of the type of the state, so that the engine can start
+//! games of them from `GameState::initial`.
+
+use game_state::{self, GameState, GridState, MoveError, Savable};
+use state::{self, CheckBox, LINES};
+use std::fmt;
+use std::marker::PhantomData;
+use variant::Variant;
+
+/// How the game is played.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Rules {
+    /// Whether players can place either mark, rather than only their own.
+    pub either_mark: bool,
+    /// Whether both players place X marks.
+    pub only_x: bool,
+    /// Whether completing a line loses, rather than wins.
+    pub line_loses: bool,
+    /// How deep the engine can search in reasonable time.
+    pub depth_limit: usize,
+}
+
+/// A set of rules, as a type.
+pub trait RuleSet: Clone + PartialEq + fmt::Debug + Send + 'static {
+    fn rules() -> Rules;
+
+    fn variant() -> Variant;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Misere;
+
+impl RuleSet for Misere {
+    fn rules() -> Rules {
+        Rules {
+            either_mark: false,
+            only_x: false,
+            line_loses: true,
+            depth_limit: 9,
+        }
+    }
+
+    fn variant() -> Variant {
+        Variant::Misere
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Wild;
+
+impl RuleSet for Wild {
+    fn rules() -> Rules {
+        Rules {
+            either_mark: true,
+            only_x: false,
+            line_loses: false,
+            // Twice the moves of tic-tac-toe at each level.
+            depth_limit: 5,
+        }
+    }
+
+    fn variant() -> Variant {
+        Variant::Wild
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notakto;
+
+impl RuleSet for Notakto {
+    fn rules() -> Rules {
+        Rules {
+            either_mark: false,
+            only_x: true,
+            line_loses: true,
+            depth_limit: 9,
+        }
+    }
+
+    fn variant() -> Variant {
+        Variant::Notakto
+    }
+}
+
+/// Which mark a move places.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Mark {
+    /// The mark of the player moving, or X in notakto.
+    Own,
+    /// The mark of the opponent, only in wild tic-tac-toe.
+    Other,
+}
+
+pub type MisereState = RulesState<Misere>;
+pub type WildState = RulesState<Wild>;
+pub type NotaktoState = RulesState<Notakto>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RulesState<R: RuleSet> {
+    field: [[CheckBox; 3]; 3],
+    /// The player that completed a line, if any. The mark doesn't say who
+    /// did in every game.
+    completed_by: CheckBox,
+    rules: PhantomData<R>,
+}
+
+impl<R: RuleSet> RulesState<R> {
+    fn line(&self) -> Option<[(usize, usize); 3]> {
+        LINES.iter().cloned().find(|line| {
+            let first = self.field[line[0].0][line[0].1];
+            first != CheckBox::Empty &&
+                line.iter().all(|&(x, y)| self.field[x][y] == first)
+        })
+    }
+
+    fn play(&self, (x, y, mark): (usize, usize, Mark), player: CheckBox) -> Self {
+        let mut ret = self.clone();
+        ret.field[x][y] = match mark {
+            _ if R::rules().only_x => CheckBox::X,
+            Mark::Own => player,
+            Mark::Other => player.next_player(),
+        };
+        if ret.line().is_some() {
+            ret.completed_by = player;
+        }
+        ret
+    }
+}
+
+impl<R: RuleSet> GameState for RulesState<R> {
+    /// The square, and the mark placed in it.
+    type Move = (usize, usize, Mark);
+
+    fn initial() -> Self {
+        Self {
+            field: [[CheckBox::Empty; 3]; 3],
+            completed_by: CheckBox::Empty,
+            rules: PhantomData,
+        }
+    }
+
+    fn winner(&self) -> CheckBox {
+        if R::rules().line_loses {
+            self.completed_by.next_player()
+        } else {
+            self.completed_by
+        }
+    }
+
+    fn successors(&self, player: CheckBox) -> Vec<(Self::Move, Self)> {
+        if self.completed_by != CheckBox::Empty {
+            return vec![];
+        }
+        let marks: &[Mark] = if R::rules().either_mark {
+            &[Mark::Own, Mark::Other]
+        } else {
+            &[Mark::Own]
+        };
+        let mut ret = vec![];
+        for x in 0..3 {
+            for y in 0..3 {
+                if self.field[x][y] != CheckBox::Empty {
+                    continue;
+                }
+                for &mark in marks {
+                    let mv = (x, y, mark);
+                    ret.push((mv, self.play(mv, player)));
+                }
+            }
+        }
+        ret
+    }
+
+    fn move_priority(&self, mv: Self::Move) -> u8 {
+        let priority = state::square_priority(mv.0, mv.1);
+        // Lines are bad news in the games where they lose, so look at the
+        // center last there.
+        if R::rules().line_loses { 2 - priority } else { priority }
+    }
+
+    fn move_error(&self, (x, y, _): Self::Move) -> MoveError {
+        if x >= 3 || y >= 3 {
+            MoveError::OutOfBounds
+        } else if self.field[x][y] != CheckBox::Empty {
+            MoveError::Occupied
+        } else {
+            MoveError::Illegal
+        }
+    }
+
+    fn is_center_move(&self, (x, y, _): Self::Move) -> bool {
+        (x, y) == (1, 1)
+    }
+
+    fn depth_limit() -> usize {
+        R::rules().depth_limit
+    }
+
+    fn is_solvable() -> bool {
+        !R::rules().either_mark
+    }
+
+    fn dump<W>(&self, indent: usize, dest: &mut W) -> fmt::Result
+        where W: fmt::Write,
+    {
+        for y in 0..3 {
+            for _ in 0..indent {
+                dest.write_char(' ')?;
+            }
+            for x in 0..3 {
+                dest.write_char(self.field[x][y].dump_char())?;
+            }
+            dest.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: RuleSet> GridState for RulesState<R> {
+    fn grid_size(&self) -> (usize, usize) {
+        (3, 3)
+    }
+
+    fn mark(&self, x: usize, y: usize) -> CheckBox {
+        self.field[x][y]
+    }
+
+    fn move_for_square(&self, x: usize, y: usize) -> Option<Self::Move> {
+        if self.completed_by != CheckBox::Empty || self.field[x][y] != CheckBox::Empty {
+            return None;
+        }
+        Some((x, y, Mark::Own))
+    }
+
+    fn other_move_for_square(&self, x: usize, y: usize) -> Option<Self::Move> {
+        if !R::rules().either_mark {
+            return None;
+        }
+        self.move_for_square(x, y).map(|(x, y, _)| (x, y, Mark::Other))
+    }
+
+    fn winning_squares(&self) -> Vec<(usize, usize)> {
+        self.line().map_or(vec![], |line| line.to_vec())
+    }
+}
+
+impl<R: RuleSet> Savable for RulesState<R> {
+    fn variant() -> Variant {
+        R::variant()
+    }
+
+    /// Moves with the mark of the opponent are written like `1,1,other`.
+    fn write_move((x, y, mark): Self::Move) -> String {
+        match mark {
+            Mark::Own => format!("{},{}", x, y),
+            Mark::Other => format!("{},{},other", x, y),
+        }
+    }
+
+    fn read_move(s: &str) -> Option<Self::Move> {
+        let (square, mark) = match s.strip_suffix(",other") {
+            Some(square) => (square, Mark::Other),
+            None => (s, Mark::Own),
+        };
+        let (x, y) = game_state::parse_pair(square)?;
+        Some((x, y, mark))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minimax::{MiniMaxTree, Solution};
+
+    fn play<R: RuleSet>(moves: &[(usize, usize, Mark)]) -> RulesState<R> {
+        let mut state = RulesState::<R>::initial();
+        let mut player = CheckBox::X;
+        for &mv in moves {
+            state = state.successors(player).into_iter().find(|s| s.0 == mv).unwrap().1;
+            player = player.next_player();
+        }
+        state
+    }
+
+    #[test]
+    fn lines_win_or_lose_depending_on_the_rules() {
+        // X completes the first column.
+        let moves = [
+            (0, 0, Mark::Own), (1, 0, Mark::Own),
+            (0, 1, Mark::Own), (1, 1, Mark::Own),
+            (0, 2, Mark::Own),
+        ];
+        assert_eq!(play::<Misere>(&moves).winner(), CheckBox::O);
+        assert_eq!(play::<Wild>(&moves[..4]).winner(), CheckBox::Empty);
+        assert_eq!(play::<Wild>(&moves).winner(), CheckBox::X);
+        assert_eq!(play::<Misere>(&moves).winning_squares(), [(0, 0), (0, 1), (0, 2)]);
+
+        // In wild tic-tac-toe, O wins completing a line of X marks.
+        let wild = play::<Wild>(&[(0, 0, Mark::Own), (0, 1, Mark::Other), (2, 2, Mark::Own), (0, 2, Mark::Other)]);
+        assert_eq!(wild.winner(), CheckBox::O);
+        assert!(wild.successors(CheckBox::X).is_empty());
+
+        // In notakto everything is an X, and the first line loses.
+        let notakto = play::<Notakto>(&moves[..3]);
+        assert_eq!(notakto.mark(1, 0), CheckBox::X);
+        assert_eq!(notakto.winner(), CheckBox::Empty);
+        assert_eq!(play::<Notakto>(&[(0, 0, Mark::Own), (0, 1, Mark::Own), (0, 2, Mark::Own)]).winner(), CheckBox::O);
+
+        assert_eq!(RulesState::<Wild>::initial().successors(CheckBox::X).len(), 18);
+        assert_eq!(RulesState::<Notakto>::initial().successors(CheckBox::X).len(), 9);
+        assert_eq!(RulesState::<Misere>::initial().other_move_for_square(1, 1), None);
+        assert_eq!(RulesState::<Wild>::initial().other_move_for_square(1, 1), Some((1, 1, Mark::Other)));
+
+        // The first player wins notakto, and misère tic-tac-toe is a draw.
+        assert!(matches!(MiniMaxTree::<NotaktoState>::new(CheckBox::X).solve(), Solution::Win(..)));
+        assert_eq!(MiniMaxTree::<MisereState>::new(CheckBox::X).solve(), Solution::Draw);
+    }
+}
@@ -0,0 +1,123 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! How the human has fared against the engine.
+
+use config::{Config, Value};
+use game_state::GameResult;
+use state::CheckBox;
+use std::fmt;
+
+/// The section of the configuration file with the all-time results.
+const SECTION: &str = "scoreboard";
+
+/// The results of some games, from the human's point of view.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct Tally {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl Tally {
+    /// Counts a game that ended with `result`, `human` being the human's
+    /// mark.
+    pub fn record(&mut self, result: GameResult, human: CheckBox) {
+        let winner = match result {
+            GameResult::XWins => CheckBox::X,
+            GameResult::OWins => CheckBox::O,
+            GameResult::TimeForfeit(loser) | GameResult::Resigned(loser) => loser.next_player(),
+            GameResult::Draw | GameResult::DrawAgreed => {
+                self.draws += 1;
+                return;
+            }
+            GameResult::InProgress => return,
+        };
+        if winner == human {
+            self.wins += 1;
+        } else {
+            self.losses += 1;
+        }
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} won, {} lost, {} drawn", self.wins, self.losses, self.draws)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Scoreboard {
+    /// The games since the program started.
+    pub session: Tally,
+    /// The games ever, which persist in the configuration file.
+    pub all_time: Tally,
+}
+
+impl Scoreboard {
+    /// Reads the all-time results from `config`, with the session ones
+    /// starting from zero.
+    pub fn from_config(config: &Config) -> Self {
+        let count = |key| {
+            config.get_integer(SECTION, key)
+                .filter(|&n| n >= 0 && n <= u32::MAX as i64)
+                .unwrap_or(0) as u32
+        };
+        Self {
+            session: Tally::default(),
+            all_time: Tally {
+                wins: count("wins"),
+                losses: count("losses"),
+                draws: count("draws"),
+            },
+        }
+    }
+
+    pub fn write_to(&self, config: &mut Config) {
+        config.set(SECTION, "wins", Value::Integer(self.all_time.wins as i64));
+        config.set(SECTION, "losses", Value::Integer(self.all_time.losses as i64));
+        config.set(SECTION, "draws", Value::Integer(self.all_time.draws as i64));
+    }
+
+    pub fn record(&mut self, result: GameResult, human: CheckBox) {
+        self.session.record(result, human);
+        self.all_time.record(result, human);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_from_the_human_side() {
+        let mut scoreboard = Scoreboard::default();
+        scoreboard.record(GameResult::OWins, CheckBox::O);
+        scoreboard.record(GameResult::OWins, CheckBox::X);
+        scoreboard.record(GameResult::Draw, CheckBox::X);
+        scoreboard.record(GameResult::InProgress, CheckBox::X);
+        let expected = Tally { wins: 1, losses: 1, draws: 1 };
+        assert_eq!(scoreboard.session, expected);
+
+        let mut config = Config::default();
+        scoreboard.write_to(&mut config);
+        let parsed = Scoreboard::from_config(&config);
+        assert_eq!(parsed.all_time, expected);
+        assert_eq!(parsed.session, Tally::default());
+    }
+}
@@ -0,0 +1,324 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Self-play games as training data: every position the engine searched,
+//! with the move it played and how the game ended.
+//!
+//! Positions are written in the rows of the grid, top to bottom, like
+//! `X_O/_X_/__O`.
+
+use connect_four::ConnectFourState;
+use cube::{CubeState, QubicState};
+use game;
+use game_state::{GameResult, GridState};
+use minimax::{MiniMaxTree, SearchConfig};
+use number_scrabble::NumberScrabbleState;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rules::{MisereState, NotaktoState, WildState};
+use state::{CheckBox, State};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::thread;
+use ultimate::UltimateState;
+use variant::Variant;
+
+pub const USAGE: &str = "\
+usage: tic-tac-minimax selfplay [options]
+
+  --variant <name>     tictactoe (default), ultimate, connect4, scrabble,
+                       misere, wild, notakto, cube or qubic
+  --games <n>          play <n> games (default: 100)
+  --depth-x <n>        search <n> levels for X (default: as deep as the game
+                       allows)
+  --depth-o <n>        the same for O
+  --randomize-x <b>    whether X picks at random among its best moves, yes or
+                       no (default: yes)
+  --randomize-o <b>    the same for O
+  --random-plies <n>   open each game with <n> random moves, which aren't
+                       written (default: 2)
+  --seed <n>           seed for the random moves (default: 0)
+  --threads <n>        play on <n> threads (default: one per CPU)
+  --format <name>      csv (default) or jsonl
+  --output <path>      write to <path> (default: the standard output)
+
+Each game gets its own random numbers, so the output doesn't depend on the
+number of threads.";
+
+#[derive(Clone, Debug)]
+struct Options {
+    variant: Variant,
+    games: usize,
+    /// The depth and whether to randomize ties of X and O, in that order.
+    depths: [Option<usize>; 2],
+    randomize: [bool; 2],
+    random_plies: usize,
+    seed: u64,
+    threads: usize,
+    jsonl: bool,
+    output: Option<String>,
+}
+
+fn side(player: CheckBox) -> usize {
+    match player {
+        CheckBox::X => 0,
+        _ => 1,
+    }
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        variant: Variant::TicTacToe,
+        games: 100,
+        depths: [None, None],
+        randomize: [true, true],
+        random_plies: 2,
+        seed: 0,
+        threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        jsonl: false,
+        output: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+        let invalid = || format!("invalid value for {}: {}", arg, value);
+        let number = || value.parse::<u64>().map_err(|_| invalid());
+        let boolean = || match value.as_str() {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            _ => Err(invalid()),
+        };
+        match arg.as_str() {
+            "--variant" => {
+                options.variant = Variant::from_id(value)
+                    .ok_or_else(|| format!("unknown variant: {}", value))?;
+            }
+            "--games" => options.games = number()? as usize,
+            "--depth-x" => options.depths[0] = Some(number()? as usize),
+            "--depth-o" => options.depths[1] = Some(number()? as usize),
+            "--randomize-x" => options.randomize[0] = boolean()?,
+            "--randomize-o" => options.randomize[1] = boolean()?,
+            "--random-plies" => options.random_plies = number()? as usize,
+            "--seed" => options.seed = number()?,
+            "--threads" => options.threads = number()? as usize,
+            "--format" => match value.as_str() {
+                "csv" => options.jsonl = false,
+                "jsonl" => options.jsonl = true,
+                _ => return Err(format!("unknown format: {}", value)),
+            },
+            "--output" => options.output = Some(value.clone()),
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+
+    for depth in &mut options.depths {
+        if let Some(ref mut depth) = *depth {
+            *depth = SearchConfig::new(*depth, options.variant.depth_limit())
+                .map_err(|err| err.to_string())?
+                .depth();
+        }
+    }
+    if options.threads == 0 {
+        return Err("there must be at least a thread".to_owned());
+    }
+    Ok(options)
+}
+
+/// A position the engine searched, and what it played.
+#[derive(Clone, Debug, PartialEq)]
+struct Sample {
+    position: String,
+    player: CheckBox,
+    /// The square the engine marked, and the mark it put there, which isn't
+    /// always the player's own.
+    square: String,
+    mark: CheckBox,
+    /// The score of the move, from O's point of view.
+    score: i8,
+}
+
+/// Returns the grid of `state`, in rows separated by slashes.
+fn grid_notation<S: GridState>(state: &S) -> String {
+    let (cols, rows) = state.grid_size();
+    (0..rows).map(|y| {
+        (0..cols).map(|x| state.mark(x, y).dump_char()).collect::<String>()
+    }).collect::<Vec<_>>().join("/")
+}
+
+/// Plays the game with the given index, returning the positions searched and
+/// how the game ended.
+fn play_game<S: GridState>(options: &Options, index: usize) -> (Vec<Sample>, GameResult) {
+    let mut rng = StdRng::seed_from_u64(options.seed.wrapping_add(index as u64));
+    let mut tree = MiniMaxTree::<S>::new(CheckBox::X);
+    let mut samples = vec![];
+    let mut ply = 0;
+    while !tree.state().is_over() {
+        let player = tree.player();
+        if ply < options.random_plies {
+            let moves = tree.state().successors(player).len();
+            tree.choose_with_index(rng.gen_range(0..moves));
+            ply += 1;
+            continue;
+        }
+
+        let depth = options.depths[side(player)].unwrap_or(S::depth_limit());
+        // Scores are from O's point of view, so X wants the lowest.
+        let scores = tree.evaluate_all_moves(depth).into_iter()
+            .map(|(mv, score)| if player == CheckBox::O { (mv, score) } else { (mv, -score) })
+            .collect::<Vec<_>>();
+        let best = scores.iter().map(|&(_, score)| score).max()
+            .expect("Should have a move in a game that isn't over");
+        let ties = scores.iter().filter(|&&(_, score)| score == best).collect::<Vec<_>>();
+        let &&(mv, _) = if options.randomize[side(player)] {
+            &ties[rng.gen_range(0..ties.len())]
+        } else {
+            &ties[0]
+        };
+
+        let before = tree.state().clone();
+        tree.choose(mv).expect("Searched moves should be legal");
+        let (mark, square) = game::name_move(&before, tree.state());
+        samples.push(Sample {
+            position: grid_notation(&before),
+            player,
+            square,
+            mark,
+            score: if player == CheckBox::O { best } else { -best },
+        });
+        ply += 1;
+    }
+    (samples, tree.state().result())
+}
+
+/// Plays all the games, spread over the threads, in the order of their
+/// indices.
+fn play_games<S: GridState>(options: &Options) -> Vec<(Vec<Sample>, GameResult)> {
+    // Without spawning, which isn't supported everywhere, like on wasm.
+    if options.threads == 1 {
+        return (0..options.games).map(|index| play_game::<S>(options, index)).collect();
+    }
+
+    let threads = (0..options.threads).map(|thread| {
+        let options = options.clone();
+        thread::spawn(move || {
+            (thread..options.games).step_by(options.threads).map(|index| {
+                (index, play_game::<S>(&options, index))
+            }).collect::<Vec<_>>()
+        })
+    }).collect::<Vec<_>>();
+
+    let mut games = threads.into_iter()
+        .flat_map(|thread| thread.join().expect("Self-play threads shouldn't panic"))
+        .collect::<Vec<_>>();
+    games.sort_by_key(|&(index, _)| index);
+    games.into_iter().map(|(_, game)| game).collect()
+}
+
+fn outcome_name(result: GameResult) -> &'static str {
+    match result {
+        GameResult::XWins => "x-wins",
+        GameResult::OWins => "o-wins",
+        GameResult::Draw => "draw",
+        _ => unreachable!(),
+    }
+}
+
+/// Writes the samples of `games`, a line each, after a header for CSV.
+fn write_samples<W>(games: &[(Vec<Sample>, GameResult)], jsonl: bool, dest: &mut W) -> io::Result<()>
+    where W: Write,
+{
+    if !jsonl {
+        writeln!(dest, "position,player,move,mark,score,outcome")?;
+    }
+    for &(ref samples, result) in games {
+        for sample in samples {
+            if jsonl {
+                writeln!(dest,
+                         "{{\"position\":\"{}\",\"player\":\"{}\",\"move\":\"{}\",\"mark\":\"{}\",\"score\":{},\"outcome\":\"{}\"}}",
+                         sample.position, sample.player.dump_char(), sample.square,
+                         sample.mark.dump_char(), sample.score, outcome_name(result))?;
+            } else {
+                writeln!(dest, "{},{},{},{},{},{}",
+                         sample.position, sample.player.dump_char(), sample.square,
+                         sample.mark.dump_char(), sample.score, outcome_name(result))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Plays the games the arguments describe, and writes their positions.
+pub fn run(args: &[String]) -> Result<(), String> {
+    if args.iter().any(|arg| arg == "--help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let options = parse_options(args)?;
+    let games = match options.variant {
+        Variant::TicTacToe => play_games::<State>(&options),
+        Variant::Ultimate => play_games::<UltimateState>(&options),
+        Variant::ConnectFour => play_games::<ConnectFourState>(&options),
+        Variant::NumberScrabble => play_games::<NumberScrabbleState>(&options),
+        Variant::Misere => play_games::<MisereState>(&options),
+        Variant::Wild => play_games::<WildState>(&options),
+        Variant::Notakto => play_games::<NotaktoState>(&options),
+        Variant::Cube => play_games::<CubeState>(&options),
+        Variant::Qubic => play_games::<QubicState>(&options),
+    };
+
+    let result = match options.output {
+        Some(ref path) => File::create(path).and_then(|file| {
+            let mut dest = BufWriter::new(file);
+            write_samples(&games, options.jsonl, &mut dest)?;
+            dest.flush()
+        }),
+        None => {
+            let stdout = io::stdout();
+            let mut dest = stdout.lock();
+            write_samples(&games, options.jsonl, &mut dest)
+        }
+    };
+    result.map_err(|err| format!("couldn't write the positions: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perfect_games_are_labeled_draws() {
+        let args = ["--games", "3", "--random-plies", "0", "--threads", "2"];
+        let options = parse_options(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>()).unwrap();
+        let games = play_games::<State>(&options);
+        assert_eq!(games.len(), 3);
+        for &(ref samples, result) in &games {
+            assert_eq!(result, GameResult::Draw);
+            assert_eq!(samples[0].position, "___/___/___");
+            assert!(samples.iter().all(|s| s.score == 0));
+        }
+
+        let mut csv = vec![];
+        write_samples(&games[..1], /* jsonl = */ false, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("position,player,move,mark,score,outcome"));
+        assert!(lines.next().unwrap().starts_with("___/___/___,X,"));
+        assert!(lines.all(|line| line.ends_with(",0,draw")));
+    }
+}
@@ -0,0 +1,204 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A quick battery of sanity checks for the engine, meant to verify a build
+//! on platforms we don't usually test on.
+
+use game::Game;
+use game_state::{GameResult, GameState};
+use minimax::{Difficulty, MiniMaxTree};
+use number_scrabble::NumberScrabbleState;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use state::{CheckBox, State};
+
+/// The number of move sequences of each length from the empty board,
+/// stopping when somebody wins.
+const PERFT: [usize; 9] = [9, 72, 504, 3024, 15120, 54720, 148176, 200448, 127872];
+
+/// A check, with its name.
+type Check = (&'static str, fn() -> Result<(), String>);
+
+/// Runs all the checks, printing the result of each of them.
+///
+/// Returns whether all of them passed.
+pub fn run() -> bool {
+    let checks: [Check; 7] = [
+        ("perft", perft),
+        ("pruning equivalence", pruning_equivalence),
+        ("perfect play draws", perfect_play_draws),
+        ("takes immediate wins", takes_immediate_wins),
+        ("blocks immediate losses", blocks_immediate_losses),
+        ("save round-trip", save_round_trip),
+        ("number scrabble isomorphism", number_scrabble_isomorphism),
+    ];
+
+    let mut all_passed = true;
+    for &(name, check) in checks.iter() {
+        match check() {
+            Ok(()) => println!("PASS {}", name),
+            Err(reason) => {
+                all_passed = false;
+                println!("FAIL {}: {}", name, reason);
+            }
+        }
+    }
+    all_passed
+}
+
+fn count_sequences(state: &State, player: CheckBox, depth: usize, counts: &mut [usize]) {
+    if state.score() != 0 {
+        return;
+    }
+    for s in state.subsequent_states(player) {
+        counts[depth] += 1;
+        count_sequences(&s, player.next_player(), depth + 1, counts);
+    }
+}
+
+fn perft() -> Result<(), String> {
+    let mut counts = [0; 9];
+    count_sequences(&State::initial(), CheckBox::X, 0, &mut counts);
+    if counts != PERFT {
+        return Err(format!("expected {:?}, got {:?}", PERFT, counts));
+    }
+    Ok(())
+}
+
+fn pruning_equivalence() -> Result<(), String> {
+    // Always sample the same positions, so that failures are reproducible.
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..50 {
+        let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+        let plies = rng.gen_range(0..8);
+        for _ in 0..plies {
+            if tree.state().score() != 0 {
+                break;
+            }
+            let moves = tree.state().subsequent_states(tree.player()).count();
+            if moves == 0 {
+                break;
+            }
+            tree.choose_with_index(rng.gen_range(0..moves));
+        }
+
+        for max_depth in 1..10 {
+            if !tree.pruning_agrees(max_depth) {
+                let mut dump = String::new();
+                let _ = tree.state().dump(0, &mut dump);
+                return Err(format!("disagreement at depth {}:\n{}", max_depth, dump));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn perfect_play_draws() -> Result<(), String> {
+    let mut game = Game::<State>::new(CheckBox::X);
+    game.set_difficulty(Difficulty::Perfect);
+    let mut rng = StdRng::seed_from_u64(0);
+    while game.play_engine_move(&mut rng).is_some() {}
+    match game.state().result() {
+        GameResult::Draw => Ok(()),
+        result => Err(format!("expected a draw, got {:?}", result)),
+    }
+}
+
+fn takes_immediate_wins() -> Result<(), String> {
+    // O can win at (2, 1). Deeper searches may find other eventually winning
+    // moves just as good, so only look one move ahead.
+    let mut tree = MiniMaxTree::<State>::new(CheckBox::X);
+    for &(x, y) in &[(0, 0), (0, 1), (1, 0), (1, 1), (2, 2)] {
+        tree.choose((x, y)).expect("Invalid test position");
+    }
+    let index = match tree.find_move_index(1).index() {
+        Some(index) => index,
+        None => return Err("no move found".to_owned()),
+    };
+    tree.choose_with_index(index);
+    match tree.state().result() {
+        GameResult::OWins => Ok(()),
+        result => Err(format!("expected O to win, got {:?}", result)),
+    }
+}
+
+fn blocks_immediate_losses() -> Result<(), String> {
+    // X threatens (0, 2), O must block it.
+    let mut game = Game::<State>::new(CheckBox::X);
+    for &(x, y) in &[(0, 0), (1, 1), (0, 1)] {
+        game.choose((x, y)).expect("Invalid test position");
+    }
+    game.set_difficulty(Difficulty::Perfect);
+    let mut rng = StdRng::seed_from_u64(0);
+    match game.play_engine_move(&mut rng) {
+        Some((0, 2)) => Ok(()),
+        other => Err(format!("expected (0, 2), got {:?}", other)),
+    }
+}
+
+fn save_round_trip() -> Result<(), String> {
+    let mut game = Game::<State>::new(CheckBox::X);
+    game.set_difficulty(Difficulty::Medium);
+    for &(x, y) in &[(1, 1), (0, 0), (2, 2)] {
+        game.choose((x, y)).expect("Invalid test position");
+    }
+
+    let mut saved = vec![];
+    game.write(&mut saved).map_err(|e| e.to_string())?;
+    let loaded = Game::<State>::read(&saved[..]).map_err(|e| e.to_string())?;
+
+    let mut saved_again = vec![];
+    loaded.write(&mut saved_again).map_err(|e| e.to_string())?;
+    if saved != saved_again {
+        return Err(format!("expected {:?}, got {:?}",
+                           String::from_utf8_lossy(&saved),
+                           String::from_utf8_lossy(&saved_again)));
+    }
+    if loaded.player() != CheckBox::O || loaded.state().get(2, 2) != CheckBox::X {
+        return Err("the loaded board doesn't match".to_owned());
+    }
+    Ok(())
+}
+
+fn number_scrabble_isomorphism() -> Result<(), String> {
+    // Every set of squares one player can hold, with the other player in the
+    // rest, so that the round-trip sees all kinds of squares.
+    for squares in 0..(1 << 9) {
+        let mut state = State::initial();
+        for i in 0..9 {
+            let mark = if squares & (1 << i) != 0 { CheckBox::X } else { CheckBox::Empty };
+            state.set(i % 3, i / 3, mark);
+        }
+        let x_only = state.clone();
+        for i in 0..9 {
+            if squares & (1 << i) == 0 && i % 2 == 0 {
+                state.set(i % 3, i / 3, CheckBox::O);
+            }
+        }
+
+        let scrabble = NumberScrabbleState::from_tic_tac_toe(&state);
+        if scrabble.to_tic_tac_toe() != state {
+            return Err(format!("{:?} doesn't round-trip", state));
+        }
+        let scrabble = NumberScrabbleState::from_tic_tac_toe(&x_only);
+        if scrabble.winner() != x_only.winner() {
+            return Err(format!("picking {:?} doesn't win like {:?}",
+                               scrabble.numbers(CheckBox::X), x_only));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,238 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The settings that persist across launches.
+
+use clock::{TimeControl, TimeoutPolicy};
+use config::{Config, Value};
+use player::PlayerConfig;
+use state::CheckBox;
+use theme::Theme;
+use variant::Variant;
+
+/// The section of the configuration file with the game settings.
+const SECTION: &str = "game";
+
+/// The sections with how the engine plays against the human, and for the
+/// hints of the human.
+const ENGINE_SECTION: &str = "engine";
+const HINTS_SECTION: &str = "hints";
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// The variant new games are of.
+    pub variant: Variant,
+    /// How the engine plays against the human.
+    pub engine: PlayerConfig,
+    /// How the engine searches the hints of the human.
+    pub hints: PlayerConfig,
+    /// The mark the human plays with. The human moves first, unless told
+    /// otherwise on the command line.
+    pub human: CheckBox,
+    /// Whether to play sounds for the moves and the end of the game.
+    pub sounds: bool,
+    /// The seconds the human has for the whole game, or zero to play
+    /// without a clock.
+    pub clock_seconds: u32,
+    /// The seconds the human gets after each of their moves.
+    pub clock_increment_seconds: u32,
+    /// The seconds the engine has for the whole game, or zero for as many
+    /// as the human.
+    pub engine_clock_seconds: u32,
+    /// The seconds the engine gets after each of its moves.
+    pub engine_clock_increment_seconds: u32,
+    /// Whether the clock of the human stops while the window is in the
+    /// background, or the computer is asleep, in the games with a clock.
+    /// Only the clock of the human, never the one of the engine.
+    pub pause_clock_when_away: bool,
+    /// What happens to whoever runs out of time.
+    pub timeout_policy: TimeoutPolicy,
+    /// Whether to ask before the human misses a win or a forced block.
+    pub trainer: bool,
+    /// Whether to show the evaluation bar beside the board. It searches as
+    /// deep as the hints after every move, so it's off unless asked for.
+    pub eval_bar: bool,
+    pub theme: Theme,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            variant: Variant::TicTacToe,
+            engine: PlayerConfig {
+                randomize: true,
+                ..PlayerConfig::default()
+            },
+            hints: PlayerConfig::default(),
+            human: CheckBox::X,
+            sounds: false,
+            clock_seconds: 0,
+            clock_increment_seconds: 0,
+            engine_clock_seconds: 0,
+            engine_clock_increment_seconds: 0,
+            pause_clock_when_away: false,
+            timeout_policy: TimeoutPolicy::Forfeit,
+            trainer: false,
+            eval_bar: false,
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// An hour is more than anyone needs for a game of any of these.
+    pub const MAX_CLOCK_SECONDS: u32 = 3600;
+
+    /// Reads the settings from `config`, using the defaults for whatever is
+    /// missing or invalid.
+    pub fn from_config(config: &Config) -> Self {
+        let mut settings = Settings::default();
+        if let Some(variant) = config.get_string(SECTION, "variant").and_then(Variant::from_id) {
+            settings.variant = variant;
+        }
+        // Where the engine settings were before the hints had their own.
+        // TODO(emilio): Drop this with the next version of the file.
+        settings.engine.update_from(config, SECTION);
+        settings.engine.update_from(config, ENGINE_SECTION);
+        settings.hints.update_from(config, HINTS_SECTION);
+        match config.get_string(SECTION, "mark") {
+            Some("X") => settings.human = CheckBox::X,
+            Some("O") => settings.human = CheckBox::O,
+            _ => {}
+        }
+        if let Some(sounds) = config.get_boolean(SECTION, "sounds") {
+            settings.sounds = sounds;
+        }
+        let clock_seconds = |key| match config.get_integer(SECTION, key) {
+            Some(seconds) if seconds >= 0 && seconds <= Self::MAX_CLOCK_SECONDS as i64 => Some(seconds as u32),
+            _ => None,
+        };
+        if let Some(seconds) = clock_seconds("clock-seconds") {
+            settings.clock_seconds = seconds;
+        }
+        if let Some(seconds) = clock_seconds("clock-increment-seconds") {
+            settings.clock_increment_seconds = seconds;
+        }
+        if let Some(seconds) = clock_seconds("engine-clock-seconds") {
+            settings.engine_clock_seconds = seconds;
+        }
+        if let Some(seconds) = clock_seconds("engine-clock-increment-seconds") {
+            settings.engine_clock_increment_seconds = seconds;
+        }
+        if let Some(pause) = config.get_boolean(SECTION, "pause-clock-when-away") {
+            settings.pause_clock_when_away = pause;
+        }
+        if let Some(policy) = config.get_string(SECTION, "on-timeout").and_then(TimeoutPolicy::from_name) {
+            settings.timeout_policy = policy;
+        }
+        if let Some(trainer) = config.get_boolean(SECTION, "trainer") {
+            settings.trainer = trainer;
+        }
+        if let Some(eval_bar) = config.get_boolean(SECTION, "eval-bar") {
+            settings.eval_bar = eval_bar;
+        }
+        settings.theme = Theme::from_config(config);
+        settings
+    }
+
+    pub fn write_to(&self, config: &mut Config) {
+        config.set(SECTION, "variant", Value::String(self.variant.id().to_owned()));
+        config.set(SECTION, "mark", Value::String(self.human.dump_char().to_string()));
+        self.engine.write_to(config, ENGINE_SECTION);
+        self.hints.write_to(config, HINTS_SECTION);
+        config.set(SECTION, "sounds", Value::Boolean(self.sounds));
+        config.set(SECTION, "clock-seconds", Value::Integer(self.clock_seconds as i64));
+        config.set(SECTION, "clock-increment-seconds", Value::Integer(self.clock_increment_seconds as i64));
+        config.set(SECTION, "engine-clock-seconds", Value::Integer(self.engine_clock_seconds as i64));
+        config.set(SECTION, "engine-clock-increment-seconds",
+                   Value::Integer(self.engine_clock_increment_seconds as i64));
+        config.set(SECTION, "pause-clock-when-away", Value::Boolean(self.pause_clock_when_away));
+        config.set(SECTION, "on-timeout", Value::String(self.timeout_policy.name().to_owned()));
+        config.set(SECTION, "trainer", Value::Boolean(self.trainer));
+        config.set(SECTION, "eval-bar", Value::Boolean(self.eval_bar));
+        self.theme.write_to(config);
+    }
+
+    /// Returns the time the human and the engine have, in that order, or
+    /// `None` to play without a clock.
+    pub fn time_controls(&self) -> Option<(TimeControl, TimeControl)> {
+        if self.clock_seconds == 0 {
+            return None;
+        }
+        let engine_seconds = match self.engine_clock_seconds {
+            0 => self.clock_seconds,
+            seconds => seconds,
+        };
+        Some((TimeControl::from_secs(self.clock_seconds as u64, self.clock_increment_seconds as u64),
+              TimeControl::from_secs(engine_seconds as u64, self.engine_clock_increment_seconds as u64)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minimax::Difficulty;
+
+    #[test]
+    fn round_trips() {
+        let mut settings = Settings {
+            variant: Variant::ConnectFour,
+            human: CheckBox::O,
+            sounds: true,
+            clock_seconds: 90,
+            clock_increment_seconds: 5,
+            engine_clock_increment_seconds: 1,
+            pause_clock_when_away: true,
+            timeout_policy: TimeoutPolicy::GreedyMove,
+            trainer: true,
+            eval_bar: true,
+            ..Settings::default()
+        };
+        settings.engine.difficulty = Difficulty::Medium;
+        settings.engine.handicap.no_center_opening = true;
+        settings.hints.depth = Some(2);
+        settings.engine.randomize = false;
+        settings.theme.font_size = 20;
+
+        let mut config = Config::default();
+        settings.write_to(&mut config);
+        let parsed = Settings::from_config(&Config::parse(&config.to_string()).unwrap());
+        assert_eq!(parsed.variant, Variant::ConnectFour);
+        assert_eq!(parsed.engine.difficulty, Difficulty::Medium);
+        assert!(parsed.engine.handicap.no_center_opening);
+        assert_eq!(parsed.hints.depth, Some(2));
+        assert_eq!(parsed.human, CheckBox::O);
+        assert!(!parsed.engine.randomize);
+        assert!(parsed.sounds);
+        assert_eq!(parsed.clock_seconds, 90);
+        assert_eq!(parsed.time_controls(), Some((TimeControl::from_secs(90, 5), TimeControl::from_secs(90, 1))));
+        assert!(parsed.pause_clock_when_away);
+        assert_eq!(parsed.timeout_policy, TimeoutPolicy::GreedyMove);
+        assert!(parsed.trainer);
+        assert!(parsed.eval_bar);
+        assert_eq!(parsed.theme.font_size, 20);
+    }
+
+    #[test]
+    fn reads_the_old_engine_settings() {
+        let config = Config::parse("version = 1\n[game]\ndifficulty = \"Hard\"\nrandomize = false\n").unwrap();
+        let settings = Settings::from_config(&config);
+        assert_eq!(settings.engine.difficulty, Difficulty::Hard);
+        assert!(!settings.engine.randomize);
+        assert_eq!(settings.hints, PlayerConfig::default());
+    }
+}
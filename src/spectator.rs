@@ -0,0 +1,497 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Spectators of a game over a WebSocket, like a browser or another
+//! instance with `--spectate ws://host:port`.
+//!
+//! The host broadcasts the events of `observer` as text messages, one event
+//! per message, and ignores whatever the spectators send. Only what's needed
+//! of RFC 6455 for that is here: no extensions, and no fragmented messages.
+
+use observer::{self, Sink, Snapshot};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+/// What the handshake appends to the key of the client before hashing it.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The most a client can send before its handshake is over.
+const MAX_REQUEST_BYTES: usize = 8192;
+
+/// The longest message a spectator takes, far longer than any event.
+const MAX_PAYLOAD_BYTES: usize = 65536;
+
+/// The longest a frame header gets: two bytes, eight of length and four of
+/// mask.
+const MAX_HEADER_BYTES: usize = 14;
+
+/// The SHA-1 of `data`, which the handshake needs.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    let bits = (data.len() as u64).wrapping_mul(8);
+    for i in (0..8).rev() {
+        message.push((bits >> (i * 8)) as u8);
+    }
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for i in 0..16 {
+            w[i] = (chunk[4 * i] as u32) << 24 | (chunk[4 * i + 1] as u32) << 16 |
+                (chunk[4 * i + 2] as u32) << 8 | chunk[4 * i + 3] as u32;
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, v) in h.iter_mut().zip(&[a, b, c, d, e]) {
+            *h = h.wrapping_add(*v);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (i, word) in h.iter().enumerate() {
+        for j in 0..4 {
+            digest[4 * i + j] = (word >> (24 - 8 * j)) as u8;
+        }
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = (chunk[0] as u32) << 16 |
+            (*chunk.get(1).unwrap_or(&0) as u32) << 8 |
+            *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Returns what the server answers to the `Sec-WebSocket-Key` of a client.
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key.trim(), WEBSOCKET_GUID).as_bytes()))
+}
+
+/// Returns the value of the header called `name` of an HTTP request or
+/// response, if any.
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).filter_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        if key.trim().eq_ignore_ascii_case(name) { Some(value.trim()) } else { None }
+    }).next()
+}
+
+/// Returns a frame with the text message `text`, masked with `mask` if
+/// given, which messages from clients must be.
+fn text_frame(text: &str, mask: Option<[u8; 4]>) -> Vec<u8> {
+    let payload = text.as_bytes();
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    let mut frame = vec![0x81];
+    match payload.len() {
+        len if len < 126 => frame.push(mask_bit | len as u8),
+        len if len <= 0xffff => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&[(len >> 8) as u8, len as u8]);
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            for i in (0..8).rev() {
+                frame.push((len as u64 >> (i * 8)) as u8);
+            }
+        }
+    }
+    match mask {
+        Some(mask) => {
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    frame
+}
+
+/// A frame read from a WebSocket.
+#[derive(Clone, Eq, PartialEq, Debug)]
+enum Frame {
+    Text(String),
+    Close,
+    /// Something else, like a ping, that spectators don't care about.
+    Other,
+}
+
+/// Reads the frame at the start of `buffer`, if it's all there, returning
+/// it and its length.
+///
+/// Frames longer than `MAX_PAYLOAD_BYTES` are an error, rather than waiting
+/// for all of them.
+fn read_frame(buffer: &[u8]) -> io::Result<Option<(Frame, usize)>> {
+    if buffer.len() < 2 {
+        return Ok(None);
+    }
+    let opcode = buffer[0] & 0x0f;
+    if buffer[0] & 0x80 == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "fragmented messages aren't supported"));
+    }
+    let masked = buffer[1] & 0x80 != 0;
+    let (len, mut start) = match buffer[1] & 0x7f {
+        126 if buffer.len() >= 4 => ((buffer[2] as u64) << 8 | buffer[3] as u64, 4),
+        127 if buffer.len() >= 10 => {
+            (buffer[2..10].iter().fold(0, |len, &b| len << 8 | b as u64), 10)
+        }
+        126 | 127 => return Ok(None),
+        len => (len as u64, 2),
+    };
+    if len > MAX_PAYLOAD_BYTES as u64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the message is too long"));
+    }
+    let len = len as usize;
+    let mask = if masked {
+        if buffer.len() < start + 4 {
+            return Ok(None);
+        }
+        start += 4;
+        Some([buffer[start - 4], buffer[start - 3], buffer[start - 2], buffer[start - 1]])
+    } else {
+        None
+    };
+    if buffer.len() < start + len {
+        return Ok(None);
+    }
+
+    let mut payload = buffer[start..start + len].to_vec();
+    if let Some(mask) = mask {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+    }
+    let frame = match opcode {
+        1 => Frame::Text(String::from_utf8(payload).map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, err)
+        })?),
+        8 => Frame::Close,
+        _ => Frame::Other,
+    };
+    Ok(Some((frame, start + len)))
+}
+
+/// Reads what's there to read from `stream` into `buffer`, without
+/// blocking, until `buffer` has `max` bytes.
+///
+/// Returns whether the other side is still there.
+fn read_available<R>(stream: &mut R, buffer: &mut Vec<u8>, max: usize) -> io::Result<bool>
+    where R: Read,
+{
+    let mut chunk = [0; 512];
+    while buffer.len() < max {
+        let len = chunk.len().min(max - buffer.len());
+        match stream.read(&mut chunk[..len]) {
+            Ok(0) => return Ok(false),
+            Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(true),
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(true)
+}
+
+/// A client that connected but didn't finish its handshake yet.
+struct Pending {
+    stream: TcpStream,
+    request: Vec<u8>,
+}
+
+/// Broadcasts the events of the game to the spectators that connect to a
+/// port.
+pub struct Broadcaster {
+    listener: TcpListener,
+    port: u16,
+    pending: Vec<Pending>,
+    spectators: Vec<TcpStream>,
+    /// What the spectators know so far, if anything.
+    last: Option<Snapshot>,
+}
+
+impl Broadcaster {
+    /// Waits for spectators on `port`, of this machine only unless `public`
+    /// is set, which lets anyone who can reach it watch.
+    pub fn bind(port: u16, public: bool) -> io::Result<Self> {
+        let host = if public { "0.0.0.0" } else { "127.0.0.1" };
+        let listener = TcpListener::bind((host, port))?;
+        // Never block the main loop waiting for spectators.
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        Ok(Self {
+            listener,
+            port,
+            pending: vec![],
+            spectators: vec![],
+            last: None,
+        })
+    }
+
+    /// The port the spectators connect to.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Answers the handshakes of the clients that sent their request, and
+    /// returns them.
+    fn finish_handshakes(&mut self) -> Vec<TcpStream> {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.pending.push(Pending { stream, request: vec![] });
+            }
+        }
+
+        let mut ready = vec![];
+        for mut pending in mem::take(&mut self.pending) {
+            match read_available(&mut pending.stream, &mut pending.request, MAX_REQUEST_BYTES) {
+                Ok(true) => {}
+                _ => continue,
+            }
+            let end = match pending.request.windows(4).position(|w| w == b"\r\n\r\n") {
+                Some(end) => end,
+                None => {
+                    if pending.request.len() < MAX_REQUEST_BYTES {
+                        self.pending.push(pending);
+                    }
+                    continue;
+                }
+            };
+            let head = String::from_utf8_lossy(&pending.request[..end]).into_owned();
+            let key = match header(&head, "Sec-WebSocket-Key") {
+                Some(key) if head.starts_with("GET ") => key,
+                _ => {
+                    let _ = pending.stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n");
+                    continue;
+                }
+            };
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(key));
+            if pending.stream.write_all(response.as_bytes()).is_ok() {
+                ready.push(pending.stream);
+            }
+        }
+        ready
+    }
+}
+
+impl Sink for Broadcaster {
+    fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// Sends the spectators what changed since the last update, and the
+    /// whole game to the ones that just connected.
+    ///
+    /// Spectators that don't keep up are dropped rather than slowing down
+    /// the game.
+    fn update(&mut self, snapshot: Snapshot) {
+        let lines = observer::events(self.last.as_ref(), &snapshot);
+        self.spectators.retain(|mut spectator| {
+            // What spectators send only matters to find out they left.
+            let mut ignored = vec![];
+            match read_available(&mut spectator, &mut ignored, MAX_REQUEST_BYTES) {
+                Ok(true) => {}
+                _ => return false,
+            }
+            lines.iter().all(|line| spectator.write_all(&text_frame(line, None)).is_ok())
+        });
+
+        for mut spectator in self.finish_handshakes() {
+            let hello = observer::events(None, &snapshot);
+            if hello.iter().all(|line| spectator.write_all(&text_frame(line, None)).is_ok()) {
+                self.spectators.push(spectator);
+            }
+        }
+        self.last = Some(snapshot);
+    }
+}
+
+/// Watches the game of a `Broadcaster`.
+pub struct Spectator {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    snapshot: Option<Snapshot>,
+    address: String,
+}
+
+impl Spectator {
+    /// Connects to the broadcaster at `url`, like `ws://localhost:9998`.
+    pub fn connect(url: &str) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_owned());
+        if !url.starts_with("ws://") {
+            return Err(invalid("only ws:// URLs are supported"));
+        }
+        let rest = &url["ws://".len()..];
+        let (address, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let addr = address.to_socket_addrs()?.next()
+            .ok_or_else(|| invalid("no such host"))?;
+        let mut stream = TcpStream::connect(addr)?;
+
+        let key = base64(&::rand::random::<[u8; 16]>());
+        write!(stream,
+               "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+               path, address, key)?;
+
+        // The handshake is quick, so block until it's done.
+        let mut response = vec![];
+        let mut byte = [0];
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() > MAX_REQUEST_BYTES || stream.read(&mut byte)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "not a WebSocket server"));
+            }
+            response.push(byte[0]);
+        }
+        let head = String::from_utf8_lossy(&response).into_owned();
+        let accepted = head.starts_with("HTTP/1.1 101") &&
+            header(&head, "Sec-WebSocket-Accept") == Some(&*accept_key(&key));
+        if !accepted {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the server refused the WebSocket"));
+        }
+
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            stream,
+            buffer: vec![],
+            snapshot: None,
+            address: address.to_owned(),
+        })
+    }
+
+    /// The address of the broadcaster.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// What's known of the game, once the broadcaster said what it is.
+    pub fn snapshot(&self) -> Option<&Snapshot> {
+        self.snapshot.as_ref()
+    }
+
+    /// Reads what the broadcaster sent since the last poll, without blocking.
+    ///
+    /// Returns whether the game changed, or an error once the broadcaster is
+    /// gone.
+    pub fn poll(&mut self) -> io::Result<bool> {
+        // Frames can't be longer than this, so whatever is left unread
+        // waits for the next poll.
+        let open = read_available(&mut self.stream, &mut self.buffer, MAX_HEADER_BYTES + MAX_PAYLOAD_BYTES)?;
+        let mut changed = false;
+        while let Some((frame, len)) = read_frame(&self.buffer)? {
+            self.buffer.drain(..len);
+            match frame {
+                Frame::Text(event) => {
+                    let snapshot = Snapshot::apply(self.snapshot.as_ref(), &event).ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, format!("unexpected event: {}", event))
+                    })?;
+                    self.snapshot = Some(snapshot);
+                    changed = true;
+                }
+                Frame::Close => return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "the game is over")),
+                Frame::Other => {}
+            }
+        }
+        if !open {
+            return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "the broadcaster left"));
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use state::CheckBox;
+    use std::thread;
+    use std::time::Duration;
+    use variant::Variant;
+
+    #[test]
+    fn spectates_over_a_websocket() {
+        // From RFC 6455.
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        let frame = text_frame(&"x".repeat(300), Some([1, 2, 3, 4]));
+        assert_eq!(read_frame(&frame).unwrap(), Some((Frame::Text("x".repeat(300)), frame.len())));
+        assert_eq!(read_frame(&frame[..5]).unwrap(), None);
+        // Lengths up to 2^64 - 1 are rejected, not waited for.
+        assert!(read_frame(&[0x81, 127, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+        let mut buffer = vec![];
+        assert!(read_available(&mut &[0u8; 1000][..], &mut buffer, 600).unwrap());
+        assert_eq!(buffer.len(), 600);
+
+        let mut broadcaster = Broadcaster::bind(0, false).unwrap();
+        let url = format!("ws://127.0.0.1:{}", broadcaster.port());
+        let client = thread::spawn(move || Spectator::connect(&url));
+        let mut game = Variant::TicTacToe.new_game(CheckBox::X);
+        let mut spectator = loop {
+            let board = game.board();
+            broadcaster.update(Snapshot::new(game.variant(), &*board.position(board.plies()), board.plies(), board.result()));
+            if !broadcaster.spectators.is_empty() {
+                break client.join().unwrap().unwrap();
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        game.board_mut().click(1, 1).unwrap();
+        let board = game.board();
+        broadcaster.update(Snapshot::new(game.variant(), &*board.position(1), 1, board.result()));
+        while spectator.snapshot().map(|s| s.plies()) != Some(1) {
+            spectator.poll().unwrap();
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(spectator.snapshot().unwrap().mark(1, 1), CheckBox::X);
+
+        drop(broadcaster);
+        while spectator.poll().is_ok() {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
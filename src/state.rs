@@ -15,7 +15,10 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+use book;
+use game_state::{self, GameResult, GameState, GridState, MoveError, Savable};
 use std::fmt;
+use variant::Variant;
 
 /// The state of a given box in the tic-tac-toe game.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -36,18 +39,115 @@ impl CheckBox {
         }
     }
 
-    fn dump_char(&self) -> char {
+    pub fn dump_char(&self) -> char {
         match *self {
             CheckBox::Empty => '_',
             CheckBox::X => 'X',
             CheckBox::O => 'O',
         }
     }
+
+    /// Returns what the mark adds to the sums of the lines through it: -1
+    /// for X, 1 for O and nothing for an empty square.
+    fn line_weight(&self) -> i8 {
+        *self as i8 / CheckBox::O as i8
+    }
+
+    /// The inverse of `dump_char`.
+    pub fn from_dump_char(c: char) -> Option<Self> {
+        Some(match c {
+            '_' => CheckBox::Empty,
+            'X' => CheckBox::X,
+            'O' => CheckBox::O,
+            _ => return None,
+        })
+    }
 }
 
-#[derive(Clone, Debug)]
+/// All the lines that win the game, as (x, y) squares.
+pub const LINES: [[(usize, usize); 3]; 8] = [
+    [(0, 0), (0, 1), (0, 2)],
+    [(1, 0), (1, 1), (1, 2)],
+    [(2, 0), (2, 1), (2, 2)],
+    [(0, 0), (1, 0), (2, 0)],
+    [(0, 1), (1, 1), (2, 1)],
+    [(0, 2), (1, 2), (2, 2)],
+    [(0, 0), (1, 1), (2, 2)],
+    [(0, 2), (1, 1), (2, 0)],
+];
+
+/// Why a position couldn't be read from its notation.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum NotationError {
+    /// It isn't the board and the player to move, separated by a space.
+    Malformed,
+    /// The board doesn't have three rows of three squares.
+    WrongSize,
+    /// A square is neither `X`, `O` nor `_`.
+    InvalidMark(char),
+    /// The player to move is neither `X` nor `O`.
+    InvalidPlayer(String),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NotationError::Malformed => write!(f, "expected a board and a player to move"),
+            NotationError::WrongSize => write!(f, "expected three rows of three squares"),
+            NotationError::InvalidMark(c) => write!(f, "not a mark: {}", c),
+            NotationError::InvalidPlayer(ref p) => write!(f, "not a player: {}", p),
+        }
+    }
+}
+
+/// Why a position can't come up in a game.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum PositionError {
+    /// A player has more than one mark more than the other, with the number
+    /// of marks of X and O.
+    UnbalancedMarks(usize, usize),
+    /// Both players have a line.
+    TwoWinners,
+    /// The given player can't be the one to move, since they have the most
+    /// marks.
+    WrongPlayer(CheckBox),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PositionError::UnbalancedMarks(x, o) => {
+                write!(f, "X has {} marks and O {}, which can't differ by more than one", x, o)
+            }
+            PositionError::TwoWinners => write!(f, "both players have a line"),
+            PositionError::WrongPlayer(player) => {
+                write!(f, "{} has the most marks, so it's not their turn", player.dump_char())
+            }
+        }
+    }
+}
+
+/// Returns the indices in `LINES` of the lines through the square at
+/// (x, y), followed by `None`s for the squares off the diagonals.
+fn lines_through(x: usize, y: usize) -> [Option<usize>; 4] {
+    [
+        Some(x),
+        Some(3 + y),
+        if x == y { Some(6) } else { None },
+        if x + y == 2 { Some(7) } else { None },
+    ]
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub struct State {
     field: [[CheckBox; 3]; 3],
+    /// The sum of the marks of each of `LINES`, see `CheckBox::line_weight`,
+    /// so that a line of three marks of a player sums to 3 or -3.
+    line_sums: [i8; 8],
+    /// The lines of three marks of a player, a bit for each of `LINES`.
+    full_lines: u8,
+    /// The number of squares that aren't empty.
+    marks: u8,
 }
 
 impl State {
@@ -58,6 +158,9 @@ impl State {
                 [CheckBox::Empty, CheckBox::Empty, CheckBox::Empty],
                 [CheckBox::Empty, CheckBox::Empty, CheckBox::Empty],
             ],
+            line_sums: [0; 8],
+            full_lines: 0,
+            marks: 0,
         }
     }
 
@@ -83,7 +186,7 @@ impl State {
         dest.write_char(row[0].dump_char())?;
         dest.write_char(' ')?;
         dest.write_char(row[1].dump_char())?;
-        dest.write_char(' ')?;;
+        dest.write_char(' ')?;
         dest.write_char(row[2].dump_char())?;
         dest.write_char(']')
     }
@@ -101,76 +204,287 @@ impl State {
             initial_state: self,
             row: 0,
             col: 0,
-            player: player,
+            player,
         }
     }
 
-    /// TODO(emilio): This can be much more efficient, but you know...
+    /// Returns the mark of the player with three in a row, as a number, or
+    /// zero if nobody has one.
+    ///
+    /// Positions where both players have a line can't come up in a game, and
+    /// score as the first of `LINES` that's full.
     pub fn score(&self) -> i8 {
-        macro_rules! return_if_nonzero {
-            ($e:expr) => {
-                {
-                    let v = $e;
-                    if v != 0 {
-                        return v;
-                    }
-                }
-            }
+        if self.full_lines == 0 {
+            return 0;
+        }
+        let line = self.full_lines.trailing_zeros() as usize;
+        self.line_sums[line].signum() * CheckBox::O as i8
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> CheckBox {
+        self.field[x][y]
+    }
+
+    /// Puts `mark` in the square at (x, y), whatever was there before.
+    pub fn set(&mut self, x: usize, y: usize, mark: CheckBox) {
+        let old = self.field[x][y];
+        if old != CheckBox::Empty {
+            self.marks -= 1;
+            self.add_to_lines(x, y, -old.line_weight());
         }
-        return_if_nonzero!(self.row_score(0));
-        return_if_nonzero!(self.row_score(1));
-        return_if_nonzero!(self.row_score(2));
-        return_if_nonzero!(self.column_score(0));
-        return_if_nonzero!(self.column_score(1));
-        return_if_nonzero!(self.column_score(2));
-        return_if_nonzero!(self.main_diagonal_score());
-        return_if_nonzero!(self.cross_diagonal_score());
-        0
+        self.field[x][y] = CheckBox::Empty;
+        if mark != CheckBox::Empty {
+            self.place(x, y, mark);
+        }
+    }
+
+    /// Marks the empty square at (x, y) for `player`, which is all a move
+    /// does, and cheaper than `set`.
+    pub fn place(&mut self, x: usize, y: usize, player: CheckBox) {
+        debug_assert_eq!(self.field[x][y], CheckBox::Empty);
+        self.field[x][y] = player;
+        self.marks += 1;
+        self.add_to_lines(x, y, player.line_weight());
     }
 
-    fn row_score(&self, row: usize) -> i8 {
-        let row = self.field[row];
-        let first = row[0];
-        if row.iter().all(|i| *i == first) {
-            return first as i8
+    /// Adds `weight` to the sums of the lines through the square at (x, y).
+    fn add_to_lines(&mut self, x: usize, y: usize, weight: i8) {
+        for line in lines_through(x, y).iter().filter_map(|line| *line) {
+            self.line_sums[line] += weight;
+            if self.line_sums[line].abs() == 3 {
+                self.full_lines |= 1 << line;
+            } else {
+                self.full_lines &= !(1 << line);
+            }
         }
-        0
     }
 
-    fn column_score(&self, col: usize) -> i8 {
-        let first = self.field[0][col];
-        for i in 1..3 {
-            if self.field[i][col] != first {
-                return 0;
+    /// Returns whether every square is marked.
+    fn is_full(&self) -> bool {
+        self.marks == 9
+    }
+
+    /// Reads a position written like `X_O/_X_/__O X`: the rows from the top,
+    /// with `_` for the empty squares, and then the player to move.
+    pub fn from_notation(notation: &str) -> Result<(Self, CheckBox), NotationError> {
+        let mut parts = notation.split_whitespace();
+        let (board, player) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(board), Some(player), None) => (board, player),
+            _ => return Err(NotationError::Malformed),
+        };
+
+        let player = match player {
+            "X" => CheckBox::X,
+            "O" => CheckBox::O,
+            _ => return Err(NotationError::InvalidPlayer(player.to_owned())),
+        };
+
+        let rows = board.split('/').collect::<Vec<_>>();
+        if rows.len() != 3 || rows.iter().any(|row| row.chars().count() != 3) {
+            return Err(NotationError::WrongSize);
+        }
+        let mut state = State::initial();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let mark = CheckBox::from_dump_char(c).ok_or(NotationError::InvalidMark(c))?;
+                state.set(x, y, mark);
             }
         }
+        Ok((state, player))
+    }
 
-        return first as i8
+    /// Reads a position from text the user pasted, like the GUI copies: the
+    /// notation on the first line with anything on it, and maybe a diagram
+    /// of the board below.
+    pub fn from_pasted(text: &str) -> Result<(Self, CheckBox), NotationError> {
+        let line = text.lines().map(str::trim).find(|line| !line.is_empty());
+        Self::from_notation(line.unwrap_or(""))
     }
 
-    pub fn get(&self, x: usize, y: usize) -> CheckBox {
-        self.field[x][y]
+    /// The inverse of `from_notation`.
+    pub fn to_notation(&self, player: CheckBox) -> String {
+        let rows = (0..3).map(|y| {
+            (0..3).map(|x| self.get(x, y).dump_char()).collect::<String>()
+        }).collect::<Vec<_>>();
+        format!("{} {}", rows.join("/"), player.dump_char())
     }
 
-    fn main_diagonal_score(&self) -> i8 {
-        let center = self.field[1][1];
-        for i in 0..3 {
-            if self.field[i][i] != center {
-                return 0;
-            }
+    /// Returns whether the position can come up in a game with `player` to
+    /// move, whoever moved first, or why not.
+    pub fn validate(&self, player: CheckBox) -> Result<(), PositionError> {
+        let count = |mark| self.field.iter().flat_map(|col| col.iter()).filter(|&&m| m == mark).count();
+        let (x, o) = (count(CheckBox::X), count(CheckBox::O));
+        if x > o + 1 || o > x + 1 {
+            return Err(PositionError::UnbalancedMarks(x, o));
+        }
+        let has_line = |mark| {
+            LINES.iter().any(|line| line.iter().all(|&(x, y)| self.get(x, y) == mark))
+        };
+        if has_line(CheckBox::X) && has_line(CheckBox::O) {
+            return Err(PositionError::TwoWinners);
         }
+        if (player == CheckBox::X && x > o) || (player == CheckBox::O && o > x) {
+            return Err(PositionError::WrongPlayer(player));
+        }
+        Ok(())
+    }
 
-        return center as i8
+    /// Returns the squares of the line that won the game, if any.
+    pub fn winning_line(&self) -> Option<[(usize, usize); 3]> {
+        LINES.iter().find(|line| {
+            let first = self.get(line[0].0, line[0].1);
+            first != CheckBox::Empty &&
+                line.iter().all(|&(x, y)| self.get(x, y) == first)
+        }).cloned()
     }
 
-    fn cross_diagonal_score(&self) -> i8 {
-        let center = self.field[1][1];
-        for i in 0..3 {
-            if self.field[i][3 - i - 1] != center {
-                return 0;
+    /// Returns the first square whose contents differ between `self` and
+    /// `other`, if any.
+    pub fn changed_square(&self, other: &State) -> Option<(usize, usize)> {
+        for x in 0..3 {
+            for y in 0..3 {
+                if self.field[x][y] != other.field[x][y] {
+                    return Some((x, y));
+                }
             }
         }
-        return center as i8
+        None
+    }
+}
+
+/// Returns how early a move to the square at (x, y) of a 3x3 board should be
+/// searched: the center first, then the corners, then the edges, which is
+/// the order of how many winning lines go through them.
+pub fn square_priority(x: usize, y: usize) -> u8 {
+    match (x, y) {
+        (1, 1) => 0,
+        (x, y) if x != 1 && y != 1 => 1,
+        _ => 2,
+    }
+}
+
+impl GameState for State {
+    type Move = (usize, usize);
+
+    fn initial() -> Self {
+        State::initial()
+    }
+
+    fn winner(&self) -> CheckBox {
+        match self.score() {
+            0 => CheckBox::Empty,
+            s if s == CheckBox::X as i8 => CheckBox::X,
+            _ => CheckBox::O,
+        }
+    }
+
+    fn successors(&self, player: CheckBox) -> Vec<(Self::Move, Self)> {
+        if self.score() != 0 {
+            return vec![];
+        }
+        self.subsequent_states(player).map(|s| {
+            (self.changed_square(&s).unwrap(), s)
+        }).collect()
+    }
+
+    fn evaluate(&self) -> i8 {
+        self.score()
+    }
+
+    fn result(&self) -> GameResult {
+        match self.winner() {
+            CheckBox::X => GameResult::XWins,
+            CheckBox::O => GameResult::OWins,
+            CheckBox::Empty if self.is_full() => GameResult::Draw,
+            CheckBox::Empty => GameResult::InProgress,
+        }
+    }
+
+    fn is_over(&self) -> bool {
+        self.score() != 0 || self.is_full()
+    }
+
+    fn move_priority(&self, mv: Self::Move) -> u8 {
+        square_priority(mv.0, mv.1)
+    }
+
+    fn book_move(&self, player: CheckBox) -> Option<Self::Move> {
+        book::lookup(self, player)
+    }
+
+    fn move_error(&self, (x, y): Self::Move) -> MoveError {
+        if x >= 3 || y >= 3 {
+            MoveError::OutOfBounds
+        } else if self.get(x, y) != CheckBox::Empty {
+            MoveError::Occupied
+        } else {
+            MoveError::Illegal
+        }
+    }
+
+    fn is_center_move(&self, mv: Self::Move) -> bool {
+        mv == (1, 1)
+    }
+
+    fn depth_limit() -> usize {
+        // Enough to search the whole game.
+        9
+    }
+
+    fn is_solvable() -> bool {
+        true
+    }
+
+    fn dump<W>(&self, indent: usize, dest: &mut W) -> fmt::Result
+        where W: fmt::Write,
+    {
+        State::dump(self, indent, dest)
+    }
+}
+
+impl GridState for State {
+    fn grid_size(&self) -> (usize, usize) {
+        (3, 3)
+    }
+
+    fn mark(&self, x: usize, y: usize) -> CheckBox {
+        self.get(x, y)
+    }
+
+    fn move_for_square(&self, x: usize, y: usize) -> Option<Self::Move> {
+        if self.get(x, y) == CheckBox::Empty && self.score() == 0 {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    fn winning_squares(&self) -> Vec<(usize, usize)> {
+        self.winning_line().map_or(vec![], |line| line.to_vec())
+    }
+}
+
+impl Savable for State {
+    fn variant() -> Variant {
+        Variant::TicTacToe
+    }
+
+    fn write_move((x, y): Self::Move) -> String {
+        format!("{},{}", x, y)
+    }
+
+    fn read_move(s: &str) -> Option<Self::Move> {
+        game_state::parse_pair(s)
+    }
+
+    fn set_up_notation(&self, player: CheckBox) -> Option<String> {
+        if *self == State::initial() { None } else { Some(self.to_notation(player)) }
+    }
+
+    fn from_set_up_notation(notation: &str) -> Option<(Self, CheckBox)> {
+        let (state, player) = State::from_notation(notation).ok()?;
+        state.validate(player).ok()?;
+        Some((state, player))
     }
 }
 
@@ -194,7 +508,7 @@ impl<'a> Iterator for SubsequentStatesIterator<'a> {
             if self.initial_state.field[self.row][self.col] == CheckBox::Empty {
                 self.col += 1;
                 let mut ret = self.initial_state.clone();
-                ret.field[self.row][self.col - 1] = self.player;
+                ret.place(self.row, self.col - 1, self.player);
                 return Some(ret)
             }
             self.col += 1;
@@ -203,3 +517,89 @@ impl<'a> Iterator for SubsequentStatesIterator<'a> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_notation() {
+        let (state, player) = State::from_notation("X_O/_X_/__O X").unwrap();
+        assert_eq!(state.get(0, 0), CheckBox::X);
+        assert_eq!(state.get(2, 0), CheckBox::O);
+        assert_eq!(state.get(2, 2), CheckBox::O);
+        assert_eq!(state.get(0, 2), CheckBox::Empty);
+        assert_eq!(player, CheckBox::X);
+        assert_eq!(state.to_notation(player), "X_O/_X_/__O X");
+        assert_eq!(State::initial().to_notation(CheckBox::O), "___/___/___ O");
+
+        assert_eq!(State::from_notation("X_O/_X_/__O"), Err(NotationError::Malformed));
+        assert_eq!(State::from_notation("X_O/_X_ X"), Err(NotationError::WrongSize));
+        assert_eq!(State::from_notation("X_O/_X_/__OO X"), Err(NotationError::WrongSize));
+        assert_eq!(State::from_notation("X_O/_Y_/__O X"), Err(NotationError::InvalidMark('Y')));
+        assert_eq!(State::from_notation("X_O/_X_/__O _"),
+                   Err(NotationError::InvalidPlayer("_".to_owned())));
+
+        let pasted = "\n  X_O/_X_/__O X\n\n    a b c\n  1 X _ O\n";
+        assert_eq!(State::from_pasted(pasted), Ok((state, player)));
+        assert_eq!(State::from_pasted("\n"), Err(NotationError::Malformed));
+    }
+
+    #[test]
+    fn keeps_the_lines_up_to_date() {
+        let mut state = State::initial();
+        let moves = [(0, 0, CheckBox::X), (1, 1, CheckBox::O), (0, 1, CheckBox::X), (2, 2, CheckBox::O)];
+        for &(x, y, player) in &moves {
+            state.place(x, y, player);
+            assert_eq!(state.score(), 0);
+            assert!(!state.is_over());
+        }
+        state.place(0, 2, CheckBox::X);
+        assert_eq!(state.score(), CheckBox::X as i8);
+        assert_eq!(state.result(), GameResult::XWins);
+
+        // Overwriting a mark undoes what it added.
+        state.set(0, 2, CheckBox::O);
+        assert_eq!(state.score(), 0);
+        state.set(0, 0, CheckBox::O);
+        assert_eq!(state.score(), CheckBox::O as i8);
+        state.set(1, 1, CheckBox::Empty);
+        assert_eq!(state.score(), 0);
+        assert_eq!(state, State::from_notation("O__/X__/O_O X").unwrap().0);
+
+        let (full, _) = State::from_notation("XOX/XOO/OXX O").unwrap();
+        assert_eq!(full.result(), GameResult::Draw);
+        assert!(full.successors(CheckBox::O).is_empty());
+
+        // Every line scores, whoever fills it.
+        for line in &LINES {
+            for &player in &[CheckBox::X, CheckBox::O] {
+                let mut state = State::initial();
+                for &(x, y) in line {
+                    state.place(x, y, player);
+                }
+                assert_eq!(state.score(), player as i8);
+                assert_eq!(state.winning_line(), Some(*line));
+            }
+        }
+    }
+
+    #[test]
+    fn validates_positions() {
+        let valid = ["___/___/___ O", "X__/___/___ O", "_O_/___/___ X", "XXX/OO_/___ O"];
+        for notation in &valid {
+            let (state, player) = State::from_notation(notation).unwrap();
+            assert_eq!(state.validate(player), Ok(()), "{}", notation);
+        }
+
+        let invalid = [
+            ("XX_/___/___ O", PositionError::UnbalancedMarks(2, 0)),
+            ("XXX/OOO/___ X", PositionError::TwoWinners),
+            ("XX_/O__/___ X", PositionError::WrongPlayer(CheckBox::X)),
+        ];
+        for &(notation, err) in &invalid {
+            let (state, player) = State::from_notation(notation).unwrap();
+            assert_eq!(state.validate(player), Err(err), "{}", notation);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A frontend for terminals, for systems without GTK.
+//!
+//! The board is printed with the columns lettered and the rows numbered,
+//! and moves are typed as squares like `b2`, or the label of the square in
+//! games that have them. A `*` after the square plays its other mark, in
+//! games that have one.
+
+use frontend::{Click, Frontend};
+use game::Board;
+use game_state::{GameResult, MoveError};
+use state::CheckBox;
+use std::io::{BufRead, Write};
+
+/// Parses a square name like `b2` into its column and row.
+fn parse_square_name(name: &str) -> Option<(usize, usize)> {
+    let mut chars = name.chars();
+    let column = chars.next()?;
+    if !column.is_ascii_lowercase() {
+        return None;
+    }
+    let row = chars.as_str().parse::<usize>().ok()?;
+    if row == 0 {
+        return None;
+    }
+    Some(((column as u8 - b'a') as usize, row - 1))
+}
+
+/// Draws the current position of `board` as text, with the columns lettered
+/// and the rows numbered.
+pub fn diagram(board: &dyn Board) -> String {
+    let (cols, rows) = board.grid_size();
+    let block = board.block_size().unwrap_or(cols.max(rows));
+    let position = board.position(board.plies());
+
+    let mut text = String::from("   ");
+    for x in 0..cols {
+        if x > 0 && x % block == 0 {
+            text.push(' ');
+        }
+        text.push(' ');
+        text.push((b'a' + x as u8) as char);
+    }
+    text.push('\n');
+    for y in 0..rows {
+        if y > 0 && y % block == 0 {
+            text.push('\n');
+        }
+        text.push_str(&format!("{:>3}", y + 1));
+        for x in 0..cols {
+            if x > 0 && x % block == 0 {
+                text.push(' ');
+            }
+            text.push(' ');
+            match (board.mark(x, y), position.square_label(x, y)) {
+                (CheckBox::Empty, Some(label)) => text.push_str(&label),
+                (mark, _) => text.push(mark.dump_char()),
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Plays on a terminal, reading moves from `input` and writing the board
+/// and messages to `output`.
+pub struct Terminal<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R, W> Terminal<R, W>
+    where R: BufRead,
+          W: Write,
+{
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+        }
+    }
+
+    /// Returns the square of `board` that `text` names, if any.
+    fn square(board: &dyn Board, text: &str) -> Option<(usize, usize)> {
+        let (cols, rows) = board.grid_size();
+        let position = board.position(board.plies());
+        for x in 0..cols {
+            for y in 0..rows {
+                if position.square_label(x, y).as_deref() == Some(text) {
+                    return Some((x, y));
+                }
+            }
+        }
+        parse_square_name(text)
+    }
+}
+
+// Errors writing to the terminal are ignored, there's nowhere else to say
+// anything, and reading the next move will fail too.
+impl<R, W> Frontend for Terminal<R, W>
+    where R: BufRead,
+          W: Write,
+{
+    fn render(&mut self, board: &dyn Board) {
+        let _ = write!(self.output, "\n{}", diagram(board));
+    }
+
+    fn prompt_move(&mut self, board: &dyn Board) -> Option<Click> {
+        loop {
+            let _ = write!(self.output, "{} to move (like b2, or quit): ", board.player().dump_char());
+            let _ = self.output.flush();
+
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Ok(0) | Err(..) => return None,
+                Ok(_) => {}
+            }
+            let line = line.trim();
+            if line == "quit" {
+                return None;
+            }
+            let (text, other_mark) = match line.strip_suffix('*') {
+                Some(text) => (text, true),
+                None => (line, false),
+            };
+            match Self::square(board, text) {
+                Some((x, y)) => return Some(Click { x, y, other_mark }),
+                None => {
+                    let _ = writeln!(self.output, "Not a square: {}", line);
+                }
+            }
+        }
+    }
+
+    fn reject_move(&mut self, err: MoveError) {
+        let _ = writeln!(self.output, "Can't play there: {}.", err);
+    }
+
+    fn announce_result(&mut self, result: GameResult) {
+        let text = match result {
+            GameResult::XWins => "X wins!".to_owned(),
+            GameResult::OWins => "O wins!".to_owned(),
+            GameResult::Draw => "It's a draw.".to_owned(),
+            GameResult::TimeForfeit(loser) => format!("{} ran out of time.", loser.dump_char()),
+            GameResult::Resigned(loser) => format!("{} resigned.", loser.dump_char()),
+            GameResult::DrawAgreed => "It's a draw by agreement.".to_owned(),
+            GameResult::InProgress => return,
+        };
+        let _ = writeln!(self.output, "{}", text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frontend;
+    use variant::Variant;
+
+    #[test]
+    fn plays_typed_moves() {
+        let input = "b2\nb2\nzz\nquit\n";
+        let mut output = vec![];
+        let mut game = Variant::TicTacToe.new_game(CheckBox::X);
+        let result = {
+            let mut terminal = Terminal::new(input.as_bytes(), &mut output);
+            frontend::play(&mut terminal, &mut game, CheckBox::X, &mut ::rand::thread_rng())
+        };
+        assert_eq!(result, GameResult::InProgress);
+        // The engine replied to the first move.
+        assert_eq!(game.board().plies(), 2);
+        assert_eq!(game.board().mark(1, 1), CheckBox::X);
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("    a b c\n  1 _ _ _\n"));
+        assert!(output.contains("Can't play there: that square is taken."));
+        assert!(output.contains("Not a square: zz"));
+        assert_eq!(parse_square_name("c3"), Some((2, 2)));
+    }
+}
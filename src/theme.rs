@@ -0,0 +1,175 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! How the GUI shows the board.
+
+use config::{Config, Value};
+use state::CheckBox;
+
+/// The section of the configuration file with the theme.
+const SECTION: &str = "theme";
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ColorScheme {
+    Default,
+    Dark,
+    HighContrast,
+}
+
+impl ColorScheme {
+    pub fn all() -> [ColorScheme; 3] {
+        [ColorScheme::Default, ColorScheme::Dark, ColorScheme::HighContrast]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ColorScheme::Default => "Default",
+            ColorScheme::Dark => "Dark",
+            ColorScheme::HighContrast => "High contrast",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().iter().cloned().find(|s| s.name() == name)
+    }
+
+    /// The background and text colors of the squares, if they're not the
+    /// GTK theme ones.
+    fn square_colors(&self) -> Option<(&'static str, &'static str)> {
+        match *self {
+            ColorScheme::Default => None,
+            ColorScheme::Dark => Some(("#2e3436", "#eeeeec")),
+            ColorScheme::HighContrast => Some(("#ffffff", "#000000")),
+        }
+    }
+
+    /// The color of the outline of the focused square.
+    fn focus_color(&self) -> &'static str {
+        match *self {
+            ColorScheme::Default => "#3465a4",
+            ColorScheme::Dark => "#fcaf3e",
+            ColorScheme::HighContrast => "#ff00ff",
+        }
+    }
+
+    /// The background colors of the winning, playable, hinted and illegal
+    /// squares.
+    fn highlight_colors(&self) -> [&'static str; 4] {
+        match *self {
+            ColorScheme::Default => ["#8ae234", "#fce94f", "#729fcf", "#ef2929"],
+            ColorScheme::Dark => ["#4e9a06", "#c4a000", "#3465a4", "#a40000"],
+            ColorScheme::HighContrast => ["#00ff00", "#ffff00", "#00ffff", "#ff0000"],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Theme {
+    /// What to show for the marks of X and O.
+    pub x_glyph: String,
+    pub o_glyph: String,
+    /// The font size of the squares, in points.
+    pub font_size: u32,
+    pub colors: ColorScheme,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            x_glyph: "X".to_owned(),
+            o_glyph: "O".to_owned(),
+            font_size: 14,
+            colors: ColorScheme::Default,
+        }
+    }
+}
+
+impl Theme {
+    /// The range of font sizes that look reasonable.
+    pub const MIN_FONT_SIZE: u32 = 6;
+    pub const MAX_FONT_SIZE: u32 = 72;
+
+    /// Reads the theme from `config`, using the defaults for whatever is
+    /// missing or invalid.
+    pub fn from_config(config: &Config) -> Self {
+        let mut theme = Theme::default();
+        if let Some(glyph) = config.get_string(SECTION, "x-glyph").filter(|g| !g.is_empty()) {
+            theme.x_glyph = glyph.to_owned();
+        }
+        if let Some(glyph) = config.get_string(SECTION, "o-glyph").filter(|g| !g.is_empty()) {
+            theme.o_glyph = glyph.to_owned();
+        }
+        if let Some(size) = config.get_integer(SECTION, "font-size") {
+            if size >= Self::MIN_FONT_SIZE as i64 && size <= Self::MAX_FONT_SIZE as i64 {
+                theme.font_size = size as u32;
+            }
+        }
+        if let Some(colors) = config.get_string(SECTION, "colors").and_then(ColorScheme::from_name) {
+            theme.colors = colors;
+        }
+        theme
+    }
+
+    pub fn write_to(&self, config: &mut Config) {
+        config.set(SECTION, "x-glyph", Value::String(self.x_glyph.clone()));
+        config.set(SECTION, "o-glyph", Value::String(self.o_glyph.clone()));
+        config.set(SECTION, "font-size", Value::Integer(self.font_size as i64));
+        config.set(SECTION, "colors", Value::String(self.colors.name().to_owned()));
+    }
+
+    /// Returns what to show for `mark`.
+    pub fn glyph(&self, mark: CheckBox) -> &str {
+        match mark {
+            CheckBox::Empty => " ",
+            CheckBox::X => &self.x_glyph,
+            CheckBox::O => &self.o_glyph,
+        }
+    }
+
+    /// Returns the style of the grid buttons. The buttons of the line that
+    /// won the game get the `winning` class, the ones the human can play in
+    /// get the `playable` class when they can't play in every empty square,
+    /// and the ones of the move the engine recommends get the `hint` class.
+    /// Squares that were clicked but can't be played get the `illegal` class.
+    /// The focused one is outlined, to play with the keyboard.
+    ///
+    /// For animations, marks grow back from the `placed` class, and winning
+    /// squares lose their color with the `flash` class.
+    pub fn css(&self) -> String {
+        let mut css = format!(
+            "button {{ font-size: {}pt; transition: font-size 150ms ease-out, background-color 150ms; }}\n",
+            self.font_size);
+        css.push_str(&format!("button.placed {{ font-size: {}pt; transition: none; }}\n",
+                              self.font_size / 2));
+        if let Some((background, text)) = self.colors.square_colors() {
+            css.push_str(&format!(
+                "button {{ background-image: none; background-color: {}; color: {}; }}\n",
+                background, text));
+        }
+        let classes = ["winning", "playable", "hint", "illegal"];
+        for (class, color) in classes.iter().zip(self.colors.highlight_colors().iter()) {
+            css.push_str(&format!(
+                "button.{} {{ background-image: none; background-color: {}; }}\n",
+                class, color));
+        }
+        css.push_str(&format!("button.winning.flash {{ background-color: {}; }}\n",
+                              self.colors.square_colors().map_or("transparent", |c| c.0)));
+        css.push_str(&format!("button:focus {{ box-shadow: inset 0 0 0 3px {}; }}\n",
+                              self.colors.focus_color()));
+        css
+    }
+}
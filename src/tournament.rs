@@ -0,0 +1,552 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Round-robin tournaments between engine configurations, to see whether a
+//! change to the search makes the engine stronger.
+//!
+//! Every pair of players plays the same openings twice, with each moving
+//! first once, and the ratings are fit to all the results at once, like Elo
+//! ratings.
+//!
+//! The games can come from a schedule file instead, with a game a line, like
+//! `1 2 0` for the first player against the second one, with X, in the first
+//! opening. `--write-schedule` writes the round-robin one like that, so that
+//! a long tournament can be split across machines, like with `split`.
+//!
+//! The results can be written as a CSV crosstable, with the points of each
+//! player against each other one, and as a line of JSON per game, for other
+//! rating tools, like:
+//!
+//! ```text
+//! {"game":1,"x":"depth=1","o":"","opening":0,"result":"o-wins","plies":6}
+//! ```
+
+use game_state::GameResult;
+use player::PlayerConfig;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use state::CheckBox;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use variant::Variant;
+
+pub const USAGE: &str = "\
+usage: tic-tac-minimax tournament [options] <player> <player>...
+
+  --variant <name>     tictactoe (default), ultimate, connect4, scrabble,
+                       misere, wild, notakto, cube or qubic
+  --games <n>          play <n> games between each pair of players, half of
+                       them moving first (default: 10)
+  --random-plies <n>   open each game with <n> random moves (default: 2)
+  --seed <n>           seed for the random moves (default: 0)
+  --schedule <path>    play the games of <path> instead of a round-robin,
+                       a line each like `1 2 0`: the number of the player
+                       of X and of O, in the order they're given, and of
+                       the opening, from zero
+  --write-schedule <path>
+                       write the round-robin schedule to <path> instead of
+                       playing it
+  --csv <path>         write the crosstable to <path>, with the points of
+                       each player against each other one
+  --json <path>        write a line of JSON to <path> for each game
+
+Players are how the engine plays, as comma-separated settings like
+`depth=2,evaluator=positional`:
+
+  difficulty=<name>    Easy, Medium, Hard, Perfect (default) or Auto
+  depth=<n>            search at most <n> levels
+  randomize=<b>        pick at random among the best moves, yes or no
+                       (default: no)
+  evaluator=<name>     default, or positional for tic-tac-toe
+  no-center-opening=<b>  never open in the center, yes or no (default: no)
+
+An empty player, `\"\"`, plays perfectly.";
+
+/// The rating of a player that scores half of the points.
+const BASE_RATING: f64 = 1500.0;
+
+#[derive(Clone, Debug)]
+struct Options {
+    variant: Variant,
+    games: usize,
+    random_plies: usize,
+    seed: u64,
+    /// The players, with the settings that describe them.
+    players: Vec<(String, PlayerConfig)>,
+    schedule: Option<String>,
+    write_schedule: Option<String>,
+    csv: Option<String>,
+    json: Option<String>,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        variant: Variant::TicTacToe,
+        games: 10,
+        random_plies: 2,
+        seed: 0,
+        players: vec![],
+        schedule: None,
+        write_schedule: None,
+        csv: None,
+        json: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            options.players.push((arg.clone(), PlayerConfig::from_spec(arg)?));
+            continue;
+        }
+        let value = args.next().ok_or_else(|| format!("missing value for {}", arg))?;
+        let number = || {
+            value.parse::<u64>().map_err(|_| format!("invalid value for {}: {}", arg, value))
+        };
+        match arg.as_str() {
+            "--variant" => {
+                options.variant = Variant::from_id(value)
+                    .ok_or_else(|| format!("unknown variant: {}", value))?;
+            }
+            "--games" => options.games = number()? as usize,
+            "--random-plies" => options.random_plies = number()? as usize,
+            "--seed" => options.seed = number()?,
+            "--schedule" => options.schedule = Some(value.clone()),
+            "--write-schedule" => options.write_schedule = Some(value.clone()),
+            "--csv" => options.csv = Some(value.clone()),
+            "--json" => options.json = Some(value.clone()),
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+
+    if options.players.len() < 2 {
+        return Err("a tournament needs at least two players".to_owned());
+    }
+    // Like an evaluator that only tic-tac-toe has.
+    let mut game = options.variant.new_game(CheckBox::X);
+    for (name, config) in &options.players {
+        game.board_mut().set_player_config(CheckBox::X, config.clone())
+            .map_err(|err| format!("{}: {}", name, err))?;
+    }
+    Ok(options)
+}
+
+/// A game of the tournament: the players of X and O, by their index in
+/// `Options::players`, and the random opening it starts with.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Pairing {
+    x: usize,
+    o: usize,
+    opening: u64,
+}
+
+/// The games where every pair of `players` plays `games` games, the same
+/// openings twice, with each player moving first once.
+fn round_robin(players: usize, games: usize) -> Vec<Pairing> {
+    let mut schedule = vec![];
+    for i in 0..players {
+        for j in i + 1..players {
+            for game in 0..games {
+                let (x, o) = if game % 2 == 0 { (i, j) } else { (j, i) };
+                schedule.push(Pairing { x, o, opening: (game / 2) as u64 });
+            }
+        }
+    }
+    schedule
+}
+
+/// Parses a schedule, see `USAGE`, for a tournament of `players` players.
+/// Blank lines and the ones starting with `#` are skipped.
+fn parse_schedule(text: &str, players: usize) -> Result<Vec<Pairing>, String> {
+    let mut schedule = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || format!("invalid game in line {} of the schedule: {}", i + 1, line);
+        let numbers = line.split_whitespace()
+            .map(|word| word.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        let player = |number: u64| match number as usize {
+            number if number >= 1 && number <= players => Ok(number - 1),
+            _ => Err(invalid()),
+        };
+        match numbers[..] {
+            [x, o, opening] if x != o => {
+                schedule.push(Pairing { x: player(x)?, o: player(o)?, opening });
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(schedule)
+}
+
+/// Writes `schedule` like `parse_schedule` reads it.
+fn write_schedule<W: Write>(schedule: &[Pairing], dest: &mut W) -> io::Result<()> {
+    for pairing in schedule {
+        writeln!(dest, "{} {} {}", pairing.x + 1, pairing.o + 1, pairing.opening)?;
+    }
+    Ok(())
+}
+
+/// Plays a game between `first` and `second`, after the random opening
+/// `opening` picks, and returns how it ended and after how many moves.
+fn play_game(options: &Options, first: &PlayerConfig, second: &PlayerConfig, opening: u64) -> (GameResult, usize) {
+    let mut rng = StdRng::seed_from_u64(options.seed.wrapping_add(opening));
+    let mut game = options.variant.new_game(CheckBox::X);
+    let board = game.board_mut();
+    for &(player, config) in &[(CheckBox::X, first), (CheckBox::O, second)] {
+        board.set_player_config(player, config.clone())
+            .expect("The players were checked when parsing them");
+    }
+
+    for _ in 0..options.random_plies {
+        let (cols, rows) = board.grid_size();
+        let squares = (0..rows).flat_map(|y| (0..cols).map(move |x| (x, y)))
+            .filter(|&(x, y)| board.is_playable(x, y))
+            .collect::<Vec<_>>();
+        if squares.is_empty() || board.result() != GameResult::InProgress {
+            break;
+        }
+        let (x, y) = squares[rng.gen_range(0..squares.len())];
+        board.click(x, y).expect("Playable squares should be playable");
+    }
+
+    while board.result() == GameResult::InProgress && board.play_engine_move(&mut rng) {}
+    (board.result(), board.plies())
+}
+
+/// How a game ended for one of its players.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+impl Outcome {
+    /// How a game that ended in `result` went for the player who played
+    /// `side`.
+    fn of(result: GameResult, side: CheckBox) -> Self {
+        let winner = match result {
+            GameResult::XWins => CheckBox::X,
+            GameResult::OWins => CheckBox::O,
+            GameResult::TimeForfeit(loser) |
+            GameResult::Resigned(loser) => loser.next_player(),
+            _ => return Outcome::Draw,
+        };
+        if winner == side { Outcome::Win } else { Outcome::Loss }
+    }
+
+    /// The points the player got, a win being a point and a draw half of
+    /// one.
+    fn points(self) -> f64 {
+        match self {
+            Outcome::Win => 1.0,
+            Outcome::Draw => 0.5,
+            Outcome::Loss => 0.0,
+        }
+    }
+}
+
+/// The outcome of a tournament, for each player.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Standing {
+    wins: usize,
+    draws: usize,
+    losses: usize,
+    rating: f64,
+}
+
+impl Standing {
+    fn record(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Win => self.wins += 1,
+            Outcome::Draw => self.draws += 1,
+            Outcome::Loss => self.losses += 1,
+        }
+    }
+
+    fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The share of the points the player got, a win being a point and a
+    /// draw half of one.
+    fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+}
+
+/// Fits ratings to `points[i][j]`, the points player `i` got in the
+/// `games[i][j]` games against player `j`.
+///
+/// These are the ratings that make the results most likely if a player rated
+/// 400 points above another is expected to score ten times as much as them.
+/// Every pair gets an extra draw, so that players who always won or always
+/// lost get a finite rating.
+fn fit_ratings(points: &[Vec<f64>], games: &[Vec<f64>]) -> Vec<f64> {
+    let n = points.len();
+    let mut strengths = vec![1.0; n];
+    for _ in 0..1000 {
+        let previous = strengths.clone();
+        for i in 0..n {
+            let mut total_points = 0.0;
+            let mut weight = 0.0;
+            for j in (0..n).filter(|&j| j != i) {
+                total_points += points[i][j] + 0.5;
+                weight += (games[i][j] + 1.0) / (previous[i] + previous[j]);
+            }
+            strengths[i] = total_points / weight;
+        }
+    }
+
+    let ratings = strengths.iter().map(|s| 400.0 * s.log10()).collect::<Vec<_>>();
+    let mean = ratings.iter().sum::<f64>() / n as f64;
+    ratings.iter().map(|r| r - mean + BASE_RATING).collect()
+}
+
+/// The results of a tournament.
+#[derive(Clone, Debug)]
+struct Results {
+    /// The standing of each player, in the order of `Options::players`.
+    standings: Vec<Standing>,
+    /// The points each player got against each other one, and in how many
+    /// games, like `fit_ratings` takes them.
+    points: Vec<Vec<f64>>,
+    games: Vec<Vec<f64>>,
+    /// Every game, with how it ended and after how many moves, in the order
+    /// they were played.
+    played: Vec<(Pairing, GameResult, usize)>,
+}
+
+/// Plays every game of `schedule`, and returns the results.
+fn play_tournament(options: &Options, schedule: &[Pairing]) -> Results {
+    let n = options.players.len();
+    let mut standings = vec![Standing::default(); n];
+    let mut points = vec![vec![0.0; n]; n];
+    let mut games = vec![vec![0.0; n]; n];
+    let mut played = vec![];
+    for &pairing in schedule {
+        let (result, plies) = play_game(options,
+                                        &options.players[pairing.x].1,
+                                        &options.players[pairing.o].1,
+                                        pairing.opening);
+        for &(player, opponent, side) in &[(pairing.x, pairing.o, CheckBox::X),
+                                           (pairing.o, pairing.x, CheckBox::O)] {
+            let outcome = Outcome::of(result, side);
+            points[player][opponent] += outcome.points();
+            games[player][opponent] += 1.0;
+            standings[player].record(outcome);
+        }
+        played.push((pairing, result, plies));
+    }
+
+    for (standing, rating) in standings.iter_mut().zip(fit_ratings(&points, &games)) {
+        standing.rating = rating;
+    }
+    Results { standings, points, games, played }
+}
+
+/// Quotes `field` for CSV if it needs it, like the settings of a player,
+/// which are separated by commas.
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Writes `results` as a crosstable, a line for each player in the order of
+/// `order`, with the points they got against each other player, in the same
+/// order, or nothing against themselves and the ones they didn't play.
+fn write_crosstable<W: Write>(results: &Results, names: &[&str], order: &[usize], dest: &mut W) -> io::Result<()> {
+    let mut header = vec!["rank", "player", "games", "wins", "draws", "losses", "score", "rating"]
+        .into_iter()
+        .map(|column| column.to_owned())
+        .collect::<Vec<_>>();
+    header.extend(order.iter().map(|&i| csv_field(names[i])));
+    writeln!(dest, "{}", header.join(","))?;
+    for (rank, &i) in order.iter().enumerate() {
+        let standing = &results.standings[i];
+        let mut row = vec![
+            (rank + 1).to_string(),
+            csv_field(names[i]),
+            standing.games().to_string(),
+            standing.wins.to_string(),
+            standing.draws.to_string(),
+            standing.losses.to_string(),
+            format!("{:.3}", standing.score()),
+            format!("{:.0}", standing.rating),
+        ];
+        row.extend(order.iter().map(|&j| {
+            if results.games[i][j] == 0.0 { String::new() } else { results.points[i][j].to_string() }
+        }));
+        writeln!(dest, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+/// Escapes `string` to go between the quotes of a JSON string.
+fn json_string(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Writes every game of `results` as a line of JSON, with the settings of
+/// the players like they were given, see the docs of the module.
+fn write_games<W: Write>(results: &Results, specs: &[&str], dest: &mut W) -> io::Result<()> {
+    for (i, &(pairing, result, plies)) in results.played.iter().enumerate() {
+        writeln!(dest,
+                 "{{\"game\":{},\"x\":\"{}\",\"o\":\"{}\",\"opening\":{},\"result\":\"{}\",\"plies\":{}}}",
+                 i + 1, json_string(specs[pairing.x]), json_string(specs[pairing.o]),
+                 pairing.opening, result.name(), plies)?;
+    }
+    Ok(())
+}
+
+/// Writes to the file at `path` with `write`.
+fn write_file<F>(path: &str, write: F) -> io::Result<()>
+    where F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    let mut dest = BufWriter::new(File::create(path)?);
+    write(&mut dest)?;
+    dest.flush()
+}
+
+/// Plays the tournament the arguments describe, and prints its results.
+pub fn run(args: &[String]) -> Result<(), String> {
+    if args.iter().any(|arg| arg == "--help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let options = parse_options(args)?;
+    let schedule = match options.schedule {
+        Some(ref path) => {
+            let text = fs::read_to_string(path)
+                .map_err(|err| format!("couldn't read the schedule: {}", err))?;
+            parse_schedule(&text, options.players.len())?
+        }
+        None => round_robin(options.players.len(), options.games),
+    };
+    if let Some(ref path) = options.write_schedule {
+        return write_file(path, |dest| write_schedule(&schedule, dest))
+            .map_err(|err| format!("couldn't write the schedule: {}", err));
+    }
+
+    let results = play_tournament(&options, &schedule);
+    let standings = &results.standings;
+    let mut order = (0..standings.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| standings[b].rating.partial_cmp(&standings[a].rating).unwrap());
+
+    let names = options.players.iter().map(|(spec, _)| {
+        if spec.is_empty() { "perfect" } else { spec.as_str() }
+    }).collect::<Vec<_>>();
+    if let Some(ref path) = options.csv {
+        write_file(path, |dest| write_crosstable(&results, &names, &order, dest))
+            .map_err(|err| format!("couldn't write the crosstable: {}", err))?;
+    }
+    if let Some(ref path) = options.json {
+        let specs = options.players.iter().map(|(spec, _)| spec.as_str()).collect::<Vec<_>>();
+        write_file(path, |dest| write_games(&results, &specs, dest))
+            .map_err(|err| format!("couldn't write the games: {}", err))?;
+    }
+
+    let width = names.iter().map(|name| name.len()).max().unwrap_or(0).max("player".len());
+    match options.schedule {
+        Some(ref path) => println!("{}, {} games from {}", options.variant.name(), schedule.len(), path),
+        None => println!("{}, {} games per pair", options.variant.name(), options.games),
+    }
+    println!("{:>2}  {:<width$}  {:>5}  {:>4}  {:>5}  {:>6}  {:>6}  {:>6}",
+             "#", "player", "games", "wins", "draws", "losses", "score", "rating", width = width);
+    for (rank, &i) in order.iter().enumerate() {
+        let standing = &standings[i];
+        println!("{:>2}  {:<width$}  {:>5}  {:>4}  {:>5}  {:>6}  {:>5.1}%  {:>6.0}",
+                 rank + 1, names[i], standing.games(), standing.wins, standing.draws,
+                 standing.losses, 100.0 * standing.score(), standing.rating, width = width);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_the_stronger_player_higher() {
+        let args = ["--games", "6", "difficulty=Easy,randomize=yes", ""];
+        let options = parse_options(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>()).unwrap();
+        let results = play_tournament(&options, &round_robin(2, options.games));
+        let standings = &results.standings;
+        // Perfect play never loses.
+        assert_eq!(standings[1].losses, 0);
+        assert_eq!(standings[0].games(), 6);
+        assert!(standings[1].rating >= standings[0].rating);
+        assert!((standings[0].rating + standings[1].rating - 2.0 * BASE_RATING).abs() < 1e-6);
+
+        // All draws rate everyone the same.
+        let ratings = fit_ratings(&[vec![0.0, 2.0], vec![2.0, 0.0]], &[vec![0.0, 4.0], vec![4.0, 0.0]]);
+        assert!((ratings[0] - ratings[1]).abs() < 1e-6);
+
+        assert_eq!(Outcome::of(GameResult::OWins, CheckBox::O), Outcome::Win);
+        assert_eq!(Outcome::of(GameResult::OWins, CheckBox::X), Outcome::Loss);
+        assert_eq!(Outcome::of(GameResult::Resigned(CheckBox::O), CheckBox::X), Outcome::Win);
+        assert_eq!(Outcome::of(GameResult::Draw, CheckBox::X), Outcome::Draw);
+
+        let args = ["evaluator=positional", "", "--variant", "connect4"];
+        assert!(parse_options(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>()).is_err());
+    }
+
+    #[test]
+    fn writes_and_reads_schedules_and_results() {
+        let schedule = round_robin(3, 2);
+        assert_eq!(schedule.len(), 6);
+        assert_eq!(schedule[1], Pairing { x: 1, o: 0, opening: 0 });
+        let mut written = vec![];
+        write_schedule(&schedule, &mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert!(written.starts_with("1 2 0\n2 1 0\n1 3 0\n"), "{}", written);
+        assert_eq!(parse_schedule(&format!("# A comment\n\n{}", written), 3).unwrap(), schedule);
+        assert!(parse_schedule("1 4 0", 3).is_err());
+        assert!(parse_schedule("1 1 0", 3).is_err());
+        assert!(parse_schedule("1 2", 3).is_err());
+
+        let args = ["depth=1,randomize=no", ""];
+        let options = parse_options(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>()).unwrap();
+        let results = play_tournament(&options, &parse_schedule("2 1 0\n2 1 0", 2).unwrap());
+        assert_eq!(results.played.len(), 2);
+        assert_eq!(results.standings[0].losses, 2);
+
+        let mut csv = vec![];
+        write_crosstable(&results, &["depth=1,randomize=no", "perfect"], &[1, 0], &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("rank,player,games,wins,draws,losses,score,rating,perfect,\"depth=1,randomize=no\""));
+        assert!(lines.next().unwrap().starts_with("1,perfect,2,2,0,0,1.000,"));
+        assert!(lines.next().unwrap().ends_with(",0,"));
+
+        let mut json = vec![];
+        write_games(&results, &["depth=1,randomize=no", ""], &mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        let first = json.lines().next().unwrap();
+        assert!(first.starts_with("{\"game\":1,\"x\":\"\",\"o\":\"depth=1,randomize=no\",\"opening\":0,\"result\":\"x-wins\",\"plies\":"),
+                "{}", first);
+        assert_eq!(json_string("a\"b\\"), "a\\\"b\\\\");
+    }
+}
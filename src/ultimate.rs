@@ -0,0 +1,236 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Ultimate tic-tac-toe: nine tic-tac-toe boards laid out as a bigger one.
+//!
+//! Playing in a square of a board sends the other player to the board at the
+//! same position of the big one. Winning a board claims its square of the big
+//! board, and winning the big board wins the game. When the board a player is
+//! sent to is already won or full, they can play in any open board instead.
+//!
+//! Boards and the squares of each board are numbered in row-major order, from
+//! 0 to 8.
+
+use game_state::{self, GameState, GridState, MoveError, Savable};
+use state::{self, CheckBox};
+use std::fmt;
+use variant::Variant;
+
+/// All the lines that win a board, as square numbers.
+const LINES: [[usize; 3]; 8] = [
+    [0, 1, 2],
+    [3, 4, 5],
+    [6, 7, 8],
+    [0, 3, 6],
+    [1, 4, 7],
+    [2, 5, 8],
+    [0, 4, 8],
+    [2, 4, 6],
+];
+
+/// Returns the line that `squares` won with, if any.
+fn winning_line(squares: &[CheckBox; 9]) -> Option<[usize; 3]> {
+    LINES.iter().cloned().find(|line| {
+        squares[line[0]] != CheckBox::Empty &&
+            squares[line[0]] == squares[line[1]] &&
+            squares[line[1]] == squares[line[2]]
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UltimateState {
+    /// The squares of each board.
+    boards: [[CheckBox; 9]; 9],
+    /// The player that won each board, if any.
+    winners: [CheckBox; 9],
+    /// The board the next player is sent to, or `None` if they can play in
+    /// any open board.
+    next_board: Option<usize>,
+}
+
+impl UltimateState {
+    /// Returns whether moves can still be played in `board`.
+    fn is_open(&self, board: usize) -> bool {
+        self.winners[board] == CheckBox::Empty &&
+            self.boards[board].contains(&CheckBox::Empty)
+    }
+
+    /// Returns whether the next player can play in `board`.
+    fn can_play_in(&self, board: usize) -> bool {
+        self.is_open(board) && self.next_board.is_none_or(|b| b == board)
+    }
+
+    /// Returns the state after `player` plays in `square` of `board`.
+    fn play(&self, board: usize, square: usize, player: CheckBox) -> Self {
+        let mut ret = self.clone();
+        ret.boards[board][square] = player;
+        if winning_line(&ret.boards[board]).is_some() {
+            ret.winners[board] = player;
+        }
+        ret.next_board = if ret.is_open(square) { Some(square) } else { None };
+        ret
+    }
+}
+
+impl GameState for UltimateState {
+    /// The board and the square within it.
+    type Move = (usize, usize);
+
+    fn initial() -> Self {
+        Self {
+            boards: [[CheckBox::Empty; 9]; 9],
+            winners: [CheckBox::Empty; 9],
+            next_board: None,
+        }
+    }
+
+    fn winner(&self) -> CheckBox {
+        winning_line(&self.winners).map_or(CheckBox::Empty, |line| {
+            self.winners[line[0]]
+        })
+    }
+
+    fn successors(&self, player: CheckBox) -> Vec<(Self::Move, Self)> {
+        if self.winner() != CheckBox::Empty {
+            return vec![];
+        }
+        let mut ret = vec![];
+        for board in (0..9).filter(|b| self.can_play_in(*b)) {
+            for square in 0..9 {
+                if self.boards[board][square] == CheckBox::Empty {
+                    ret.push(((board, square), self.play(board, square, player)));
+                }
+            }
+        }
+        ret
+    }
+
+    /// Won boards are what matter the most, so count them, keeping the
+    /// score away from the one of an actual win.
+    fn evaluate(&self) -> i8 {
+        let winner = self.winner();
+        if winner != CheckBox::Empty {
+            return winner as i8;
+        }
+        let boards = self.winners.iter().map(|w| *w as i8 / 10).sum::<i8>();
+        (boards * 2).clamp(-9, 9)
+    }
+
+    fn move_priority(&self, mv: Self::Move) -> u8 {
+        state::square_priority(mv.1 % 3, mv.1 / 3)
+    }
+
+    fn move_error(&self, (board, square): Self::Move) -> MoveError {
+        if board >= 9 || square >= 9 {
+            MoveError::OutOfBounds
+        } else if self.boards[board][square] != CheckBox::Empty {
+            MoveError::Occupied
+        } else {
+            // Not in the board the last move sent the player to.
+            MoveError::Illegal
+        }
+    }
+
+    fn is_center_move(&self, mv: Self::Move) -> bool {
+        // The center square of the center board.
+        mv == (4, 4)
+    }
+
+    fn depth_limit() -> usize {
+        // Players can often move in any of the 81 squares, so deeper searches
+        // get way too slow.
+        3
+    }
+
+    fn dump<W>(&self, indent: usize, dest: &mut W) -> fmt::Result
+        where W: fmt::Write,
+    {
+        for y in 0..9 {
+            for _ in 0..indent {
+                dest.write_char(' ')?;
+            }
+            for x in 0..9 {
+                if x != 0 && x % 3 == 0 {
+                    dest.write_char(' ')?;
+                }
+                dest.write_char(self.mark(x, y).dump_char())?;
+            }
+            dest.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the board and the square within it for column `x` and row `y` of
+/// the whole grid.
+fn move_at(x: usize, y: usize) -> (usize, usize) {
+    ((y / 3) * 3 + x / 3, (y % 3) * 3 + x % 3)
+}
+
+impl GridState for UltimateState {
+    fn grid_size(&self) -> (usize, usize) {
+        (9, 9)
+    }
+
+    fn block_size(&self) -> Option<usize> {
+        Some(3)
+    }
+
+    fn mark(&self, x: usize, y: usize) -> CheckBox {
+        let (board, square) = move_at(x, y);
+        self.boards[board][square]
+    }
+
+    fn move_for_square(&self, x: usize, y: usize) -> Option<Self::Move> {
+        let (board, square) = move_at(x, y);
+        if self.winner() == CheckBox::Empty && self.can_play_in(board) &&
+            self.boards[board][square] == CheckBox::Empty {
+            Some((board, square))
+        } else {
+            None
+        }
+    }
+
+    /// All the squares of the boards that won the game.
+    fn winning_squares(&self) -> Vec<(usize, usize)> {
+        let line = match winning_line(&self.winners) {
+            Some(line) => line,
+            None => return vec![],
+        };
+        let mut ret = vec![];
+        for board in line.iter() {
+            for square in 0..9 {
+                ret.push(((board % 3) * 3 + square % 3, (board / 3) * 3 + square / 3));
+            }
+        }
+        ret
+    }
+}
+
+impl Savable for UltimateState {
+    fn variant() -> Variant {
+        Variant::Ultimate
+    }
+
+    fn write_move((board, square): Self::Move) -> String {
+        format!("{},{}", board, square)
+    }
+
+    fn read_move(s: &str) -> Option<Self::Move> {
+        game_state::parse_pair(s)
+    }
+}
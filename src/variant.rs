@@ -0,0 +1,236 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The games that can be played.
+
+use connect_four::ConnectFourState;
+use cube::{CubeState, QubicState};
+use game::{Board, Game, LoadError, SavedGame};
+use game_state::GameState;
+use number_scrabble::NumberScrabbleState;
+use rules::{MisereState, NotaktoState, WildState};
+use state::{CheckBox, State};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use ultimate::UltimateState;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Variant {
+    TicTacToe,
+    Ultimate,
+    ConnectFour,
+    NumberScrabble,
+    Misere,
+    Wild,
+    Notakto,
+    Cube,
+    Qubic,
+}
+
+impl Variant {
+    pub fn all() -> [Variant; 9] {
+        [
+            Variant::TicTacToe,
+            Variant::Ultimate,
+            Variant::ConnectFour,
+            Variant::NumberScrabble,
+            Variant::Misere,
+            Variant::Wild,
+            Variant::Notakto,
+            Variant::Cube,
+            Variant::Qubic,
+        ]
+    }
+
+    /// A short name for the command line.
+    pub fn id(&self) -> &'static str {
+        match *self {
+            Variant::TicTacToe => "tictactoe",
+            Variant::Ultimate => "ultimate",
+            Variant::ConnectFour => "connect4",
+            Variant::NumberScrabble => "scrabble",
+            Variant::Misere => "misere",
+            Variant::Wild => "wild",
+            Variant::Notakto => "notakto",
+            Variant::Cube => "cube",
+            Variant::Qubic => "qubic",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::all().iter().cloned().find(|v| v.id() == id)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Variant::TicTacToe => "Tic-tac-toe",
+            Variant::Ultimate => "Ultimate tic-tac-toe",
+            Variant::ConnectFour => "Connect Four",
+            Variant::NumberScrabble => "Number scrabble",
+            Variant::Misere => "Misère tic-tac-toe",
+            Variant::Wild => "Wild tic-tac-toe",
+            Variant::Notakto => "Notakto",
+            Variant::Cube => "3D tic-tac-toe",
+            Variant::Qubic => "Qubic",
+        }
+    }
+
+    /// The maximum number of levels the engine searches in this variant,
+    /// `GameState::depth_limit`.
+    pub fn depth_limit(&self) -> usize {
+        match *self {
+            Variant::TicTacToe => State::depth_limit(),
+            Variant::Ultimate => UltimateState::depth_limit(),
+            Variant::ConnectFour => ConnectFourState::depth_limit(),
+            Variant::NumberScrabble => NumberScrabbleState::depth_limit(),
+            Variant::Misere => MisereState::depth_limit(),
+            Variant::Wild => WildState::depth_limit(),
+            Variant::Notakto => NotaktoState::depth_limit(),
+            Variant::Cube => CubeState::depth_limit(),
+            Variant::Qubic => QubicState::depth_limit(),
+        }
+    }
+
+    /// Returns the variant of this game on a board of `size` squares a side,
+    /// if it comes in that size.
+    pub fn with_size(&self, size: usize) -> Option<Self> {
+        match (*self, size) {
+            (Variant::Cube, 3) | (Variant::Qubic, 3) => Some(Variant::Cube),
+            (Variant::Cube, 4) | (Variant::Qubic, 4) => Some(Variant::Qubic),
+            (Variant::Ultimate, 9) => Some(Variant::Ultimate),
+            (Variant::ConnectFour, _) | (Variant::Cube, _) | (Variant::Qubic, _) | (Variant::Ultimate, _) => None,
+            // Everything else is played on the tic-tac-toe board.
+            (variant, 3) => Some(variant),
+            _ => None,
+        }
+    }
+
+    pub fn new_game(&self, first_player: CheckBox) -> AnyGame {
+        match *self {
+            Variant::TicTacToe => AnyGame::TicTacToe(Game::new(first_player)),
+            Variant::Ultimate => AnyGame::Ultimate(Game::new(first_player)),
+            Variant::ConnectFour => AnyGame::ConnectFour(Game::new(first_player)),
+            Variant::NumberScrabble => AnyGame::NumberScrabble(Game::new(first_player)),
+            Variant::Misere => AnyGame::Misere(Game::new(first_player)),
+            Variant::Wild => AnyGame::Wild(Game::new(first_player)),
+            Variant::Notakto => AnyGame::Notakto(Game::new(first_player)),
+            Variant::Cube => AnyGame::Cube(Game::new(first_player)),
+            Variant::Qubic => AnyGame::Qubic(Game::new(first_player)),
+        }
+    }
+}
+
+/// A game of any of the variants.
+#[derive(Debug)]
+pub enum AnyGame {
+    TicTacToe(Game),
+    Ultimate(Game<UltimateState>),
+    ConnectFour(Game<ConnectFourState>),
+    NumberScrabble(Game<NumberScrabbleState>),
+    Misere(Game<MisereState>),
+    Wild(Game<WildState>),
+    Notakto(Game<NotaktoState>),
+    Cube(Game<CubeState>),
+    Qubic(Game<QubicState>),
+}
+
+impl AnyGame {
+    pub fn variant(&self) -> Variant {
+        match *self {
+            AnyGame::TicTacToe(..) => Variant::TicTacToe,
+            AnyGame::Ultimate(..) => Variant::Ultimate,
+            AnyGame::ConnectFour(..) => Variant::ConnectFour,
+            AnyGame::NumberScrabble(..) => Variant::NumberScrabble,
+            AnyGame::Misere(..) => Variant::Misere,
+            AnyGame::Wild(..) => Variant::Wild,
+            AnyGame::Notakto(..) => Variant::Notakto,
+            AnyGame::Cube(..) => Variant::Cube,
+            AnyGame::Qubic(..) => Variant::Qubic,
+        }
+    }
+
+    pub fn board(&self) -> &dyn Board {
+        match *self {
+            AnyGame::TicTacToe(ref game) => game,
+            AnyGame::Ultimate(ref game) => game,
+            AnyGame::ConnectFour(ref game) => game,
+            AnyGame::NumberScrabble(ref game) => game,
+            AnyGame::Misere(ref game) => game,
+            AnyGame::Wild(ref game) => game,
+            AnyGame::Notakto(ref game) => game,
+            AnyGame::Cube(ref game) => game,
+            AnyGame::Qubic(ref game) => game,
+        }
+    }
+
+    pub fn board_mut(&mut self) -> &mut dyn Board {
+        match *self {
+            AnyGame::TicTacToe(ref mut game) => game,
+            AnyGame::Ultimate(ref mut game) => game,
+            AnyGame::ConnectFour(ref mut game) => game,
+            AnyGame::NumberScrabble(ref mut game) => game,
+            AnyGame::Misere(ref mut game) => game,
+            AnyGame::Wild(ref mut game) => game,
+            AnyGame::Notakto(ref mut game) => game,
+            AnyGame::Cube(ref mut game) => game,
+            AnyGame::Qubic(ref mut game) => game,
+        }
+    }
+
+    /// Loads a game of any variant saved with `save`.
+    pub fn load<P>(path: P) -> Result<Self, LoadError>
+        where P: AsRef<Path>,
+    {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a game of any variant in the save format from `src`.
+    pub fn read<R>(src: R) -> Result<Self, LoadError>
+        where R: BufRead,
+    {
+        let saved = SavedGame::read(src)?;
+        Ok(match saved.variant()? {
+            Variant::TicTacToe => AnyGame::TicTacToe(Game::from_saved(&saved)?),
+            Variant::Ultimate => AnyGame::Ultimate(Game::from_saved(&saved)?),
+            Variant::ConnectFour => AnyGame::ConnectFour(Game::from_saved(&saved)?),
+            Variant::NumberScrabble => AnyGame::NumberScrabble(Game::from_saved(&saved)?),
+            Variant::Misere => AnyGame::Misere(Game::from_saved(&saved)?),
+            Variant::Wild => AnyGame::Wild(Game::from_saved(&saved)?),
+            Variant::Notakto => AnyGame::Notakto(Game::from_saved(&saved)?),
+            Variant::Cube => AnyGame::Cube(Game::from_saved(&saved)?),
+            Variant::Qubic => AnyGame::Qubic(Game::from_saved(&saved)?),
+        })
+    }
+
+    /// Saves the game to `path`, so that it can be resumed with `load`.
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+        where P: AsRef<Path>,
+    {
+        let mut file = File::create(path)?;
+        self.board().write_record(&mut file)
+    }
+
+    /// Returns the game if it is plain tic-tac-toe, which is the only one
+    /// with a notation for its positions, and puzzles.
+    pub fn tic_tac_toe(&mut self) -> Option<&mut Game> {
+        match *self {
+            AnyGame::TicTacToe(ref mut game) => Some(game),
+            _ => None,
+        }
+    }
+}
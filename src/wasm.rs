@@ -0,0 +1,141 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The engine for JavaScript, with the `wasm` feature:
+//!
+//! ```text
+//! wasm-pack build --target web --no-default-features --features wasm
+//! ```
+//!
+//! ```js
+//! const game = new Game("tictactoe");
+//! game.play(1, 1);
+//! const [x, y] = game.bestMove();
+//! ```
+//!
+//! Searches run on the calling thread, so big ones are better off in a web
+//! worker.
+
+use game_state::GameResult;
+use state::CheckBox;
+use variant::{AnyGame, Variant};
+use wasm_bindgen::prelude::*;
+
+/// A game of any variant, where X moves first.
+#[wasm_bindgen]
+pub struct Game {
+    game: AnyGame,
+}
+
+#[wasm_bindgen]
+impl Game {
+    /// Starts a game of the variant with the given id, like `"connect4"`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(variant: &str) -> Result<Game, JsValue> {
+        let variant = Variant::from_id(variant)
+            .ok_or_else(|| JsValue::from_str(&format!("unknown variant: {}", variant)))?;
+        Ok(Game { game: variant.new_game(CheckBox::X) })
+    }
+
+    /// Loads a game written by `save`.
+    pub fn load(saved: &str) -> Result<Game, JsValue> {
+        let game = AnyGame::read(saved.as_bytes())
+            .map_err(|err| JsValue::from_str(&format!("couldn't load the game: {}", err)))?;
+        Ok(Game { game: game })
+    }
+
+    /// Returns the game in the save format.
+    pub fn save(&self) -> String {
+        let mut saved = vec![];
+        self.game.board().write_record(&mut saved).expect("Writing to memory can't fail");
+        String::from_utf8(saved).expect("Saved games are UTF-8")
+    }
+
+    pub fn variant(&self) -> String {
+        self.game.variant().id().to_owned()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.game.board().grid_size().0
+    }
+
+    pub fn rows(&self) -> usize {
+        self.game.board().grid_size().1
+    }
+
+    /// Returns `"X"`, `"O"` or `"_"`.
+    pub fn mark(&self, x: usize, y: usize) -> String {
+        self.game.board().mark(x, y).dump_char().to_string()
+    }
+
+    /// The player to move, `"X"` or `"O"`.
+    pub fn player(&self) -> String {
+        self.game.board().player().dump_char().to_string()
+    }
+
+    /// Returns `"in-progress"`, `"x-wins"`, `"o-wins"` or `"draw"`.
+    pub fn result(&self) -> String {
+        match self.game.board().result() {
+            GameResult::InProgress => "in-progress",
+            GameResult::XWins | GameResult::TimeForfeit(CheckBox::O) | GameResult::Resigned(CheckBox::O) => "x-wins",
+            GameResult::OWins | GameResult::TimeForfeit(_) | GameResult::Resigned(_) => "o-wins",
+            GameResult::Draw | GameResult::DrawAgreed => "draw",
+        }.to_owned()
+    }
+
+    /// Plays the square in column `x` and row `y`, or throws why it can't be
+    /// played.
+    pub fn play(&mut self, x: usize, y: usize) -> Result<(), JsValue> {
+        self.game.board_mut().click(x, y)
+            .map_err(|err| JsValue::from_str(&format!("can't play there: {}", err)))
+    }
+
+    /// Returns the square of the best move for the player to move, as
+    /// `[x, y]`, searching as deep as the game allows, without playing it.
+    #[wasm_bindgen(js_name = bestMove)]
+    pub fn best_move(&self) -> Option<Vec<u32>> {
+        let board = self.game.board();
+        board.hint_squares(board.player()).first().map(|&(x, y)| vec![x as u32, y as u32])
+    }
+
+    /// Takes back the last move, returning whether there was one.
+    pub fn undo(&mut self) -> bool {
+        self.game.board_mut().undo()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_and_saves() {
+        let mut game = Game::new("tictactoe").unwrap();
+        while game.result() == "in-progress" {
+            let best = game.best_move().unwrap();
+            game.play(best[0] as usize, best[1] as usize).unwrap();
+        }
+        assert_eq!(game.result(), "draw");
+
+        let loaded = Game::load(&game.save()).unwrap();
+        assert_eq!(loaded.result(), "draw");
+
+        let mut game = Game::new("connect4").unwrap();
+        game.play(3, 0).unwrap();
+        assert_eq!(Game::load(&game.save()).unwrap().variant(), "connect4");
+    }
+}
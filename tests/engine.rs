@@ -0,0 +1,71 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! How the engine plays, through the public API of the library.
+
+extern crate tic_tac_minimax;
+
+use tic_tac_minimax::number_scrabble::NumberScrabbleState;
+use tic_tac_minimax::prelude::*;
+use tic_tac_minimax::rand::SeedableRng;
+use tic_tac_minimax::rand::rngs::StdRng;
+use tic_tac_minimax::rules::MisereState;
+
+/// Plays a game of the engine against itself, picking at random among the
+/// best moves with random numbers from `seed`.
+fn self_play<S: GameState>(seed: u64) -> GameResult {
+    let mut game = Game::<S>::new(CheckBox::X);
+    game.set_randomize(true);
+    let mut rng = StdRng::seed_from_u64(seed);
+    while game.play_engine_move(&mut rng).is_some() {}
+    game.state().result()
+}
+
+/// Returns the move the engine plays in the position written in `notation`.
+fn engine_move(notation: &str) -> (usize, usize) {
+    let (state, player) = State::from_notation(notation).unwrap();
+    let mut tree = MiniMaxTree::from_state(state, player);
+    let index = tree.find_move_index(State::depth_limit()).index().unwrap();
+    tree.move_for_index(index)
+}
+
+#[test]
+fn perfect_self_play_draws() {
+    for seed in 0..20 {
+        assert_eq!(self_play::<State>(seed), GameResult::Draw, "Seed {}", seed);
+    }
+    for seed in 0..5 {
+        assert_eq!(self_play::<NumberScrabbleState>(seed), GameResult::Draw, "Seed {}", seed);
+        assert_eq!(self_play::<MisereState>(seed), GameResult::Draw, "Seed {}", seed);
+    }
+}
+
+#[test]
+fn takes_wins() {
+    // Blocking X would only draw.
+    assert_eq!(engine_move("OO_/__X/XX_ O"), (2, 0));
+}
+
+#[test]
+fn blocks_threats() {
+    assert_eq!(engine_move("XX_/_O_/___ O"), (2, 0));
+    // Anything but the center loses against a corner opening.
+    assert_eq!(engine_move("X__/___/___ O"), (1, 1));
+    // And anything but an edge loses against the opposite corners.
+    let mv = engine_move("X__/_O_/__X O");
+    assert!(mv.0 == 1 || mv.1 == 1, "{:?}", mv);
+}
@@ -0,0 +1,149 @@
+/*
+ * Copyright (C) 2017 Emilio Cobos Álvarez <emilio@crisal.io>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Whole games through the `GameDriver`, as the frontends play them but
+//! without any, with the human's moves scripted.
+//!
+//! The human plays every line of play there is against the engine, which
+//! searches as deep as told and never varies its moves, so each script has
+//! a single game.
+
+extern crate tic_tac_minimax;
+
+use tic_tac_minimax::driver::{GameDriver, GameEvent};
+use tic_tac_minimax::frontend::Click;
+use tic_tac_minimax::prelude::*;
+use tic_tac_minimax::rand::SeedableRng;
+use tic_tac_minimax::rand::rngs::StdRng;
+
+/// Where a scripted game stopped.
+enum Outcome {
+    Over(GameResult),
+    /// The script ran out, and the human can play these squares next.
+    Awaiting(Vec<(usize, usize)>),
+}
+
+/// Plays tic-tac-toe with the human playing `human` and the squares of
+/// `script`, and the engine searching `depth` levels for the other side,
+/// until the game ends or the script runs out.
+///
+/// Checks that the engine blocks every line the human could complete on
+/// their next move, unless it wins right away or there's more than one.
+fn play(human: CheckBox, depth: usize, script: &[(usize, usize)]) -> Outcome {
+    let engine = human.next_player();
+    let mut game = Variant::TicTacToe.new_game(CheckBox::X);
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut driver = GameDriver::new(game.board_mut(), &mut rng);
+    let config = PlayerConfig { depth: Some(depth), ..PlayerConfig::default() };
+    driver.board_mut().set_player_config(engine, config).unwrap();
+    driver.set_engine(engine, true);
+
+    let mut script = script.iter();
+    loop {
+        let threats = if driver.board().player() == engine {
+            driver.board().immediate_threats(human)
+        } else {
+            vec![]
+        };
+        let event = driver.next().expect("The driver should wait for the human only once per move");
+        match event {
+            GameEvent::MoveMade(player, _) if player == engine => {
+                let board = driver.board();
+                if threats.len() == 1 && board.result() == GameResult::InProgress {
+                    let (x, y) = threats[0];
+                    assert_eq!(board.mark(x, y), engine,
+                               "Depth {} didn't block {:?} after {:?}", depth, (x, y), board.move_names());
+                }
+            }
+            GameEvent::MoveMade(..) => {}
+            GameEvent::AwaitingInput(player) => {
+                assert_eq!(player, human);
+                let (x, y) = match script.next() {
+                    Some(&square) => square,
+                    None => {
+                        let board = driver.board();
+                        let free = (0..3).flat_map(|y| (0..3).map(move |x| (x, y)))
+                            .filter(|&(x, y)| board.mark(x, y) == CheckBox::Empty)
+                            .collect();
+                        return Outcome::Awaiting(free);
+                    }
+                };
+                driver.submit(Click { x, y, other_mark: false }).unwrap();
+            }
+            GameEvent::GameOver(result) => return Outcome::Over(result),
+            event => panic!("Nobody offered a draw, got {:?}", event),
+        }
+    }
+}
+
+/// Plays every game the human can play against the engine searching
+/// `depth` levels, and returns their results.
+fn play_every_line(human: CheckBox, depth: usize) -> Vec<GameResult> {
+    let mut results = vec![];
+    let mut scripts = vec![vec![]];
+    while let Some(script) = scripts.pop() {
+        match play(human, depth, &script) {
+            Outcome::Over(result) => results.push(result),
+            Outcome::Awaiting(squares) => {
+                for square in squares {
+                    let mut longer = script.clone();
+                    longer.push(square);
+                    scripts.push(longer);
+                }
+            }
+        }
+    }
+    results
+}
+
+#[test]
+fn never_loses_at_full_depth() {
+    for &human in &[CheckBox::X, CheckBox::O] {
+        let human_wins = if human == CheckBox::X { GameResult::XWins } else { GameResult::OWins };
+        let results = play_every_line(human, State::depth_limit());
+        assert!(!results.contains(&human_wins), "{:?} beat the engine", human);
+        // The engine wins whenever the human slips.
+        assert!(results.contains(&GameResult::Draw));
+        assert!(results.iter().any(|&r| r != GameResult::Draw));
+    }
+}
+
+#[test]
+fn blocks_immediate_threats_at_every_depth() {
+    // One level only looks at the engine's own moves.
+    for depth in 2..5 {
+        for &human in &[CheckBox::X, CheckBox::O] {
+            assert!(!play_every_line(human, depth).is_empty());
+        }
+    }
+}
+
+#[test]
+fn plays_scripted_games() {
+    let free = |script: &[(usize, usize)]| match play(CheckBox::X, State::depth_limit(), script) {
+        Outcome::Awaiting(squares) => squares,
+        Outcome::Over(result) => panic!("The game shouldn't be over yet, it's {:?}", result),
+    };
+    // Anything but the center loses against a corner opening.
+    let squares = free(&[(0, 0)]);
+    assert_eq!(squares.len(), 7);
+    assert!(!squares.contains(&(1, 1)));
+    // And anything but an edge loses against the opposite corners.
+    let squares = free(&[(0, 0), (2, 2)]);
+    assert_eq!(squares.len(), 5);
+    assert!(squares.contains(&(2, 0)) && squares.contains(&(0, 2)));
+}